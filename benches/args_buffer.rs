@@ -0,0 +1,180 @@
+#[macro_use]
+extern crate mozjs;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use mozjs::jsapi::NewArrayObject;
+use mozjs::jsval::UndefinedValue;
+use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+use spidermonkey_runtime::jsapi_utils;
+use spidermonkey_runtime::spidermonkeyruntimewrapper::{do_with_args_buffer, SmRuntime};
+
+// the number of calls per iteration of a benchmark
+const CALLS: i32 = 1000;
+
+fn call_loop(c: &mut Criterion) {
+    let rt = EsRuntimeBuilder::new().build();
+    rt.eval_sync(
+        "this.bench_args_join = function(a, b, c){return a + b + c;};",
+        "bench_args_buffer.es",
+    )
+    .ok()
+    .expect("script failed");
+
+    let mut group = c.benchmark_group("call_loop");
+
+    // the public path, every call goes through the event queue and do_with_rooted_esvf_vec()
+    group.bench_function("call_sync", |b| {
+        b.iter(|| {
+            for x in 0..CALLS {
+                rt.call_sync(
+                    vec![],
+                    "bench_args_join",
+                    vec![
+                        EsValueFacade::new_i32(x),
+                        EsValueFacade::new_i32(1),
+                        EsValueFacade::new_i32(2),
+                    ],
+                )
+                .ok()
+                .expect("call failed");
+            }
+        })
+    });
+
+    // the current do_with_rooted_esvf_vec() path without the event queue, the args are rooted in a vec
+    group.bench_function("sm_rt_call", |b| {
+        b.iter(|| {
+            rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                for x in 0..CALLS {
+                    sm_rt
+                        .call(
+                            vec![],
+                            "bench_args_join",
+                            vec![
+                                EsValueFacade::new_i32(x),
+                                EsValueFacade::new_i32(1),
+                                EsValueFacade::new_i32(2),
+                            ],
+                        )
+                        .ok()
+                        .expect("call failed");
+                }
+            })
+        })
+    });
+
+    // what do_with_rooted_esvf_vec() used to do, the same as sm_rt_call but the args
+    // were also copied into a new rooted array object for every call
+    group.bench_function("sm_rt_call_with_array_object", |b| {
+        b.iter(|| {
+            rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.do_with_jsapi(|_rt, cx, global| {
+                    for x in 0..CALLS {
+                        let args = vec![
+                            EsValueFacade::new_i32(x),
+                            EsValueFacade::new_i32(1),
+                            EsValueFacade::new_i32(2),
+                        ];
+                        // a new buffer per call roots the args like the vec in do_with_rooted_esvf_vec()
+                        do_with_args_buffer(cx, 3, |buffer| {
+                            buffer.fill(&args);
+                            let hva = *buffer.handle_value_array();
+                            rooted!(in (cx) let _argument_object = unsafe { NewArrayObject(cx, &hva) });
+                            rooted!(in (cx) let mut rval = UndefinedValue());
+                            jsapi_utils::functions::call_namespace_function_name2(
+                                cx,
+                                global,
+                                vec![],
+                                "bench_args_join",
+                                hva,
+                                rval.handle_mut(),
+                            )
+                            .ok()
+                            .expect("call failed");
+                        });
+                    }
+                })
+            })
+        })
+    });
+
+    // one buffer which is filled again for every call
+    group.bench_function("reused_buffer_fill", |b| {
+        b.iter(|| {
+            rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.do_with_jsapi(|_rt, cx, global| {
+                    rooted!(in (cx) let mut func_root = UndefinedValue());
+                    jsapi_utils::objects::get_es_obj_prop_val(
+                        cx,
+                        global,
+                        "bench_args_join",
+                        func_root.handle_mut(),
+                    )
+                    .ok()
+                    .expect("no function");
+                    do_with_args_buffer(cx, 3, |buffer| {
+                        for x in 0..CALLS {
+                            buffer.fill(&[
+                                EsValueFacade::new_i32(x),
+                                EsValueFacade::new_i32(1),
+                                EsValueFacade::new_i32(2),
+                            ]);
+                            rooted!(in (cx) let mut rval = UndefinedValue());
+                            jsapi_utils::functions::call_function_value2(
+                                cx,
+                                global,
+                                func_root.handle(),
+                                *buffer.handle_value_array(),
+                                rval.handle_mut(),
+                            )
+                            .ok()
+                            .expect("call failed");
+                        }
+                    });
+                })
+            })
+        })
+    });
+
+    // one buffer where only the changing argument is replaced
+    group.bench_function("reused_buffer_set", |b| {
+        b.iter(|| {
+            rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.do_with_jsapi(|_rt, cx, global| {
+                    rooted!(in (cx) let mut func_root = UndefinedValue());
+                    jsapi_utils::objects::get_es_obj_prop_val(
+                        cx,
+                        global,
+                        "bench_args_join",
+                        func_root.handle_mut(),
+                    )
+                    .ok()
+                    .expect("no function");
+                    do_with_args_buffer(cx, 3, |buffer| {
+                        buffer.set(1, &EsValueFacade::new_i32(1));
+                        buffer.set(2, &EsValueFacade::new_i32(2));
+                        for x in 0..CALLS {
+                            buffer.set(0, &EsValueFacade::new_i32(x));
+                            rooted!(in (cx) let mut rval = UndefinedValue());
+                            jsapi_utils::functions::call_function_value2(
+                                cx,
+                                global,
+                                func_root.handle(),
+                                *buffer.handle_value_array(),
+                                rval.handle_mut(),
+                            )
+                            .ok()
+                            .expect("call failed");
+                        }
+                    });
+                })
+            })
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, call_loop);
+criterion_main!(benches);
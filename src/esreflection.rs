@@ -56,7 +56,8 @@
 //!
 use crate::esruntime::EsRuntime;
 use crate::esvaluefacade::EsValueFacade;
-use crate::jsapi_utils::reflection::{get_proxy, ProxyBuilder};
+use crate::jsapi_utils::reflection::{get_proxy, ProxyBuilder, SetterError};
+use crate::jsapi_utils::EsErrorInfo;
 use mozjs::jsval::UndefinedValue;
 use std::collections::{HashMap, HashSet};
 use std::ptr::replace;
@@ -97,7 +98,6 @@ impl EsProxy {
     pub fn builder(namespace: Vec<&'static str>, class_name: &'static str) -> EsProxyBuilder {
         EsProxyBuilder::new(namespace, class_name)
     }
-    // todo do we want sync variants which may return a veto boolean or an altered EsValueFacade?
     /// dispatch an event for an instance of the class
     ///
     /// you can pass an EsValueFacade as event obj
@@ -139,6 +139,50 @@ impl EsProxy {
         });
     }
 
+    /// dispatch an event for an instance of the class and wait for the return values of the listeners
+    /// this can be used to let listeners veto an action by returning false
+    /// this fails with the error of the first listener which throws, the listeners after it are not called
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esreflection::EsProxyBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    ///let rt = EsRuntimeBuilder::default().build();
+    ///let es_proxy = EsProxyBuilder::new(vec!["my", "biz"], "MyClass")
+    ///.constructor(|args| {
+    ///    Ok(1)
+    ///})
+    ///.event("before_save").build(&rt);
+    ///rt.eval_sync("let i = new my.biz.MyClass(); \
+    ///              i.addEventListener('before_save', (evtObj) => {\
+    ///                  return false;\
+    ///              });", "test_dispatch_event_collecting.es");
+    ///let results = es_proxy.dispatch_event_collecting(&rt, 1, "before_save", EsValueFacade::undefined())
+    ///    .ok().expect("listener failed");
+    ///let vetoed = results.iter().any(|res| res.is_boolean() && !res.get_boolean());
+    ///assert!(vetoed);
+    /// ```
+    pub fn dispatch_event_collecting(
+        &self,
+        rt: &EsRuntime,
+        obj_id: i32,
+        event_name: &'static str,
+        event_obj: EsValueFacade,
+    ) -> Result<Vec<EsValueFacade>, EsErrorInfo> {
+        let p_name = self.get_canonical_name();
+        rt.do_in_es_event_queue_sync(move |sm_rt| {
+            sm_rt.do_with_jsapi(move |_rt, cx, _global| {
+                let proxy = get_proxy(p_name.as_str()).unwrap();
+                rooted!(in (cx) let mut event_obj_root = UndefinedValue());
+                event_obj.to_es_value(cx, event_obj_root.handle_mut());
+                proxy.dispatch_event_collect(obj_id, event_name, cx, event_obj_root.handle().into())
+            })
+        })
+    }
+
     /// dispatch an event for an instance of the class
     ///
     /// you can pass an EsValueFacade as event obj
@@ -597,7 +641,7 @@ impl EsProxyBuilder {
                         },
                         move |cx, obj_id, val| {
                             let es_val = EsValueFacade::new_v(cx, val);
-                            es_setter(&obj_id, es_val)
+                            es_setter(&obj_id, es_val).map_err(SetterError::from)
                         },
                     );
                     true
@@ -653,7 +697,7 @@ impl EsProxyBuilder {
                         },
                         move |cx, val| {
                             let es_val = EsValueFacade::new_v(cx, val);
-                            es_setter(es_val)
+                            es_setter(es_val).map_err(SetterError::from)
                         },
                     );
                     true
@@ -1,19 +1,28 @@
+use std::any::Any;
 use std::{str, thread};
 
-use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
 
 use crate::es_sys_scripts;
 use crate::features;
+pub use crate::features::console::{ConsoleLevel, ConsoleLine};
 
 use crate::esruntimeinner::EsRuntimeInner;
-use crate::esvaluefacade::EsValueFacade;
-use crate::jsapi_utils::EsErrorInfo;
+use crate::esvaluefacade;
+use crate::esvaluefacade::{Deferred, EsValueFacade};
+use crate::jsapi_utils::modules::MetaBuilder;
+use crate::jsapi_utils::reflection::ProxyInfo;
+use crate::jsapi_utils::{arrays, functions, EsErrorInfo};
+use mozjs::jsval::UndefinedValue;
 
 use crate::esruntimebuilder::EsRuntimeBuilder;
 use crate::spidermonkeyruntimewrapper::SmRuntime;
 
 use std::cell::RefCell;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
 
 use hirofa_utils::js_utils::Script;
 use hirofa_utils::task_manager::TaskManager;
@@ -23,8 +32,69 @@ lazy_static! {
     static ref HELPER_TASKS: Arc<TaskManager> = Arc::new(TaskManager::new(std::cmp::max(2, num_cpus::get())));
 }
 
+/// the number of helper tasks which were added but have not completed yet
+static HELPER_TASK_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // the helper tasks of the runtime this thread works for, this is set in the worker thread of a runtime and while running one of its helper tasks
+    static CURRENT_HELPER_TASKS: RefCell<Option<Arc<HelperTaskCounter>>> = RefCell::new(None);
+}
+
+/// counts the helper tasks of a single runtime so EsRuntime::run_until_idle_sync() can wait for them
+#[derive(Default)]
+pub(crate) struct HelperTaskCounter {
+    count: Mutex<usize>,
+    done: Condvar,
+}
+
+impl HelperTaskCounter {
+    fn add(&self) {
+        *self.count.lock().unwrap() += 1;
+    }
+
+    fn remove(&self) {
+        let count = &mut *self.count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.done.notify_all();
+        }
+    }
+
+    fn count(&self) -> usize {
+        *self.count.lock().unwrap()
+    }
+
+    // wait until all helper tasks are done, returns false if they were not done within the timeout
+    fn wait_until_done(&self, timeout: Duration) -> bool {
+        let count = self.count.lock().unwrap();
+        let (count, _timeout_res) = self
+            .done
+            .wait_timeout_while(count, timeout, |count| *count > 0)
+            .unwrap();
+        *count == 0
+    }
+}
+
+// decrements the helper task counters when a helper task is done, even if it panicked
+struct HelperTaskGuard {
+    runtime_tasks: Option<Arc<HelperTaskCounter>>,
+}
+
+impl Drop for HelperTaskGuard {
+    fn drop(&mut self) {
+        HELPER_TASK_COUNT.fetch_sub(1, Ordering::SeqCst);
+        if let Some(runtime_tasks) = self.runtime_tasks.take() {
+            CURRENT_HELPER_TASKS.with(|rc| rc.replace(None));
+            runtime_tasks.remove();
+        }
+    }
+}
+
 /// the EsRuntime is a facade that adds all script todo's to the EsRuntimes's event queue so they are invoked in a single worker thread
 /// you can wait for those tasks to complete by calling the _sync variants of the public methods here
+///
+/// every EsRuntime has its own engine runtime with a single realm, intrinsics like Array.prototype are never shared between runtimes
+/// so scripts of different tenants should be run in different runtimes, the trade-off is a worker thread and a few MB of memory per runtime
 pub struct EsRuntime {
     inner: Arc<EsRuntimeInner>,
 }
@@ -35,6 +105,370 @@ pub struct EsRuntime {
 /// the EsScriptCode struct which is returned should allways contain an absolute path even if the module is loaded with a relative path
 pub type ModuleCodeLoader = dyn Fn(&str, &str) -> Option<Script> + Send + Sync + 'static;
 
+/// the ImportMetaPopulator is a closure which is called when the import.meta object of a module is created
+/// The first argument is the absolute path of the module, the MetaBuilder can be used to add properties to import.meta
+pub type ImportMetaPopulator = dyn Fn(&str, &mut MetaBuilder) + Send + Sync + 'static;
+
+/// a function which can be added to a namespace with EsRuntime::register_module_object()
+pub enum NamespaceFunction {
+    /// a function which is run in the worker thread of the runtime, its result is returned directly
+    Sync(Box<dyn Fn(Vec<EsValueFacade>) -> Result<EsValueFacade, String> + Send + 'static>),
+    /// a function which is run in a helper thread, script gets a Promise which is resolved with its result
+    Async(Arc<dyn Fn(Vec<EsValueFacade>) -> Result<EsValueFacade, String> + Send + Sync + 'static>),
+}
+
+/// the phase of a garbage collection, this is passed to the closure set with EsRuntimeBuilder::on_gc()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcPhase {
+    Begin,
+    /// the duration is the time since the collection began
+    End {
+        duration: Duration,
+    },
+}
+
+/// the GcCallback is a closure which is called when a garbage collection begins or ends
+/// it is called from the worker thread of the runtime while the collection is running so it should be fast and should not use the runtime
+pub type GcCallback = dyn Fn(GcPhase) + Send + Sync + 'static;
+
+/// the ErrorCallback is a closure which is called for uncaught errors of async tasks like promise jobs and setImmediate callbacks
+/// it is called from the worker thread of the runtime, see EsRuntimeBuilder::error_callback()
+pub type ErrorCallback = dyn Fn(EsErrorInfo) + Send + Sync + 'static;
+
+/// the RealmOptionsCallback is a closure which may alter the options of the realm of a runtime before its global object is created
+/// see EsRuntimeBuilder::realm_options()
+pub type RealmOptionsCallback = dyn Fn(&mut mozjs::jsapi::RealmOptions) + Send + Sync + 'static;
+
+/// the JobScheduler is a closure which decides when the promise jobs of a runtime are run, see EsRuntimeBuilder::job_scheduler()
+/// it is called from the worker thread of the runtime for every promise job
+pub type JobScheduler = dyn Fn(PromiseJob) + Send + Sync + 'static;
+
+/// a promise job (e.g. the reaction to a resolved Promise) which was passed to the JobScheduler of a runtime
+/// the job is not run until run() is called, dropping it without running discards the job
+pub struct PromiseJob {
+    rti_ref: Weak<EsRuntimeInner>,
+    job_obj_id: Option<usize>,
+}
+
+impl PromiseJob {
+    pub(crate) fn new(rti_ref: Weak<EsRuntimeInner>, job_obj_id: usize) -> Self {
+        PromiseJob {
+            rti_ref,
+            job_obj_id: Some(job_obj_id),
+        }
+    }
+
+    /// add the job to the end of the event queue of its runtime
+    /// this does nothing if the runtime was dropped
+    pub fn run(mut self) {
+        if let Some(job_obj_id) = self.job_obj_id.take() {
+            if let Some(rt_arc) = self.rti_ref.upgrade() {
+                rt_arc.do_in_es_event_queue(move |_sm_rt| {
+                    crate::spidermonkeyruntimewrapper::run_cached_promise_job(job_obj_id);
+                });
+            }
+        }
+    }
+}
+
+impl Drop for PromiseJob {
+    fn drop(&mut self) {
+        if let Some(job_obj_id) = self.job_obj_id.take() {
+            esvaluefacade::release_cached_object_later(&self.rti_ref, job_obj_id);
+        }
+    }
+}
+
+/// used by a function added with EsRuntime::add_global_async_function_with_progress() to report progress
+/// the values are passed to the listeners which script added with the onProgress method of the returned Promise
+#[derive(Clone)]
+pub struct ProgressSender {
+    listeners: Arc<ProgressListeners>,
+}
+
+struct ProgressListeners {
+    rti_ref: Weak<EsRuntimeInner>,
+    listeners_obj_id: usize,
+    released: Arc<AtomicBool>,
+}
+
+impl ProgressSender {
+    pub(crate) fn new(
+        rti_ref: Weak<EsRuntimeInner>,
+        listeners_obj_id: usize,
+        released: Arc<AtomicBool>,
+    ) -> Self {
+        ProgressSender {
+            listeners: Arc::new(ProgressListeners {
+                rti_ref,
+                listeners_obj_id,
+                released,
+            }),
+        }
+    }
+
+    /// send a progress value to the onProgress listeners, the listeners are called asynchronously in the worker thread of the runtime
+    /// this does nothing if the runtime was dropped
+    pub fn send(&self, progress: EsValueFacade) {
+        if let Some(rt_arc) = self.listeners.rti_ref.upgrade() {
+            let listeners_obj_id = self.listeners.listeners_obj_id;
+            rt_arc.do_in_es_event_queue(move |sm_rt| {
+                sm_rt.do_with_jsapi(|_rt, cx, global| {
+                    let listeners_obj = crate::spidermonkeyruntimewrapper::do_with_cached_object(
+                        listeners_obj_id,
+                        |epr| epr.get(),
+                    );
+                    rooted!(in (cx) let listeners_root = listeners_obj);
+                    let len = match arrays::get_array_length(cx, listeners_root.handle()) {
+                        Ok(len) => len,
+                        Err(err) => {
+                            log::error!(
+                                "could not get the onProgress listeners: {}",
+                                err.err_msg()
+                            );
+                            return;
+                        }
+                    };
+                    for x in 0..len {
+                        rooted!(in (cx) let mut listener_root = UndefinedValue());
+                        if arrays::get_array_element(
+                            cx,
+                            listeners_root.handle(),
+                            x,
+                            listener_root.handle_mut(),
+                        )
+                        .is_err()
+                        {
+                            continue;
+                        }
+                        rooted!(in (cx) let mut progress_root = UndefinedValue());
+                        progress.to_es_value(cx, progress_root.handle_mut());
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        if let Err(err) = functions::call_function_value(
+                            cx,
+                            global,
+                            listener_root.handle(),
+                            vec![*progress_root],
+                            rval.handle_mut(),
+                        ) {
+                            log::error!("onProgress listener failed: {}", err.err_msg());
+                        }
+                    }
+                });
+            });
+        }
+    }
+}
+
+impl Drop for ProgressListeners {
+    fn drop(&mut self) {
+        // the listeners are released after all progress values which were sent have been handled
+        if let Some(rt_arc) = self.rti_ref.upgrade() {
+            let listeners_obj_id = self.listeners_obj_id;
+            let released = self.released.clone();
+            rt_arc.do_in_es_event_queue(move |_sm_rt| {
+                released.store(true, Ordering::SeqCst);
+                crate::spidermonkeyruntimewrapper::release_cached_object(listeners_obj_id);
+            });
+        }
+    }
+}
+
+/// the outcome of EsRuntime::eval_sync_captured(), the result of a script and the lines it wrote to the console
+pub struct EvalOutcome {
+    pub result: Result<EsValueFacade, EsErrorInfo>,
+    pub console_lines: Vec<ConsoleLine>,
+}
+
+/// a session of a read-eval-print loop, see EsRuntime::repl_session()
+/// top-level declarations of a line persist for the lines after it, a line which is a single `let x = ...` or `const x = ...` may be declared again in a later line
+pub struct ReplSession {
+    inner: Arc<EsRuntimeInner>,
+    line_count: usize,
+}
+
+impl ReplSession {
+    /// eval a line (or multiple lines) of the repl and wait for it to complete
+    /// when the whole input is a single `let x = ...` or `const x = ...` declaration it is defined as a property of the global object
+    /// so it can be declared again in a later line, const declarations are not writable
+    /// all other input (e.g. multiple statements, multiple declarators or destructuring) is evaluated as is, so those names can't be declared again
+    pub fn eval(&mut self, line: &str) -> Result<EsValueFacade, EsErrorInfo> {
+        self.line_count += 1;
+        let code = prepare_repl_line(line);
+        let file_name = format!("repl_{}.es", self.line_count);
+        self.inner.eval_sync(code.as_str(), file_name.as_str())
+    }
+}
+
+// rewrite a line which is a single let or const declaration so it does not fail when the name is declared again in a later line
+// anything else is returned unchanged
+fn prepare_repl_line(line: &str) -> String {
+    let trimmed = line.trim();
+    let (is_const, rest) = if let Some(rest) = trimmed.strip_prefix("let ") {
+        (false, rest)
+    } else if let Some(rest) = trimmed.strip_prefix("const ") {
+        (true, rest)
+    } else {
+        return line.to_string();
+    };
+
+    // the simple form: name = expression or just name (for let)
+    let simple = match rest.find('=') {
+        Some(eq_idx) => {
+            let expr = rest[eq_idx + 1..].trim().trim_end_matches(';');
+            if expr.starts_with('=') || expr.starts_with('>') || has_top_level_separator(expr) {
+                None
+            } else {
+                Some((rest[..eq_idx].trim(), expr))
+            }
+        }
+        None if !is_const && !has_top_level_separator(rest.trim_end_matches(';')) => {
+            Some((rest.trim_end_matches(';').trim(), "undefined"))
+        }
+        None => None,
+    };
+
+    match simple {
+        Some((name, expr)) if is_es_binding_name(name) && !expr.trim().is_empty() => format!(
+            // the newline ends a comment at the end of the expression
+            "Object.defineProperty(globalThis, '{}', {{value: ({}\n), writable: {}, enumerable: true, configurable: true}}); undefined;",
+            name, expr, !is_const
+        ),
+        _ => line.to_string(),
+    }
+}
+
+// the reserved words of ES (including those which are only reserved in strict mode) which can't be used as a binding name
+const ES_RESERVED_WORDS: &[&str] = &[
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+// an identifier which is not a reserved word
+fn is_es_binding_name(name: &str) -> bool {
+    is_es_identifier(name) && !ES_RESERVED_WORDS.contains(&name)
+}
+
+fn is_es_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == '$' => {
+            chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        }
+        _ => false,
+    }
+}
+
+// check if an expression contains a , ; or line break which is not in a string, a comment or in brackets
+// e.g. `1, b = 2`, `1; b` or `1\nfoo()`, a line break may also continue the expression but then the line is just not rewritten
+fn has_top_level_separator(expr: &str) -> bool {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut in_line_comment = false;
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_line_comment {
+            if c == '\n' {
+                in_line_comment = false;
+                if depth == 0 {
+                    return true;
+                }
+            }
+            continue;
+        }
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' | '`' => quote = Some(c),
+            '/' if chars.peek() == Some(&'/') => in_line_comment = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' | ';' | '\n' if depth == 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// a function which was resolved by EsRuntime::prepare_call()
+pub struct PreparedCall {
+    rti_ref: Weak<EsRuntimeInner>,
+    this_obj_id: usize,
+    func_obj_id: usize,
+}
+
+impl PreparedCall {
+    /// call the prepared function and wait for it to complete
+    pub fn call_sync(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
+        let rt_arc = esvaluefacade::upgrade_rti_ref(&self.rti_ref)?;
+        let this_obj_id = self.this_obj_id;
+        let func_obj_id = self.func_obj_id;
+        rt_arc.do_in_es_event_queue_sync_recorded(move |sm_rt| {
+            sm_rt.call_prepared(this_obj_id, func_obj_id, args)
+        })
+    }
+}
+
+impl Drop for PreparedCall {
+    fn drop(&mut self) {
+        esvaluefacade::release_cached_object_later(&self.rti_ref, self.this_obj_id);
+        esvaluefacade::release_cached_object_later(&self.rti_ref, self.func_obj_id);
+    }
+}
+
 impl EsRuntime {
     /// create a builder to instantiate an EsRuntime
     pub fn builder() -> EsRuntimeBuilder {
@@ -44,6 +478,14 @@ impl EsRuntime {
     pub(crate) fn new_inner(inner: EsRuntimeInner) -> Self {
         let arc_inner = Arc::new(inner);
         let sm_ref_inner: Weak<EsRuntimeInner> = Arc::downgrade(&arc_inner);
+        let max_call_depth = arc_inner.config.max_call_depth;
+        let max_script_bytes = arc_inner.config.max_script_bytes;
+        let name = arc_inner.config.name.clone();
+        let realm_options = arc_inner.config.realm_options.clone();
+        let strict_string_conversion = arc_inner.config.strict_string_conversion;
+        let eager_object_max_props = arc_inner.config.eager_object_max_props;
+        let eager_string_max_len = arc_inner.config.eager_string_max_len;
+        let helper_tasks = arc_inner.helper_tasks.clone();
         let rt = EsRuntime { inner: arc_inner };
 
         // pass arc around inner to sm_rt thread
@@ -51,9 +493,19 @@ impl EsRuntime {
         rt.inner.event_loop.exe(move || {
             // todo this should also be in init_info
 
+            crate::jsapi_utils::set_max_call_depth(max_call_depth);
+            crate::jsapi_utils::set_runtime_name(name);
+            CURRENT_HELPER_TASKS.with(|rc| rc.replace(Some(helper_tasks)));
+            // the SmRuntime is created when SM_RT is first used so this needs to be set before that
+            crate::spidermonkeyruntimewrapper::set_realm_options_callback(realm_options);
+
             crate::spidermonkeyruntimewrapper::SM_RT.with(move |sm_rc: &RefCell<SmRuntime>| {
                 let sm_rt = &mut *sm_rc.borrow_mut();
                 sm_rt.opt_esrt_inner = Some(sm_ref_inner);
+                sm_rt.strict_string_conversion = strict_string_conversion;
+                sm_rt.eager_object_max_props = eager_object_max_props;
+                sm_rt.eager_string_max_len = eager_string_max_len;
+                sm_rt.init_gc_callback();
             });
         });
 
@@ -61,6 +513,17 @@ impl EsRuntime {
 
         features::init(&rt);
         es_sys_scripts::init_es(&rt);
+        // the sandbox is applied last so it can remove globals which were added by the init code
+        features::sandbox::init(&rt);
+
+        if let Some(gc_interval) = rt.inner.config.gc_interval {
+            rt.start_gc_deamon(gc_interval);
+        }
+
+        // the max script size is set after the init scripts ran so it only applies to user scripts
+        rt.inner.event_loop.exe(move || {
+            crate::jsapi_utils::scripts::set_max_script_bytes(max_script_bytes);
+        });
 
         rt
     }
@@ -94,6 +557,48 @@ impl EsRuntime {
         self.do_with_inner(move |inner| inner.eval_sync(code, file_name))
     }
 
+    /// eval a script and wait for it to complete, then wait for the promise jobs (microtasks) which were added by the script
+    /// so e.g. the then() reactions of Promise.resolve(x).then(...) have run when this returns, even if drain_jobs_after_eval was set to false
+    /// only microtasks are drained, tasks like setImmediate callbacks may still be pending when this returns
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().drain_jobs_after_eval(false).build();
+    /// rt.eval_sync_drain("let result = 0; Promise.resolve(2).then((x) => {result = x * 2;});", "test_eval_sync_drain.es")
+    ///     .ok().expect("script failed");
+    /// let esvf = rt.eval_sync("result;", "test_eval_sync_drain2.es").ok().expect("script failed");
+    /// assert_eq!(esvf.get_i32(), 4);
+    /// ```
+    pub fn eval_sync_drain(
+        &self,
+        code: &str,
+        file_name: &str,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        self.do_with_inner(move |inner| inner.eval_sync_drain(code, file_name))
+    }
+
+    /// eval a script from raw source bytes and wait for it to complete
+    /// the source may be UTF-8 or UTF-16 (LE or BE) with or without a BOM, see jsapi_utils::scripts::decode_source_bytes
+    /// an invalid source results in an EsErrorInfo instead of a panic
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_bytes_sync(b"\xEF\xBB\xBF(1 + 2);", "test_eval_bytes.es").ok().expect("script failed");
+    /// assert_eq!(esvf.get_i32(), 3);
+    /// ```
+    pub fn eval_bytes_sync(
+        &self,
+        bytes: &[u8],
+        file_name: &str,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        let decode_res = crate::jsapi_utils::scripts::decode_source_bytes(bytes, file_name);
+        let code = self.inner.record_last_error(decode_res)?;
+        self.eval_sync(code.as_str(), file_name)
+    }
+
     /// load a script module and run it
     /// # Example
     /// ```rust
@@ -109,6 +614,115 @@ impl EsRuntime {
         self.do_with_inner(|inner| inner.load_module_sync(module_src, module_file_name))
     }
 
+    /// resize the cache of compiled modules, the least recently used modules are evicted when shrinking
+    /// the initial size is set with EsRuntimeBuilder::module_cache_size()
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().module_cache_size(100).build();
+    /// // memory is getting low
+    /// rt.set_module_cache_size(10);
+    /// ```
+    pub fn set_module_cache_size(&self, size: usize) {
+        self.do_in_es_event_queue_sync(move |_sm_rt| {
+            crate::jsapi_utils::modules::set_module_cache_size(size);
+        })
+    }
+
+    /// replace the module_code_loader which was set with EsRuntimeBuilder::module_code_loader(), pass None to remove it
+    /// this is used for imports which are started after this call, modules which were already loaded stay in the module cache
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use hirofa_utils::js_utils::Script;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.set_module_source_loader(Some(Box::new(|path: &str, _ref_path: &str| {
+    ///     Some(Script::new(path, "export default 1;"))
+    /// })));
+    /// ```
+    pub fn set_module_source_loader(&self, loader: Option<Box<ModuleCodeLoader>>) {
+        self.do_with_inner(move |inner| inner.set_module_source_loader(loader))
+    }
+
+    /// store data which native functions and proxies of this runtime can get by its type, data of the same type is replaced
+    /// this way callbacks don't need to capture application state
+    /// in a callback the data can be got with SmRuntime::get_context_data()
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use spidermonkey_runtime::spidermonkeyruntimewrapper::SmRuntime;
+    ///
+    /// struct AppConfig {
+    ///     name: String,
+    /// }
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.set_context_data(AppConfig { name: "my app".to_string() });
+    /// rt.add_global_sync_function("appName", |_args| {
+    ///     let config = SmRuntime::get_current_context_data::<AppConfig>().expect("no config");
+    ///     Ok(EsValueFacade::new_str(config.name.clone()))
+    /// });
+    /// ```
+    pub fn set_context_data<T: Any + Send + Sync>(&self, data: T) {
+        self.inner.set_context_data(data)
+    }
+
+    /// get the data of a type which was stored with set_context_data()
+    pub fn get_context_data<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.inner.get_context_data()
+    }
+
+    /// get the application state of a type which was set with EsRuntimeBuilder::app_data(), this is the same as get_context_data()
+    pub fn app_data<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.inner.get_context_data()
+    }
+
+    /// create a new and independent runtime with the same configuration as this one, e.g. to build a pool of runtimes
+    /// the new runtime has its own worker thread and realm, it shares the loaders, callbacks and app_data which were set in the EsRuntimeBuilder
+    /// context data which was set with set_context_data() after the runtime was built is not shared
+    /// globals and modules which were added to this runtime after it was built (e.g. with add_global_function() or eval) are not copied
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().name("worker".to_string()).max_call_depth(500).build();
+    /// let pool: Vec<_> = (0..4).map(|_| rt.spawn_sibling()).collect();
+    /// assert_eq!(pool[0].name(), Some("worker"));
+    /// ```
+    pub fn spawn_sibling(&self) -> EsRuntime {
+        EsRuntime::new_inner(self.inner.new_sibling())
+    }
+
+    /// get the name of the runtime which was set with EsRuntimeBuilder::name()
+    pub fn name(&self) -> Option<&str> {
+        self.inner.config.name.as_deref()
+    }
+
+    /// take the error of the last eval, call or module load which the current thread did in this runtime
+    /// the error is kept in the worker thread of the runtime until it is taken or until the next operation of the current thread, a successful operation clears it
+    /// operations of other threads do not replace it
+    /// this is useful if you build an api which can't return a Result, like a C api
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let _ = rt.eval_sync("throw Error('oops');", "test_take_last_error.es");
+    /// let err = rt.take_last_error().expect("no error");
+    /// assert_eq!(err.message.as_str(), "oops");
+    /// // the error was taken
+    /// assert!(rt.take_last_error().is_none());
+    /// ```
+    pub fn take_last_error(&self) -> Option<EsErrorInfo> {
+        let caller = thread::current().id();
+        self.do_in_es_event_queue_sync(move |_sm_rt| {
+            crate::spidermonkeyruntimewrapper::take_last_error(caller)
+        })
+    }
+
     /// eval a script and wait for it to complete
     pub fn eval_void_sync(&self, code: &str, file_name: &str) -> Result<(), EsErrorInfo> {
         self.do_with_inner(move |inner| inner.eval_void_sync(code, file_name))
@@ -132,11 +746,224 @@ impl EsRuntime {
         self.do_with_inner(move |inner| inner.call_sync(obj_names, function_name, args))
     }
 
+    /// resolve a function by name once so it can be called repeatedly without looking it up again
+    /// the resolved function and the object it is a member of are kept alive until the PreparedCall is dropped
+    ///
+    /// please note that the function is resolved only once, if the script later reassigns it the
+    /// PreparedCall will keep calling the old function, call prepare_call again to get the new one
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.eval_sync("this.com = {stuff: {add: function(a, b){return a + b;}}}", "test_prepare_call.es").ok().expect("script failed");
+    /// let add = rt.prepare_call(vec!["com", "stuff"], "add").ok().expect("prepare_call failed");
+    /// for x in 0..10 {
+    ///     let res = add.call_sync(vec![EsValueFacade::new_i32(x), EsValueFacade::new_i32(1)]).ok().expect("call failed");
+    ///     assert_eq!(res.get_i32(), x + 1);
+    /// }
+    /// ```
+    pub fn prepare_call(
+        &self,
+        obj_names: Vec<&str>,
+        function_name: &str,
+    ) -> Result<PreparedCall, EsErrorInfo> {
+        let obj_names: Vec<String> = obj_names.iter().map(|n| n.to_string()).collect();
+        let f_n = function_name.to_string();
+        let (this_obj_id, func_obj_id) = self.do_in_es_event_queue_sync_recorded(move |sm_rt| {
+            sm_rt.prepare_call(obj_names.iter().map(|n| n.as_str()).collect(), f_n.as_str())
+        })?;
+        Ok(PreparedCall {
+            rti_ref: Arc::downgrade(&self.inner),
+            this_obj_id,
+            func_obj_id,
+        })
+    }
+
     /// eval a script and don't wait for it to complete
     pub fn eval(&self, eval_code: &str, file_name: &str) {
         self.do_with_inner(move |inner| inner.eval(eval_code, file_name))
     }
 
+    /// eval a script and receive the lines it writes to the console while it runs
+    /// the first returned Receiver yields the console lines as they are written in the worker thread
+    /// and the second one receives the result of the script when it is done
+    ///
+    /// the result is received instead of joined (like a JoinHandle) because no thread is started for the script,
+    /// it is added to the event queue of the runtime and sends its result from there
+    ///
+    /// please note that only lines which are written while the script itself runs are streamed,
+    /// lines written later (e.g. from a setImmediate callback or a Promise reaction) are only logged
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let (lines, result) = rt.eval_streaming("console.log('working'); 123;", "test_eval_streaming.es");
+    /// for line in lines {
+    ///     println!("{}", line.message);
+    /// }
+    /// let esvf = result.recv().unwrap().ok().expect("script failed");
+    /// assert_eq!(esvf.get_i32(), 123);
+    /// ```
+    pub fn eval_streaming(
+        &self,
+        code: &str,
+        file_name: &str,
+    ) -> (
+        Receiver<ConsoleLine>,
+        Receiver<Result<EsValueFacade, EsErrorInfo>>,
+    ) {
+        let (tx, rx) = channel();
+        let (result_tx, result_rx) = channel();
+        let eval_code = code.to_string();
+        let file_name = file_name.to_string();
+        let caller = thread::current().id();
+
+        self.do_in_es_event_queue(move |sm_rt: &SmRuntime| {
+            let prev_sink = features::console::set_console_sink(Some(tx));
+            let res = sm_rt.eval(eval_code.as_str(), file_name.as_str());
+            // restoring the previous sink drops our sender so the receiver ends
+            features::console::set_console_sink(prev_sink);
+            crate::spidermonkeyruntimewrapper::record_last_error(
+                caller,
+                res.as_ref().err().cloned(),
+            );
+            // the caller may have dropped the receiver
+            let _ = result_tx.send(res);
+        });
+
+        (rx, result_rx)
+    }
+
+    /// eval a script and collect the lines it writes to the console, both are returned in a single EvalOutcome
+    /// unlike a global console sink the lines are only captured for this script,
+    /// lines written later (e.g. from a setImmediate callback or a Promise reaction) are only logged
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let outcome = rt.eval_sync_captured("console.log('working'); 123;", "test_eval_sync_captured.es");
+    /// assert_eq!(outcome.console_lines[0].message, "working");
+    /// assert_eq!(outcome.result.ok().expect("script failed").get_i32(), 123);
+    /// ```
+    pub fn eval_sync_captured(&self, code: &str, file_name: &str) -> EvalOutcome {
+        let eval_code = code.to_string();
+        let file_name = file_name.to_string();
+        let caller = thread::current().id();
+
+        self.do_in_es_event_queue_sync(move |sm_rt: &SmRuntime| {
+            let (tx, rx) = channel();
+            let prev_sink = features::console::set_console_sink(Some(tx));
+            let result = sm_rt.eval(eval_code.as_str(), file_name.as_str());
+            features::console::set_console_sink(prev_sink);
+            crate::spidermonkeyruntimewrapper::record_last_error(
+                caller,
+                result.as_ref().err().cloned(),
+            );
+            EvalOutcome {
+                result,
+                console_lines: rx.try_iter().collect(),
+            }
+        })
+    }
+
+    /// eval a script and get multiple values it defined in one go
+    /// the values are read from the global object after the script ran, names which are not a property of the global object are read as an identifier
+    /// so top-level let and const declarations can also be extracted, keys which were not defined and reserved words like this or new are undefined
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let values = rt.eval_extract_sync(
+    ///     "const dbConfig = {host: 'localhost'}; var port = 8080;",
+    ///     "test_eval_extract_sync.es",
+    ///     &["dbConfig", "port"],
+    /// ).ok().expect("script failed");
+    /// assert_eq!(values.get("port").unwrap().get_i32(), 8080);
+    /// ```
+    pub fn eval_extract_sync(
+        &self,
+        code: &str,
+        file_name: &str,
+        keys: &[&str],
+    ) -> Result<HashMap<String, EsValueFacade>, EsErrorInfo> {
+        let code = code.to_string();
+        let file_name = file_name.to_string();
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt: &SmRuntime| {
+            sm_rt.eval(code.as_str(), file_name.as_str())?;
+
+            let mut values = HashMap::new();
+            for key in keys {
+                let value = sm_rt.do_with_jsapi(|_rt, cx, global| {
+                    rooted!(in (cx) let mut val_root = UndefinedValue());
+                    crate::jsapi_utils::objects::get_es_obj_prop_val(
+                        cx,
+                        global,
+                        key.as_str(),
+                        val_root.handle_mut(),
+                    )?;
+                    Ok(EsValueFacade::new_v(cx, val_root.handle()))
+                })?;
+                let value = if value.is_undefined() && is_es_binding_name(key.as_str()) {
+                    // top-level let and const declarations are not properties of the global object
+                    sm_rt.eval(
+                        format!("typeof {0} === 'undefined' ? undefined : {0};", key).as_str(),
+                        file_name.as_str(),
+                    )?
+                } else {
+                    value
+                };
+                values.insert(key, value);
+            }
+            Ok(values)
+        })
+    }
+
+    /// start a new read-eval-print loop session, top-level declarations of a line persist for the lines after it
+    /// and may be declared again in a later line like in the console of a browser
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let mut repl = rt.repl_session();
+    /// repl.eval("let x = 1;").ok().expect("line failed");
+    /// repl.eval("let x = x + 1;").ok().expect("line failed");
+    /// let esvf = repl.eval("x").ok().expect("line failed");
+    /// assert_eq!(esvf.get_i32(), 2);
+    /// ```
+    pub fn repl_session(&self) -> ReplSession {
+        ReplSession {
+            inner: self.inner.clone(),
+            line_count: 0,
+        }
+    }
+
+    /// check if a script compiles without running it, e.g. to lint a script while it is being edited
+    /// returns the syntax error (including its line and column) if the script does not compile
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// assert!(rt.check_syntax_sync("let a = 1;", "test_check_syntax_sync.es").is_ok());
+    /// let err = rt.check_syntax_sync("let a = ;", "test_check_syntax_sync.es").err().expect("script compiled");
+    /// println!("syntax error at {}:{}", err.lineno, err.column);
+    /// ```
+    pub fn check_syntax_sync(&self, code: &str, file_name: &str) -> Result<(), EsErrorInfo> {
+        let code = code.to_string();
+        let file_name = file_name.to_string();
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt: &SmRuntime| {
+            sm_rt.check_syntax(code.as_str(), file_name.as_str())
+        })
+    }
+
     /// call a function by name and don't wait for it to complete
     pub fn call(
         &self,
@@ -170,20 +997,221 @@ impl EsRuntime {
         self.do_with_inner(|inner| inner.do_in_es_event_queue_sync(immutable_job))
     }
 
-    /// add a task the the "helper" thread pool
-    pub fn add_helper_task<T>(task: T)
+    // run an operation in the worker thread and record its error as the last error of the calling thread
+    fn do_in_es_event_queue_sync_recorded<R: Send + 'static, J>(
+        &self,
+        job: J,
+    ) -> Result<R, EsErrorInfo>
     where
-        T: FnOnce() + Send + 'static,
+        J: FnOnce(&SmRuntime) -> Result<R, EsErrorInfo> + Send + 'static,
     {
-        log::trace!("adding a helper task");
+        self.do_with_inner(|inner| inner.do_in_es_event_queue_sync_recorded(job))
+    }
+
+    /// create a new Promise and the Deferred which resolves it
+    /// unlike EsValueFacade::new_promise() you decide when and from which thread the Promise is resolved or rejected
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use std::time::Duration;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let (prom_esvf, deferred) = rt.new_deferred();
+    /// std::thread::spawn(move || {
+    ///     // do some work and then resolve the promise
+    ///     deferred.resolve(EsValueFacade::new_i32(123));
+    /// });
+    /// let res = prom_esvf.get_promise_result_blocking(Duration::from_secs(5)).ok().expect("timed out");
+    /// assert_eq!(res.ok().expect("promise was rejected").get_i32(), 123);
+    /// ```
+    pub fn new_deferred(&self) -> (EsValueFacade, Deferred) {
+        self.do_in_es_event_queue_sync(|sm_rt| Deferred::new_pair(sm_rt))
+    }
+
+    /// list the metadata of all proxy classes which are registered in this runtime
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let infos = rt.list_proxies_sync();
+    /// assert!(infos.iter().any(|info| info.canonical_name.eq("console")));
+    /// ```
+    pub fn list_proxies_sync(&self) -> Vec<ProxyInfo> {
+        self.do_in_es_event_queue_sync(|_sm_rt| crate::jsapi_utils::reflection::list_proxies())
+    }
+
+    /// add a task the the "helper" thread pool
+    /// a task which is added from the worker thread of a runtime (or from one of its helper tasks) is waited for by run_until_idle_sync() of that runtime
+    pub fn add_helper_task<T>(task: T)
+    where
+        T: FnOnce() + Send + 'static,
+    {
+        log::trace!("adding a helper task");
 
         let tm = HELPER_TASKS.clone();
 
-        tm.add_task(task);
+        let runtime_tasks = CURRENT_HELPER_TASKS.with(|rc| rc.borrow().clone());
+        if let Some(runtime_tasks) = &runtime_tasks {
+            runtime_tasks.add();
+        }
+        HELPER_TASK_COUNT.fetch_add(1, Ordering::SeqCst);
+        tm.add_task(move || {
+            // so helper tasks which are added by this task count for the same runtime
+            CURRENT_HELPER_TASKS.with(|rc| rc.replace(runtime_tasks.clone()));
+            let _guard = HelperTaskGuard { runtime_tasks };
+            task();
+        });
+    }
+
+    /// get the number of helper tasks (e.g. the resolvers of EsValueFacade::new_promise()) which are queued or running
+    /// please note that the helper thread pool is shared by all runtimes
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntime::EsRuntime;
+    ///
+    /// if EsRuntime::helper_task_count() > 1000 {
+    ///     println!("too busy, try again later");
+    /// }
+    /// ```
+    pub fn helper_task_count() -> usize {
+        HELPER_TASK_COUNT.load(Ordering::SeqCst)
+    }
+
+    /// get the number of jobs which are waiting in the event queue of this runtime
+    /// this includes evals, calls and other jobs which were added but have not started yet
+    pub fn pending_event_queue_len(&self) -> usize {
+        self.inner.pending_event_queue_len()
+    }
+
+    /// run the event queue until it is idle and wait for that to happen
+    /// the runtime is idle when no jobs are waiting in the event queue, no promise jobs are pending and no helper tasks of this runtime are running
+    /// the helper tasks of a runtime are the tasks which were added from its worker thread (see add_helper_task()), so the resolver of an EsValueFacade::new_promise() which was created in another thread is not waited for
+    /// promise jobs which are held by a job scheduler are not waited for
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use std::time::Duration;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.eval_sync("setImmediate(() => {Promise.resolve().then(() => {console.log('done');});});", "run_until_idle_sync.es")
+    ///     .ok().expect("script failed");
+    /// rt.run_until_idle_sync(Duration::from_secs(5)).ok().expect("runtime did not become idle");
+    /// ```
+    pub fn run_until_idle_sync(&self, timeout: Duration) -> Result<(), EsErrorInfo> {
+        let deadline = Instant::now() + timeout;
+        let timeout_err = || {
+            EsErrorInfo::new(
+                format!("runtime did not become idle within {:?}", timeout),
+                "".to_string(),
+                0,
+                0,
+            )
+            .with_runtime_name(self.inner.config.name.clone())
+        };
+        loop {
+            // wait for all jobs which are in the event queue now
+            let (tx, rx) = channel();
+            self.do_in_es_event_queue(move |_sm_rt| {
+                let _ = tx.send(crate::spidermonkeyruntimewrapper::pending_microtask_count());
+            });
+            let pending_microtasks = rx
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .map_err(|_| timeout_err())?;
+
+            // helper tasks add their results to the event queue before they are done so they are checked first
+            let helper_tasks = self.inner.helper_tasks.count();
+            if pending_microtasks == 0 && self.pending_event_queue_len() == 0 {
+                if helper_tasks == 0 {
+                    return Ok(());
+                }
+                // nothing to run until the helper tasks are done
+                if !self
+                    .inner
+                    .helper_tasks
+                    .wait_until_done(deadline.saturating_duration_since(Instant::now()))
+                {
+                    return Err(timeout_err());
+                }
+            }
+        }
+    }
+
+    /// get a namespace object like com.acme, parts of the namespace which do not exist yet are created as empty objects
+    /// the returned value is an object reference (see EsValueFacade::is_object_ref()) so it can be passed back to script functions as the same object
+    /// this fails if a part of the namespace exists but is not an object or could not be created (e.g. because the global is frozen)
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let acme = rt.define_namespace_sync(vec!["com", "acme"]).ok().expect("could not define com.acme");
+    /// rt.eval_sync("this.setConfig = function(ns, cfg){ns.config = cfg;};", "define_namespace_sync.es")
+    ///     .ok().expect("script failed");
+    /// rt.call_sync(vec![], "setConfig", vec![acme, EsValueFacade::new_i32(1)])
+    ///     .ok().expect("call failed");
+    /// ```
+    pub fn define_namespace_sync(
+        &self,
+        namespace: Vec<&str>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        let namespace: Vec<String> = namespace.into_iter().map(|n| n.to_string()).collect();
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let ns_obj = crate::jsapi_utils::objects::try_get_or_define_namespace(
+                    cx,
+                    global,
+                    namespace.iter().map(|n| n.as_str()).collect(),
+                )?;
+                Ok(EsValueFacade::new_object_ref(cx, ns_obj))
+            })
+        })
+    }
+
+    /// add a namespace object with functions to the runtime, like the JSON or Math objects
+    /// parts of the namespace which do not exist yet are created, this fails if a part exists but is not an object
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntime::NamespaceFunction;
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use std::sync::Arc;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.register_module_object(
+    ///     vec!["mylib"],
+    ///     vec![
+    ///         ("add", NamespaceFunction::Sync(Box::new(|args| {
+    ///             Ok(EsValueFacade::new_i32(args[0].get_i32() + args[1].get_i32()))
+    ///         }))),
+    ///         ("fetchNumber", NamespaceFunction::Async(Arc::new(|_args| {
+    ///             Ok(EsValueFacade::new_i32(42))
+    ///         }))),
+    ///     ],
+    /// ).ok().expect("could not register mylib");
+    /// let esvf = rt.eval_sync("mylib.add(1, 2);", "test_register_module_object.es").ok().expect("script failed");
+    /// assert_eq!(esvf.get_i32(), 3);
+    /// ```
+    pub fn register_module_object(
+        &self,
+        namespace: Vec<&str>,
+        funcs: Vec<(&str, NamespaceFunction)>,
+    ) -> Result<(), EsErrorInfo> {
+        let namespace: Vec<String> = namespace.into_iter().map(|n| n.to_string()).collect();
+        let funcs: Vec<(String, NamespaceFunction)> = funcs
+            .into_iter()
+            .map(|(name, func)| (name.to_string(), func))
+            .collect();
+        self.do_with_inner(move |inner| inner.register_module_object(namespace, funcs))
     }
 
     /// add a global function to the runtime which is callable just like any other js function
     ///
+    /// the function may also be used as a template tag, the first argument is then an array of the cooked strings
+    /// with a raw prop (see EsValueFacade::get_array_props()) and the other arguments are the substitution values
+    ///
     /// # Example
     /// ```no_run
     /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
@@ -230,16 +1258,53 @@ impl EsRuntime {
             inner.add_global_async_function(name, func);
         })
     }
+
+    /// add a global async function which can report progress while it runs
+    /// the returned Promise has an onProgress(listener) method which returns the Promise so script can do `await my_func().onProgress((p) => {...});`
+    /// the listeners are called for every value passed to ProgressSender::send(), adding a listener after the function completed does nothing
+    /// this fails if the onProgress method could not be created in the runtime
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use std::time::Duration;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.add_global_async_function_with_progress("test_download", |_args, progress| {
+    ///     progress.send(EsValueFacade::new_i32(50));
+    ///     progress.send(EsValueFacade::new_i32(100));
+    ///     Ok(EsValueFacade::new_str("done".to_string()))
+    /// }).ok().expect("could not add test_download");
+    /// let esvf = rt.eval_sync("test_download().onProgress((pct) => {console.log('progress: %s', pct);});", "test_download.es")
+    ///     .ok().expect("script failed");
+    /// let prom_res = esvf.get_promise_result_blocking(Duration::from_secs(5)).ok().expect("promise timed out");
+    /// assert_eq!(prom_res.ok().expect("test_download failed").get_string(), "done");
+    /// ```
+    pub fn add_global_async_function_with_progress<F>(
+        &self,
+        name: &'static str,
+        func: F,
+    ) -> Result<(), EsErrorInfo>
+    where
+        F: Fn(Vec<EsValueFacade>, ProgressSender) -> Result<EsValueFacade, String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.do_with_inner(move |inner| inner.add_global_async_function_with_progress(name, func))
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
 
-    use crate::esruntime::EsRuntime;
+    use crate::esruntime::{EsRuntime, GcPhase, NamespaceFunction, PromiseJob};
     use crate::esvaluefacade::EsValueFacade;
+    use crate::features::console::{ConsoleLevel, ConsoleLine};
     use crate::jsapi_utils::EsErrorInfo;
     use hirofa_utils::js_utils::Script;
     use log::LevelFilter;
+    use std::sync::Arc;
     use std::thread;
     use std::time::Duration;
 
@@ -439,4 +1504,1409 @@ pub mod tests {
         assert_eq!(123, esvf_res.get_i32());
         log::info!("test: test_async_await / 6");
     }
+
+    #[test]
+    fn test_force_strict_mode() {
+        log::info!("test: test_force_strict_mode");
+        let rt = EsRuntime::builder().force_strict_mode().build();
+
+        let res = rt.eval_sync(
+            "test_force_strict_mode_undeclared = 1;",
+            "test_force_strict_mode.es",
+        );
+        assert!(res.is_err());
+
+        let esvf = rt
+            .eval_sync(
+                "(function(){return this === undefined;})();",
+                "test_force_strict_mode2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.get_boolean());
+
+        // scripts without an expression should not return "use strict"
+        let esvf = rt
+            .eval_sync(
+                "let test_force_strict_mode_a = 1;",
+                "test_force_strict_mode3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_undefined());
+
+        // without force_strict_mode implicit globals still work
+        let rt = init_test_runtime();
+        let esvf = rt
+            .eval_sync(
+                "test_force_strict_mode_undeclared = 1; test_force_strict_mode_undeclared;",
+                "test_force_strict_mode4.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 1);
+    }
+
+    #[test]
+    fn test_eval_streaming() {
+        log::info!("test: test_eval_streaming");
+        let rt = init_test_runtime();
+
+        // the script blocks in this function until the test has received the first line
+        let (proceed_tx, proceed_rx) = std::sync::mpsc::channel::<()>();
+        rt.add_global_sync_function("test_eval_streaming_wait", move |_args| {
+            proceed_rx
+                .recv_timeout(Duration::from_secs(5))
+                .map_err(|_| "timed out".to_string())?;
+            Ok(EsValueFacade::undefined())
+        });
+
+        let (lines, result) = rt.eval_streaming(
+            "console.log('step %s', 1); test_eval_streaming_wait(); console.warn('step 2'); 123;",
+            "test_eval_streaming.es",
+        );
+
+        let first = lines
+            .recv_timeout(Duration::from_secs(5))
+            .ok()
+            .expect("no line received");
+        assert_eq!(first.message.as_str(), "step 1");
+        assert_eq!(first.level, ConsoleLevel::Log);
+
+        proceed_tx.send(()).ok().expect("send failed");
+
+        let rest: Vec<ConsoleLine> = lines.iter().collect();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].message.as_str(), "step 2");
+        assert_eq!(rest[0].level, ConsoleLevel::Warn);
+
+        let esvf = result
+            .recv_timeout(Duration::from_secs(5))
+            .ok()
+            .expect("no result received")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 123);
+    }
+
+    #[test]
+    fn test_eval_bytes_sync() {
+        log::info!("test: test_eval_bytes_sync");
+        let rt = init_test_runtime();
+
+        let mut utf16_src = vec![0xFF, 0xFE];
+        for unit in "'h\u{e9}llo';".encode_utf16() {
+            utf16_src.extend_from_slice(&unit.to_le_bytes());
+        }
+        let esvf = rt
+            .eval_bytes_sync(&utf16_src, "test_eval_bytes_sync.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "h\u{e9}llo");
+
+        let err = rt
+            .eval_bytes_sync(&[0x27, 0xC3, 0x28, 0x27], "test_eval_bytes_sync2.es")
+            .err()
+            .expect("invalid source should fail");
+        assert_eq!(err.filename.as_str(), "test_eval_bytes_sync2.es");
+    }
+
+    #[test]
+    fn test_eval_invalid_source() {
+        log::info!("test: test_eval_invalid_source");
+        let rt = init_test_runtime();
+
+        // utf-8 source with an invalid byte sequence
+        let err = rt
+            .eval_bytes_sync(b"let a = 1;\n'\xC3\x28';", "test_eval_invalid_source.es")
+            .err()
+            .expect("invalid source should fail");
+        assert!(err.message.starts_with("invalid UTF-8 source"));
+
+        // a str is always valid so replacement and NUL characters in it are evaluated as is
+        let esvf = rt
+            .eval_sync("'\u{FFFD}' + '\0';", "test_eval_invalid_source2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "\u{FFFD}\0");
+        rt.load_module_sync("let b = '\u{FFFD}';", "test_eval_invalid_source.mes")
+            .ok()
+            .expect("module failed");
+    }
+
+    #[test]
+    fn test_new_deferred() {
+        log::info!("test: test_new_deferred");
+        let rt = init_test_runtime();
+
+        rt.eval_sync(
+            "this.test_new_deferred_double = function(p){return p.then((v) => {return v * 2;});};",
+            "test_new_deferred.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let (prom_esvf, deferred) = rt.new_deferred();
+        assert!(prom_esvf.is_promise());
+
+        let doubled_esvf = rt
+            .call_sync(vec![], "test_new_deferred_double", vec![prom_esvf])
+            .ok()
+            .expect("call failed");
+        assert!(doubled_esvf.is_promise());
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            deferred.resolve(EsValueFacade::new_i32(21));
+        });
+
+        let res = doubled_esvf
+            .get_promise_result_blocking(Duration::from_secs(5))
+            .ok()
+            .expect("promise timed out");
+        assert_eq!(res.ok().expect("promise was rejected").get_i32(), 42);
+
+        let (prom_esvf, deferred) = rt.new_deferred();
+        thread::spawn(move || {
+            deferred.reject(EsValueFacade::new_str("nope".to_string()));
+        });
+        let res = prom_esvf
+            .get_promise_result_blocking(Duration::from_secs(5))
+            .ok()
+            .expect("promise timed out");
+        assert_eq!(
+            res.err().expect("promise was resolved").get_string(),
+            "nope"
+        );
+    }
+
+    #[test]
+    fn test_panicking_global_function() {
+        log::info!("test: test_panicking_global_function");
+        let rt = init_test_runtime();
+
+        rt.add_global_sync_function("test_panicking_global_function", |args| {
+            if args.is_empty() {
+                panic!("i was called without args");
+            }
+            Ok(EsValueFacade::new_i32(args.len() as i32))
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let test_panic_msg; try {test_panicking_global_function();} catch(ex) {test_panic_msg = '' + ex;} test_panic_msg;",
+                "test_panicking_global_function.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_string());
+        assert!(esvf.get_string().contains("i was called without args"));
+
+        // the runtime and the function are still usable
+        let esvf = rt
+            .eval_sync(
+                "test_panicking_global_function(1, 2);",
+                "test_panicking_global_function2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 2);
+    }
+
+    #[test]
+    fn test_task_counters() {
+        log::info!("test: test_task_counters");
+        // no gc deamon here, that would add jobs to the event queue
+        let rt = EsRuntime::builder().build();
+
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let (started_tx, started_rx) = std::sync::mpsc::channel::<()>();
+
+        // block the event queue so the next jobs stay pending
+        rt.do_in_es_event_queue(move |_sm_rt| {
+            started_tx.send(()).ok().expect("send failed");
+            rx.recv_timeout(Duration::from_secs(5)).ok();
+        });
+        started_rx
+            .recv_timeout(Duration::from_secs(5))
+            .ok()
+            .expect("job did not start");
+
+        rt.eval("1 + 1;", "test_task_counters.es");
+        rt.eval("2 + 2;", "test_task_counters2.es");
+        assert_eq!(rt.pending_event_queue_len(), 2);
+
+        tx.send(()).ok().expect("send failed");
+        rt.eval_sync("3 + 3;", "test_task_counters3.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(rt.pending_event_queue_len(), 0);
+
+        let (task_tx, task_rx) = std::sync::mpsc::channel::<()>();
+        let (task_done_tx, task_done_rx) = std::sync::mpsc::channel::<()>();
+        EsRuntime::add_helper_task(move || {
+            task_rx.recv_timeout(Duration::from_secs(5)).ok();
+            task_done_tx.send(()).ok();
+        });
+        // other tests may run helper tasks at the same time so we can only check a lower bound
+        assert!(EsRuntime::helper_task_count() >= 1);
+        task_tx.send(()).ok().expect("send failed");
+        task_done_rx
+            .recv_timeout(Duration::from_secs(5))
+            .ok()
+            .expect("task did not run");
+    }
+
+    #[test]
+    fn test_set_module_cache_size() {
+        log::info!("test: test_set_module_cache_size");
+        let rt = init_test_runtime();
+
+        rt.load_module_sync(
+            "import a from 'test_cache_a.mes'; import b from 'test_cache_b.mes'; import c from 'test_cache_c.mes';",
+            "test_set_module_cache_size.mes",
+        )
+        .ok()
+        .expect("module failed");
+
+        let len =
+            rt.do_in_es_event_queue_sync(|_sm_rt| crate::jsapi_utils::modules::module_cache_len());
+        assert!(len >= 3);
+
+        rt.set_module_cache_size(1);
+        let len =
+            rt.do_in_es_event_queue_sync(|_sm_rt| crate::jsapi_utils::modules::module_cache_len());
+        assert_eq!(len, 1);
+
+        // the runtime can still load (evicted) modules
+        rt.load_module_sync(
+            "import a from 'test_cache_a.mes'; import b from 'test_cache_b.mes';",
+            "test_set_module_cache_size2.mes",
+        )
+        .ok()
+        .expect("module failed");
+        let len =
+            rt.do_in_es_event_queue_sync(|_sm_rt| crate::jsapi_utils::modules::module_cache_len());
+        assert_eq!(len, 1);
+    }
+
+    #[test]
+    fn test_set_module_source_loader() {
+        log::info!("test: test_set_module_source_loader");
+        let rt = EsRuntime::builder()
+            .module_code_loader(Box::new(|path: &str, _ref_path: &str| {
+                Some(Script::new(path, "export const a = 'first';"))
+            }))
+            .build();
+
+        let import_a = |file_name: &'static str| {
+            let code = format!("import('{}').then((res) => {{return res.a;}});", file_name);
+            rt.eval_sync(code.as_str(), "test_set_module_source_loader.es")
+                .ok()
+                .expect("script failed")
+                .get_promise_result_blocking(Duration::from_secs(10))
+                .ok()
+                .expect("promise timed out")
+                .ok()
+                .expect("import failed")
+                .get_string()
+                .to_string()
+        };
+
+        assert_eq!(import_a("test_set_loader1.mes"), "first");
+
+        rt.set_module_source_loader(Some(Box::new(|path: &str, _ref_path: &str| {
+            Some(Script::new(path, "export const a = 'second';"))
+        })));
+        assert_eq!(import_a("test_set_loader2.mes"), "second");
+        // already loaded modules come from the cache
+        assert_eq!(import_a("test_set_loader1.mes"), "first");
+    }
+
+    #[test]
+    fn test_sync_module_source_loader() {
+        log::info!("test: test_sync_module_source_loader");
+        let async_loader_used = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let async_loader_used2 = async_loader_used.clone();
+
+        let rt = EsRuntime::builder()
+            .sync_module_source_loader(Box::new(|path: &str, _ref_path: &str| {
+                if path.eq("test_sync_mod.mes") {
+                    Some(Script::new(path, "export const a = 'sync';"))
+                } else {
+                    None
+                }
+            }))
+            .module_code_loader(Box::new(move |path: &str, _ref_path: &str| {
+                async_loader_used2.store(true, std::sync::atomic::Ordering::SeqCst);
+                Some(Script::new(path, "export const a = 'async';"))
+            }))
+            .build();
+
+        let prom_esvf = rt
+            .eval_sync(
+                "import('test_sync_mod.mes').then((res) => {return res.a;});",
+                "test_sync_module_source_loader.es",
+            )
+            .ok()
+            .expect("script failed");
+        let res = prom_esvf
+            .get_promise_result_blocking(Duration::from_secs(10))
+            .ok()
+            .expect("promise timed out")
+            .ok()
+            .expect("import failed");
+        assert_eq!(res.get_string(), "sync");
+        // the module was resolved without the helper thread
+        assert!(!async_loader_used.load(std::sync::atomic::Ordering::SeqCst));
+
+        // unknown modules fall back to the async loader
+        let prom_esvf = rt
+            .eval_sync(
+                "import('test_async_mod.mes').then((res) => {return res.a;});",
+                "test_sync_module_source_loader2.es",
+            )
+            .ok()
+            .expect("script failed");
+        let res = prom_esvf
+            .get_promise_result_blocking(Duration::from_secs(10))
+            .ok()
+            .expect("promise timed out")
+            .ok()
+            .expect("import failed");
+        assert_eq!(res.get_string(), "async");
+        assert!(async_loader_used.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_max_microtasks_per_turn() {
+        log::info!("test: test_max_microtasks_per_turn");
+        let rt = EsRuntime::builder().max_microtasks_per_turn(100).build();
+
+        // without the limit the promise jobs would keep the setImmediate task from ever running
+        rt.eval_sync(
+            "this.test_flood_count = 0; this.test_flood_done = false;\
+            function test_flood(){test_flood_count++; if (!test_flood_done) {Promise.resolve().then(test_flood);}}\
+            setImmediate(() => {test_flood_done = true;});\
+            test_flood();",
+            "test_max_microtasks_per_turn.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let mut done = false;
+        for _ in 0..100 {
+            done = rt
+                .eval_sync("test_flood_done;", "test_max_microtasks_per_turn2.es")
+                .ok()
+                .expect("script failed")
+                .get_boolean();
+            if done {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(done);
+
+        let count = rt
+            .eval_sync("test_flood_count;", "test_max_microtasks_per_turn3.es")
+            .ok()
+            .expect("script failed")
+            .get_i32();
+        assert!(count > 100);
+    }
+
+    #[test]
+    fn test_import_meta_populator() {
+        log::info!("test: test_import_meta_populator");
+        let rt = EsRuntime::builder()
+            .import_meta_populator(Box::new(|path: &str, meta| {
+                let mut env = std::collections::HashMap::new();
+                env.insert(
+                    "MODE".to_string(),
+                    EsValueFacade::new_str("development".to_string()),
+                );
+                env.insert("PATH".to_string(), EsValueFacade::new_str(path.to_string()));
+                meta.set("env", EsValueFacade::new_obj(env));
+            }))
+            .build();
+
+        rt.load_module_sync(
+            "globalThis.test_meta_mode = import.meta.env.MODE; globalThis.test_meta_path = import.meta.env.PATH + '|' + import.meta.url;",
+            "test_import_meta_populator.mes",
+        )
+        .ok()
+        .expect("module failed");
+
+        let mode = rt
+            .eval_sync("test_meta_mode;", "test_import_meta_populator.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(mode.get_string(), "development");
+        let path = rt
+            .eval_sync("test_meta_path;", "test_import_meta_populator2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(
+            path.get_string(),
+            "test_import_meta_populator.mes|test_import_meta_populator.mes"
+        );
+    }
+
+    #[test]
+    fn test_on_gc() {
+        log::info!("test: test_on_gc");
+        let begins = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ends = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let begins2 = begins.clone();
+        let ends2 = ends.clone();
+
+        let rt = EsRuntime::builder()
+            .on_gc(Box::new(move |phase| match phase {
+                GcPhase::Begin => {
+                    begins2.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+                GcPhase::End { .. } => {
+                    ends2.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+            }))
+            .build();
+
+        rt.do_in_es_event_queue_sync(|sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                crate::jsapi_utils::gc(cx);
+            })
+        });
+
+        assert!(begins.load(std::sync::atomic::Ordering::SeqCst) >= 1);
+        assert_eq!(
+            begins.load(std::sync::atomic::Ordering::SeqCst),
+            ends.load(std::sync::atomic::Ordering::SeqCst)
+        );
+    }
+
+    #[test]
+    fn test_intrinsics_are_isolated() {
+        log::info!("test: test_intrinsics_are_isolated");
+        let rt1 = EsRuntime::builder().build();
+        let rt2 = EsRuntime::builder().build();
+
+        rt1.eval_sync(
+            "Array.prototype.push = function() {return 'poisoned';};",
+            "test_intrinsics_are_isolated.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let code = "let arr = []; arr.push(1);";
+        let res1 = rt1
+            .eval_sync(code, "test_intrinsics_are_isolated2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(res1.get_string(), "poisoned");
+        let res2 = rt2
+            .eval_sync(code, "test_intrinsics_are_isolated3.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(res2.get_i32(), 1);
+    }
+
+    #[test]
+    fn test_run_until_idle_sync() {
+        log::info!("test: test_run_until_idle_sync");
+        let rt = EsRuntime::builder().build();
+
+        rt.eval_sync(
+            "globalThis.test_idle_steps = []; \
+            setImmediate(() => { \
+                test_idle_steps.push('immediate'); \
+                Promise.resolve().then(() => { \
+                    test_idle_steps.push('then'); \
+                    setImmediate(() => { \
+                        test_idle_steps.push('nested immediate'); \
+                        (async function(){ await null; test_idle_steps.push('async'); })(); \
+                    }); \
+                }); \
+            });",
+            "test_run_until_idle_sync.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        rt.run_until_idle_sync(Duration::from_secs(5))
+            .ok()
+            .expect("runtime did not become idle");
+        assert_eq!(rt.pending_event_queue_len(), 0);
+
+        let steps = rt
+            .eval_sync("test_idle_steps.join(',');", "test_run_until_idle_sync2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(steps.get_string(), "immediate,then,nested immediate,async");
+
+        // a helper task which was added from the worker thread is waited for, also when it adds another helper task
+        let helper_done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let helper_done2 = helper_done.clone();
+        rt.do_in_es_event_queue_sync(move |_sm_rt| {
+            EsRuntime::add_helper_task(move || {
+                EsRuntime::add_helper_task(move || {
+                    thread::sleep(Duration::from_millis(50));
+                    helper_done2.store(true, std::sync::atomic::Ordering::SeqCst);
+                });
+            });
+        });
+        // a helper task of another thread is not waited for
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        EsRuntime::add_helper_task(move || {
+            release_rx.recv_timeout(Duration::from_secs(10)).ok();
+        });
+
+        rt.run_until_idle_sync(Duration::from_secs(5))
+            .ok()
+            .expect("runtime did not become idle");
+        assert!(helper_done.load(std::sync::atomic::Ordering::SeqCst));
+        release_tx.send(()).ok().expect("send failed");
+    }
+
+    #[test]
+    fn test_context_data() {
+        log::info!("test: test_context_data");
+        struct AppConfig {
+            name: String,
+        }
+
+        let rt = init_test_runtime();
+        assert!(rt.get_context_data::<AppConfig>().is_none());
+        rt.set_context_data(AppConfig {
+            name: "test app".to_string(),
+        });
+        assert_eq!(rt.get_context_data::<AppConfig>().unwrap().name, "test app");
+
+        rt.add_global_sync_function("test_context_data_name", |_args| {
+            let config = crate::spidermonkeyruntimewrapper::SmRuntime::get_current_context_data::<
+                AppConfig,
+            >()
+            .ok_or_else(|| "no config".to_string())?;
+            Ok(EsValueFacade::new_str(config.name.clone()))
+        });
+        let esvf = rt
+            .eval_sync("test_context_data_name();", "test_context_data.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "test app");
+    }
+
+    #[test]
+    fn test_app_data() {
+        log::info!("test: test_app_data");
+        struct AppState {
+            name: String,
+        }
+
+        let rt1 = EsRuntime::builder()
+            .app_data(AppState {
+                name: "state 1".to_string(),
+            })
+            .build();
+        let rt2 = EsRuntime::builder()
+            .app_data(AppState {
+                name: "state 2".to_string(),
+            })
+            .build();
+        assert!(init_test_runtime().app_data::<AppState>().is_none());
+        // the app data is the initial context data and is passed to siblings
+        assert_eq!(rt1.get_context_data::<AppState>().unwrap().name, "state 1");
+        rt1.set_context_data(42_i32);
+        let sibling = rt1.spawn_sibling();
+        assert_eq!(sibling.app_data::<AppState>().unwrap().name, "state 1");
+        assert!(sibling.get_context_data::<i32>().is_none());
+
+        for rt in &[&rt1, &rt2] {
+            rt.add_global_sync_function("test_app_data_name", |_args| {
+                let state =
+                    crate::spidermonkeyruntimewrapper::SmRuntime::current_app_data::<AppState>()
+                        .ok_or_else(|| "no app data".to_string())?;
+                Ok(EsValueFacade::new_str(state.name.clone()))
+            });
+        }
+        let name1 = rt1
+            .eval_sync("test_app_data_name();", "test_app_data.es")
+            .ok()
+            .expect("script failed");
+        let name2 = rt2
+            .eval_sync("test_app_data_name();", "test_app_data.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(name1.get_string(), "state 1");
+        assert_eq!(name2.get_string(), "state 2");
+    }
+
+    #[test]
+    fn test_eval_extract_sync() {
+        log::info!("test: test_eval_extract_sync");
+        let rt = init_test_runtime();
+
+        let values = rt
+            .eval_extract_sync(
+                "const test_extract_db = {host: 'localhost'}; var test_extract_port = 8080; \
+                 globalThis.test_extract_name = 'app'; let test_extract_unused = 1;",
+                "test_eval_extract_sync.es",
+                &[
+                    "test_extract_db",
+                    "test_extract_port",
+                    "test_extract_name",
+                    "test_extract_missing",
+                ],
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(values.len(), 4);
+        assert_eq!(
+            values
+                .get("test_extract_db")
+                .unwrap()
+                .get_object()
+                .get("host")
+                .unwrap()
+                .get_string(),
+            "localhost"
+        );
+        assert_eq!(values.get("test_extract_port").unwrap().get_i32(), 8080);
+        assert_eq!(values.get("test_extract_name").unwrap().get_string(), "app");
+        assert!(values.get("test_extract_missing").unwrap().is_undefined());
+
+        assert!(rt
+            .eval_extract_sync("throw Error('oops');", "test_eval_extract_sync2.es", &["a"])
+            .is_err());
+
+        // reserved words are never evaluated as an expression
+        let values = rt
+            .eval_extract_sync(
+                "1;",
+                "test_eval_extract_sync3.es",
+                &["this", "new", "typeof"],
+            )
+            .ok()
+            .expect("script failed");
+        assert!(values.values().all(|value| value.is_undefined()));
+    }
+
+    #[test]
+    fn test_repl_session() {
+        log::info!("test: test_repl_session");
+        let rt = init_test_runtime();
+        let mut repl = rt.repl_session();
+
+        let eval = |repl: &mut crate::esruntime::ReplSession, line: &str| {
+            repl.eval(line).ok().expect("line failed")
+        };
+
+        assert!(eval(&mut repl, "let test_repl_x = 1;").is_undefined());
+        assert_eq!(eval(&mut repl, "test_repl_x + 1").get_i32(), 2);
+        // declaring it again is fine in a repl
+        eval(&mut repl, "let test_repl_x = 5 // five");
+        assert_eq!(eval(&mut repl, "test_repl_x").get_i32(), 5);
+
+        eval(&mut repl, "const test_repl_c = 3");
+        // assigning a const fails silently in sloppy mode
+        assert_eq!(
+            eval(&mut repl, "test_repl_c = 4; test_repl_c;").get_i32(),
+            3
+        );
+        eval(&mut repl, "const test_repl_c = 7;");
+        assert_eq!(eval(&mut repl, "test_repl_c").get_i32(), 7);
+
+        // forms which are not rewritten to a property are evaluated as is
+        eval(&mut repl, "let test_repl_a = 1, test_repl_b = 2;");
+        eval(&mut repl, "let [test_repl_d] = [test_repl_x];");
+        assert_eq!(
+            eval(&mut repl, "test_repl_a + test_repl_b + test_repl_d").get_i32(),
+            8
+        );
+        // so they are real let declarations which can't be declared again
+        assert!(repl.eval("let test_repl_a = 3, test_repl_e = 4;").is_err());
+        eval(&mut repl, "function test_repl_f(s) {return s.length;}");
+        assert_eq!(eval(&mut repl, "test_repl_f('a, b; c')").get_i32(), 7);
+
+        // a declaration followed by another statement is not wrapped in the declaration
+        eval(&mut repl, "let test_repl_g = 1\ntest_repl_f('abc')");
+        assert_eq!(eval(&mut repl, "test_repl_g").get_i32(), 1);
+        eval(&mut repl, "let test_repl_h = 1\nlet test_repl_i = 2");
+        assert_eq!(eval(&mut repl, "test_repl_h + test_repl_i").get_i32(), 3);
+        // line breaks within brackets and comments with quotes don't prevent the rewrite
+        eval(
+            &mut repl,
+            "let test_repl_j = {\n  a: 1\n} // it's an object",
+        );
+        eval(&mut repl, "let test_repl_j = [test_repl_j.a, 2]");
+        assert_eq!(eval(&mut repl, "test_repl_j.length").get_i32(), 2);
+
+        // reserved words are not rewritten to a property so they fail like they would in a script
+        assert!(repl.eval("let if = 1").is_err());
+        assert!(repl.eval("const this = 1;").is_err());
+        assert_eq!(
+            eval(&mut repl, "typeof globalThis['if']").get_string(),
+            "undefined"
+        );
+    }
+
+    #[test]
+    fn test_realm_options() {
+        log::info!("test: test_realm_options");
+        let called = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let called2 = called.clone();
+        let rt = EsRuntime::builder()
+            .realm_options(Box::new(move |_options| {
+                called2.store(true, std::sync::atomic::Ordering::SeqCst);
+            }))
+            .build();
+        assert!(called.load(std::sync::atomic::Ordering::SeqCst));
+
+        let esvf = rt
+            .eval_sync("1 + 1;", "test_realm_options.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 2);
+    }
+
+    #[test]
+    fn test_error_callback() {
+        log::info!("test: test_error_callback");
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        let rt = EsRuntime::builder()
+            .error_callback(Box::new(move |err: EsErrorInfo| {
+                tx.lock().unwrap().send(err.message).expect("send failed");
+            }))
+            .build();
+
+        rt.eval_sync(
+            "setImmediate(() => {throw Error('immediate failed');});",
+            "test_error_callback.es",
+        )
+        .ok()
+        .expect("script failed");
+        let msg = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("error callback was not called");
+        assert!(msg.contains("immediate failed"));
+
+        // a rejection which is handled in the same job is not reported
+        rt.eval_sync(
+            "Promise.reject(Error('handled')).catch(() => {}); Promise.reject(Error('not handled'));",
+            "test_error_callback_rejection.es",
+        )
+        .ok()
+        .expect("script failed");
+        let msg = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("error callback was not called");
+        assert_eq!(msg, "unhandled promise rejection: not handled");
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // the runtime is still usable
+        let esvf = rt
+            .eval_sync("1 + 1;", "test_error_callback2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 2);
+    }
+
+    #[test]
+    fn test_runtime_name() {
+        log::info!("test: test_runtime_name");
+        let rt = EsRuntime::builder().name("tenant_1".to_string()).build();
+        assert_eq!(rt.name(), Some("tenant_1"));
+
+        let err = rt
+            .eval_sync("throw Error('oops');", "test_runtime_name.es")
+            .err()
+            .expect("script did not fail");
+        assert_eq!(err.runtime_name.as_deref(), Some("tenant_1"));
+        assert_eq!(err.clone().runtime_name.as_deref(), Some("tenant_1"));
+
+        let err = init_test_runtime()
+            .eval_sync("throw Error('oops');", "test_runtime_name.es")
+            .err()
+            .expect("script did not fail");
+        assert!(err.runtime_name.is_none());
+    }
+
+    #[test]
+    fn test_spawn_sibling() {
+        log::info!("test: test_spawn_sibling");
+        let rt = EsRuntime::builder()
+            .name("test_sibling".to_string())
+            .max_script_bytes(256)
+            .sync_module_source_loader(Box::new(|path: &str, _ref_path: &str| {
+                if path.eq("test_sibling_mod.mes") {
+                    Some(Script::new(path, "export const val = 42;"))
+                } else {
+                    None
+                }
+            }))
+            .build();
+        rt.eval_sync(
+            "globalThis.test_sibling_global = 1;",
+            "test_spawn_sibling.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let sibling = rt.spawn_sibling();
+        assert_eq!(sibling.name(), Some("test_sibling"));
+
+        // the sibling has its own realm
+        let esvf = sibling
+            .eval_sync("typeof test_sibling_global;", "test_spawn_sibling2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "undefined");
+
+        // but shares the configuration
+        sibling
+            .load_module_sync(
+                "import {val} from 'test_sibling_mod.mes'; globalThis.test_sibling_val = val;",
+                "test_spawn_sibling3.mes",
+            )
+            .ok()
+            .expect("module failed");
+        let esvf = sibling
+            .eval_sync("test_sibling_val;", "test_spawn_sibling4.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 42);
+        let big_script = format!("let test_sibling_big = '{}';", "a".repeat(300));
+        assert!(sibling
+            .eval_sync(big_script.as_str(), "test_spawn_sibling5.es")
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_script_bytes() {
+        log::info!("test: test_max_script_bytes");
+        let rt = EsRuntime::builder().max_script_bytes(64).build();
+
+        let esvf = rt
+            .eval_sync("1 + 2;", "test_max_script_bytes.es")
+            .ok()
+            .expect("small script failed");
+        assert_eq!(esvf.get_i32(), 3);
+
+        let big_script = format!("let test_big_script = '{}';", "a".repeat(100));
+        let err = rt
+            .eval_sync(big_script.as_str(), "test_max_script_bytes2.es")
+            .err()
+            .expect("big script did not fail");
+        assert!(err.message.contains("max script size"));
+        assert_eq!(err.filename.as_str(), "test_max_script_bytes2.es");
+
+        assert!(rt
+            .load_module_sync(big_script.as_str(), "test_max_script_bytes3.mes")
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        log::info!("test: test_max_call_depth");
+        let rt = EsRuntime::builder().max_call_depth(20).build();
+        let max_depth = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let max_depth2 = max_depth.clone();
+        rt.do_in_es_event_queue_sync(move |sm_rt| {
+            sm_rt.add_global_function("test_call_depth_op", move |cx, args| {
+                max_depth2.fetch_max(
+                    crate::jsapi_utils::current_call_depth(),
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+                // call the function which was passed, which calls this op again
+                let func = unsafe { mozjs::rust::Handle::from_raw(args.get(0)) };
+                rooted!(in (cx) let global = unsafe { mozjs::jsapi::CurrentGlobalOrNull(cx) });
+                rooted!(in (cx) let mut rval = mozjs::jsval::UndefinedValue());
+                match crate::jsapi_utils::functions::call_function_value(
+                    cx,
+                    global.handle(),
+                    func,
+                    vec![*func],
+                    rval.handle_mut(),
+                ) {
+                    Ok(()) => {
+                        args.rval().set(*rval);
+                        true
+                    }
+                    Err(err) => {
+                        crate::jsapi_utils::report_exception2(cx, err.message);
+                        false
+                    }
+                }
+            });
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "function test_recurse(f) {return test_call_depth_op(f);}\n\
+                 try {test_recurse(test_recurse); 'no error';} catch(ex) {ex.message;}",
+                "test_max_call_depth.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.get_string().contains("max call depth of 20"));
+        assert_eq!(max_depth.load(std::sync::atomic::Ordering::SeqCst), 20);
+
+        // the runtime is still usable
+        let esvf = rt
+            .eval_sync("1 + 1;", "test_max_call_depth2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 2);
+    }
+
+    #[test]
+    fn test_drain_jobs_after_eval() {
+        log::info!("test: test_drain_jobs_after_eval");
+        let marked = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let marked2 = marked.clone();
+
+        // draining is the default
+        let rt = EsRuntime::builder().build();
+        rt.add_global_sync_function("test_drain_mark", move |_args| {
+            marked2.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(EsValueFacade::undefined())
+        });
+
+        let res = rt
+            .eval_sync(
+                "Promise.resolve().then(() => 1).then(() => {test_drain_mark();}); 'sync';",
+                "test_drain_jobs_after_eval.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(res.get_string(), "sync");
+        // the promise jobs ran before eval_sync returned
+        assert!(marked.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_drain_jobs_after_eval_requeuing_chain() {
+        log::info!("test: test_drain_jobs_after_eval_requeuing_chain");
+        let rt = EsRuntime::builder().drain_jobs_after_eval(true).build();
+
+        // the chain is longer than the max number of drained jobs so the eval returns before it is done
+        rt.eval_sync(
+            "globalThis.test_requeue_count = 0; function test_requeue() {if (++test_requeue_count < 50000) {Promise.resolve().then(test_requeue);}}; test_requeue();",
+            "test_drain_jobs_after_eval_requeuing_chain.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        // the remaining jobs ran as microtasks after the eval job
+        let esvf = rt
+            .eval_sync(
+                "test_requeue_count;",
+                "test_drain_jobs_after_eval_requeuing_chain2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 50000);
+    }
+
+    #[test]
+    fn test_eval_sync_drain() {
+        log::info!("test: test_eval_sync_drain");
+        let rt = EsRuntime::builder().drain_jobs_after_eval(false).build();
+
+        let prom_esvf = rt
+            .eval_sync_drain(
+                "globalThis.test_drain_val = 1; Promise.resolve(2).then((x) => {test_drain_val = x * 2; return x + 1;});",
+                "test_eval_sync_drain.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(prom_esvf.is_promise());
+
+        // the then() mutation is visible without waiting for the promise
+        let esvf = rt
+            .eval_sync("test_drain_val;", "test_eval_sync_drain2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 4);
+
+        let res = prom_esvf
+            .get_promise_result_blocking(Duration::from_secs(1))
+            .ok()
+            .expect("promise timed out")
+            .ok()
+            .expect("promise was rejected");
+        assert_eq!(res.get_i32(), 3);
+    }
+
+    #[test]
+    fn test_define_namespace_sync() {
+        log::info!("test: test_define_namespace_sync");
+        let rt = init_test_runtime();
+        rt.eval_sync(
+            "globalThis.test_ns_com = {keep: 1};",
+            "test_define_namespace_sync.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let acme = rt
+            .define_namespace_sync(vec!["test_ns_com", "acme"])
+            .ok()
+            .expect("could not define namespace");
+        assert!(acme.is_object_ref());
+
+        rt.eval_sync(
+            "this.test_ns_set_config = function(ns, cfg){ns.config = cfg; return ns === test_ns_com.acme;};",
+            "test_define_namespace_sync2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let same = rt
+            .call_sync(
+                vec![],
+                "test_ns_set_config",
+                vec![acme, EsValueFacade::new_str("cfg".to_string())],
+            )
+            .ok()
+            .expect("call failed");
+        assert!(same.get_boolean());
+
+        let esvf = rt
+            .eval_sync(
+                "test_ns_com.keep + ':' + test_ns_com.acme.config;",
+                "test_define_namespace_sync3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "1:cfg");
+
+        assert!(rt
+            .define_namespace_sync(vec!["test_ns_com", "keep", "acme"])
+            .is_err());
+
+        let rt2 = EsRuntime::builder().build();
+        rt2.eval_sync(
+            "Object.freeze(globalThis);",
+            "test_define_namespace_sync4.es",
+        )
+        .ok()
+        .expect("script failed");
+        assert!(rt2.define_namespace_sync(vec!["test_ns_com"]).is_err());
+    }
+
+    #[test]
+    fn test_async_function_progress() {
+        log::info!("test: test_async_function_progress");
+        let rt = init_test_runtime();
+        rt.add_global_async_function_with_progress("test_progress_op", |_args, progress| {
+            progress.send(EsValueFacade::new_i32(1));
+            progress.send(EsValueFacade::new_i32(2));
+            Ok(EsValueFacade::new_i32(3))
+        })
+        .ok()
+        .expect("could not add test_progress_op");
+        let esvf = rt
+            .eval_sync(
+                "this.test_progress_vals = []; this.test_progress_prom = test_progress_op(); test_progress_prom.onProgress((p) => {test_progress_vals.push(p);});",
+                "test_async_function_progress.es",
+            )
+            .ok()
+            .expect("script failed");
+        let prom_res = esvf
+            .get_promise_result_blocking(Duration::from_secs(5))
+            .ok()
+            .expect("promise timed out");
+        assert_eq!(prom_res.ok().expect("promise was rejected").get_i32(), 3);
+        let vals = rt
+            .eval_sync(
+                "test_progress_vals.join(',');",
+                "test_async_function_progress2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(vals.get_string(), "1,2");
+
+        // onProgress still returns the promise after the function completed
+        rt.run_until_idle_sync(Duration::from_secs(5))
+            .ok()
+            .expect("runtime did not become idle");
+        let same = rt
+            .eval_sync(
+                "test_progress_prom.onProgress(() => {}) === test_progress_prom;",
+                "test_async_function_progress3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(same.get_boolean());
+    }
+
+    #[test]
+    fn test_eval_sync_captured() {
+        log::info!("test: test_eval_sync_captured");
+        let rt = init_test_runtime();
+        let outcome = rt.eval_sync_captured(
+            "console.log('first %s', 1); console.warn('second'); 'done';",
+            "test_eval_sync_captured.es",
+        );
+        assert_eq!(
+            outcome.result.ok().expect("script failed").get_string(),
+            "done"
+        );
+        assert_eq!(outcome.console_lines.len(), 2);
+        assert_eq!(outcome.console_lines[0].message.as_str(), "first 1");
+        assert_eq!(outcome.console_lines[1].level, ConsoleLevel::Warn);
+
+        let outcome = rt.eval_sync_captured(
+            "console.log('failing'); throw Error('boom');",
+            "test_eval_sync_captured2.es",
+        );
+        assert!(outcome.result.is_err());
+        assert_eq!(outcome.console_lines.len(), 1);
+    }
+
+    #[test]
+    fn test_check_syntax_sync() {
+        log::info!("test: test_check_syntax_sync");
+        let rt = init_test_runtime();
+        rt.check_syntax_sync(
+            "this.test_check_syntax_ran = true;",
+            "test_check_syntax_sync.es",
+        )
+        .ok()
+        .expect("script did not compile");
+        let ran = rt
+            .eval_sync(
+                "typeof test_check_syntax_ran;",
+                "test_check_syntax_sync2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(ran.get_string(), "undefined");
+
+        let err = rt
+            .check_syntax_sync("let a = 1;\nlet b = ;", "test_check_syntax_sync3.es")
+            .err()
+            .expect("script compiled");
+        assert_eq!(err.filename.as_str(), "test_check_syntax_sync3.es");
+        assert_eq!(err.lineno, 2);
+        assert!(err.column > 0);
+    }
+
+    #[test]
+    fn test_job_scheduler() {
+        log::info!("test: test_job_scheduler");
+        let jobs: Arc<std::sync::Mutex<Vec<PromiseJob>>> = Arc::new(std::sync::Mutex::new(vec![]));
+        let jobs2 = jobs.clone();
+
+        let rt = EsRuntime::builder()
+            .job_scheduler(Box::new(move |job| {
+                jobs2.lock().unwrap().push(job);
+            }))
+            .build();
+
+        rt.eval_sync(
+            "globalThis.test_sched_done = false; Promise.resolve().then(() => {test_sched_done = true;});",
+            "test_job_scheduler.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        // the job is held by the scheduler so it did not run yet
+        let done = rt
+            .eval_sync("test_sched_done;", "test_job_scheduler2.es")
+            .ok()
+            .expect("script failed");
+        assert!(!done.get_boolean());
+
+        let held: Vec<PromiseJob> = jobs.lock().unwrap().drain(..).collect();
+        assert!(!held.is_empty());
+        for job in held {
+            job.run();
+        }
+
+        let done = rt
+            .eval_sync("test_sched_done;", "test_job_scheduler3.es")
+            .ok()
+            .expect("script failed");
+        assert!(done.get_boolean());
+    }
+
+    #[test]
+    fn test_register_module_object() {
+        log::info!("test: test_register_module_object");
+        let rt = init_test_runtime();
+        rt.register_module_object(
+            vec!["mylib"],
+            vec![
+                (
+                    "add",
+                    NamespaceFunction::Sync(Box::new(|args| {
+                        Ok(EsValueFacade::new_i32(
+                            args[0].get_i32() + args[1].get_i32(),
+                        ))
+                    })),
+                ),
+                (
+                    "sub",
+                    NamespaceFunction::Sync(Box::new(|args| {
+                        Ok(EsValueFacade::new_i32(
+                            args[0].get_i32() - args[1].get_i32(),
+                        ))
+                    })),
+                ),
+                (
+                    "mul",
+                    NamespaceFunction::Async(Arc::new(|args| {
+                        Ok(EsValueFacade::new_i32(
+                            args[0].get_i32() * args[1].get_i32(),
+                        ))
+                    })),
+                ),
+            ],
+        )
+        .ok()
+        .expect("could not register mylib");
+
+        let esvf = rt
+            .eval_sync(
+                "mylib.add(1, 2) * 10 + mylib.sub(5, 3);",
+                "test_register_module_object.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 32);
+
+        let prom_esvf = rt
+            .eval_sync("mylib.mul(3, 4);", "test_register_module_object2.es")
+            .ok()
+            .expect("script failed");
+        assert!(prom_esvf.is_promise());
+        let res = prom_esvf
+            .get_promise_result_blocking(Duration::from_secs(5))
+            .ok()
+            .expect("timed out");
+        assert_eq!(res.ok().expect("promise was rejected").get_i32(), 12);
+    }
+
+    #[test]
+    fn test_take_last_error() {
+        log::info!("test: test_take_last_error");
+        let rt = Arc::new(init_test_runtime());
+
+        let res = rt.eval_sync("throw Error('take me');", "test_take_last_error.es");
+        assert!(res.is_err());
+        let err = rt.take_last_error().expect("no last error");
+        assert_eq!(err.message.as_str(), "take me");
+        assert!(rt.take_last_error().is_none());
+
+        // a successful operation clears a stale error
+        let _ = rt.eval_sync("throw Error('stale');", "test_take_last_error2.es");
+        rt.eval_sync("1 + 1;", "test_take_last_error3.es")
+            .ok()
+            .expect("script failed");
+        assert!(rt.take_last_error().is_none());
+
+        // other paths which produce an EsErrorInfo are recorded too
+        let _ = rt.check_syntax_sync("let a = ;", "test_take_last_error4.es");
+        assert!(rt.take_last_error().is_some());
+
+        // an operation of another thread does not replace the error of this thread
+        let _ = rt.eval_sync("throw Error('mine');", "test_take_last_error5.es");
+        let rt2 = rt.clone();
+        thread::spawn(move || {
+            rt2.eval_sync("1 + 1;", "test_take_last_error6.es")
+                .ok()
+                .expect("script failed");
+            assert!(rt2.take_last_error().is_none());
+        })
+        .join()
+        .expect("thread failed");
+        let err = rt.take_last_error().expect("no last error");
+        assert_eq!(err.message.as_str(), "mine");
+    }
+
+    #[test]
+    fn test_prepare_call() {
+        log::info!("test: test_prepare_call");
+        let rt = init_test_runtime();
+
+        rt.eval_sync(
+            "this.test_prepare_call_ns = {offset: 10, add: function(a){return this.offset + a;}};",
+            "test_prepare_call.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let prepared = rt
+            .prepare_call(vec!["test_prepare_call_ns"], "add")
+            .ok()
+            .expect("prepare_call failed");
+
+        for x in 0..5 {
+            let direct = rt
+                .call_sync(
+                    vec!["test_prepare_call_ns"],
+                    "add",
+                    vec![EsValueFacade::new_i32(x)],
+                )
+                .ok()
+                .expect("call_sync failed");
+            let res = prepared
+                .call_sync(vec![EsValueFacade::new_i32(x)])
+                .ok()
+                .expect("prepared call failed");
+            assert_eq!(direct.get_i32(), res.get_i32());
+            assert_eq!(res.get_i32(), 10 + x);
+        }
+
+        // a reassigned function is not picked up by the prepared call
+        rt.eval_sync(
+            "test_prepare_call_ns.add = function(a){return a;};",
+            "test_prepare_call2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = prepared
+            .call_sync(vec![EsValueFacade::new_i32(1)])
+            .ok()
+            .expect("prepared call failed");
+        assert_eq!(res.get_i32(), 11);
+
+        assert!(rt
+            .prepare_call(vec!["test_prepare_call_ns"], "offset")
+            .is_err());
+    }
+
+    #[test]
+    fn test_tagged_template() {
+        log::info!("test: test_tagged_template");
+        let rt = init_test_runtime();
+        rt.add_global_sync_function("test_sql", |args| {
+            let strings = args[0].get_array();
+            let raw = args[0]
+                .get_array_props()
+                .expect("no array props")
+                .get("raw")
+                .expect("no raw prop")
+                .get_array();
+            let mut res = String::new();
+            for (x, s) in strings.iter().enumerate() {
+                res.push_str(format!("{}|{}", s.get_string(), raw[x].get_string()).as_str());
+                if let Some(val) = args.get(x + 1) {
+                    res.push_str(format!("[{}]", val.get_i32()).as_str());
+                }
+            }
+            Ok(EsValueFacade::new_str(res))
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let test_tt_id = 12; test_sql`SELECT\\n${test_tt_id}, ${3}`;",
+                "test_tagged_template.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "SELECT\n|SELECT\\n[12], |, [3]|");
+    }
 }
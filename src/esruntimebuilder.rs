@@ -1,5 +1,12 @@
-use crate::esruntime::{EsRuntime, ModuleCodeLoader};
+use crate::esruntime::{
+    ErrorCallback, EsRuntime, GcCallback, ImportMetaPopulator, JobScheduler, ModuleCodeLoader,
+    RealmOptionsCallback,
+};
 use crate::esruntimeinner::EsRuntimeInner;
+pub use crate::features::sandbox::SandboxProfile;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// The EsRuntimeBuilder struct can be used to initialize a new EsRuntime
@@ -13,21 +20,68 @@ use std::time::Duration;
 /// ```
 ///
 
-#[derive(Default)]
 pub struct EsRuntimeBuilder {
-    gc_interval: Option<Duration>,
+    pub(crate) gc_interval: Option<Duration>,
     pub(crate) module_code_loader: Option<Box<ModuleCodeLoader>>,
+    pub(crate) sync_module_source_loader: Option<Box<ModuleCodeLoader>>,
+    pub(crate) import_meta_populator: Option<Box<ImportMetaPopulator>>,
     pub(crate) module_cache_size: usize,
+    pub(crate) eager_object_max_props: usize,
+    pub(crate) eager_string_max_len: usize,
+    pub(crate) strict_string_conversion: bool,
+    pub(crate) max_microtasks_per_turn: usize,
+    pub(crate) drain_jobs_after_eval: bool,
+    pub(crate) job_scheduler: Option<Box<JobScheduler>>,
+    pub(crate) on_gc: Option<Box<GcCallback>>,
+    pub(crate) error_callback: Option<Box<ErrorCallback>>,
+    pub(crate) realm_options: Option<Arc<RealmOptionsCallback>>,
+    pub(crate) force_strict_mode: bool,
+    pub(crate) sandbox_profile: SandboxProfile,
+    pub(crate) removed_globals: Vec<String>,
+    pub(crate) lock_globals: bool,
+    // the initial context data of the runtime, keyed by type
+    pub(crate) app_data: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    pub(crate) max_call_depth: u32,
+    pub(crate) max_script_bytes: usize,
+    pub(crate) name: Option<String>,
     built: bool,
 }
 
+impl Default for EsRuntimeBuilder {
+    fn default() -> Self {
+        let mut builder = Self::new();
+        // the derived Default this replaced did not cache modules
+        builder.module_cache_size = 0;
+        builder
+    }
+}
+
 impl EsRuntimeBuilder {
     /// create a new instance of a EsRuntimeBuilder with it's default options
     pub fn new() -> Self {
         EsRuntimeBuilder {
             gc_interval: None,
             module_code_loader: None,
+            sync_module_source_loader: None,
+            import_meta_populator: None,
             module_cache_size: 50,
+            eager_object_max_props: 10000,
+            eager_string_max_len: usize::MAX,
+            strict_string_conversion: false,
+            max_microtasks_per_turn: usize::MAX,
+            drain_jobs_after_eval: true,
+            job_scheduler: None,
+            on_gc: None,
+            error_callback: None,
+            realm_options: None,
+            force_strict_mode: false,
+            sandbox_profile: SandboxProfile::Full,
+            removed_globals: vec![],
+            lock_globals: false,
+            app_data: HashMap::new(),
+            max_call_depth: u32::MAX,
+            max_script_bytes: usize::MAX,
+            name: None,
             built: false,
         }
     }
@@ -44,6 +98,51 @@ impl EsRuntimeBuilder {
         self
     }
 
+    /// set a closure which is used to provide source code of in-memory modules
+    /// unlike the module_code_loader this loader is called directly in the worker thread of the runtime, also for dynamic imports
+    /// so it should never block, when it returns None the module_code_loader is used
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use hirofa_utils::js_utils::Script;
+    ///
+    /// let rt = EsRuntimeBuilder::default()
+    ///     .sync_module_source_loader(Box::new(|path: &str, _ref_path: &str| {
+    ///         if path.eq("mem.mes") {
+    ///             Some(Script::new(path, "export const a = 1;"))
+    ///         } else {
+    ///             None
+    ///         }
+    ///     }))
+    ///     .build();
+    /// ```
+    pub fn sync_module_source_loader(&mut self, loader: Box<ModuleCodeLoader>) -> &mut Self {
+        self.sync_module_source_loader = Some(loader);
+        self
+    }
+
+    /// set a closure which is called when the import.meta object of a module is created
+    /// this can be used to add custom properties to import.meta, the url property is always set to the path of the module
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use spidermonkey_runtime::jsapi_utils::modules::MetaBuilder;
+    /// use std::collections::HashMap;
+    ///
+    /// let rt = EsRuntimeBuilder::default()
+    ///     .import_meta_populator(Box::new(|_path: &str, meta: &mut MetaBuilder| {
+    ///         let mut env = HashMap::new();
+    ///         env.insert("MODE".to_string(), EsValueFacade::new_str("production".to_string()));
+    ///         meta.set("env", EsValueFacade::new_obj(env));
+    ///     }))
+    ///     .build();
+    /// ```
+    pub fn import_meta_populator(&mut self, populator: Box<ImportMetaPopulator>) -> &mut Self {
+        self.import_meta_populator = Some(populator);
+        self
+    }
+
     /// set the number of loaded modules you want to cache
     /// the modules are stored in a LruMap with a fixed max size
     pub fn module_cache_size(&mut self, size: usize) -> &mut Self {
@@ -51,6 +150,276 @@ impl EsRuntimeBuilder {
         self
     }
 
+    /// set the max number of properties an object may have to be converted to an EsValueFacade eagerly
+    /// objects with more properties are not copied to a HashMap but kept as a reference to the object in the runtime
+    /// those EsValueFacades will return true for is_object_ref() and you can use get_property() to get their properties
+    /// the default is 10000
+    pub fn eager_object_max_props(&mut self, max_props: usize) -> &mut Self {
+        self.eager_object_max_props = max_props;
+        self
+    }
+
+    /// set the max length (in UTF-16 code units) a string may have to be converted to an EsValueFacade eagerly
+    /// longer strings are not copied but kept as a reference to the string in the runtime
+    /// those EsValueFacades will return true for is_string_ref() and you can use string_length() and substring() to read them
+    /// the default is usize::MAX so all strings are copied
+    pub fn eager_string_max_len(&mut self, max_len: usize) -> &mut Self {
+        self.eager_string_max_len = max_len;
+        self
+    }
+
+    /// if set to true strings which contain an unpaired surrogate are not converted to a rust String when creating an EsValueFacade
+    /// but kept as a reference to the string in the runtime (just like strings longer than eager_string_max_len) so they are not altered
+    /// if false (the default) unpaired surrogates are replaced by U+FFFD
+    pub fn strict_string_conversion(&mut self, strict: bool) -> &mut Self {
+        self.strict_string_conversion = strict;
+        self
+    }
+
+    /// set the max number of microtasks (promise jobs) which may be run before the next task in the event queue is run
+    /// this prevents scripts which keep adding promise jobs from starving other tasks like setImmediate or async calls
+    /// exceeding the limit does not drop microtasks, the remaining microtasks are deferred to new tasks at the end of the event queue
+    /// the default is usize::MAX so all microtasks are run before the next task
+    pub fn max_microtasks_per_turn(&mut self, max: usize) -> &mut Self {
+        self.max_microtasks_per_turn = max;
+        self
+    }
+
+    /// if true eval_sync and eval_void_sync run the promise jobs which were added by the script before they return
+    /// so the effects of e.g. Promise.resolve().then(() => {...}) can be seen when eval_sync returns
+    /// promise jobs which were deferred by max_microtasks_per_turn or which are held by a job_scheduler are not run
+    /// at most 10000 jobs are run so a promise chain which keeps adding jobs does not block the eval forever, the remaining jobs run later
+    /// jobs are not drained when eval_sync is called from the worker thread of the runtime (e.g. from a native function)
+    /// the default is true, set it to false to run the promise jobs after eval_sync returned like previous versions did
+    pub fn drain_jobs_after_eval(&mut self, drain: bool) -> &mut Self {
+        self.drain_jobs_after_eval = drain;
+        self
+    }
+
+    /// set a closure which decides when promise jobs are run
+    /// by default promise jobs are run as microtasks before the next task in the event queue,
+    /// when a scheduler is set every job is passed to it and only runs when PromiseJob::run() is called
+    /// run() adds the job to the end of the event queue so jobs can be reordered, delayed or prioritized (e.g. fair queuing between tenants)
+    /// max_microtasks_per_turn is not used when a scheduler is set
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::default()
+    ///     .job_scheduler(Box::new(|job| {
+    ///         // a real scheduler would queue the job and run it later
+    ///         job.run();
+    ///     }))
+    ///     .build();
+    /// ```
+    pub fn job_scheduler(&mut self, scheduler: Box<JobScheduler>) -> &mut Self {
+        self.job_scheduler = Some(scheduler);
+        self
+    }
+
+    /// set the application state (e.g. a db pool or config) of the runtime, state of the same type is replaced
+    /// the state is the initial context data of the runtime (see EsRuntime::set_context_data()), it is also passed to the siblings of the runtime
+    /// native functions can get it with SmRuntime::current_app_data() so they don't need global statics
+    /// and multiple runtimes can have different application state
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use spidermonkey_runtime::spidermonkeyruntimewrapper::SmRuntime;
+    ///
+    /// struct AppState {
+    ///     db_url: String,
+    /// }
+    ///
+    /// let rt = EsRuntimeBuilder::new()
+    ///     .app_data(AppState { db_url: "db://localhost".to_string() })
+    ///     .build();
+    /// rt.add_global_sync_function("dbUrl", |_args| {
+    ///     let state = SmRuntime::current_app_data::<AppState>().expect("no app data");
+    ///     Ok(EsValueFacade::new_str(state.db_url.clone()))
+    /// });
+    /// ```
+    pub fn app_data<T: Any + Send + Sync>(&mut self, app_data: T) -> &mut Self {
+        self.app_data.insert(TypeId::of::<T>(), Arc::new(app_data));
+        self
+    }
+
+    /// set the name of the runtime, this is added to every EsErrorInfo (as runtime_name) and to the log output of the console and gc callback
+    /// so errors and log lines can be attributed to a runtime when you have multiple runtimes
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().name("tenant_1".to_string()).build();
+    /// let err = rt.eval_sync("throw Error('oops');", "name.es").err().unwrap();
+    /// assert_eq!(err.runtime_name.as_deref(), Some("tenant_1"));
+    /// ```
+    pub fn name(&mut self, name: String) -> &mut Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// set the max depth of nested native calls, e.g. a proxy method which calls a script function which calls the proxy method again
+    /// when the depth is exceeded the native function throws an error which can be caught in script instead of overflowing the stack of the worker thread
+    /// the default is u32::MAX (no limit)
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().max_call_depth(500).build();
+    /// ```
+    pub fn max_call_depth(&mut self, max_call_depth: u32) -> &mut Self {
+        self.max_call_depth = max_call_depth;
+        self
+    }
+
+    /// set the max size in bytes of a script or module source, larger sources are rejected with an error before they are compiled
+    /// this applies to eval, load_module and imported modules, but not to the init scripts of the runtime itself
+    /// the default is usize::MAX (no limit)
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().max_script_bytes(64 * 1024).build();
+    /// ```
+    pub fn max_script_bytes(&mut self, max_script_bytes: usize) -> &mut Self {
+        self.max_script_bytes = max_script_bytes;
+        self
+    }
+
+    /// set a closure which is called when a garbage collection begins and ends
+    /// this can be used to measure gc pauses and count collections
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntime::GcPhase;
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::default()
+    ///     .on_gc(Box::new(|phase: GcPhase| {
+    ///         if let GcPhase::End { duration } = phase {
+    ///             println!("gc took {:?}", duration);
+    ///         }
+    ///     }))
+    ///     .build();
+    /// ```
+    pub fn on_gc(&mut self, callback: Box<GcCallback>) -> &mut Self {
+        self.on_gc = Some(callback);
+        self
+    }
+
+    /// set a closure which may alter the options of the realm of the runtime before its global object is created
+    /// this is an escape hatch to toggle engine features (like weakrefs or streams) which have no method in this builder
+    /// please note that changing these options may destabilize the runtime, the rest of this crate is only tested with the default options
+    /// there is no option to isolate intrinsics like Array.prototype, every runtime creates a single realm of its own so they are never shared
+    /// between runtimes, use a runtime per tenant for isolation (see EsRuntime)
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::default()
+    ///     .realm_options(Box::new(|options: &mut mozjs::jsapi::RealmOptions| {
+    ///         // alter options.creationOptions_ or options.behaviors_ here
+    ///     }))
+    ///     .build();
+    /// ```
+    pub fn realm_options(&mut self, callback: Box<RealmOptionsCallback>) -> &mut Self {
+        self.realm_options = Some(Arc::from(callback));
+        self
+    }
+
+    /// set a closure which is called for uncaught errors of async tasks like promise jobs and setImmediate callbacks
+    /// and for promises which are rejected without a rejection handler (if no handler is added in the same job)
+    /// those errors can't be returned as a Result, without an error callback they are logged
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::EsErrorInfo;
+    ///
+    /// let rt = EsRuntimeBuilder::default()
+    ///     .error_callback(Box::new(|err: EsErrorInfo| {
+    ///         println!("uncaught error: {}", err.err_msg());
+    ///     }))
+    ///     .build();
+    /// ```
+    pub fn error_callback(&mut self, callback: Box<ErrorCallback>) -> &mut Self {
+        self.error_callback = Some(callback);
+        self
+    }
+
+    /// run all scripts evaluated with eval/eval_sync in strict mode
+    /// this is done by prepending "use strict" to the script, modules are always strict
+    ///
+    /// please note that in strict mode
+    /// * assigning to an undeclared variable throws a ReferenceError instead of creating a global
+    /// * `this` is undefined in functions which are called without a receiver
+    /// * the column numbers of errors on the first line of a script are shifted by the length of the prepended statement
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().force_strict_mode().build();
+    /// assert!(rt.eval_sync("my_undeclared_var = 1;", "test_strict.es").is_err());
+    /// ```
+    pub fn force_strict_mode(&mut self) -> &mut Self {
+        self.force_strict_mode = true;
+        self
+    }
+
+    /// set the profile which determines which builtins are available to scripts
+    /// use SandboxProfile::Minimal for untrusted scripts, it removes all ways to evaluate dynamic code and all timers
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esruntimebuilder::SandboxProfile;
+    ///
+    /// let rt = EsRuntimeBuilder::new().sandbox_profile(SandboxProfile::Minimal).build();
+    /// assert!(rt.eval_sync("eval('1 + 1');", "test_sandbox_profile.es").is_err());
+    /// ```
+    pub fn sandbox_profile(&mut self, profile: SandboxProfile) -> &mut Self {
+        self.sandbox_profile = profile;
+        self
+    }
+
+    /// remove a global from the runtime after it was initialized
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().remove_global("setImmediate").build();
+    /// let esvf = rt.eval_sync("typeof setImmediate;", "test_remove_global.es").ok().expect("script failed");
+    /// assert_eq!(esvf.get_string(), "undefined");
+    /// ```
+    pub fn remove_global(&mut self, name: &str) -> &mut Self {
+        self.removed_globals.push(name.to_string());
+        self
+    }
+
+    /// make all properties of the global object readonly and permanent after the runtime was initialized
+    /// so scripts can not replace or delete builtins like Math or JSON, the global object stays extensible so scripts can still add new globals
+    pub fn lock_globals(&mut self, lock: bool) -> &mut Self {
+        self.lock_globals = lock;
+        self
+    }
+
+    /// a safe baseline for untrusted scripts, this is the same as calling
+    /// sandbox_profile(SandboxProfile::Minimal) and lock_globals(true), which means
+    /// * eval, Function and WebAssembly are removed and the constructor property of functions throws instead of compiling code
+    /// * setImmediate, setTimeout, setInterval and fetch are removed
+    /// * all properties of the global object are made readonly and permanent
+    ///
+    /// you can loosen this by calling sandbox_profile() or lock_globals() after sandboxed()
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().sandboxed().build();
+    /// assert!(rt.eval_sync("eval('1');", "test_sandboxed.es").is_err());
+    /// ```
+    pub fn sandboxed(&mut self) -> &mut Self {
+        self.sandbox_profile(SandboxProfile::Minimal)
+            .lock_globals(true)
+    }
+
     /// build a new EsRuntime based on the settings of this builder
     /// please note that this can be used only once
     pub fn build(&mut self) -> EsRuntime {
@@ -60,19 +429,7 @@ impl EsRuntimeBuilder {
 
         self.built = true;
 
-        // consume opts
-
-        let mcl_opt: Option<Box<ModuleCodeLoader>> = if self.module_code_loader.is_some() {
-            std::mem::replace(&mut self.module_code_loader, None)
-        } else {
-            None
-        };
-
-        let inner = EsRuntimeInner::build(mcl_opt, self.module_cache_size);
-        let es_rt = EsRuntime::new_inner(inner);
-        if self.gc_interval.is_some() {
-            es_rt.start_gc_deamon(self.gc_interval.unwrap());
-        }
-        es_rt
+        let inner = EsRuntimeInner::build(self);
+        EsRuntime::new_inner(inner)
     }
 }
@@ -1,33 +1,160 @@
-use crate::esruntime::ModuleCodeLoader;
+use crate::esruntime::{
+    ErrorCallback, GcCallback, HelperTaskCounter, ImportMetaPopulator, JobScheduler,
+    ModuleCodeLoader, NamespaceFunction, ProgressSender, RealmOptionsCallback,
+};
+use crate::esruntimebuilder::EsRuntimeBuilder;
 use crate::esvaluefacade::EsValueFacade;
+use crate::features::sandbox::SandboxProfile;
 use crate::jsapi_utils::handles::from_raw_handle_mut;
+use crate::jsapi_utils::objects::NULL_JSOBJECT;
+use crate::jsapi_utils::{arrays, functions, objects};
 use crate::jsapi_utils::{report_exception2, EsErrorInfo};
-use crate::spidermonkeyruntimewrapper::SmRuntime;
+use crate::spidermonkeyruntimewrapper::{do_with_cached_object, register_cached_object, SmRuntime};
 use hirofa_utils::eventloop::EventLoop;
 use log::{debug, trace};
 use mozjs::jsapi::CallArgs;
-use std::sync::Arc;
+use mozjs::jsapi::{JSContext, JSObject};
+use mozjs::jsval::{ObjectValue, UndefinedValue};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+// the max number of promise jobs which are run after an eval, see EsRuntimeBuilder::drain_jobs_after_eval()
+const MAX_DRAINED_MICROTASKS: usize = 10000;
+
+// convert the arguments of a call of a global function to EsValueFacades
+fn args_to_esvf_vec(cx: *mut JSContext, args: &CallArgs) -> Vec<EsValueFacade> {
+    let mut args_vec = vec![];
+    for x in 0..args.argc_ {
+        let arg = args.get(x); // jsapi handle
+        let var_arg: mozjs::rust::HandleValue = unsafe { mozjs::rust::Handle::from_raw(arg) };
+        args_vec.push(EsValueFacade::new_v(cx, var_arg));
+    }
+    args_vec
+}
 
 pub struct EsRuntimeInner {
     pub(crate) event_loop: EventLoop,
+    // the number of jobs which were added to the event_loop but have not started yet
+    pending_jobs: Arc<AtomicUsize>,
+    // the helper tasks which were added from the worker thread of this runtime, see EsRuntime::add_helper_task()
+    pub(crate) helper_tasks: Arc<HelperTaskCounter>,
     pub(crate) _pre_cleanup_tasks: Vec<Box<dyn Fn(&EsRuntimeInner) + Send + Sync>>,
-    pub(crate) module_source_loader: Option<Box<ModuleCodeLoader>>,
+    // behind a lock so it can be replaced after the runtime was built, see EsRuntime::set_module_source_loader()
+    module_source_loader: RwLock<Option<Arc<ModuleCodeLoader>>>,
+    // data for native callbacks, keyed by type, see EsRuntime::set_context_data()
+    context_data: RwLock<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+    pub(crate) config: EsRuntimeConfig,
+}
+
+/// the settings of a runtime which were set with the EsRuntimeBuilder
+/// the siblings of a runtime get a clone, see EsRuntime::spawn_sibling()
+#[derive(Clone)]
+pub(crate) struct EsRuntimeConfig {
+    pub(crate) sync_module_source_loader: Option<Arc<ModuleCodeLoader>>,
+    pub(crate) import_meta_populator: Option<Arc<ImportMetaPopulator>>,
     pub(crate) module_cache_size: usize,
+    pub(crate) eager_object_max_props: usize,
+    pub(crate) eager_string_max_len: usize,
+    pub(crate) strict_string_conversion: bool,
+    pub(crate) max_microtasks_per_turn: usize,
+    pub(crate) drain_jobs_after_eval: bool,
+    // the callbacks are shared with the siblings of this runtime
+    pub(crate) job_scheduler: Option<Arc<JobScheduler>>,
+    pub(crate) on_gc: Option<Arc<GcCallback>>,
+    pub(crate) error_callback: Option<Arc<ErrorCallback>>,
+    pub(crate) realm_options: Option<Arc<RealmOptionsCallback>>,
+    pub(crate) force_strict_mode: bool,
+    pub(crate) sandbox_profile: SandboxProfile,
+    pub(crate) removed_globals: Vec<String>,
+    pub(crate) lock_globals: bool,
+    // the initial context data which was set with EsRuntimeBuilder::app_data()
+    pub(crate) app_data: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    pub(crate) max_call_depth: u32,
+    pub(crate) max_script_bytes: usize,
+    pub(crate) name: Option<String>,
+    pub(crate) gc_interval: Option<Duration>,
 }
 
 impl EsRuntimeInner {
-    pub(crate) fn build(
-        module_source_loader: Option<Box<ModuleCodeLoader>>,
-        module_cache_size: usize,
-    ) -> Self {
+    pub(crate) fn build(builder: &mut EsRuntimeBuilder) -> Self {
+        // consume opts
+        let module_source_loader: Option<Arc<ModuleCodeLoader>> =
+            builder.module_code_loader.take().map(Arc::from);
+
+        let config = EsRuntimeConfig {
+            sync_module_source_loader: builder.sync_module_source_loader.take().map(Arc::from),
+            import_meta_populator: builder.import_meta_populator.take().map(Arc::from),
+            module_cache_size: builder.module_cache_size,
+            eager_object_max_props: builder.eager_object_max_props,
+            eager_string_max_len: builder.eager_string_max_len,
+            strict_string_conversion: builder.strict_string_conversion,
+            max_microtasks_per_turn: builder.max_microtasks_per_turn,
+            drain_jobs_after_eval: builder.drain_jobs_after_eval,
+            job_scheduler: builder.job_scheduler.take().map(Arc::from),
+            on_gc: builder.on_gc.take().map(Arc::from),
+            error_callback: builder.error_callback.take().map(Arc::from),
+            realm_options: builder.realm_options.take(),
+            force_strict_mode: builder.force_strict_mode,
+            sandbox_profile: builder.sandbox_profile,
+            removed_globals: builder.removed_globals.drain(..).collect(),
+            lock_globals: builder.lock_globals,
+            app_data: builder.app_data.drain().collect(),
+            max_call_depth: builder.max_call_depth,
+            max_script_bytes: builder.max_script_bytes,
+            name: builder.name.take(),
+            gc_interval: builder.gc_interval,
+        };
+
+        Self::new(config, module_source_loader)
+    }
+
+    /// create a new inner with the same configuration as this one, see EsRuntime::spawn_sibling()
+    pub(crate) fn new_sibling(&self) -> Self {
+        Self::new(self.config.clone(), self.get_module_source_loader())
+    }
+
+    fn new(config: EsRuntimeConfig, module_source_loader: Option<Arc<ModuleCodeLoader>>) -> Self {
         EsRuntimeInner {
             event_loop: EventLoop::new(),
+            pending_jobs: Arc::new(AtomicUsize::new(0)),
+            helper_tasks: Arc::new(HelperTaskCounter::default()),
             _pre_cleanup_tasks: vec![],
-            module_source_loader,
-            module_cache_size,
+            module_source_loader: RwLock::new(module_source_loader),
+            context_data: RwLock::new(config.app_data.clone()),
+            config,
         }
     }
 
+    /// get the current (async) module source loader
+    pub(crate) fn get_module_source_loader(&self) -> Option<Arc<ModuleCodeLoader>> {
+        self.module_source_loader.read().unwrap().clone()
+    }
+
+    pub(crate) fn set_module_source_loader(&self, loader: Option<Box<ModuleCodeLoader>>) {
+        *self.module_source_loader.write().unwrap() = loader.map(Arc::from);
+    }
+
+    /// store data for native callbacks, data of the same type is replaced
+    pub fn set_context_data<T: Any + Send + Sync>(&self, data: T) {
+        self.context_data
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Arc::new(data));
+    }
+
+    /// get the data of a type which was stored with set_context_data()
+    pub fn get_context_data<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.context_data
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .and_then(|data| data.downcast::<T>().ok())
+    }
+
     pub fn call(
         &self,
         obj_names: Vec<&'static str>,
@@ -53,9 +180,9 @@ impl EsRuntimeInner {
     ) -> Result<EsValueFacade, EsErrorInfo> {
         trace!("call_sync {} in thread {}", function_name, thread_id::get());
         let f_n = function_name.to_string();
-        self.do_in_es_event_queue_sync(Box::new(move |sm_rt: &SmRuntime| {
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt: &SmRuntime| {
             sm_rt.call(obj_names, f_n.as_str(), args)
-        }))
+        })
     }
 
     pub fn eval(&self, eval_code: &str, file_name: &str) {
@@ -73,22 +200,62 @@ impl EsRuntimeInner {
     }
 
     pub fn eval_sync(&self, code: &str, file_name: &str) -> Result<EsValueFacade, EsErrorInfo> {
+        self.eval_sync2(code, file_name, self.config.drain_jobs_after_eval)
+    }
+
+    pub fn eval_sync_drain(
+        &self,
+        code: &str,
+        file_name: &str,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        self.eval_sync2(code, file_name, true)
+    }
+
+    fn eval_sync2(
+        &self,
+        code: &str,
+        file_name: &str,
+        drain: bool,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
         debug!("eval_sync {} in thread {}", code, thread_id::get());
         let eval_code = code.to_string();
         let file_name = file_name.to_string();
 
-        self.do_in_es_event_queue_sync(Box::new(move |sm_rt: &SmRuntime| {
-            sm_rt.eval(eval_code.as_str(), file_name.as_str())
-        }))
+        let drain = drain && Self::may_drain_microtasks();
+
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt: &SmRuntime| {
+            let res = sm_rt.eval(eval_code.as_str(), file_name.as_str());
+            if drain {
+                crate::spidermonkeyruntimewrapper::drain_microtasks(MAX_DRAINED_MICROTASKS);
+            }
+            res
+        })
     }
 
     pub fn eval_void_sync(&self, code: &str, file_name: &str) -> Result<(), EsErrorInfo> {
         let eval_code = code.to_string();
         let file_name = file_name.to_string();
 
-        self.do_in_es_event_queue_sync(Box::new(move |sm_rt: &SmRuntime| {
-            sm_rt.eval_void(eval_code.as_str(), file_name.as_str())
-        }))
+        let drain = self.config.drain_jobs_after_eval && Self::may_drain_microtasks();
+
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt: &SmRuntime| {
+            let res = sm_rt.eval_void(eval_code.as_str(), file_name.as_str());
+            if drain {
+                crate::spidermonkeyruntimewrapper::drain_microtasks(MAX_DRAINED_MICROTASKS);
+            }
+            res
+        })
+    }
+
+    // when an eval is called from the worker thread (e.g. from a native function) it runs inline in the current job
+    // draining there would run promise jobs while the calling script is still running so it is skipped
+    fn may_drain_microtasks() -> bool {
+        if EventLoop::is_a_pool_thread() {
+            trace!("not draining microtasks in the worker thread");
+            false
+        } else {
+            true
+        }
     }
 
     pub fn load_module_sync(
@@ -99,9 +266,9 @@ impl EsRuntimeInner {
         let module_src_str = module_src.to_string();
         let module_file_name_str = module_file_name.to_string();
 
-        self.do_in_es_event_queue_sync(Box::new(move |sm_rt: &SmRuntime| {
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt: &SmRuntime| {
             sm_rt.load_module(module_src_str.as_str(), module_file_name_str.as_str())
-        }))
+        })
     }
 
     pub(crate) fn cleanup_sync(&self) {
@@ -114,6 +281,40 @@ impl EsRuntimeInner {
         // reset cleaning var here
     }
 
+    /// run an operation in the worker thread and wait for it, its error is recorded as the last error of the calling thread in the same job
+    /// see EsRuntime::take_last_error()
+    pub(crate) fn do_in_es_event_queue_sync_recorded<R: Send + 'static, J>(
+        &self,
+        job: J,
+    ) -> Result<R, EsErrorInfo>
+    where
+        J: FnOnce(&SmRuntime) -> Result<R, EsErrorInfo> + Send + 'static,
+    {
+        let caller = std::thread::current().id();
+        self.do_in_es_event_queue_sync(move |sm_rt| {
+            let res = job(sm_rt);
+            crate::spidermonkeyruntimewrapper::record_last_error(
+                caller,
+                res.as_ref().err().cloned(),
+            );
+            res
+        })
+    }
+
+    /// record the result of an operation which failed before it reached the worker thread as the last error of the calling thread
+    /// the record is queued so it is stored before a later take_last_error() of the calling thread runs
+    pub(crate) fn record_last_error<R>(
+        &self,
+        res: Result<R, EsErrorInfo>,
+    ) -> Result<R, EsErrorInfo> {
+        let caller = std::thread::current().id();
+        let err = res.as_ref().err().cloned();
+        self.do_in_es_event_queue(move |_sm_rt| {
+            crate::spidermonkeyruntimewrapper::record_last_error(caller, err);
+        });
+        res
+    }
+
     pub fn do_in_es_event_queue<J>(&self, job: J)
     where
         J: FnOnce(&SmRuntime) + Send + 'static,
@@ -121,7 +322,12 @@ impl EsRuntimeInner {
         trace!("do_in_spidermonkey_runtime_thread");
         // this is executed in the single thread in the Threadpool, therefore Runtime and global are stored in a thread_local
 
-        let async_job = || {
+        let pending_jobs = self.pending_jobs.clone();
+        pending_jobs.fetch_add(1, Ordering::SeqCst);
+
+        let async_job = move || {
+            pending_jobs.fetch_sub(1, Ordering::SeqCst);
+            crate::spidermonkeyruntimewrapper::reset_microtask_count();
             crate::spidermonkeyruntimewrapper::SM_RT.with(|sm_rt| {
                 debug!("got rt from thread_local");
                 job(&mut sm_rt.borrow())
@@ -131,6 +337,10 @@ impl EsRuntimeInner {
         self.event_loop.add_void(async_job);
     }
 
+    pub(crate) fn pending_event_queue_len(&self) -> usize {
+        self.pending_jobs.load(Ordering::SeqCst)
+    }
+
     pub fn do_in_es_event_queue_sync<R: Send + 'static, J>(&self, job: J) -> R
     where
         J: FnOnce(&SmRuntime) -> R + Send + 'static,
@@ -138,7 +348,12 @@ impl EsRuntimeInner {
         trace!("do_in_spidermonkey_runtime_thread_sync");
         // this is executed in the single thread in the Threadpool, therefore Runtime and global are stored in a thread_local
 
-        let job = || {
+        let pending_jobs = self.pending_jobs.clone();
+        pending_jobs.fetch_add(1, Ordering::SeqCst);
+
+        let job = move || {
+            pending_jobs.fetch_sub(1, Ordering::SeqCst);
+            crate::spidermonkeyruntimewrapper::reset_microtask_count();
             crate::spidermonkeyruntimewrapper::SM_RT.with(|sm_rt| {
                 debug!("got rt from thread_local");
                 job(&mut sm_rt.borrow())
@@ -155,14 +370,7 @@ impl EsRuntimeInner {
         let func_rc = Arc::new(func);
         self.do_in_es_event_queue_sync(move |sm_rt| {
             sm_rt.add_global_function(name, move |cx, args: CallArgs| {
-                let mut args_vec = vec![];
-
-                for x in 0..args.argc_ {
-                    let arg = args.get(x); // jsapi handle
-                    let var_arg: mozjs::rust::HandleValue =
-                        unsafe { mozjs::rust::Handle::from_raw(arg) };
-                    args_vec.push(EsValueFacade::new_v(cx, var_arg));
-                }
+                let args_vec = args_to_esvf_vec(cx, &args);
 
                 let func_rc_clone = func_rc.clone();
                 let prom_res_esvf = EsValueFacade::new_promise(move || func_rc_clone(args_vec));
@@ -173,20 +381,105 @@ impl EsRuntimeInner {
         });
     }
 
+    pub fn add_global_async_function_with_progress<F>(
+        &self,
+        name: &'static str,
+        func: F,
+    ) -> Result<(), EsErrorInfo>
+    where
+        F: Fn(Vec<EsValueFacade>, ProgressSender) -> Result<EsValueFacade, String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let func_rc = Arc::new(func);
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt| {
+            let rti_ref = Arc::downgrade(&sm_rt.clone_esrt_inner());
+            // onProgress is a script closure around the promise so it returns the promise as long as it lives
+            let factory_res: Result<usize, EsErrorInfo> = sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                rooted!(in (cx) let mut factory_root = std::ptr::null_mut::<mozjs::jsapi::JSFunction>());
+                functions::compile_function(
+                    cx,
+                    false,
+                    "on_progress_factory",
+                    "return function onProgress(listener) {add_listener(listener); return prom;};",
+                    vec!["add_listener", "prom"],
+                    factory_root.handle_mut(),
+                )?;
+                Ok(register_cached_object(cx, *factory_root as *mut JSObject))
+            });
+            let on_progress_factory_id = factory_res?;
+            sm_rt.add_global_function(name, move |cx, args: CallArgs| {
+                let args_vec = args_to_esvf_vec(cx, &args);
+
+                // the listeners added by onProgress are stored in an array which lives as long as the ProgressSender
+                rooted!(in (cx) let mut listeners_root = NULL_JSOBJECT);
+                arrays::new_array(cx, listeners_root.handle_mut());
+                let listeners_obj_id = register_cached_object(cx, *listeners_root);
+                let released = Arc::new(AtomicBool::new(false));
+                let progress_sender =
+                    ProgressSender::new(rti_ref.clone(), listeners_obj_id, released.clone());
+
+                let func_rc_clone = func_rc.clone();
+                let prom_res_esvf =
+                    EsValueFacade::new_promise(move || func_rc_clone(args_vec, progress_sender));
+                let rval = from_raw_handle_mut(args.rval());
+                prom_res_esvf.to_es_value(cx, rval);
+                rooted!(in (cx) let prom_root = from_raw_handle_mut(args.rval()).get().to_object());
+
+                rooted!(in (cx) let mut add_listener_root = NULL_JSOBJECT);
+                functions::new_callback(
+                    cx,
+                    add_listener_root.handle_mut(),
+                    move |cx, args, _rval| {
+                        if args.is_empty() || !functions::value_is_function(cx, args[0]) {
+                            return Err("onProgress expects a function".to_string());
+                        }
+                        if released.load(Ordering::SeqCst) {
+                            // the function completed, there will be no more progress
+                            return Ok(());
+                        }
+                        let listeners_obj =
+                            do_with_cached_object(listeners_obj_id, |epr| epr.get());
+                        rooted!(in (cx) let listeners_root = listeners_obj);
+                        arrays::push_array_element(cx, listeners_root.handle(), args[0])
+                            .map_err(|err| err.err_msg())
+                    },
+                );
+
+                let factory_obj = do_with_cached_object(on_progress_factory_id, |epr| epr.get());
+                rooted!(in (cx) let factory_root = factory_obj as *mut mozjs::jsapi::JSFunction);
+                rooted!(in (cx) let mut on_progress_val = UndefinedValue());
+                rooted!(in (cx) let this_obj = NULL_JSOBJECT);
+                if let Err(err) = functions::call_function(
+                    cx,
+                    this_obj.handle(),
+                    factory_root.handle(),
+                    vec![ObjectValue(*add_listener_root), ObjectValue(*prom_root)],
+                    on_progress_val.handle_mut(),
+                ) {
+                    report_exception2(cx, err.err_msg());
+                    return false;
+                }
+                objects::set_es_obj_prop_value(
+                    cx,
+                    prom_root.handle(),
+                    "onProgress",
+                    on_progress_val.handle(),
+                );
+                true
+            });
+            Ok(())
+        })
+    }
+
     pub fn add_global_sync_function<F>(&self, name: &'static str, func: F)
     where
         F: Fn(Vec<EsValueFacade>) -> Result<EsValueFacade, String> + Send + 'static,
     {
         self.do_in_es_event_queue_sync(move |sm_rt| {
             sm_rt.add_global_function(name, move |cx, args: CallArgs| {
-                let mut args_vec = vec![];
-
-                for x in 0..args.argc_ {
-                    let arg = args.get(x); // jsapi handle
-                    let var_arg: mozjs::rust::HandleValue =
-                        unsafe { mozjs::rust::Handle::from_raw(arg) };
-                    args_vec.push(EsValueFacade::new_v(cx, var_arg));
-                }
+                let args_vec = args_to_esvf_vec(cx, &args);
 
                 let func_res = func(args_vec);
                 match func_res {
@@ -206,6 +499,56 @@ impl EsRuntimeInner {
             });
         });
     }
+
+    pub(crate) fn register_module_object(
+        &self,
+        namespace: Vec<String>,
+        funcs: Vec<(String, NamespaceFunction)>,
+    ) -> Result<(), EsErrorInfo> {
+        self.do_in_es_event_queue_sync_recorded(move |sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let ns_names: Vec<&str> = namespace.iter().map(|n| n.as_str()).collect();
+                rooted!(in (cx) let ns_root = objects::try_get_or_define_namespace(cx, global, ns_names)?);
+
+                for (name, func) in funcs {
+                    rooted!(in (cx) let mut func_root = NULL_JSOBJECT);
+                    match func {
+                        NamespaceFunction::Sync(func) => {
+                            functions::new_callback(cx, func_root.handle_mut(), move |cx, args, rval| {
+                                let args_vec: Vec<EsValueFacade> = args
+                                    .into_iter()
+                                    .map(|arg| EsValueFacade::new_v(cx, arg))
+                                    .collect();
+                                let esvf = func(args_vec)?;
+                                esvf.to_es_value(cx, rval);
+                                Ok(())
+                            });
+                        }
+                        NamespaceFunction::Async(func) => {
+                            functions::new_callback(cx, func_root.handle_mut(), move |cx, args, rval| {
+                                let args_vec: Vec<EsValueFacade> = args
+                                    .into_iter()
+                                    .map(|arg| EsValueFacade::new_v(cx, arg))
+                                    .collect();
+                                let func = func.clone();
+                                let prom_esvf = EsValueFacade::new_promise(move || func(args_vec));
+                                prom_esvf.to_es_value(cx, rval);
+                                Ok(())
+                            });
+                        }
+                    }
+                    rooted!(in (cx) let func_val_root = ObjectValue(*func_root));
+                    objects::set_es_obj_prop_value(
+                        cx,
+                        ns_root.handle(),
+                        name.as_str(),
+                        func_val_root.handle(),
+                    );
+                }
+                Ok(())
+            })
+        })
+    }
 }
 
 impl Drop for EsRuntimeInner {
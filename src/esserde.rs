@@ -0,0 +1,1063 @@
+//! # esserde
+//!
+//! serde support for EsValueFacade
+//!
+//! this mod allows you to read data objects which were produced by script into rust structs
+//! and to pass rust structs to script without manually constructing HashMaps
+//! only a data-only subset is supported (objects, arrays, strings, numbers, booleans and null/undefined), functions and promises will result in an error
+//! the properties of object references and the value of string references are read from the script engine when deserializing
+//!
+//! # Example
+//!
+//! ```no_run
+//! use serde::Deserialize;
+//! use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use spidermonkey_runtime::esserde::from_esvf;
+//!
+//! #[derive(Deserialize)]
+//! struct MyConfig {
+//!     name: String,
+//!     port: u16,
+//!     hosts: Vec<String>,
+//! }
+//!
+//! let rt = EsRuntimeBuilder::new().build();
+//! let esvf = rt.eval_sync("({name: 'my_app', port: 8080, hosts: ['a', 'b']});", "my_config.es")
+//!     .ok().expect("script failed");
+//! let cfg: MyConfig = from_esvf(&esvf).ok().expect("invalid config");
+//! assert_eq!(cfg.port, 8080);
+//! ```
+//!
+//! ```no_run
+//! use serde::Serialize;
+//! use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use spidermonkey_runtime::esserde::to_esvf;
+//!
+//! #[derive(Serialize)]
+//! struct User {
+//!     name: String,
+//!     age: u8,
+//! }
+//!
+//! let rt = EsRuntimeBuilder::new().build();
+//! rt.eval_sync("this.greet = function(user){return 'hi ' + user.name;};", "greet.es")
+//!     .ok().expect("script failed");
+//! let user = User{name: "Jane".to_string(), age: 31};
+//! let esvf = rt.call_sync(vec![], "greet", vec![to_esvf(&user).ok().expect("invalid user")])
+//!     .ok().expect("greet failed");
+//! assert_eq!(esvf.get_string(), "hi Jane");
+//! ```
+
+use crate::esvaluefacade::EsValueFacade;
+use crate::jsapi_utils::objects::PropKey;
+use crate::jsapi_utils::EsErrorInfo;
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{Deserializer, Serializer};
+use std::collections::hash_map::Iter as HashMapIter;
+use std::collections::HashMap;
+use std::fmt;
+use std::slice::Iter as SliceIter;
+use std::vec::IntoIter as VecIntoIter;
+
+/// error which is returned when an EsValueFacade could not be converted to or from a rust type
+#[derive(Debug, Clone)]
+pub struct EsSerdeError {
+    pub message: String,
+}
+
+impl fmt::Display for EsSerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message.as_str())
+    }
+}
+
+impl std::error::Error for EsSerdeError {}
+
+impl serde::ser::Error for EsSerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        EsSerdeError {
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl serde::de::Error for EsSerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        EsSerdeError {
+            message: msg.to_string(),
+        }
+    }
+}
+
+// reading an object or string reference from the script engine failed
+fn ref_error(err: EsErrorInfo) -> EsSerdeError {
+    EsSerdeError {
+        message: format!("could not read a reference: {}", err.err_msg()),
+    }
+}
+
+/// deserialize an EsValueFacade into a rust type
+/// # Example
+/// ```no_run
+/// use serde::Deserialize;
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::esserde::from_esvf;
+///
+/// #[derive(Deserialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// let esvf = rt.eval_sync("({x: 1, y: 2});", "test_from_esvf.es").ok().expect("script failed");
+/// let point: Point = from_esvf(&esvf).ok().expect("not a point");
+/// assert_eq!(point.y, 2);
+/// ```
+pub fn from_esvf<T>(esvf: &EsValueFacade) -> Result<T, EsSerdeError>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(EsValueFacadeDeserializer { esvf })
+}
+
+/// serde Deserializer which reads from an EsValueFacade
+pub struct EsValueFacadeDeserializer<'a> {
+    esvf: &'a EsValueFacade,
+}
+
+impl<'a> EsValueFacadeDeserializer<'a> {
+    pub fn new(esvf: &'a EsValueFacade) -> Self {
+        EsValueFacadeDeserializer { esvf }
+    }
+
+    fn unsupported(&self) -> EsSerdeError {
+        let type_name = if self.esvf.is_function() {
+            "a function"
+        } else if self.esvf.is_promise() {
+            "a promise"
+        } else {
+            "an unsupported value"
+        };
+        EsSerdeError {
+            message: format!(
+                "can not deserialize {}, only data values are supported",
+                type_name
+            ),
+        }
+    }
+
+    /// get a double value as a whole number, only works when the double has no fraction
+    fn get_whole_f64(&self) -> Option<f64> {
+        if self.esvf.is_f64() {
+            let f = self.esvf.get_f64();
+            if f.is_finite() && f.fract() == 0.0 {
+                return Some(f);
+            }
+        }
+        None
+    }
+}
+
+// numbers outside of the i32 range are passed from script as doubles, accept those for integer types
+macro_rules! deserialize_whole_number {
+    ($method:ident) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value, EsSerdeError>
+        where
+            V: Visitor<'de>,
+        {
+            match self.get_whole_f64() {
+                Some(f) if f < 0.0 => visitor.visit_i64(f as i64),
+                Some(f) => visitor.visit_u64(f as u64),
+                None => self.deserialize_any(visitor),
+            }
+        }
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for EsValueFacadeDeserializer<'a> {
+    type Error = EsSerdeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, EsSerdeError>
+    where
+        V: Visitor<'de>,
+    {
+        let esvf = self.esvf;
+        if esvf.is_undefined() || esvf.is_null() {
+            visitor.visit_unit()
+        } else if esvf.is_boolean() {
+            visitor.visit_bool(esvf.get_boolean())
+        } else if esvf.is_i32() {
+            visitor.visit_i32(esvf.get_i32())
+        } else if esvf.is_f64() {
+            visitor.visit_f64(esvf.get_f64())
+        } else if esvf.is_string() {
+            visitor.visit_str(esvf.get_string())
+        } else if esvf.is_string_ref() {
+            visitor.visit_string(esvf.to_string_lossy().map_err(ref_error)?)
+        } else if esvf.is_array() {
+            visitor.visit_seq(EsSeqAccess {
+                iter: esvf.get_array().iter(),
+            })
+        } else if esvf.is_object() {
+            visitor.visit_map(EsMapAccess {
+                iter: esvf.get_object().iter(),
+                value: None,
+            })
+        } else if esvf.is_object_ref() {
+            // the enumerable own props are read one by one like the props of an eagerly copied object
+            let mut entries = vec![];
+            for key in esvf
+                .get_all_own_prop_names(false, false)
+                .map_err(ref_error)?
+            {
+                if let PropKey::String(name) = key {
+                    let value = esvf.get_property(name.as_str()).map_err(ref_error)?;
+                    entries.push((name, value));
+                }
+            }
+            visitor.visit_map(EsRefMapAccess {
+                iter: entries.into_iter(),
+                value: None,
+            })
+        } else {
+            Err(self.unsupported())
+        }
+    }
+
+    deserialize_whole_number!(deserialize_i8);
+    deserialize_whole_number!(deserialize_i16);
+    deserialize_whole_number!(deserialize_i32);
+    deserialize_whole_number!(deserialize_i64);
+    deserialize_whole_number!(deserialize_u8);
+    deserialize_whole_number!(deserialize_u16);
+    deserialize_whole_number!(deserialize_u32);
+    deserialize_whole_number!(deserialize_u64);
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, EsSerdeError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.esvf.is_undefined() || self.esvf.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EsSerdeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EsSerdeError>
+    where
+        V: Visitor<'de>,
+    {
+        if self.esvf.is_string() {
+            // unit variant e.g. 'Red'
+            visitor.visit_enum(self.esvf.get_string().into_deserializer())
+        } else if self.esvf.is_string_ref() {
+            visitor.visit_enum(
+                self.esvf
+                    .to_string_lossy()
+                    .map_err(ref_error)?
+                    .into_deserializer(),
+            )
+        } else if self.esvf.is_object() && self.esvf.get_object().len() == 1 {
+            // externally tagged variant e.g. {Rgb: [0, 0, 0]}
+            let (variant, value) = self.esvf.get_object().iter().next().unwrap();
+            visitor.visit_enum(EsEnumAccess { variant, value })
+        } else {
+            Err(EsSerdeError {
+                message: "expected a string or an object with a single property for enum"
+                    .to_string(),
+            })
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct EsSeqAccess<'a> {
+    iter: SliceIter<'a, EsValueFacade>,
+}
+
+impl<'de, 'a> SeqAccess<'de> for EsSeqAccess<'a> {
+    type Error = EsSerdeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, EsSerdeError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(esvf) => seed
+                .deserialize(EsValueFacadeDeserializer { esvf })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EsMapAccess<'a> {
+    iter: HashMapIter<'a, String, EsValueFacade>,
+    value: Option<&'a EsValueFacade>,
+}
+
+impl<'de, 'a> MapAccess<'de> for EsMapAccess<'a> {
+    type Error = EsSerdeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, EsSerdeError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.as_str().into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, EsSerdeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(esvf) => seed.deserialize(EsValueFacadeDeserializer { esvf }),
+            None => Err(EsSerdeError {
+                message: "value requested before key".to_string(),
+            }),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+// the props of an object reference, the values were read from the script engine so they are owned
+struct EsRefMapAccess {
+    iter: VecIntoIter<(String, EsValueFacade)>,
+    value: Option<EsValueFacade>,
+}
+
+impl<'de> MapAccess<'de> for EsRefMapAccess {
+    type Error = EsSerdeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, EsSerdeError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, EsSerdeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(esvf) => seed.deserialize(EsValueFacadeDeserializer { esvf: &esvf }),
+            None => Err(EsSerdeError {
+                message: "value requested before key".to_string(),
+            }),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EsEnumAccess<'a> {
+    variant: &'a String,
+    value: &'a EsValueFacade,
+}
+
+impl<'de, 'a> EnumAccess<'de> for EsEnumAccess<'a> {
+    type Error = EsSerdeError;
+    type Variant = EsValueFacadeDeserializer<'a>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), EsSerdeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant.as_str().into_deserializer())?;
+        Ok((variant, EsValueFacadeDeserializer { esvf: self.value }))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for EsValueFacadeDeserializer<'a> {
+    type Error = EsSerdeError;
+
+    fn unit_variant(self) -> Result<(), EsSerdeError> {
+        serde::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, EsSerdeError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, EsSerdeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EsSerdeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+}
+
+/// serialize a rust value to an EsValueFacade
+/// structs and maps are converted to objects, sequences to arrays and None to null
+/// # Example
+/// ```no_run
+/// use serde::Serialize;
+/// use spidermonkey_runtime::esserde::to_esvf;
+///
+/// #[derive(Serialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let esvf = to_esvf(&Point{x: 1, y: 2}).ok().expect("could not serialize");
+/// assert!(esvf.is_object());
+/// assert_eq!(esvf.get_object().get("y").unwrap().get_i32(), 2);
+/// ```
+pub fn to_esvf<T>(value: &T) -> Result<EsValueFacade, EsSerdeError>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(EsValueFacadeSerializer {})
+}
+
+/// serde Serializer which produces an EsValueFacade
+pub struct EsValueFacadeSerializer {}
+
+impl EsValueFacadeSerializer {
+    fn new_number(num: f64) -> EsValueFacade {
+        if num.fract() == 0.0 && num >= i32::MIN as f64 && num <= i32::MAX as f64 {
+            EsValueFacade::new_i32(num as i32)
+        } else {
+            EsValueFacade::new_f64(num)
+        }
+    }
+
+    fn new_variant(variant: &'static str, value: EsValueFacade) -> EsValueFacade {
+        let mut map = HashMap::new();
+        map.insert(variant.to_string(), value);
+        EsValueFacade::new_obj(map)
+    }
+}
+
+impl Serializer for EsValueFacadeSerializer {
+    type Ok = EsValueFacade;
+    type Error = EsSerdeError;
+    type SerializeSeq = EsSeqSerializer;
+    type SerializeTuple = EsSeqSerializer;
+    type SerializeTupleStruct = EsSeqSerializer;
+    type SerializeTupleVariant = EsSeqSerializer;
+    type SerializeMap = EsMapSerializer;
+    type SerializeStruct = EsMapSerializer;
+    type SerializeStructVariant = EsMapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_i32(v as i32))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_i32(v as i32))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_i32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(Self::new_number(v as f64))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_i32(v as i32))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_i32(v as i32))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(Self::new_number(v as f64))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(Self::new_number(v as f64))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_f64(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_f64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_array(
+            v.iter()
+                .map(|b| EsValueFacade::new_i32(*b as i32))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::null())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<EsValueFacade, EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::null())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::null())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(EsValueFacade::new_str(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<EsValueFacade, EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<EsValueFacade, EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(Self::new_variant(variant, to_esvf(value)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<EsSeqSerializer, EsSerdeError> {
+        Ok(EsSeqSerializer {
+            variant: None,
+            vec: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<EsSeqSerializer, EsSerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<EsSeqSerializer, EsSerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<EsSeqSerializer, EsSerdeError> {
+        Ok(EsSeqSerializer {
+            variant: Some(variant),
+            vec: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<EsMapSerializer, EsSerdeError> {
+        Ok(EsMapSerializer {
+            variant: None,
+            map: HashMap::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<EsMapSerializer, EsSerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<EsMapSerializer, EsSerdeError> {
+        Ok(EsMapSerializer {
+            variant: Some(variant),
+            map: HashMap::new(),
+            next_key: None,
+        })
+    }
+}
+
+/// used to serialize sequences, tuples and tuple variants
+pub struct EsSeqSerializer {
+    variant: Option<&'static str>,
+    vec: Vec<EsValueFacade>,
+}
+
+impl EsSeqSerializer {
+    fn finish(self) -> EsValueFacade {
+        let arr = EsValueFacade::new_array(self.vec);
+        match self.variant {
+            Some(variant) => EsValueFacadeSerializer::new_variant(variant, arr),
+            None => arr,
+        }
+    }
+}
+
+impl SerializeSeq for EsSeqSerializer {
+    type Ok = EsValueFacade;
+    type Error = EsSerdeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.vec.push(to_esvf(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl SerializeTuple for EsSeqSerializer {
+    type Ok = EsValueFacade;
+    type Error = EsSerdeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl SerializeTupleStruct for EsSeqSerializer {
+    type Ok = EsValueFacade;
+    type Error = EsSerdeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl SerializeTupleVariant for EsSeqSerializer {
+    type Ok = EsValueFacade;
+    type Error = EsSerdeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(self.finish())
+    }
+}
+
+/// used to serialize maps, structs and struct variants
+pub struct EsMapSerializer {
+    variant: Option<&'static str>,
+    map: HashMap<String, EsValueFacade>,
+    next_key: Option<String>,
+}
+
+impl EsMapSerializer {
+    fn finish(self) -> EsValueFacade {
+        let obj = EsValueFacade::new_obj(self.map);
+        match self.variant {
+            Some(variant) => EsValueFacadeSerializer::new_variant(variant, obj),
+            None => obj,
+        }
+    }
+}
+
+impl SerializeMap for EsMapSerializer {
+    type Ok = EsValueFacade;
+    type Error = EsSerdeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        // object keys are always strings in script so convert numbers and bools to strings
+        let key_esvf = to_esvf(key)?;
+        let key_str = if key_esvf.is_string() {
+            key_esvf.get_string().to_string()
+        } else if key_esvf.is_i32() {
+            key_esvf.get_i32().to_string()
+        } else if key_esvf.is_f64() {
+            key_esvf.get_f64().to_string()
+        } else if key_esvf.is_boolean() {
+            key_esvf.get_boolean().to_string()
+        } else {
+            return Err(EsSerdeError {
+                message: "map keys must be strings, numbers or booleans".to_string(),
+            });
+        };
+        self.next_key = Some(key_str);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        match self.next_key.take() {
+            Some(key) => {
+                self.map.insert(key, to_esvf(value)?);
+                Ok(())
+            }
+            None => Err(EsSerdeError {
+                message: "value serialized before key".to_string(),
+            }),
+        }
+    }
+
+    fn end(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl SerializeStruct for EsMapSerializer {
+    type Ok = EsValueFacade;
+    type Error = EsSerdeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.map.insert(key.to_string(), to_esvf(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl SerializeStructVariant for EsMapSerializer {
+    type Ok = EsValueFacade;
+    type Error = EsSerdeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), EsSerdeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<EsValueFacade, EsSerdeError> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::esruntime::tests::init_test_runtime;
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::esserde::{from_esvf, to_esvf};
+    use crate::esvaluefacade::EsValueFacade;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Mode {
+        Development,
+        Production,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct MyConfig {
+        name: String,
+        debug: bool,
+        ratio: f64,
+        mode: Mode,
+        servers: Vec<Server>,
+        labels: HashMap<String, String>,
+        timeout: Option<u32>,
+        retries: Option<u32>,
+    }
+
+    #[test]
+    fn test_from_esvf() {
+        let rt = init_test_runtime();
+        let esvf = rt
+            .eval_sync(
+                "({name: 'my_app', debug: true, ratio: 0.5, mode: 'Production', \
+                 servers: [{host: 'a.local', port: 8080}, {host: 'b.local', port: 8081}], \
+                 labels: {env: 'test'}, timeout: 3000000000, retries: null});",
+                "test_from_esvf.es",
+            )
+            .ok()
+            .expect("script failed");
+
+        let cfg: MyConfig = from_esvf(&esvf).ok().expect("could not deserialize config");
+
+        assert_eq!(cfg.name, "my_app");
+        assert!(cfg.debug);
+        assert!((cfg.ratio - 0.5).abs() < f64::EPSILON);
+        assert_eq!(cfg.mode, Mode::Production);
+        assert_eq!(cfg.servers.len(), 2);
+        assert_eq!(cfg.servers[1].host, "b.local");
+        assert_eq!(cfg.servers[1].port, 8081);
+        assert_eq!(cfg.labels.get("env").unwrap(), "test");
+        assert_eq!(cfg.timeout, Some(3_000_000_000));
+        assert_eq!(cfg.retries, None);
+    }
+
+    #[test]
+    fn test_from_esvf_invalid() {
+        let rt = init_test_runtime();
+
+        let esvf = rt
+            .eval_sync(
+                "({host: 'a.local', port: 'abc'});",
+                "test_from_esvf_invalid.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(from_esvf::<Server>(&esvf).is_err());
+
+        let esvf = rt
+            .eval_sync("({host: 'a.local'});", "test_from_esvf_invalid2.es")
+            .ok()
+            .expect("script failed");
+        let err = from_esvf::<Server>(&esvf)
+            .err()
+            .expect("should have failed");
+        assert!(err.message.contains("port"));
+
+        let esvf = rt
+            .eval_sync(
+                "({host: 'a.local', port: 70000});",
+                "test_from_esvf_invalid3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(from_esvf::<Server>(&esvf).is_err());
+
+        let esvf = rt
+            .eval_sync("(function(){});", "test_from_esvf_invalid4.es")
+            .ok()
+            .expect("script failed");
+        let err = from_esvf::<Server>(&esvf)
+            .err()
+            .expect("should have failed");
+        assert!(err.message.contains("function"));
+    }
+
+    #[test]
+    fn test_to_esvf() {
+        let rt = init_test_runtime();
+        rt.eval_sync(
+            "this.test_to_esvf = function(cfg){return JSON.stringify([cfg.name, cfg.debug, cfg.mode, cfg.servers[1].port, cfg.labels.env, cfg.timeout, cfg.retries]);};",
+            "test_to_esvf.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let mut labels = HashMap::new();
+        labels.insert("env".to_string(), "test".to_string());
+        let cfg = MyConfig {
+            name: "my_app".to_string(),
+            debug: true,
+            ratio: 0.5,
+            mode: Mode::Development,
+            servers: vec![
+                Server {
+                    host: "a.local".to_string(),
+                    port: 8080,
+                },
+                Server {
+                    host: "b.local".to_string(),
+                    port: 8081,
+                },
+            ],
+            labels,
+            timeout: Some(3_000_000_000),
+            retries: None,
+        };
+
+        let cfg_esvf = to_esvf(&cfg).ok().expect("could not serialize config");
+        assert!(cfg_esvf.is_object());
+        assert!(cfg_esvf.get_object().get("retries").unwrap().is_null());
+
+        let res: EsValueFacade = rt
+            .call_sync(vec![], "test_to_esvf", vec![cfg_esvf])
+            .ok()
+            .expect("test_to_esvf failed");
+        assert_eq!(
+            res.get_string(),
+            "[\"my_app\",true,\"Development\",8081,\"test\",3000000000,null]"
+        );
+
+        // and back again
+        let cfg_esvf = to_esvf(&cfg).ok().expect("could not serialize config");
+        rt.eval_sync(
+            "this.test_to_esvf_echo = function(cfg){return cfg;};",
+            "test_to_esvf2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let echo_esvf = rt
+            .call_sync(vec![], "test_to_esvf_echo", vec![cfg_esvf])
+            .ok()
+            .expect("test_to_esvf_echo failed");
+        let cfg2: MyConfig = from_esvf(&echo_esvf).ok().expect("could not deserialize");
+        assert_eq!(cfg2.mode, Mode::Development);
+        assert_eq!(cfg2.servers[0].host, "a.local");
+        assert_eq!(cfg2.timeout, Some(3_000_000_000));
+        assert_eq!(cfg2.retries, None);
+    }
+
+    #[test]
+    fn test_from_esvf_refs() {
+        let rt = EsRuntimeBuilder::new()
+            .eager_object_max_props(0)
+            .eager_string_max_len(4)
+            .build();
+        let esvf = rt
+            .eval_sync("({host: 'a.local', port: 8080});", "test_from_esvf_refs.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object_ref());
+        let server: Server = from_esvf(&esvf).ok().expect("could not deserialize server");
+        assert_eq!(server.host, "a.local");
+        assert_eq!(server.port, 8080);
+
+        let esvf = rt
+            .eval_sync("'Production';", "test_from_esvf_refs2.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_string_ref());
+        let host: String = from_esvf(&esvf).ok().expect("could not deserialize string");
+        assert_eq!(host, "Production");
+        let mode: Mode = from_esvf(&esvf).ok().expect("could not deserialize mode");
+        assert_eq!(mode, Mode::Production);
+
+        // a getter which throws fails the deserialization instead of panicking
+        let esvf = rt
+            .eval_sync(
+                "({host: 'a.local', get port() {throw Error('no port');}});",
+                "test_from_esvf_refs3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(from_esvf::<Server>(&esvf).is_err());
+    }
+}
@@ -2,7 +2,9 @@ use log::trace;
 
 use crate::esruntime::EsRuntime;
 use crate::esruntimeinner::EsRuntimeInner;
-use crate::jsapi_utils::arrays::{get_array_element, get_array_length, new_array, object_is_array};
+use crate::jsapi_utils::arrays::{
+    get_array_element, get_array_length, get_array_named_prop_names, new_array, object_is_array,
+};
 use crate::jsapi_utils::objects::NULL_JSOBJECT;
 use crate::jsapi_utils::rooting::EsPersistentRooted;
 use crate::jsapi_utils::{objects, EsErrorInfo};
@@ -16,38 +18,280 @@ use log::debug;
 use mozjs::jsapi::HandleValueArray;
 use mozjs::jsapi::JSContext;
 use mozjs::jsapi::JSObject;
-use mozjs::jsval::{BooleanValue, DoubleValue, Int32Value, JSVal, ObjectValue, UndefinedValue};
-use mozjs::rust::{HandleValue, MutableHandleValue};
+use mozjs::jsapi::PromiseState;
+use mozjs::jsval::{
+    BooleanValue, DoubleValue, Int32Value, JSVal, NullValue, ObjectValue, UndefinedValue,
+};
+use mozjs::rust::{HandleObject, HandleValue, MutableHandleValue};
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
-use std::sync::{Arc, Weak};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+
+// the cached placeholders below only have a weak ref to the runtime so they don't keep it alive
+// when they are dropped after the runtime was dropped their cached object is gone with the worker thread
+
+pub(crate) fn release_cached_object_later(rti_ref: &Weak<EsRuntimeInner>, cached_obj_id: usize) {
+    if let Some(rt_arc) = rti_ref.upgrade() {
+        rt_arc.do_in_es_event_queue(move |_sm_rt| {
+            spidermonkeyruntimewrapper::release_cached_object(cached_obj_id);
+        });
+    } else {
+        trace!(
+            "runtime was dropped, not releasing cached obj {}",
+            cached_obj_id
+        );
+    }
+}
+
+pub(crate) fn upgrade_rti_ref(
+    rti_ref: &Weak<EsRuntimeInner>,
+) -> Result<Arc<EsRuntimeInner>, EsErrorInfo> {
+    rti_ref.upgrade().ok_or_else(|| {
+        EsErrorInfo::new(
+            "the runtime of this value was dropped".to_string(),
+            "".to_string(),
+            0,
+            0,
+        )
+        .with_runtime_name(None)
+    })
+}
+
+// check if the current thread is the worker thread of the given runtime
+// the ids of cached objects are only valid in the worker thread of the runtime which cached them
+fn is_current_runtime(rti_ref: &Weak<EsRuntimeInner>) -> bool {
+    spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+        let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+        match &sm_rt.opt_esrt_inner {
+            Some(current_rti_ref) => Weak::ptr_eq(current_rti_ref, rti_ref),
+            None => false,
+        }
+    })
+}
+
+// set a cached object as rval, a value of another runtime is converted to undefined
+fn cached_object_to_js_value(
+    cached_obj_id: usize,
+    rti_ref: &Weak<EsRuntimeInner>,
+    rval: MutableHandleValue,
+) {
+    let mut rval = rval;
+    if is_current_runtime(rti_ref) {
+        let obj = spidermonkeyruntimewrapper::do_with_cached_object(
+            cached_obj_id,
+            |epr: &EsPersistentRooted| epr.get(),
+        );
+        rval.set(ObjectValue(obj));
+    } else {
+        log::error!("a value of another runtime can not be used in this runtime, using undefined");
+        rval.set(UndefinedValue());
+    }
+}
+
+// an error without a script location, e.g. for EsValueConvertible methods which are not supported by a type of value
+fn unsupported_err(message: &str) -> EsErrorInfo {
+    EsErrorInfo::new(message.to_string(), "".to_string(), 0, 0).with_runtime_name(None)
+}
 
 // placeholder for promises that were passed from the script engine to rust
 struct CachedJSPromise {
     cached_obj_id: usize,
-    opt_receiver: Option<Receiver<Result<EsValueFacade, EsValueFacade>>>,
-    rti_ref: Arc<EsRuntimeInner>,
+    // taken when waiting was canceled, see EsValueFacade::get_promise_result_blocking_cancellable()
+    opt_receiver: RefCell<Option<Receiver<PromiseMessage>>>,
+    // used to wake up a waiting thread when waiting is canceled
+    // the sender is owned by the reactions of the promise so the channel is disconnected when the runtime drops them
+    wake_sender: Weak<Mutex<Sender<PromiseMessage>>>,
+    // when set the reactions of the promise no longer send its result
+    canceled: Arc<AtomicBool>,
+    rti_ref: Weak<EsRuntimeInner>,
+}
+
+// the messages which are sent to a thread which waits for a CachedJSPromise
+enum PromiseMessage {
+    Settled(Result<EsValueFacade, EsValueFacade>),
+    // sent when a CancelToken was canceled, this is ignored by waits which do not use that token
+    Wake,
+}
+
+// wait for the result of a promise, a Wake message only ends the wait if the cancel_token was canceled
+fn recv_promise_message(
+    rx: &Receiver<PromiseMessage>,
+    timeout: Duration,
+    cancel_token: Option<&CancelToken>,
+) -> Result<Result<EsValueFacade, EsValueFacade>, RecvTimeoutError> {
+    let deadline = Instant::now().checked_add(timeout);
+    loop {
+        let msg = match deadline {
+            Some(deadline) => {
+                rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))?
+            }
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected)?,
+        };
+        match msg {
+            PromiseMessage::Settled(res) => return Ok(res),
+            PromiseMessage::Wake => {
+                if cancel_token
+                    .map(|token| token.is_canceled())
+                    .unwrap_or(false)
+                {
+                    return Err(RecvTimeoutError::Disconnected);
+                }
+                trace!("ignoring a wake up of an earlier wait");
+            }
+        }
+    }
+}
+
+/// the error of the try_get_* methods of EsValueFacade when the value is not of the expected type
+#[derive(Clone, Debug, PartialEq)]
+pub struct EsTypeError {
+    /// the type which was expected, e.g. "i32"
+    pub expected: &'static str,
+    /// the actual type of the value, see EsValueFacade::get_type_name()
+    pub actual: &'static str,
+}
+
+impl std::fmt::Display for EsTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {} but got {}", self.expected, self.actual)
+    }
+}
+
+impl std::error::Error for EsTypeError {}
+
+/// a token which can be used to stop waiting for a Promise, see EsValueFacade::get_promise_result_blocking_cancellable()
+/// clones of a token share the same state so a token can be canceled from another thread
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    state: Arc<Mutex<CancelState>>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    canceled: bool,
+    next_hook_id: usize,
+    on_cancel: Vec<(usize, Box<dyn FnOnce() + Send>)>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// cancel the token, threads which are waiting with this token stop waiting
+    pub fn cancel(&self) {
+        let on_cancel: Vec<(usize, Box<dyn FnOnce() + Send>)> = {
+            let state = &mut *self.state.lock().unwrap();
+            if state.canceled {
+                return;
+            }
+            state.canceled = true;
+            state.on_cancel.drain(..).collect()
+        };
+        for (_id, hook) in on_cancel {
+            hook();
+        }
+    }
+
+    /// check if the token was canceled
+    pub fn is_canceled(&self) -> bool {
+        self.state.lock().unwrap().canceled
+    }
+
+    /// add a closure which is called when the token is canceled, it is called right away if the token was already canceled
+    /// returns the id to remove the closure with, or None if it was already called
+    fn on_cancel(&self, hook: Box<dyn FnOnce() + Send>) -> Option<usize> {
+        {
+            let state = &mut *self.state.lock().unwrap();
+            if !state.canceled {
+                let id = state.next_hook_id;
+                state.next_hook_id += 1;
+                state.on_cancel.push((id, hook));
+                return Some(id);
+            }
+        }
+        hook();
+        None
+    }
+
+    /// remove a closure which was added with on_cancel(), this is done when a wait ends so hooks do not pile up on a long lived token
+    fn remove_on_cancel(&self, id: usize) {
+        let state = &mut *self.state.lock().unwrap();
+        state.on_cancel.retain(|(hook_id, _)| *hook_id != id);
+    }
 }
 
 impl Drop for CachedJSPromise {
     fn drop(&mut self) {
-        let rt_arc = self.rti_ref.clone();
-        let cached_obj_id = self.cached_obj_id;
+        release_cached_object_later(&self.rti_ref, self.cached_obj_id);
+    }
+}
 
-        rt_arc.do_in_es_event_queue(move |_sm_rt| {
-            spidermonkeyruntimewrapper::remove_cached_object(cached_obj_id);
-        });
+impl CachedJSPromise {
+    // get the state of the promise and its result if it was settled, this fails if the runtime was dropped
+    fn get_state(&self) -> Result<(PromiseState, Option<EsValueFacade>), EsErrorInfo> {
+        let cached_id = self.cached_obj_id;
+        Ok(upgrade_rti_ref(&self.rti_ref)?.do_in_es_event_queue_sync(move |sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                spidermonkeyruntimewrapper::do_with_cached_object(
+                    cached_id,
+                    |epr: &EsPersistentRooted| {
+                        rooted!(in (cx) let prom_root = epr.get());
+                        let state = jsapi_utils::promises::get_promise_state(prom_root.handle());
+                        match state {
+                            PromiseState::Pending => (state, None),
+                            _ => {
+                                rooted!(in (cx) let res_root = jsapi_utils::promises::get_promise_result(prom_root.handle()));
+                                (state, Some(EsValueFacade::new_v(cx, res_root.handle())))
+                            }
+                        }
+                    },
+                )
+            })
+        }))
     }
 }
 
 // placeholder for functions that were passed from the script engine to rust
 struct CachedJSFunction {
     cached_obj_id: usize,
-    rti_ref: Arc<EsRuntimeInner>,
+    rti_ref: Weak<EsRuntimeInner>,
+}
+
+// placeholder for objects that were passed from the script engine to rust without copying their properties
+struct CachedJSObject {
+    cached_obj_id: usize,
+    rti_ref: Weak<EsRuntimeInner>,
+}
+
+// placeholder for WeakMaps and WeakSets that were passed from the script engine to rust
+// these can't be enumerated so they are always passed by reference
+struct CachedJSWeakCollection {
+    obj: CachedJSObject,
+    is_set: bool,
 }
 
+// a new WeakMap which is created when it is passed to script, see EsValueFacade::new_weakmap()
+struct EsNewWeakMap {}
+
+// placeholder for large strings that were passed from the script engine to rust without copying them
+// the string is stored as a property of a holder object so it can be kept in the object cache
+struct CachedJSString {
+    cached_obj_id: usize,
+    rti_ref: Weak<EsRuntimeInner>,
+}
+
+const CACHED_STRING_PROP: &str = "str";
+
+// the max number of chars of a string reference which is shown by EsValueFacade::to_debug_string()
+const DEBUG_MAX_STRING_LEN: usize = 10000;
+
 struct RustPromise {
     id: usize,
 }
@@ -88,7 +332,15 @@ impl RustPromise {
 
         let task = move || {
             trace!("running prom reso task for {}", id);
-            let res = resolver();
+            // a panicking resolver rejects the promise instead of leaving it pending forever
+            let res = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(resolver)) {
+                Ok(res) => res,
+                Err(payload) => {
+                    let msg = jsapi_utils::panic_message(&payload);
+                    log::error!("promise resolver {} panicked: {}", id, msg);
+                    Err(format!("promise resolver panicked: {}", msg))
+                }
+            };
             trace!("got prom result for {}, ok={}", id, res.is_ok());
             let either_opt: Option<(PromiseResultContainer, Result<EsValueFacade, String>)> = {
                 // locked scope
@@ -207,7 +459,18 @@ impl RustPromise {
     }
 }
 
-pub trait EsValueConvertible {
+/// helper trait to get a &dyn Any for an EsValueConvertible, this is implemented for all 'static types
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub trait EsValueConvertible: AsAny {
     fn to_js_value(&self, cx: *mut JSContext, return_val: MutableHandleValue);
 
     fn to_es_value_facade(self) -> EsValueFacade
@@ -216,6 +479,7 @@ pub trait EsValueConvertible {
     {
         EsValueFacade {
             convertible: Box::new(self),
+            origin: None,
         }
     }
 
@@ -257,6 +521,25 @@ pub trait EsValueConvertible {
     fn invoke_function(&self, _args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
         panic!("i am not a function");
     }
+    fn apply(
+        &self,
+        _this_val: EsValueFacade,
+        _args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        panic!("i am not a function");
+    }
+    fn construct(&self, _args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
+        panic!("i am not a function");
+    }
+    fn function_name(&self) -> Result<String, EsErrorInfo> {
+        Err(unsupported_err("i am not a function"))
+    }
+    fn function_arity(&self) -> Result<u32, EsErrorInfo> {
+        Err(unsupported_err("i am not a function"))
+    }
+    fn function_source(&self) -> Result<String, EsErrorInfo> {
+        Err(unsupported_err("i am not a function"))
+    }
     fn is_promise(&self) -> bool {
         false
     }
@@ -266,6 +549,13 @@ pub trait EsValueConvertible {
     ) -> Result<Result<EsValueFacade, EsValueFacade>, RecvTimeoutError> {
         panic!("i am not a promise");
     }
+    fn await_promise_blocking_cancellable(
+        &self,
+        _timeout: Duration,
+        _cancel_token: &CancelToken,
+    ) -> Result<Result<EsValueFacade, EsValueFacade>, RecvTimeoutError> {
+        panic!("i am not a promise");
+    }
     fn is_object(&self) -> bool {
         false
     }
@@ -278,6 +568,54 @@ pub trait EsValueConvertible {
     fn get_array(&self) -> &Vec<EsValueFacade> {
         panic!("i am not an array");
     }
+    fn get_array_props(&self) -> Option<&HashMap<String, EsValueFacade>> {
+        None
+    }
+    fn is_object_ref(&self) -> bool {
+        false
+    }
+    fn get_property(&self, _name: &str) -> Result<EsValueFacade, EsErrorInfo> {
+        panic!("i am not an object reference");
+    }
+    fn set_property(&self, _name: &str, _value: EsValueFacade) -> Result<(), EsErrorInfo> {
+        panic!("i am not an object reference");
+    }
+    fn get_all_own_prop_names(
+        &self,
+        _include_non_enumerable: bool,
+        _include_symbols: bool,
+    ) -> Result<Vec<objects::PropKey>, EsErrorInfo> {
+        Err(unsupported_err("i am not an object reference"))
+    }
+    fn invoke_method(
+        &self,
+        _name: &str,
+        _args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        panic!("i am not an object reference");
+    }
+    fn is_string_ref(&self) -> bool {
+        false
+    }
+    fn is_weakmap(&self) -> bool {
+        false
+    }
+    fn is_weakset(&self) -> bool {
+        false
+    }
+    /// the id of the object in the object cache and the runtime it belongs to, for facades which reference an object in the runtime
+    fn get_cached_object(&self) -> Option<(usize, &Weak<EsRuntimeInner>)> {
+        None
+    }
+    fn string_length(&self) -> Result<usize, EsErrorInfo> {
+        Err(unsupported_err("i am not a string"))
+    }
+    fn substring(&self, _start: usize, _end: usize) -> Result<String, EsErrorInfo> {
+        Err(unsupported_err("i am not a string"))
+    }
+    fn get_string_strict(&self) -> Result<String, EsErrorInfo> {
+        Err(unsupported_err("i am not a string"))
+    }
 }
 
 struct EsUndefinedValue {}
@@ -286,11 +624,32 @@ impl EsValueConvertible for EsUndefinedValue {
     fn to_js_value(&self, _cx: *mut JSContext, _rval: MutableHandleValue) {
         //
     }
+
+    fn is_undefined(&self) -> bool {
+        true
+    }
+}
+
+struct EsNullValue {}
+
+impl EsValueConvertible for EsNullValue {
+    fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
+        let mut rval = rval;
+        rval.set(NullValue());
+    }
+
+    fn is_null(&self) -> bool {
+        true
+    }
 }
 
 impl EsValueConvertible for CachedJSPromise {
-    fn to_js_value(&self, _cx: *mut JSContext, _rval: MutableHandleValue) {
-        unimplemented!()
+    fn get_cached_object(&self) -> Option<(usize, &Weak<EsRuntimeInner>)> {
+        Some((self.cached_obj_id, &self.rti_ref))
+    }
+
+    fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
+        cached_object_to_js_value(self.cached_obj_id, &self.rti_ref, rval);
     }
 
     fn is_promise(&self) -> bool {
@@ -312,8 +671,46 @@ impl EsValueConvertible for CachedJSPromise {
             panic!("you really should not wait for promises in a RT's event queue thread");
         }
 
-        let rx = self.opt_receiver.as_ref().expect("not a waiting promise");
-        rx.recv_timeout(timeout)
+        match &*self.opt_receiver.borrow() {
+            Some(rx) => recv_promise_message(rx, timeout, None),
+            None => Err(RecvTimeoutError::Disconnected),
+        }
+    }
+
+    fn await_promise_blocking_cancellable(
+        &self,
+        timeout: Duration,
+        cancel_token: &CancelToken,
+    ) -> Result<Result<EsValueFacade, EsValueFacade>, RecvTimeoutError> {
+        if EventLoop::is_a_pool_thread() {
+            log::error!("waiting for esvf prom from event queue thread, bad dev bad!");
+            panic!("you really should not wait for promises in a RT's event queue thread");
+        }
+
+        let canceled = self.canceled.clone();
+        let wake_sender = self.wake_sender.clone();
+        let hook_id = cancel_token.on_cancel(Box::new(move || {
+            canceled.store(true, Ordering::SeqCst);
+            // the sender is gone when the runtime dropped the reactions of the promise, the wait then ends as disconnected
+            if let Some(wake_sender) = wake_sender.upgrade() {
+                let _ = wake_sender.lock().unwrap().send(PromiseMessage::Wake);
+            }
+        }));
+
+        let opt_receiver = &mut *self.opt_receiver.borrow_mut();
+        let res = match opt_receiver {
+            Some(rx) => recv_promise_message(rx, timeout, Some(cancel_token)),
+            None => Err(RecvTimeoutError::Disconnected),
+        };
+        if let Some(hook_id) = hook_id {
+            cancel_token.remove_on_cancel(hook_id);
+        }
+        if cancel_token.is_canceled() {
+            // drop the receiver, the promise can not be waited for anymore
+            opt_receiver.take();
+            return Err(RecvTimeoutError::Disconnected);
+        }
+        res
     }
 }
 
@@ -399,8 +796,52 @@ impl EsValueConvertible for RustPromise {
 }
 
 impl CachedJSFunction {
+    // run a closure with the function in the worker thread of the runtime, this fails if the runtime was dropped
+    fn do_with_function<R, C>(&self, consumer: C) -> Result<R, EsErrorInfo>
+    where
+        R: Send + 'static,
+        C: FnOnce(*mut JSContext, HandleObject) -> R + Send + 'static,
+    {
+        let cached_id = self.cached_obj_id;
+        Ok(
+            upgrade_rti_ref(&self.rti_ref)?.do_in_es_event_queue_sync(move |sm_rt| {
+                sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                    rooted!(in (cx) let func_root = spidermonkeyruntimewrapper::do_with_cached_object(
+                        cached_id,
+                        |epr: &EsPersistentRooted| epr.get(),
+                    ));
+                    consumer(cx, func_root.handle())
+                })
+            }),
+        )
+    }
+
+    fn get_name(&self) -> Result<String, EsErrorInfo> {
+        self.do_with_function(|cx, func| {
+            objects::get_es_obj_prop_val_as_string(cx, func, "name").unwrap_or_default()
+        })
+    }
+
+    fn get_arity(&self) -> Result<u32, EsErrorInfo> {
+        self.do_with_function(|cx, func| {
+            rooted!(in (cx) let mut length_root = UndefinedValue());
+            match objects::get_es_obj_prop_val(cx, func, "length", length_root.handle_mut()) {
+                Ok(_) if length_root.is_int32() && length_root.to_int32() > 0 => {
+                    length_root.to_int32() as u32
+                }
+                _ => 0,
+            }
+        })
+    }
+
+    fn get_source(&self) -> Result<String, EsErrorInfo> {
+        self.do_with_function(|cx, func| {
+            jsapi_utils::es_value_to_display_str(cx, ObjectValue(func.get()))
+        })
+    }
+
     fn invoke_function1(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
-        let rt_arc = self.rti_ref.clone();
+        let rt_arc = upgrade_rti_ref(&self.rti_ref)?;
         let cached_id = self.cached_obj_id;
 
         let job = move |sm_rt: &SmRuntime| Self::invoke_function2(cached_id, sm_rt, args);
@@ -454,917 +895,4222 @@ impl CachedJSFunction {
             }
         })
     }
-}
 
-impl EsValueConvertible for CachedJSFunction {
-    fn to_js_value(&self, _cx: *mut JSContext, _rval: MutableHandleValue) {
-        unimplemented!()
-    }
+    fn apply1(
+        &self,
+        this_val: EsValueFacade,
+        args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        trace!("EsValueFacade.apply1()");
+        self.do_with_function(move |cx, func| {
+            rooted!(in (cx) let mut this_val_root = UndefinedValue());
+            this_val.to_es_value(cx, this_val_root.handle_mut());
+
+            rooted!(in (cx) let function_val = ObjectValue(func.get()));
+
+            spidermonkeyruntimewrapper::do_with_rooted_esvf_vec(cx, args, |arguments_value_array| {
+                rooted!(in (cx) let mut rval = UndefinedValue());
+                jsapi_utils::functions::call_function_value_with_this(
+                    cx,
+                    this_val_root.handle(),
+                    function_val.handle(),
+                    arguments_value_array,
+                    rval.handle_mut(),
+                )?;
 
-    fn is_function(&self) -> bool {
-        true
+                Ok(EsValueFacade::new_v(cx, rval.handle()))
+            })
+        })?
     }
 
-    fn invoke_function(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
-        self.invoke_function1(args)
-    }
-}
+    fn construct1(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
+        let cached_id = self.cached_obj_id;
 
-impl EsValueConvertible for String {
-    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
-        jsapi_utils::new_es_value_from_str(cx, self.as_str(), rval);
-    }
+        let job = move |sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| Self::construct2(cached_id, cx, args))
+        };
 
-    fn is_str(&self) -> bool {
-        true
+        upgrade_rti_ref(&self.rti_ref)?.do_in_es_event_queue_sync(job)
     }
 
-    fn get_str(&self) -> &str {
-        self.as_str()
+    fn construct2(
+        cached_id: usize,
+        cx: *mut JSContext,
+        args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        trace!("EsValueFacade.construct2()");
+        spidermonkeyruntimewrapper::do_with_cached_object(cached_id, |epr: &EsPersistentRooted| {
+            auto_root!(in (cx) let mut args_rooted_vec = vec![]);
+
+            for esvf in &args {
+                rooted!(in (cx) let mut arg_val = UndefinedValue());
+                esvf.to_es_value(cx, arg_val.handle_mut());
+                args_rooted_vec.push(*arg_val);
+            }
+
+            let arguments_value_array =
+                unsafe { HandleValueArray::from_rooted_slice(&*args_rooted_vec) };
+
+            rooted!(in (cx) let mut instance = NULL_JSOBJECT);
+            rooted!(in (cx) let constructor_val = mozjs::jsval::ObjectValue(epr.get()));
+
+            let res = jsapi_utils::functions::construct_function_value(
+                cx,
+                constructor_val.handle(),
+                arguments_value_array,
+                instance.handle_mut(),
+            );
+
+            if res.is_ok() {
+                rooted!(in (cx) let instance_val = ObjectValue(instance.get()));
+                Ok(EsValueFacade::new_v(cx, instance_val.handle()))
+            } else {
+                Err(res.err().unwrap())
+            }
+        })
     }
 }
 
-impl EsValueConvertible for i32 {
+impl EsValueConvertible for CachedJSFunction {
+    fn get_cached_object(&self) -> Option<(usize, &Weak<EsRuntimeInner>)> {
+        Some((self.cached_obj_id, &self.rti_ref))
+    }
+
     fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
-        let mut rval = rval;
-        rval.set(Int32Value(*self))
+        cached_object_to_js_value(self.cached_obj_id, &self.rti_ref, rval);
     }
 
-    fn is_i32(&self) -> bool {
+    fn is_function(&self) -> bool {
         true
     }
 
-    fn get_i32(&self) -> i32 {
-        *self
+    fn invoke_function(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
+        self.invoke_function1(args)
     }
-}
 
-impl EsValueConvertible for bool {
-    fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
-        let mut rval = rval;
-        rval.set(BooleanValue(*self))
-    }
-    fn is_bool(&self) -> bool {
-        true
+    fn apply(
+        &self,
+        this_val: EsValueFacade,
+        args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        self.apply1(this_val, args)
     }
 
-    fn get_bool(&self) -> bool {
-        *self
+    fn function_name(&self) -> Result<String, EsErrorInfo> {
+        self.get_name()
     }
-}
 
-impl EsValueConvertible for f64 {
-    fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
-        let mut rval = rval;
-        rval.set(DoubleValue(*self))
+    fn function_arity(&self) -> Result<u32, EsErrorInfo> {
+        self.get_arity()
     }
-    fn is_f64(&self) -> bool {
-        true
+
+    fn function_source(&self) -> Result<String, EsErrorInfo> {
+        self.get_source()
     }
 
-    fn get_f64(&self) -> f64 {
-        *self
+    fn construct(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
+        self.construct1(args)
     }
 }
 
-impl EsValueConvertible for Vec<EsValueFacade> {
-    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
-        rooted!(in (cx) let mut arr_root = NULL_JSOBJECT);
-        // create the array
-        new_array(cx, arr_root.handle_mut());
-        // add items
-        for item in self {
-            rooted!(in (cx) let mut arr_elem_val = UndefinedValue());
-            // convert elem to JSVal
-            item.to_es_value(cx, arr_elem_val.handle_mut());
-            // add to array
-            jsapi_utils::arrays::push_array_element(cx, arr_root.handle(), arr_elem_val.handle())
-                .ok()
-                .expect("jsapi_utils::arrays::push_array_element failed");
-        }
-        let mut rval = rval;
-        rval.set(ObjectValue(*arr_root));
+impl CachedJSObject {
+    fn get_property1(&self, name: &str) -> Result<EsValueFacade, EsErrorInfo> {
+        let cached_id = self.cached_obj_id;
+        let name = name.to_string();
+
+        let job = move |sm_rt: &SmRuntime| {
+            sm_rt
+                .do_with_jsapi(|_rt, cx, _global| Self::get_property2(cached_id, cx, name.as_str()))
+        };
+
+        upgrade_rti_ref(&self.rti_ref)?.do_in_es_event_queue_sync(job)
     }
 
-    fn is_array(&self) -> bool {
+    fn get_property2(
+        cached_id: usize,
+        cx: *mut JSContext,
+        name: &str,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        spidermonkeyruntimewrapper::do_with_cached_object(cached_id, |epr: &EsPersistentRooted| {
+            rooted!(in (cx) let obj_root = epr.get());
+            rooted!(in (cx) let mut rval = UndefinedValue());
+            let res = objects::get_es_obj_prop_val(cx, obj_root.handle(), name, rval.handle_mut());
+            if res.is_ok() {
+                Ok(EsValueFacade::new_v(cx, rval.handle()))
+            } else {
+                Err(res.err().unwrap())
+            }
+        })
+    }
+
+    fn set_property1(&self, name: &str, value: EsValueFacade) -> Result<(), EsErrorInfo> {
+        let cached_id = self.cached_obj_id;
+        let name = name.to_string();
+
+        let job = move |sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                spidermonkeyruntimewrapper::do_with_cached_object(
+                    cached_id,
+                    |epr: &EsPersistentRooted| {
+                        rooted!(in (cx) let obj_root = epr.get());
+                        rooted!(in (cx) let mut val_root = UndefinedValue());
+                        value.to_es_value(cx, val_root.handle_mut());
+                        objects::assign_es_obj_prop_value(
+                            cx,
+                            obj_root.handle(),
+                            name.as_str(),
+                            val_root.handle(),
+                        )
+                    },
+                )
+            })
+        };
+
+        upgrade_rti_ref(&self.rti_ref)?.do_in_es_event_queue_sync(job)
+    }
+
+    fn get_all_own_prop_names1(
+        &self,
+        include_non_enumerable: bool,
+        include_symbols: bool,
+    ) -> Result<Vec<objects::PropKey>, EsErrorInfo> {
+        let cached_id = self.cached_obj_id;
+
+        let job = move |sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                spidermonkeyruntimewrapper::do_with_cached_object(
+                    cached_id,
+                    |epr: &EsPersistentRooted| {
+                        rooted!(in (cx) let obj_root = epr.get());
+                        objects::get_all_own_prop_names(
+                            cx,
+                            obj_root.handle(),
+                            include_non_enumerable,
+                            include_symbols,
+                        )
+                    },
+                )
+            })
+        };
+
+        upgrade_rti_ref(&self.rti_ref)?.do_in_es_event_queue_sync(job)
+    }
+
+    fn invoke_method1(
+        &self,
+        name: &str,
+        args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        let cached_id = self.cached_obj_id;
+        let name = name.to_string();
+
+        let job = move |sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                spidermonkeyruntimewrapper::do_with_cached_object(
+                    cached_id,
+                    |epr: &EsPersistentRooted| {
+                        rooted!(in (cx) let obj_root = epr.get());
+                        auto_root!(in (cx) let mut args_rooted_vec = vec![]);
+
+                        for esvf in &args {
+                            rooted!(in (cx) let mut arg_val = UndefinedValue());
+                            esvf.to_es_value(cx, arg_val.handle_mut());
+                            args_rooted_vec.push(*arg_val);
+                        }
+
+                        let arguments_value_array =
+                            unsafe { HandleValueArray::from_rooted_slice(&*args_rooted_vec) };
+
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        jsapi_utils::functions::call_function_name2(
+                            cx,
+                            obj_root.handle(),
+                            name.as_str(),
+                            arguments_value_array,
+                            rval.handle_mut(),
+                        )?;
+                        Ok(EsValueFacade::new_v(cx, rval.handle()))
+                    },
+                )
+            })
+        };
+
+        upgrade_rti_ref(&self.rti_ref)?.do_in_es_event_queue_sync(job)
+    }
+}
+
+impl EsValueConvertible for CachedJSObject {
+    fn get_cached_object(&self) -> Option<(usize, &Weak<EsRuntimeInner>)> {
+        Some((self.cached_obj_id, &self.rti_ref))
+    }
+
+    fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
+        cached_object_to_js_value(self.cached_obj_id, &self.rti_ref, rval);
+    }
+
+    fn is_object_ref(&self) -> bool {
         true
     }
 
-    fn get_array(&self) -> &Vec<EsValueFacade> {
-        self
+    fn get_property(&self, name: &str) -> Result<EsValueFacade, EsErrorInfo> {
+        self.get_property1(name)
+    }
+
+    fn set_property(&self, name: &str, value: EsValueFacade) -> Result<(), EsErrorInfo> {
+        self.set_property1(name, value)
+    }
+
+    fn get_all_own_prop_names(
+        &self,
+        include_non_enumerable: bool,
+        include_symbols: bool,
+    ) -> Result<Vec<objects::PropKey>, EsErrorInfo> {
+        self.get_all_own_prop_names1(include_non_enumerable, include_symbols)
+    }
+
+    fn invoke_method(
+        &self,
+        name: &str,
+        args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        self.invoke_method1(name, args)
     }
 }
 
-impl EsValueConvertible for HashMap<String, EsValueFacade> {
+impl EsValueConvertible for CachedJSWeakCollection {
+    fn get_cached_object(&self) -> Option<(usize, &Weak<EsRuntimeInner>)> {
+        self.obj.get_cached_object()
+    }
+
     fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
-        trace!("to_es_value.6");
-        rooted!(in(cx) let mut obj_root = NULL_JSOBJECT);
-        jsapi_utils::objects::new_object(cx, obj_root.handle_mut());
+        self.obj.to_js_value(cx, rval)
+    }
 
-        for prop in self {
-            let prop_name = prop.0;
-            let prop_esvf = prop.1;
-            rooted!(in(cx) let mut val_root = UndefinedValue());
-            prop_esvf.to_es_value(cx, val_root.handle_mut());
-            jsapi_utils::objects::set_es_obj_prop_value(
-                cx,
-                obj_root.handle(),
-                prop_name,
-                val_root.handle(),
-            );
-        }
-        let mut rval = rval;
-        rval.set(ObjectValue(*obj_root));
+    fn is_weakmap(&self) -> bool {
+        !self.is_set
     }
 
-    fn is_object(&self) -> bool {
+    fn is_weakset(&self) -> bool {
+        self.is_set
+    }
+
+    fn is_object_ref(&self) -> bool {
         true
     }
 
-    fn get_object(&self) -> &HashMap<String, EsValueFacade, RandomState> {
-        self
+    fn get_property(&self, name: &str) -> Result<EsValueFacade, EsErrorInfo> {
+        self.obj.get_property1(name)
     }
-}
 
-/// the EsValueFacade is a converter between rust variables and script objects
-/// when receiving a EsValueFacade from the script engine it's data is always a clone from the actual data so we need not worry about the value being garbage collected
-///
-/// # Example
-///
-/// ```no_run
-/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
-///
-/// let rt = EsRuntimeBuilder::default().build();
-/// let esvf = rt.eval_sync("123", "test_es_value_facade.es").ok().unwrap();
-/// assert!(esvf.is_i32());
-/// assert_eq!(esvf.get_i32(), 123);
-/// ```
-pub struct EsValueFacade {
-    convertible: Box<dyn EsValueConvertible + Send>,
+    fn set_property(&self, name: &str, value: EsValueFacade) -> Result<(), EsErrorInfo> {
+        self.obj.set_property1(name, value)
+    }
+
+    fn get_all_own_prop_names(
+        &self,
+        include_non_enumerable: bool,
+        include_symbols: bool,
+    ) -> Result<Vec<objects::PropKey>, EsErrorInfo> {
+        self.obj
+            .get_all_own_prop_names1(include_non_enumerable, include_symbols)
+    }
+
+    fn invoke_method(
+        &self,
+        name: &str,
+        args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        self.obj.invoke_method1(name, args)
+    }
 }
 
-type PromiseAnswersMap = AutoIdMap<PromiseResultContainerOption>;
+impl EsValueConvertible for EsNewWeakMap {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        rooted!(in (cx) let mut map_root = NULL_JSOBJECT);
+        objects::new_weakmap(cx, map_root.handle_mut());
+        let mut rval = rval;
+        rval.set(ObjectValue(*map_root));
+    }
 
-lazy_static! {
-    static ref PROMISE_ANSWERS: Arc<DebugMutex<PromiseAnswersMap>> =
-        Arc::new(DebugMutex::new(AutoIdMap::new(), "PROMISE_ANSWERS"));
+    fn is_weakmap(&self) -> bool {
+        true
+    }
 }
 
-impl EsValueFacade {
-    /// create a new EsValueFacade representing an undefined value
-    pub fn undefined() -> Self {
-        EsUndefinedValue {}.to_es_value_facade()
+impl EsValueConvertible for String {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        jsapi_utils::new_es_value_from_str(cx, self.as_str(), rval);
     }
 
-    /// create a new EsValueFacade representing a float
-    pub fn new_f64(num: f64) -> Self {
-        num.to_es_value_facade()
+    fn is_str(&self) -> bool {
+        true
     }
 
-    /// create a new EsValueFacade representing a basic object with properties as defined in the HashMap
-    pub fn new_obj(props: HashMap<String, EsValueFacade>) -> Self {
-        props.to_es_value_facade()
+    fn get_str(&self) -> &str {
+        self.as_str()
     }
 
-    /// create a new EsValueFacade representing a signed integer
-    pub fn new_i32(num: i32) -> Self {
-        num.to_es_value_facade()
+    fn string_length(&self) -> Result<usize, EsErrorInfo> {
+        Ok(self.encode_utf16().count())
     }
 
-    /// create a new EsValueFacade representing a String
-    pub fn new_str(s: String) -> Self {
-        s.to_es_value_facade()
+    fn substring(&self, start: usize, end: usize) -> Result<String, EsErrorInfo> {
+        let units: Vec<u16> = self.encode_utf16().collect();
+        let end = std::cmp::min(end, units.len());
+        let start = std::cmp::min(start, end);
+        Ok(String::from_utf16_lossy(&units[start..end]))
     }
+}
 
-    /// create a new EsValueFacade representing a bool
-    pub fn new_bool(b: bool) -> Self {
-        b.to_es_value_facade()
+// a static str is only converted to a JSString when it is passed to the script engine
+impl EsValueConvertible for &'static str {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        jsapi_utils::new_es_value_from_str(cx, self, rval);
     }
 
-    /// create a new EsValueFacade representing an Array
-    pub fn new_array(vals: Vec<EsValueFacade>) -> Self {
-        vals.to_es_value_facade()
+    fn is_str(&self) -> bool {
+        true
     }
 
-    /// create a new EsValueFacade representing a Promise, the passed closure will actually run in a seperate helper thread and resolve the Promise that is created in the script runtime
-    ///
-    /// # Example
-    ///
-    /// ```no_run
-    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
-    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
-    /// use std::time::Duration;
-    ///
-    /// let rt = EsRuntimeBuilder::new().build();
-    /// rt.eval_sync("let myFunc = function(a){\
-    ///     a.then((res) => {\
-    ///         console.log('a resolved with %s', res);\
-    ///     });\
-    /// };", "test_new_promise.es");
-    /// let esvf_arg = EsValueFacade::new_promise(|| {
-    ///     // do complicated calculations or whatever here, it will run async
-    ///     // then return Ok to resolve the promise or Err to reject it
-    ///     Ok(EsValueFacade::new_i32(123))
-    /// });
-    /// rt.call_sync(vec![], "myFunc", vec![esvf_arg]);
-    /// // wait for promise to resolve
-    /// std::thread::sleep(Duration::from_secs(1));
-    /// ```
-    pub fn new_promise<C>(resolver: C) -> EsValueFacade
-    where
-        C: FnOnce() -> Result<EsValueFacade, String> + Send + 'static,
-    {
-        RustPromise::new_esvf(resolver)
+    fn get_str(&self) -> &str {
+        self
     }
 
-    pub(crate) fn new_v(context: *mut JSContext, val_handle: HandleValue) -> Self {
-        let val: JSVal = *val_handle;
+    fn string_length(&self) -> Result<usize, EsErrorInfo> {
+        Ok(self.encode_utf16().count())
+    }
 
-        trace!("EsValueFacade::new_v");
+    fn substring(&self, start: usize, end: usize) -> Result<String, EsErrorInfo> {
+        self.to_string().substring(start, end)
+    }
+}
 
-        if val.is_boolean() {
-            trace!("EsValueFacade::new_v -> boolean");
-            val.to_boolean().to_es_value_facade()
-        } else if val.is_int32() {
-            trace!("EsValueFacade::new_v -> int32");
-            val.to_int32().to_es_value_facade()
-        } else if val.is_double() {
-            trace!("EsValueFacade::new_v -> double");
-            val.to_number().to_es_value_facade()
-        } else if val.is_string() {
-            trace!("EsValueFacade::new_v -> string");
-            jsapi_utils::es_value_to_str(context, val)
-                .expect("could not convert jsval to string")
-                .to_es_value_facade()
-        } else if val.is_object() {
-            trace!("EsValueFacade::new_v -> object");
-            let obj: *mut JSObject = val.to_object();
-            Self::new_v_from_object(context, obj)
-        } else if val.is_null() {
-            trace!("EsValueFacade::new_v -> null");
-            // todo impl EsNull
-            EsUndefinedValue {}.to_es_value_facade()
-        } else if val.is_undefined() {
-            trace!("EsValueFacade::new_v -> undefined");
-            EsUndefinedValue {}.to_es_value_facade()
-        } else {
-            trace!("EsValueFacade::new_v -> unknown");
-            EsUndefinedValue {}.to_es_value_facade()
-        }
+impl EsValueConvertible for Cow<'static, str> {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        jsapi_utils::new_es_value_from_str(cx, self, rval);
     }
 
-    fn new_v_from_object(context: *mut JSContext, obj: *mut JSObject) -> Self {
-        rooted!(in(context) let obj_root = obj);
+    fn is_str(&self) -> bool {
+        true
+    }
 
-        if object_is_array(context, obj_root.handle()) {
-            trace!("EsValueFacade::new_v -> object -> array");
-            let mut vals = vec![];
-            // add vals
+    fn get_str(&self) -> &str {
+        self
+    }
 
-            let arr_len = get_array_length(context, obj_root.handle()).ok().unwrap();
-            for x in 0..arr_len {
-                rooted!(in (context) let mut arr_element_root = UndefinedValue());
-                let get_res =
-                    get_array_element(context, obj_root.handle(), x, arr_element_root.handle_mut());
-                if get_res.is_err() {
-                    panic!(
-                        "could not get element of array: {}",
-                        get_res.err().unwrap().err_msg()
-                    );
-                }
-                vals.push(EsValueFacade::new_v(context, arr_element_root.handle()));
-            }
+    fn string_length(&self) -> Result<usize, EsErrorInfo> {
+        Ok(self.encode_utf16().count())
+    }
 
-            vals.to_es_value_facade()
-        } else if jsapi_utils::promises::object_is_promise(obj_root.handle()) {
-            trace!("EsValueFacade::new_v -> object -> promise");
+    fn substring(&self, start: usize, end: usize) -> Result<String, EsErrorInfo> {
+        self.to_string().substring(start, end)
+    }
+}
 
-            let cached_prom_id =
-                spidermonkeyruntimewrapper::register_cached_object(context, *obj_root);
+// a char is stored as its utf-8 bytes so get_str() can borrow it without allocating a String
+struct EsChar {
+    bytes: [u8; 4],
+    len: usize,
+}
 
-            let (tx, rx) = channel();
-            let tx2 = tx.clone();
-            assert!(jsapi_utils::promises::add_promise_reactions_callbacks(
-                context,
-                obj_root.handle(),
-                Some(
-                    move |cx, mut args: Vec<HandleValue>, _rval: MutableHandleValue| {
-                        // promsie was resolved
-                        let resolution = args.remove(0);
-                        let res_esvf = EsValueFacade::new_v(cx, resolution);
+impl EsValueConvertible for EsChar {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        jsapi_utils::new_es_value_from_str(cx, self.get_str(), rval);
+    }
 
-                        match tx.send(Ok(res_esvf)) {
-                            Ok(_) => Ok(()),
-                            // todo, does not include error (which is "sending on a closed channel") which is not ASCII and thus fails the error handler
-                            Err(e) => {
-                                debug!("send res error: {}", e);
-                                Err("send res error".to_string())
-                            }
-                        }
-                    }
-                ),
-                Some(
-                    move |cx, mut args: Vec<HandleValue>, _rval: MutableHandleValue| {
-                        // promsie was rejected
-                        let rejection = args.remove(0);
-                        let rej_esvf = EsValueFacade::new_v(cx, rejection);
+    fn is_str(&self) -> bool {
+        true
+    }
 
-                        match tx2.send(Err(rej_esvf)) {
-                            Ok(_) => Ok(()),
-                            // todo, does not include error (which is "sending on a closed channel") which is not ASCII and thus fails the error handler
-                            Err(e) => {
-                                debug!("send rejection error: {}", e);
-                                Err("send rejection error".to_string())
-                            }
-                        }
-                        // release epr
-                    }
-                )
-            ));
+    fn get_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len]).expect("invalid char bytes")
+    }
 
-            let opt_receiver = Some(rx);
+    fn string_length(&self) -> Result<usize, EsErrorInfo> {
+        Ok(self.get_str().encode_utf16().count())
+    }
 
-            let rti_ref = spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
-                let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
-                sm_rt.clone_esrt_inner()
-            });
-            let rmev: CachedJSPromise = CachedJSPromise {
-                cached_obj_id: cached_prom_id,
-                opt_receiver,
-                rti_ref,
-            };
+    fn substring(&self, start: usize, end: usize) -> Result<String, EsErrorInfo> {
+        self.get_str().to_string().substring(start, end)
+    }
+}
 
-            rmev.to_es_value_facade()
-        } else if jsapi_utils::functions::object_is_function(obj) {
-            trace!("EsValueFacade::new_v -> object -> function");
-            // wrap function in persistentrooted
+impl EsValueConvertible for char {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        let mut bytes = [0; 4];
+        jsapi_utils::new_es_value_from_str(cx, self.encode_utf8(&mut bytes), rval);
+    }
 
-            let rti_ref = spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
-                let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
-                sm_rt.clone_esrt_inner()
-            });
-            let cached_obj_id = spidermonkeyruntimewrapper::register_cached_object(context, obj);
-            let cf = CachedJSFunction {
-                cached_obj_id,
-                rti_ref,
-            };
-            cf.to_es_value_facade()
-        } else {
-            let mut map = HashMap::new();
-            trace!("EsValueFacade::new_v -> object -> object");
-            let prop_names: Vec<String> =
-                objects::get_js_obj_prop_names(context, obj_root.handle());
-            for prop_name in prop_names {
-                rooted!(in (context) let mut prop_val_root = UndefinedValue());
-                let prop_val_res = objects::get_es_obj_prop_val(
-                    context,
-                    obj_root.handle(),
-                    prop_name.as_str(),
-                    prop_val_root.handle_mut(),
-                );
+    fn to_es_value_facade(self) -> EsValueFacade {
+        let mut bytes = [0; 4];
+        let len = self.encode_utf8(&mut bytes).len();
+        EsChar { bytes, len }.to_es_value_facade()
+    }
+}
 
-                if prop_val_res.is_err() {
-                    panic!(
-                        "error getting prop {}: {}",
-                        prop_name,
-                        prop_val_res.err().unwrap().err_msg()
+impl CachedJSString {
+    // run a closure with the string in the worker thread of the runtime, this fails if the runtime was dropped
+    fn do_with_js_string<R, C>(&self, consumer: C) -> Result<R, EsErrorInfo>
+    where
+        C: FnOnce(*mut JSContext, *mut mozjs::jsapi::JSString) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let cached_id = self.cached_obj_id;
+        Ok(
+            upgrade_rti_ref(&self.rti_ref)?.do_in_es_event_queue_sync(move |sm_rt: &SmRuntime| {
+                sm_rt.do_with_jsapi(move |_rt, cx, _global| {
+                    let holder = spidermonkeyruntimewrapper::do_with_cached_object(
+                        cached_id,
+                        |epr: &EsPersistentRooted| epr.get(),
                     );
-                }
+                    rooted!(in (cx) let holder_root = holder);
+                    rooted!(in (cx) let mut str_val_root = UndefinedValue());
+                    objects::get_es_obj_prop_val(
+                        cx,
+                        holder_root.handle(),
+                        CACHED_STRING_PROP,
+                        str_val_root.handle_mut(),
+                    )
+                    .ok()
+                    .expect("could not get cached string");
+                    consumer(cx, str_val_root.get().to_string())
+                })
+            }),
+        )
+    }
+}
 
-                let prop_esvf = EsValueFacade::new_v(context, prop_val_root.handle());
-                map.insert(prop_name, prop_esvf);
-            }
-            map.to_es_value_facade()
+impl EsValueConvertible for CachedJSString {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        rooted!(in (cx) let mut holder_root = UndefinedValue());
+        cached_object_to_js_value(self.cached_obj_id, &self.rti_ref, holder_root.handle_mut());
+        if holder_root.is_object() {
+            rooted!(in (cx) let holder_obj_root = holder_root.to_object());
+            objects::get_es_obj_prop_val(cx, holder_obj_root.handle(), CACHED_STRING_PROP, rval)
+                .ok()
+                .expect("could not get cached string");
         }
     }
 
-    /// get the String value
-    pub fn get_string(&self) -> &str {
-        self.convertible.get_str()
+    fn is_string_ref(&self) -> bool {
+        true
     }
 
-    /// get the i32 value
-    pub fn get_i32(&self) -> i32 {
-        self.convertible.get_i32()
+    fn string_length(&self) -> Result<usize, EsErrorInfo> {
+        self.do_with_js_string(|_cx, js_string| jsapi_utils::es_jsstring_length(js_string))
     }
 
-    /// get the f64 value
-    pub fn get_f64(&self) -> f64 {
-        self.convertible.get_f64()
+    fn get_string_strict(&self) -> Result<String, EsErrorInfo> {
+        self.do_with_js_string(|cx, js_string| {
+            jsapi_utils::es_jsstring_to_string_strict(cx, js_string)
+        })?
+        .map_err(unsupported_err)
     }
 
-    /// get the boolean value
-    pub fn get_boolean(&self) -> bool {
-        self.convertible.get_bool()
+    fn substring(&self, start: usize, end: usize) -> Result<String, EsErrorInfo> {
+        self.do_with_js_string(move |cx, js_string| {
+            jsapi_utils::es_jsstring_substring(cx, js_string, start, end)
+        })?
     }
+}
 
-    /// check if this esvf was a promise which was returned from the script engine
-    pub fn is_promise(&self) -> bool {
-        self.convertible.is_promise()
+impl EsValueConvertible for i32 {
+    fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
+        let mut rval = rval;
+        rval.set(Int32Value(*self))
     }
 
-    /// wait for a promise to resolve in rust
-    /// # Example
-    /// ```no_run
-    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
-    /// use std::time::Duration;
-    ///
-    /// let rt = EsRuntimeBuilder::new().build();
-    /// // run the script and fail if script fails
-    /// let esvf_prom = rt.eval_sync(
-    ///     "let p = new Promise((resolve, reject) => {setImmediate(() => {resolve(123);});}); p;",
-    ///     "test_get_promise_result_blocking.es").ok().expect("script failed");
-    /// // wait for the promise or fail on timeout
-    /// let wait_res = esvf_prom.get_promise_result_blocking(Duration::from_secs(1))
-    ///     .ok().expect("promise timed out");
-    /// // get the ok result, fail is promise was rejected
-    /// let esvf = wait_res.ok().expect("promise was rejected");
-    /// // check the result
-    /// assert_eq!(esvf.get_i32(), 123);
-    /// ```
-    pub fn get_promise_result_blocking(
-        &self,
-        timeout: Duration,
-    ) -> Result<Result<EsValueFacade, EsValueFacade>, RecvTimeoutError> {
-        // todo
-        self.convertible.await_promise_blocking(timeout)
+    fn is_i32(&self) -> bool {
+        true
     }
 
-    /// get the value as a Map of EsValueFacades, this works when the value was an object in the script engine
-    /// # Example
-    /// ```no_run
-    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
-    ///
-    /// let rt = EsRuntimeBuilder::new().build();
-    /// let esvf = rt.eval_sync("{a: 1, b: 2};", "test_get_object.es").ok().expect("script failed");
-    /// let map = esvf.get_object();
-    /// assert!(map.contains_key("a"));
-    /// assert!(map.contains_key("b"));
-    /// ```
-    pub fn get_object(&self) -> &HashMap<String, EsValueFacade> {
-        self.convertible.get_object()
+    fn get_i32(&self) -> i32 {
+        *self
     }
+}
 
-    /// get the value as a Vec of EsValueFacades, this works when the value was an array in the script engine
-    /// # Example
-    /// ```no_run
-    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
-    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
-    ///
-    /// let rt = EsRuntimeBuilder::new().build();
-    /// let esvf = rt.eval_sync("[1, 2, 3];", "test_get_array.es").ok().expect("script failed");
-    /// let arr: &Vec<EsValueFacade> = esvf.get_array();
-    /// assert_eq!(arr.len(), 3);
-    /// ```
-    pub fn get_array(&self) -> &Vec<EsValueFacade> {
-        self.convertible.get_array()
+impl EsValueConvertible for bool {
+    fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
+        let mut rval = rval;
+        rval.set(BooleanValue(*self))
     }
-
-    /// invoke the function that was returned from the script engine
-    /// # Example
-    /// ```no_run
-    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
-    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
-    ///
-    /// let rt = EsRuntimeBuilder::new().build();
-    /// let func_esvf = rt.eval_sync("(function(a){return (a / 2);});", "test_invoke_function.es")
-    ///     .ok().expect("script failed");
-    /// // invoke the function with 18
-    /// let res_esvf = func_esvf.invoke_function(vec![EsValueFacade::new_i32(18)])
-    ///     .ok().expect("function failed");
-    /// // check that 19 / 2 = 9
-    /// let res_i32 = res_esvf.get_i32();
-    /// assert_eq!(res_i32, 9);
-    /// ```
-    pub fn invoke_function(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
-        trace!("EsValueFacade.invoke_function()");
-        self.convertible.invoke_function(args)
+    fn is_bool(&self) -> bool {
+        true
     }
 
-    /// check if the value is a String
-    pub fn is_string(&self) -> bool {
-        self.convertible.is_str()
+    fn get_bool(&self) -> bool {
+        *self
     }
+}
 
-    /// check if the value is a i32
-    pub fn is_i32(&self) -> bool {
-        self.convertible.is_i32()
+impl EsValueConvertible for f64 {
+    fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
+        let mut rval = rval;
+        if self.is_nan() {
+            // a NaN with a non canonical bit pattern would be read as a tagged value by spidermonkey
+            rval.set(DoubleValue(f64::NAN))
+        } else {
+            rval.set(DoubleValue(*self))
+        }
     }
-
-    /// check if the value is a f64
-    pub fn is_f64(&self) -> bool {
-        self.convertible.is_f64()
+    fn is_f64(&self) -> bool {
+        true
     }
 
-    /// check if the value is a bool
-    pub fn is_boolean(&self) -> bool {
-        self.convertible.is_bool()
+    fn get_f64(&self) -> f64 {
+        *self
     }
+}
 
-    /// check if the value is an object
-    pub fn is_object(&self) -> bool {
-        self.convertible.is_object()
+impl EsValueConvertible for Vec<EsValueFacade> {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        rooted!(in (cx) let mut arr_root = NULL_JSOBJECT);
+        // create the array
+        new_array(cx, arr_root.handle_mut());
+        // add items
+        for item in self {
+            rooted!(in (cx) let mut arr_elem_val = UndefinedValue());
+            // convert elem to JSVal
+            item.to_es_value(cx, arr_elem_val.handle_mut());
+            // add to array
+            jsapi_utils::arrays::push_array_element(cx, arr_root.handle(), arr_elem_val.handle())
+                .ok()
+                .expect("jsapi_utils::arrays::push_array_element failed");
+        }
+        let mut rval = rval;
+        rval.set(ObjectValue(*arr_root));
     }
 
-    /// check if the value is an array
-    pub fn is_array(&self) -> bool {
-        self.convertible.is_array()
+    fn is_array(&self) -> bool {
+        true
     }
 
-    /// check if the value is an function
-    pub fn is_function(&self) -> bool {
-        self.convertible.is_function()
+    fn get_array(&self) -> &Vec<EsValueFacade> {
+        self
     }
+}
 
-    pub(crate) fn to_es_value(&self, context: *mut JSContext, return_val: MutableHandleValue) {
-        trace!("to_es_value.1");
+// an array which only contains numbers, the EsValueFacades of the elements are only created when get_array() is called
+struct EsNumberArray {
+    numbers: Vec<f64>,
+    vals: OnceLock<Vec<EsValueFacade>>,
+}
 
-        self.convertible.to_js_value(context, return_val)
+impl EsValueConvertible for EsNumberArray {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        jsapi_utils::arrays::vec_to_array(cx, rval, self.numbers.clone());
+    }
+
+    fn is_array(&self) -> bool {
+        true
+    }
+
+    fn get_array(&self) -> &Vec<EsValueFacade> {
+        self.vals.get_or_init(|| {
+            self.numbers
+                .iter()
+                .map(|number| {
+                    let number = *number;
+                    // the same as new_v does for int32 values, -0 is not an int32
+                    if number.fract() == 0.0
+                        && number >= i32::MIN as f64
+                        && number <= i32::MAX as f64
+                        && !(number == 0.0 && number.is_sign_negative())
+                    {
+                        EsValueFacade::new_i32(number as i32)
+                    } else {
+                        EsValueFacade::new_f64(number)
+                    }
+                })
+                .collect()
+        })
     }
 }
 
-type PromiseResultContainer = Either<Result<EsValueFacade, String>, (usize, Weak<EsRuntimeInner>)>;
-type PromiseResultContainerOption = Option<PromiseResultContainer>;
+// an array which has named properties besides its elements, e.g. `let a = [1, 2]; a.meta = 'x';`
+struct EsArrayWithProps {
+    vals: Vec<EsValueFacade>,
+    props: HashMap<String, EsValueFacade>,
+}
 
-impl Drop for RustPromise {
-    fn drop(&mut self) {
-        // drop from map if val is None, task has not run yet and to_es_val was not called
-        let map: &mut PromiseAnswersMap = &mut PROMISE_ANSWERS.lock("EsValueFacade::drop").unwrap();
-        let id = self.id;
-        if let Some(opt) = map.get(&id) {
-            if opt.is_none() {
-                map.remove(&id);
-            }
+impl EsValueConvertible for EsArrayWithProps {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        self.vals.to_js_value(cx, rval);
+        rooted!(in (cx) let arr_root = rval.to_object());
+        for (prop_name, prop_esvf) in &self.props {
+            rooted!(in(cx) let mut val_root = UndefinedValue());
+            prop_esvf.to_es_value(cx, val_root.handle_mut());
+            jsapi_utils::objects::set_es_obj_prop_value(
+                cx,
+                arr_root.handle(),
+                prop_name,
+                val_root.handle(),
+            );
         }
     }
+
+    fn is_array(&self) -> bool {
+        true
+    }
+
+    fn get_array(&self) -> &Vec<EsValueFacade> {
+        &self.vals
+    }
+
+    fn get_array_props(&self) -> Option<&HashMap<String, EsValueFacade>> {
+        Some(&self.props)
+    }
 }
 
-impl Drop for CachedJSFunction {
+// an ArrayBuffer which takes ownership of the bytes when it is passed to script, see EsValueFacade::new_array_buffer_transfer()
+struct EsArrayBufferTransfer {
+    bytes: Mutex<Option<Vec<u8>>>,
+}
+
+impl EsValueConvertible for EsArrayBufferTransfer {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        // the bytes can only be transferred once, after that script gets an empty ArrayBuffer
+        let bytes = self.bytes.lock().unwrap().take().unwrap_or_else(|| {
+            log::warn!("ArrayBuffer was already transferred to script");
+            vec![]
+        });
+        rooted!(in (cx) let mut buf_root = NULL_JSOBJECT);
+        jsapi_utils::typed_arrays::new_array_buffer_from_vec(cx, bytes, buf_root.handle_mut());
+        let mut rval = rval;
+        rval.set(ObjectValue(*buf_root));
+    }
+}
+
+struct EsIterable {
+    iter: Mutex<Option<Box<dyn Iterator<Item = EsValueFacade> + Send>>>,
+}
+
+impl EsValueConvertible for EsIterable {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        // the iterator can only be consumed once, after that script gets an empty iterable
+        let mut iter = self.iter.lock().unwrap().take().unwrap_or_else(|| {
+            log::warn!("iterator was already passed to script");
+            Box::new(std::iter::empty())
+        });
+        jsapi_utils::iterators::new_iterator(cx, Box::new(move || iter.next()), rval);
+    }
+}
+
+impl EsValueConvertible for HashMap<String, EsValueFacade> {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        trace!("to_es_value.6");
+        rooted!(in(cx) let mut obj_root = NULL_JSOBJECT);
+        jsapi_utils::objects::new_object(cx, obj_root.handle_mut());
+
+        for prop in self {
+            let prop_name = prop.0;
+            let prop_esvf = prop.1;
+            rooted!(in(cx) let mut val_root = UndefinedValue());
+            prop_esvf.to_es_value(cx, val_root.handle_mut());
+            jsapi_utils::objects::set_es_obj_prop_value(
+                cx,
+                obj_root.handle(),
+                prop_name,
+                val_root.handle(),
+            );
+        }
+        let mut rval = rval;
+        rval.set(ObjectValue(*obj_root));
+    }
+
+    fn is_object(&self) -> bool {
+        true
+    }
+
+    fn get_object(&self) -> &HashMap<String, EsValueFacade, RandomState> {
+        self
+    }
+}
+
+// an object which defines its properties in script in the order in which they were added, see EsObjectBuilder
+struct EsOrderedObject {
+    keys: Vec<String>,
+    props: HashMap<String, EsValueFacade>,
+}
+
+impl EsValueConvertible for EsOrderedObject {
+    fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        rooted!(in(cx) let mut obj_root = NULL_JSOBJECT);
+        jsapi_utils::objects::new_object(cx, obj_root.handle_mut());
+
+        for prop_name in &self.keys {
+            let prop_esvf = self.props.get(prop_name).unwrap();
+            rooted!(in(cx) let mut val_root = UndefinedValue());
+            prop_esvf.to_es_value(cx, val_root.handle_mut());
+            jsapi_utils::objects::set_es_obj_prop_value(
+                cx,
+                obj_root.handle(),
+                prop_name,
+                val_root.handle(),
+            );
+        }
+        let mut rval = rval;
+        rval.set(ObjectValue(*obj_root));
+    }
+
+    fn is_object(&self) -> bool {
+        true
+    }
+
+    fn get_object(&self) -> &HashMap<String, EsValueFacade> {
+        &self.props
+    }
+}
+
+/// a builder for a basic object, the properties are defined in script in the order in which they were added
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esvaluefacade::{EsObjectBuilder, EsValueFacade};
+///
+/// let esvf = EsObjectBuilder::new()
+///     .prop("name", EsValueFacade::new_str("john".to_string()))
+///     .prop(
+///         "address",
+///         EsObjectBuilder::new()
+///             .prop("city", EsValueFacade::new_str("Amsterdam".to_string()))
+///             .build(),
+///     )
+///     .build();
+/// assert!(esvf.is_object());
+/// ```
+#[derive(Default)]
+pub struct EsObjectBuilder {
+    keys: Vec<String>,
+    props: HashMap<String, EsValueFacade>,
+}
+
+impl EsObjectBuilder {
+    /// create a new EsObjectBuilder without properties
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add a property, when a property with the same name was already added its value is replaced but it keeps its position
+    pub fn prop(&mut self, name: &str, value: EsValueFacade) -> &mut Self {
+        if self.props.insert(name.to_string(), value).is_none() {
+            self.keys.push(name.to_string());
+        }
+        self
+    }
+
+    /// create the EsValueFacade, this takes the properties from the builder so it is empty afterwards
+    pub fn build(&mut self) -> EsValueFacade {
+        EsOrderedObject {
+            keys: std::mem::take(&mut self.keys),
+            props: std::mem::take(&mut self.props),
+        }
+        .to_es_value_facade()
+    }
+}
+
+/// the EsValueFacade is a converter between rust variables and script objects
+/// when receiving a EsValueFacade from the script engine primitives, objects and arrays are copied so we need not worry about the value being garbage collected
+/// functions, promises and objects with more than EsRuntimeBuilder::eager_object_max_props() properties are references to the script object (see is_object_ref())
+///
+/// a copied object or array also keeps the object it was copied from alive until the facade is dropped,
+/// so passing it back to the same runtime passes the original object (with its identity and prototype) instead of a new copy
+/// please drop large results when you are done with them, holding many of them also holds their script objects in memory
+///
+/// # Example
+///
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+///
+/// let rt = EsRuntimeBuilder::default().build();
+/// let esvf = rt.eval_sync("123", "test_es_value_facade.es").ok().unwrap();
+/// assert!(esvf.is_i32());
+/// assert_eq!(esvf.get_i32(), 123);
+/// ```
+pub struct EsValueFacade {
+    convertible: Box<dyn EsValueConvertible + Send>,
+    // the object in the runtime an object or array was copied from, see EsValueFacade::new_v_from_object()
+    origin: Option<CachedOrigin>,
+}
+
+// a cached ref to the object an EsValueFacade was copied from
+struct CachedOrigin {
+    cached_obj_id: usize,
+    rti_ref: Weak<EsRuntimeInner>,
+}
+
+impl Drop for CachedOrigin {
     fn drop(&mut self) {
-        let rt_arc = self.rti_ref.clone();
+        release_cached_object_later(&self.rti_ref, self.cached_obj_id);
+    }
+}
+
+/// the resolving end of a Promise created with EsRuntime::new_deferred()
+/// resolve or reject may be called from any thread, the Promise is settled in the event queue of the runtime
+/// the Promise is kept alive until it is settled, if the Deferred is dropped without settling it the Promise will stay pending
+pub struct Deferred {
+    cached_obj_id: usize,
+    rti_ref: Arc<EsRuntimeInner>,
+    settled: bool,
+}
+
+impl Deferred {
+    pub(crate) fn new_pair(sm_rt: &SmRuntime) -> (EsValueFacade, Self) {
+        let rti_ref = sm_rt.clone_esrt_inner();
+        let (esvf, cached_obj_id) = sm_rt.do_with_jsapi(|_rt, cx, _global| {
+            rooted!(in (cx) let prom_root = jsapi_utils::promises::new_promise(cx));
+            let cached_obj_id =
+                spidermonkeyruntimewrapper::register_cached_object(cx, prom_root.get());
+            rooted!(in (cx) let prom_val_root = ObjectValue(prom_root.get()));
+            let esvf = EsValueFacade::new_v(cx, prom_val_root.handle());
+            (esvf, cached_obj_id)
+        });
+        (
+            esvf,
+            Deferred {
+                cached_obj_id,
+                rti_ref,
+                settled: false,
+            },
+        )
+    }
+
+    /// resolve the Promise with a value
+    pub fn resolve(self, value: EsValueFacade) {
+        self.settle(Ok(value))
+    }
+
+    /// reject the Promise with a value
+    pub fn reject(self, value: EsValueFacade) {
+        self.settle(Err(value))
+    }
+
+    fn settle(mut self, result: Result<EsValueFacade, EsValueFacade>) {
+        self.settled = true;
         let cached_obj_id = self.cached_obj_id;
+        self.rti_ref.do_in_es_event_queue(move |sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(move |_rt, cx, _global| {
+                let prom_obj: *mut JSObject = {
+                    let epr = spidermonkeyruntimewrapper::remove_cached_object(cached_obj_id);
+                    epr.get()
+                };
+                rooted!(in (cx) let prom_obj_root = prom_obj);
+                rooted!(in (cx) let mut res_root = UndefinedValue());
+
+                let settle_res = match result {
+                    Ok(val) => {
+                        val.to_es_value(cx, res_root.handle_mut());
+                        jsapi_utils::promises::resolve_promise(
+                            cx,
+                            prom_obj_root.handle(),
+                            res_root.handle(),
+                        )
+                    }
+                    Err(val) => {
+                        val.to_es_value(cx, res_root.handle_mut());
+                        jsapi_utils::promises::reject_promise(
+                            cx,
+                            prom_obj_root.handle(),
+                            res_root.handle(),
+                        )
+                    }
+                };
+                if let Err(err) = settle_res {
+                    log::error!("could not settle deferred promise: {}", err.err_msg());
+                }
+            });
+        });
+    }
+}
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        if !self.settled {
+            let cached_obj_id = self.cached_obj_id;
+            self.rti_ref.do_in_es_event_queue(move |_sm_rt| {
+                spidermonkeyruntimewrapper::release_cached_object(cached_obj_id);
+            });
+        }
+    }
+}
+
+type PromiseAnswersMap = AutoIdMap<PromiseResultContainerOption>;
+
+lazy_static! {
+    static ref PROMISE_ANSWERS: Arc<DebugMutex<PromiseAnswersMap>> =
+        Arc::new(DebugMutex::new(AutoIdMap::new(), "PROMISE_ANSWERS"));
+}
+
+impl EsValueFacade {
+    /// create a new EsValueFacade representing an undefined value
+    pub fn undefined() -> Self {
+        EsUndefinedValue {}.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a null value
+    pub fn null() -> Self {
+        EsNullValue {}.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a float
+    /// NaN, Infinity, -Infinity and -0 are passed to script as is
+    pub fn new_f64(num: f64) -> Self {
+        num.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a basic object with properties as defined in the HashMap
+    pub fn new_obj(props: HashMap<String, EsValueFacade>) -> Self {
+        props.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a signed integer
+    pub fn new_i32(num: i32) -> Self {
+        num.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a String
+    pub fn new_str(s: String) -> Self {
+        s.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a static str, unlike new_str this does not allocate a String
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let esvf = EsValueFacade::new_static_str("hello");
+    /// assert_eq!(esvf.get_string(), "hello");
+    /// ```
+    pub fn new_static_str(s: &'static str) -> Self {
+        s.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a borrowed or owned str
+    pub fn new_cow_str(s: Cow<'static, str>) -> Self {
+        s.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a char, this is converted to a string with a length of 1 (or 2 for chars outside the BMP) in script
+    pub fn new_char(c: char) -> Self {
+        c.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a bool
+    pub fn new_bool(b: bool) -> Self {
+        b.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing an Array
+    pub fn new_array(vals: Vec<EsValueFacade>) -> Self {
+        vals.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing an array which also has named properties
+    pub fn new_array_with_props(
+        vals: Vec<EsValueFacade>,
+        props: HashMap<String, EsValueFacade>,
+    ) -> Self {
+        if props.is_empty() {
+            vals.to_es_value_facade()
+        } else {
+            EsArrayWithProps { vals, props }.to_es_value_facade()
+        }
+    }
+
+    /// create a new EsValueFacade representing an instance of a proxy class (see ProxyBuilder) with the given obj_id
+    /// this lets rust pass a reflected object to script without calling `new MyClass()` in script
+    /// the instance is created once and the EsValueFacade is a reference to it, so passing it to script more than once passes the same instance
+    /// this fails if there is no proxy with that canonical name, needs to run in the workerthread of the event queue
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let instance = rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+    ///         let _proxy = ProxyBuilder::new(vec!["my", "biz"], "MyClass")
+    ///             .constructor(|_cx, _args| Ok(1))
+    ///             .build(cx, global);
+    ///         EsValueFacade::new_proxy_instance(cx, "my.biz.MyClass", 12)
+    ///     })
+    /// }).ok().expect("could not create instance");
+    /// rt.eval_sync("function myFunc(obj){return obj instanceof my.biz.MyClass;}", "new_proxy_instance.es")
+    ///     .ok().expect("script failed");
+    /// let res = rt.call_sync(vec![], "myFunc", vec![instance])
+    ///     .ok().expect("call failed");
+    /// assert!(res.get_boolean());
+    /// ```
+    pub fn new_proxy_instance(
+        context: *mut JSContext,
+        canonical_name: &str,
+        obj_id: i32,
+    ) -> Result<Self, EsErrorInfo> {
+        rooted!(in (context) let mut instance_root = UndefinedValue());
+        jsapi_utils::reflection::new_instance(
+            context,
+            canonical_name,
+            obj_id,
+            instance_root.handle_mut(),
+        )?;
+        Ok(Self::new_object_ref(context, instance_root.to_object()))
+    }
+
+    /// create a new EsValueFacade representing a new and empty WeakMap
+    /// a new WeakMap is created every time the EsValueFacade is passed to script
+    pub fn new_weakmap() -> Self {
+        EsNewWeakMap {}.to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing an ArrayBuffer which takes ownership of the bytes instead of copying them
+    /// the Vec is dropped when the ArrayBuffer is garbage collected, so the rust side can not retain (a ref to) the buffer
+    /// the bytes are transferred when the EsValueFacade is first passed to script, passing it again results in an empty ArrayBuffer
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.eval_sync("function myFunc(buf){return new Uint8Array(buf)[0];}", "new_array_buffer_transfer.es")
+    ///     .ok().expect("script failed");
+    /// let bytes: Vec<u8> = vec![1; 1024 * 1024];
+    /// let res = rt.call_sync(vec![], "myFunc", vec![EsValueFacade::new_array_buffer_transfer(bytes)])
+    ///     .ok().expect("call failed");
+    /// assert_eq!(res.get_i32(), 1);
+    /// ```
+    pub fn new_array_buffer_transfer(bytes: Vec<u8>) -> Self {
+        EsArrayBufferTransfer {
+            bytes: Mutex::new(Some(bytes)),
+        }
+        .to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing an iterable object which pulls its values from a rust iterator
+    /// the iterator is not collected into an array, next() is called on the rust iterator when script asks for the next value (e.g. in a for of loop)
+    /// the iterator is moved to script when the EsValueFacade is first passed to script, passing it again results in an empty iterable
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.eval_sync("function sumFirst(it, max){let s = 0; for (const x of it) {if (x > max) break; s += x;} return s;}", "new_iterable.es")
+    ///     .ok().expect("script failed");
+    /// // an endless iterator, only the values script asks for are produced
+    /// let numbers = (1..).map(EsValueFacade::new_i32);
+    /// let res = rt.call_sync(vec![], "sumFirst", vec![EsValueFacade::new_iterable(numbers), EsValueFacade::new_i32(4)])
+    ///     .ok().expect("call failed");
+    /// assert_eq!(res.get_i32(), 10);
+    /// ```
+    pub fn new_iterable<I>(iter: I) -> Self
+    where
+        I: Iterator<Item = EsValueFacade> + Send + 'static,
+    {
+        EsIterable {
+            iter: Mutex::new(Some(Box::new(iter))),
+        }
+        .to_es_value_facade()
+    }
+
+    /// create a new EsValueFacade representing a Promise, the passed closure will actually run in a seperate helper thread and resolve the Promise that is created in the script runtime
+    /// if the closure returns a Promise itself (e.g. another new_promise) the Promise adopts its state just like chained Promises in script
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use std::time::Duration;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.eval_sync("let myFunc = function(a){\
+    ///     a.then((res) => {\
+    ///         console.log('a resolved with %s', res);\
+    ///     });\
+    /// };", "test_new_promise.es");
+    /// let esvf_arg = EsValueFacade::new_promise(|| {
+    ///     // do complicated calculations or whatever here, it will run async
+    ///     // then return Ok to resolve the promise or Err to reject it
+    ///     Ok(EsValueFacade::new_i32(123))
+    /// });
+    /// rt.call_sync(vec![], "myFunc", vec![esvf_arg]);
+    /// // wait for promise to resolve
+    /// std::thread::sleep(Duration::from_secs(1));
+    /// ```
+    pub fn new_promise<C>(resolver: C) -> EsValueFacade
+    where
+        C: FnOnce() -> Result<EsValueFacade, String> + Send + 'static,
+    {
+        RustPromise::new_esvf(resolver)
+    }
+
+    pub(crate) fn new_v(context: *mut JSContext, val_handle: HandleValue) -> Self {
+        Self::new_v2(context, val_handle, true)
+    }
+
+    // only the top level value of a copy remembers the object it was copied from, see with_origin()
+    // the nested objects and arrays of a copy are converted with track_origin set to false
+    fn new_v2(context: *mut JSContext, val_handle: HandleValue, track_origin: bool) -> Self {
+        let val: JSVal = *val_handle;
+
+        trace!("EsValueFacade::new_v");
+
+        if val.is_boolean() {
+            trace!("EsValueFacade::new_v -> boolean");
+            val.to_boolean().to_es_value_facade()
+        } else if val.is_int32() {
+            trace!("EsValueFacade::new_v -> int32");
+            val.to_int32().to_es_value_facade()
+        } else if val.is_double() {
+            trace!("EsValueFacade::new_v -> double");
+            val.to_number().to_es_value_facade()
+        } else if val.is_string() {
+            let js_string = val.to_string();
+            if jsapi_utils::es_jsstring_length(js_string) > Self::get_eager_string_max_len() {
+                Self::new_string_ref(context, val_handle)
+            } else if Self::is_strict_string_conversion() {
+                // the string is decoded once, if it contains an unpaired surrogate it is kept in the runtime
+                match jsapi_utils::es_jsstring_to_string_strict(context, js_string) {
+                    Ok(s) => {
+                        trace!("EsValueFacade::new_v -> string");
+                        s.to_es_value_facade()
+                    }
+                    Err(_) => Self::new_string_ref(context, val_handle),
+                }
+            } else {
+                trace!("EsValueFacade::new_v -> string");
+                jsapi_utils::es_jsstring_to_string(context, js_string).to_es_value_facade()
+            }
+        } else if val.is_object() {
+            trace!("EsValueFacade::new_v -> object");
+            let obj: *mut JSObject = val.to_object();
+            Self::new_v_from_object(context, obj, track_origin)
+        } else if val.is_null() {
+            trace!("EsValueFacade::new_v -> null");
+            EsNullValue {}.to_es_value_facade()
+        } else if val.is_undefined() {
+            trace!("EsValueFacade::new_v -> undefined");
+            EsUndefinedValue {}.to_es_value_facade()
+        } else {
+            trace!("EsValueFacade::new_v -> unknown");
+            EsUndefinedValue {}.to_es_value_facade()
+        }
+    }
+
+    /// create a reference to an object in the script engine without copying its properties, see is_object_ref()
+    pub(crate) fn new_object_ref(context: *mut JSContext, obj: *mut JSObject) -> Self {
+        let rti_ref = spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+            let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+            Arc::downgrade(&sm_rt.clone_esrt_inner())
+        });
+        let cached_obj_id = spidermonkeyruntimewrapper::register_cached_object(context, obj);
+        CachedJSObject {
+            cached_obj_id,
+            rti_ref,
+        }
+        .to_es_value_facade()
+    }
+
+    fn new_v_from_object(context: *mut JSContext, obj: *mut JSObject, track_origin: bool) -> Self {
+        rooted!(in(context) let obj_root = obj);
+
+        if object_is_array(context, obj_root.handle()) {
+            trace!("EsValueFacade::new_v -> object -> array");
+
+            let mut prop_names = get_array_named_prop_names(context, obj_root.handle())
+                .unwrap_or_else(|err| {
+                    // e.g. a Proxy of an array which throws in its ownKeys trap, the elements may still be readable
+                    log::debug!(
+                        "could not get the named props of an array: {}",
+                        err.err_msg()
+                    );
+                    vec![]
+                });
+            if Self::is_template_strings_array(context, obj_root.handle()) {
+                // the raw prop of the strings array of a tagged template is not enumerable
+                prop_names.push("raw".to_string());
+            }
+
+            // every element is read exactly once (getters or a Proxy may have side effects) and kept rooted until it is converted
+            auto_root!(in (context) let mut elements = vec![]);
+            let arr_len = match get_array_length(context, obj_root.handle()) {
+                Ok(arr_len) => arr_len,
+                Err(err) => {
+                    // e.g. a Proxy of an array which throws in its get trap
+                    debug!(
+                        "could not get the length of an array, passing it as a reference: {}",
+                        err.err_msg()
+                    );
+                    return Self::new_object_ref(context, obj);
+                }
+            };
+            for x in 0..arr_len {
+                rooted!(in (context) let mut arr_element_root = UndefinedValue());
+                let get_res =
+                    get_array_element(context, obj_root.handle(), x, arr_element_root.handle_mut());
+                if let Err(err) = get_res {
+                    debug!(
+                        "could not get element of array, passing it as a reference: {}",
+                        err.err_msg()
+                    );
+                    return Self::new_object_ref(context, obj);
+                }
+                elements.push(*arr_element_root);
+            }
+
+            // fast path for arrays of numbers, no EsValueFacade is created per element
+            if prop_names.is_empty() && elements.iter().all(|element| element.is_number()) {
+                trace!("EsValueFacade::new_v -> object -> number array");
+                return EsNumberArray {
+                    numbers: elements.iter().map(|element| element.to_number()).collect(),
+                    vals: OnceLock::new(),
+                }
+                .to_es_value_facade()
+                .with_origin(context, obj, track_origin);
+            }
+
+            let mut vals = Vec::with_capacity(elements.len());
+            for element in elements.iter() {
+                rooted!(in (context) let element_root = *element);
+                vals.push(EsValueFacade::new_v2(context, element_root.handle(), false));
+            }
+
+            let esvf = if prop_names.is_empty() {
+                vals.to_es_value_facade()
+            } else {
+                trace!("EsValueFacade::new_v -> object -> array with props");
+                let mut props = HashMap::new();
+                for prop_name in prop_names {
+                    rooted!(in (context) let mut prop_val_root = UndefinedValue());
+                    let prop_val_res = objects::get_es_obj_prop_val(
+                        context,
+                        obj_root.handle(),
+                        prop_name.as_str(),
+                        prop_val_root.handle_mut(),
+                    );
+                    if let Err(err) = prop_val_res {
+                        // e.g. a throwing getter
+                        debug!(
+                            "error getting prop {}, passing the array as a reference: {}",
+                            prop_name,
+                            err.err_msg()
+                        );
+                        return Self::new_object_ref(context, obj);
+                    }
+                    props.insert(
+                        prop_name,
+                        EsValueFacade::new_v2(context, prop_val_root.handle(), false),
+                    );
+                }
+                EsArrayWithProps { vals, props }.to_es_value_facade()
+            };
+            esvf.with_origin(context, obj, track_origin)
+        } else if jsapi_utils::promises::object_is_promise(obj_root.handle()) {
+            trace!("EsValueFacade::new_v -> object -> promise");
+
+            let cached_prom_id =
+                spidermonkeyruntimewrapper::register_cached_object(context, *obj_root);
+
+            let (tx, rx) = channel();
+            let tx = Arc::new(Mutex::new(tx));
+            let tx2 = tx.clone();
+            let wake_sender = Arc::downgrade(&tx);
+            let canceled = Arc::new(AtomicBool::new(false));
+            let canceled2 = canceled.clone();
+            let canceled3 = canceled.clone();
+            assert!(jsapi_utils::promises::add_promise_reactions_callbacks(
+                context,
+                obj_root.handle(),
+                Some(
+                    move |cx, mut args: Vec<HandleValue>, _rval: MutableHandleValue| {
+                        if canceled2.load(Ordering::SeqCst) {
+                            // nobody is waiting for the result anymore
+                            return Ok(());
+                        }
+                        // promsie was resolved
+                        let resolution = args.remove(0);
+                        let res_esvf = EsValueFacade::new_v(cx, resolution);
+
+                        match tx
+                            .lock()
+                            .unwrap()
+                            .send(PromiseMessage::Settled(Ok(res_esvf)))
+                        {
+                            Ok(_) => Ok(()),
+                            // todo, does not include error (which is "sending on a closed channel") which is not ASCII and thus fails the error handler
+                            Err(e) => {
+                                debug!("send res error: {}", e);
+                                Err("send res error".to_string())
+                            }
+                        }
+                    }
+                ),
+                Some(
+                    move |cx, mut args: Vec<HandleValue>, _rval: MutableHandleValue| {
+                        if canceled3.load(Ordering::SeqCst) {
+                            return Ok(());
+                        }
+                        // promsie was rejected
+                        let rejection = args.remove(0);
+                        let rej_esvf = EsValueFacade::new_v(cx, rejection);
+
+                        match tx2
+                            .lock()
+                            .unwrap()
+                            .send(PromiseMessage::Settled(Err(rej_esvf)))
+                        {
+                            Ok(_) => Ok(()),
+                            // todo, does not include error (which is "sending on a closed channel") which is not ASCII and thus fails the error handler
+                            Err(e) => {
+                                debug!("send rejection error: {}", e);
+                                Err("send rejection error".to_string())
+                            }
+                        }
+                        // release epr
+                    }
+                )
+            ));
+
+            let opt_receiver = RefCell::new(Some(rx));
+
+            let rti_ref = spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+                let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+                Arc::downgrade(&sm_rt.clone_esrt_inner())
+            });
+            let rmev: CachedJSPromise = CachedJSPromise {
+                cached_obj_id: cached_prom_id,
+                opt_receiver,
+                wake_sender,
+                canceled,
+                rti_ref,
+            };
+
+            rmev.to_es_value_facade()
+        } else if jsapi_utils::functions::object_is_function(obj) {
+            trace!("EsValueFacade::new_v -> object -> function");
+            // wrap function in persistentrooted
+
+            let rti_ref = spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+                let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+                Arc::downgrade(&sm_rt.clone_esrt_inner())
+            });
+            let cached_obj_id = spidermonkeyruntimewrapper::register_cached_object(context, obj);
+            let cf = CachedJSFunction {
+                cached_obj_id,
+                rti_ref,
+            };
+            cf.to_es_value_facade()
+        } else if objects::object_is_weakmap(obj) || objects::object_is_weakset(obj) {
+            trace!("EsValueFacade::new_v -> object -> weakmap/weakset");
+
+            let rti_ref = spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+                let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+                Arc::downgrade(&sm_rt.clone_esrt_inner())
+            });
+            let is_set = !objects::object_is_weakmap(obj);
+            let cached_obj_id = spidermonkeyruntimewrapper::register_cached_object(context, obj);
+            CachedJSWeakCollection {
+                obj: CachedJSObject {
+                    cached_obj_id,
+                    rti_ref,
+                },
+                is_set,
+            }
+            .to_es_value_facade()
+        } else {
+            // the keys are enumerated once, the names are only converted if the object is copied
+            let prop_names = match objects::get_js_obj_prop_names_max(
+                context,
+                obj_root.handle(),
+                Self::get_eager_object_max_props(),
+            ) {
+                Ok(Some(prop_names)) => prop_names,
+                Ok(None) => {
+                    trace!("EsValueFacade::new_v -> object -> object_ref");
+                    return Self::new_object_ref(context, obj);
+                }
+                Err(err) => {
+                    // e.g. a Proxy which throws in its ownKeys trap, its properties may still be readable
+                    debug!(
+                        "could not get the props of an object, passing it as a reference: {}",
+                        err.err_msg()
+                    );
+                    return Self::new_object_ref(context, obj);
+                }
+            };
+            let mut map = HashMap::new();
+            trace!("EsValueFacade::new_v -> object -> object");
+            for prop_name in prop_names {
+                rooted!(in (context) let mut prop_val_root = UndefinedValue());
+                let prop_val_res = objects::get_es_obj_prop_val(
+                    context,
+                    obj_root.handle(),
+                    prop_name.as_str(),
+                    prop_val_root.handle_mut(),
+                );
+
+                if let Err(err) = prop_val_res {
+                    // e.g. a throwing getter
+                    debug!(
+                        "error getting prop {}, passing the object as a reference: {}",
+                        prop_name,
+                        err.err_msg()
+                    );
+                    return Self::new_object_ref(context, obj);
+                }
+
+                let prop_esvf = EsValueFacade::new_v2(context, prop_val_root.handle(), false);
+                map.insert(prop_name, prop_esvf);
+            }
+            map.to_es_value_facade()
+                .with_origin(context, obj, track_origin)
+        }
+    }
+
+    // the first argument of a template tag function is an array of strings with a raw prop which is an array of the raw strings
+    fn is_template_strings_array(context: *mut JSContext, arr_obj: HandleObject) -> bool {
+        rooted!(in (context) let mut raw_root = UndefinedValue());
+        objects::get_es_obj_prop_val(context, arr_obj, "raw", raw_root.handle_mut()).is_ok()
+            && raw_root.is_object()
+            && {
+                rooted!(in (context) let raw_obj_root = raw_root.to_object());
+                object_is_array(context, raw_obj_root.handle())
+            }
+    }
+
+    // remember the object this value was copied from so it is passed back to the runtime as is
+    // this is only done for the top level value of a copy so a large copied graph does not root every nested object
+    fn with_origin(
+        mut self,
+        context: *mut JSContext,
+        obj: *mut JSObject,
+        track_origin: bool,
+    ) -> Self {
+        if !track_origin {
+            return self;
+        }
+        let rti_ref_opt = spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+            let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+            sm_rt.opt_esrt_inner.clone()
+        });
+        let rti_ref = match rti_ref_opt {
+            Some(rti_ref) => rti_ref,
+            None => return self,
+        };
+        let cached_obj_id = spidermonkeyruntimewrapper::register_cached_object(context, obj);
+        self.origin = Some(CachedOrigin {
+            cached_obj_id,
+            rti_ref,
+        });
+        self
+    }
+
+    fn get_eager_object_max_props() -> usize {
+        spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+            let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+            sm_rt.eager_object_max_props
+        })
+    }
+
+    fn get_eager_string_max_len() -> usize {
+        spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+            let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+            sm_rt.eager_string_max_len
+        })
+    }
+
+    fn is_strict_string_conversion() -> bool {
+        spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+            let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+            sm_rt.strict_string_conversion
+        })
+    }
+
+    // keep a string in the runtime instead of copying it, see is_string_ref()
+    fn new_string_ref(context: *mut JSContext, val_handle: HandleValue) -> Self {
+        trace!("EsValueFacade::new_v -> string_ref");
+        rooted!(in (context) let mut holder_root = std::ptr::null_mut::<JSObject>());
+        objects::new_object(context, holder_root.handle_mut());
+        objects::set_es_obj_prop_value(
+            context,
+            holder_root.handle(),
+            CACHED_STRING_PROP,
+            val_handle,
+        );
+
+        let rti_ref = spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+            let sm_rt: &SmRuntime = &*sm_rt_rc.borrow();
+            Arc::downgrade(&sm_rt.clone_esrt_inner())
+        });
+        let cached_obj_id =
+            spidermonkeyruntimewrapper::register_cached_object(context, *holder_root);
+        CachedJSString {
+            cached_obj_id,
+            rti_ref,
+        }
+        .to_es_value_facade()
+    }
+
+    /// get the name of the type of the value, e.g. "i32", "string" or "object"
+    /// this is used as the actual type in an EsTypeError
+    pub fn get_type_name(&self) -> &'static str {
+        if self.is_undefined() {
+            "undefined"
+        } else if self.is_null() {
+            "null"
+        } else if self.is_boolean() {
+            "boolean"
+        } else if self.is_i32() {
+            "i32"
+        } else if self.is_f64() {
+            "f64"
+        } else if self.is_string() {
+            "string"
+        } else if self.is_string_ref() {
+            "string reference"
+        } else if self.is_weakmap() {
+            "weakmap"
+        } else if self.is_weakset() {
+            "weakset"
+        } else if self.is_promise() {
+            "promise"
+        } else if self.is_function() {
+            "function"
+        } else if self.is_array() {
+            "array"
+        } else if self.is_object() {
+            "object"
+        } else if self.is_object_ref() {
+            "object reference"
+        } else {
+            "unknown"
+        }
+    }
+
+    fn type_error(&self, expected: &'static str) -> EsTypeError {
+        EsTypeError {
+            expected,
+            actual: self.get_type_name(),
+        }
+    }
+
+    /// get the String value
+    /// this panics if the value is not a string, use try_get_string() for values which come from script
+    /// note that strings with an unpaired surrogate are string references if the runtime was built with strict_string_conversion(true), see try_get_string_strict()
+    pub fn get_string(&self) -> &str {
+        self.convertible.get_str()
+    }
+
+    /// get the String value or an EsTypeError if the value is not a string
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_sync("123;", "test_try_get_string.es").ok().expect("script failed");
+    /// let err = esvf.try_get_string().err().expect("123 is not a string");
+    /// assert_eq!(err.to_string().as_str(), "expected string but got i32");
+    /// ```
+    pub fn try_get_string(&self) -> Result<&str, EsTypeError> {
+        if self.is_string() {
+            Ok(self.get_string())
+        } else {
+            Err(self.type_error("string"))
+        }
+    }
+
+    /// get the value of a string or a string reference as a String, this fails if the value is not a string or if it contains an unpaired surrogate
+    /// only strings which were kept in the runtime (see is_string_ref()) can be checked for unpaired surrogates
+    /// so use EsRuntimeBuilder::strict_string_conversion(true) if strings from script should never be altered
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().strict_string_conversion(true).build();
+    /// let esvf = rt.eval_sync("'a\\uD800b';", "test_try_get_string_strict.es").ok().expect("script failed");
+    /// assert!(esvf.try_get_string_strict().is_err());
+    /// let esvf = rt.eval_sync("'ab';", "test_try_get_string_strict2.es").ok().expect("script failed");
+    /// assert_eq!(esvf.try_get_string_strict().ok().unwrap(), "ab");
+    /// ```
+    pub fn try_get_string_strict(&self) -> Result<String, EsErrorInfo> {
+        if self.is_string() {
+            Ok(self.get_string().to_string())
+        } else {
+            self.convertible.get_string_strict()
+        }
+    }
+
+    /// get the value of a string or a string reference as a String, unpaired surrogates are replaced by U+FFFD
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().strict_string_conversion(true).build();
+    /// let esvf = rt.eval_sync("'a\\uD800b';", "test_to_string_lossy.es").ok().expect("script failed");
+    /// // the string is not altered when converting it to an EsValueFacade
+    /// assert!(esvf.is_string_ref());
+    /// assert_eq!(esvf.to_string_lossy().ok().unwrap(), "a\u{FFFD}b");
+    /// ```
+    pub fn to_string_lossy(&self) -> Result<String, EsErrorInfo> {
+        if self.is_string_ref() {
+            self.substring(0, self.string_length()?)
+        } else {
+            Ok(self.get_string().to_string())
+        }
+    }
+
+    /// get the i32 value
+    /// this panics if the value is not an i32, use try_get_i32() for values which come from script
+    pub fn get_i32(&self) -> i32 {
+        self.convertible.get_i32()
+    }
+
+    /// get the i32 value or an EsTypeError if the value is not an i32
+    pub fn try_get_i32(&self) -> Result<i32, EsTypeError> {
+        if self.is_i32() {
+            Ok(self.get_i32())
+        } else {
+            Err(self.type_error("i32"))
+        }
+    }
+
+    /// get the f64 value
+    /// this panics if the value is not an f64, use try_get_f64() for values which come from script
+    pub fn get_f64(&self) -> f64 {
+        self.convertible.get_f64()
+    }
+
+    /// get the f64 value or an EsTypeError if the value is not an f64
+    pub fn try_get_f64(&self) -> Result<f64, EsTypeError> {
+        if self.is_f64() {
+            Ok(self.get_f64())
+        } else {
+            Err(self.type_error("f64"))
+        }
+    }
+
+    /// get the boolean value
+    /// this panics if the value is not a boolean, use try_get_boolean() for values which come from script
+    pub fn get_boolean(&self) -> bool {
+        self.convertible.get_bool()
+    }
+
+    /// get the boolean value or an EsTypeError if the value is not a boolean
+    pub fn try_get_boolean(&self) -> Result<bool, EsTypeError> {
+        if self.is_boolean() {
+            Ok(self.get_boolean())
+        } else {
+            Err(self.type_error("boolean"))
+        }
+    }
+
+    /// check if this esvf was a promise which was returned from the script engine
+    pub fn is_promise(&self) -> bool {
+        self.convertible.is_promise()
+    }
+
+    /// wait for a promise to resolve in rust
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use std::time::Duration;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// // run the script and fail if script fails
+    /// let esvf_prom = rt.eval_sync(
+    ///     "let p = new Promise((resolve, reject) => {setImmediate(() => {resolve(123);});}); p;",
+    ///     "test_get_promise_result_blocking.es").ok().expect("script failed");
+    /// // wait for the promise or fail on timeout
+    /// let wait_res = esvf_prom.get_promise_result_blocking(Duration::from_secs(1))
+    ///     .ok().expect("promise timed out");
+    /// // get the ok result, fail is promise was rejected
+    /// let esvf = wait_res.ok().expect("promise was rejected");
+    /// // check the result
+    /// assert_eq!(esvf.get_i32(), 123);
+    /// ```
+    pub fn get_promise_result_blocking(
+        &self,
+        timeout: Duration,
+    ) -> Result<Result<EsValueFacade, EsValueFacade>, RecvTimeoutError> {
+        // todo
+        self.convertible.await_promise_blocking(timeout)
+    }
+
+    /// wait for a Promise like get_promise_result_blocking() but stop waiting when the CancelToken is canceled
+    /// when canceled this returns RecvTimeoutError::Disconnected, the result of the promise is then no longer sent to this facade
+    /// and waiting for it again also returns Disconnected
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::CancelToken;
+    /// use std::sync::mpsc::RecvTimeoutError;
+    /// use std::time::Duration;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf_prom = rt.eval_sync("new Promise(() => {});", "test_get_promise_result_blocking_cancellable.es")
+    ///     .ok().expect("script failed");
+    /// let token = CancelToken::new();
+    /// let token2 = token.clone();
+    /// std::thread::spawn(move || {
+    ///     // e.g. the client disconnected
+    ///     token2.cancel();
+    /// });
+    /// let res = esvf_prom.get_promise_result_blocking_cancellable(Duration::from_secs(60), &token);
+    /// assert_eq!(res.err(), Some(RecvTimeoutError::Disconnected));
+    /// ```
+    pub fn get_promise_result_blocking_cancellable(
+        &self,
+        timeout: Duration,
+        cancel_token: &CancelToken,
+    ) -> Result<Result<EsValueFacade, EsValueFacade>, RecvTimeoutError> {
+        self.convertible
+            .await_promise_blocking_cancellable(timeout, cancel_token)
+    }
+
+    /// get the value as a Map of EsValueFacades, this works when the value was an object in the script engine
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_sync("{a: 1, b: 2};", "test_get_object.es").ok().expect("script failed");
+    /// let map = esvf.get_object();
+    /// assert!(map.contains_key("a"));
+    /// assert!(map.contains_key("b"));
+    /// ```
+    pub fn get_object(&self) -> &HashMap<String, EsValueFacade> {
+        self.convertible.get_object()
+    }
+
+    /// get the value as a Map of EsValueFacades or an EsTypeError if the value is not an object
+    pub fn try_get_object(&self) -> Result<&HashMap<String, EsValueFacade>, EsTypeError> {
+        if self.is_object() {
+            Ok(self.get_object())
+        } else {
+            Err(self.type_error("object"))
+        }
+    }
+
+    /// get the value as a Vec of EsValueFacades, this works when the value was an array in the script engine
+    /// named properties of the array (e.g. `arr.meta = 1;`) are not part of the Vec, use get_array_props() to get those
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_sync("[1, 2, 3];", "test_get_array.es").ok().expect("script failed");
+    /// let arr: &Vec<EsValueFacade> = esvf.get_array();
+    /// assert_eq!(arr.len(), 3);
+    /// ```
+    pub fn get_array(&self) -> &Vec<EsValueFacade> {
+        self.convertible.get_array()
+    }
+
+    /// get the elements of an array of numbers as f64s, this panics if the value is not an array or if an element is not a number
+    /// arrays which only contain numbers are read from script in one pass, for those this does not create an EsValueFacade per element
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_sync("[1, 2.5, 3];", "test_get_number_array.es").ok().expect("script failed");
+    /// assert_eq!(esvf.get_number_array(), vec![1.0, 2.5, 3.0]);
+    /// ```
+    pub fn get_number_array(&self) -> Vec<f64> {
+        if let Some(number_array) = self.downcast_ref::<EsNumberArray>() {
+            return number_array.numbers.clone();
+        }
+        self.get_array()
+            .iter()
+            .map(|esvf| {
+                if esvf.is_i32() {
+                    esvf.get_i32() as f64
+                } else {
+                    esvf.get_f64()
+                }
+            })
+            .collect()
+    }
+
+    /// get the value as a Vec of EsValueFacades or an EsTypeError if the value is not an array
+    pub fn try_get_array(&self) -> Result<&Vec<EsValueFacade>, EsTypeError> {
+        if self.is_array() {
+            Ok(self.get_array())
+        } else {
+            Err(self.type_error("array"))
+        }
+    }
+
+    /// get the named properties of an array, returns None if the value is not an array or if the array has no named properties
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_sync("let arr = [1, 2]; arr.total = 2; arr;", "test_get_array_props.es").ok().expect("script failed");
+    /// assert_eq!(esvf.get_array().len(), 2);
+    /// let props = esvf.get_array_props().expect("no props");
+    /// assert_eq!(props.get("total").unwrap().get_i32(), 2);
+    /// ```
+    pub fn get_array_props(&self) -> Option<&HashMap<String, EsValueFacade>> {
+        self.convertible.get_array_props()
+    }
+
+    /// invoke the function that was returned from the script engine
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let func_esvf = rt.eval_sync("(function(a){return (a / 2);});", "test_invoke_function.es")
+    ///     .ok().expect("script failed");
+    /// // invoke the function with 18
+    /// let res_esvf = func_esvf.invoke_function(vec![EsValueFacade::new_i32(18)])
+    ///     .ok().expect("function failed");
+    /// // check that 19 / 2 = 9
+    /// let res_i32 = res_esvf.get_i32();
+    /// assert_eq!(res_i32, 9);
+    /// ```
+    pub fn invoke_function(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
+        trace!("EsValueFacade.invoke_function()");
+        self.convertible.invoke_function(args)
+    }
+
+    /// invoke the function that was returned from the script engine with an explicit this value, like Reflect.apply() does
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use std::collections::HashMap;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let func_esvf = rt.eval_sync("(function(a){return this.prefix + a;});", "test_apply.es")
+    ///     .ok().expect("script failed");
+    /// let mut props = HashMap::new();
+    /// props.insert("prefix".to_string(), EsValueFacade::new_str("hello ".to_string()));
+    /// let res_esvf = func_esvf.apply(EsValueFacade::new_obj(props), vec![EsValueFacade::new_str("world".to_string())])
+    ///     .ok().expect("function failed");
+    /// assert_eq!(res_esvf.get_string(), "hello world");
+    /// ```
+    pub fn apply(
+        &self,
+        this_val: EsValueFacade,
+        args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        trace!("EsValueFacade.apply()");
+        self.convertible.apply(this_val, args)
+    }
+
+    /// get the name of the function that was returned from the script engine, this is an empty String for anonymous functions
+    /// this fails if this is not a function or if the runtime of the function was dropped
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let func_esvf = rt.eval_sync("(function add(a, b){return a + b;});", "test_function_name.es")
+    ///     .ok().expect("script failed");
+    /// assert_eq!(func_esvf.function_name().ok().unwrap().as_str(), "add");
+    /// assert_eq!(func_esvf.function_arity().ok().unwrap(), 2);
+    /// ```
+    pub fn function_name(&self) -> Result<String, EsErrorInfo> {
+        self.convertible.function_name()
+    }
+
+    /// get the number of declared parameters (the length property) of the function that was returned from the script engine
+    /// like in script rest parameters and parameters after the first one with a default value are not counted
+    /// this fails if this is not a function or if the runtime of the function was dropped
+    pub fn function_arity(&self) -> Result<u32, EsErrorInfo> {
+        self.convertible.function_arity()
+    }
+
+    /// get the source of the function that was returned from the script engine, this is the same as calling toString() on the function in script
+    /// this fails if this is not a function or if the runtime of the function was dropped
+    pub fn function_source(&self) -> Result<String, EsErrorInfo> {
+        self.convertible.function_source()
+    }
+
+    /// construct a new instance by calling the constructor function that was returned from the script engine with new
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let class_esvf = rt.eval_sync("(class Point {constructor(x, y){this.x = x; this.y = y;}});", "test_construct.es")
+    ///     .ok().expect("script failed");
+    /// let point_esvf = class_esvf.construct(vec![EsValueFacade::new_i32(3), EsValueFacade::new_i32(4)])
+    ///     .ok().expect("constructor failed");
+    /// assert_eq!(point_esvf.get_object().get("y").unwrap().get_i32(), 4);
+    /// ```
+    pub fn construct(&self, args: Vec<EsValueFacade>) -> Result<EsValueFacade, EsErrorInfo> {
+        trace!("EsValueFacade.construct()");
+        self.convertible.construct(args)
+    }
+
+    /// get a reference to the EsValueConvertible this EsValueFacade was created from if it is of type T
+    /// this can be used to pass rust data to other rust code without converting it to a script value
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esvaluefacade::{EsValueConvertible, EsValueFacade};
+    ///
+    /// let esvf = EsValueFacade::new_str("hello".to_string());
+    /// assert_eq!(esvf.downcast_ref::<String>().unwrap().as_str(), "hello");
+    /// assert!(esvf.downcast_ref::<i32>().is_none());
+    /// ```
+    pub fn downcast_ref<T: EsValueConvertible + 'static>(&self) -> Option<&T> {
+        // deref the box first so we get the Any of the convertible and not of the box
+        (*self.convertible).as_any().downcast_ref::<T>()
+    }
+
+    /// check if the value is a String
+    pub fn is_string(&self) -> bool {
+        self.convertible.is_str()
+    }
+
+    /// check if the value is a i32
+    pub fn is_i32(&self) -> bool {
+        self.convertible.is_i32()
+    }
+
+    /// check if the value is a f64
+    /// this is also true for NaN, Infinity, -Infinity and -0 which are never represented as an i32
+    pub fn is_f64(&self) -> bool {
+        self.convertible.is_f64()
+    }
+
+    /// check if the value is a finite number, this returns false for NaN, Infinity and values which are not a number
+    pub fn is_finite(&self) -> bool {
+        if self.is_i32() {
+            true
+        } else {
+            self.is_f64() && self.get_f64().is_finite()
+        }
+    }
+
+    /// check if the value is NaN, this returns false for values which are not a number
+    pub fn is_nan(&self) -> bool {
+        self.is_f64() && self.get_f64().is_nan()
+    }
+
+    /// check if the value is Infinity or -Infinity, this returns false for values which are not a number
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_sync("1 / 0;", "test_is_infinite.es").ok().expect("script failed");
+    /// assert!(esvf.is_infinite());
+    /// assert!(!esvf.is_finite());
+    /// ```
+    pub fn is_infinite(&self) -> bool {
+        self.is_f64() && self.get_f64().is_infinite()
+    }
+
+    /// check if the value is a bool
+    pub fn is_boolean(&self) -> bool {
+        self.convertible.is_bool()
+    }
+
+    /// check if the value is an object
+    pub fn is_object(&self) -> bool {
+        self.convertible.is_object()
+    }
+
+    /// check if the value is an array
+    pub fn is_array(&self) -> bool {
+        self.convertible.is_array()
+    }
+
+    /// check if the value is a reference to an object in the script engine
+    /// this is the case for objects with more properties than configured by EsRuntimeBuilder::eager_object_max_props()
+    /// the properties of those objects are not copied so you can't use get_object(), use get_property(), set_property() and invoke_method() instead
+    /// use eager_object_max_props(0) to keep all objects as references
+    pub fn is_object_ref(&self) -> bool {
+        self.convertible.is_object_ref()
+    }
+
+    /// get a property of an object reference, this will get the property from the object in the script engine
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().eager_object_max_props(1).build();
+    /// let esvf = rt.eval_sync("({a: 1, b: 2});", "test_get_property.es").ok().expect("script failed");
+    /// assert!(esvf.is_object_ref());
+    /// let b = esvf.get_property("b").ok().expect("could not get b");
+    /// assert_eq!(b.get_i32(), 2);
+    /// ```
+    pub fn get_property(&self, name: &str) -> Result<EsValueFacade, EsErrorInfo> {
+        self.convertible.get_property(name)
+    }
+
+    /// set a property of an object reference, this will set the property of the object in the script engine
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().eager_object_max_props(0).build();
+    /// let esvf = rt.eval_sync("this.myObj = {a: 1}; myObj;", "test_set_property.es").ok().expect("script failed");
+    /// esvf.set_property("a", EsValueFacade::new_i32(2)).ok().expect("could not set a");
+    /// let a = rt.eval_sync("myObj.a;", "test_set_property2.es").ok().expect("script failed");
+    /// assert_eq!(a.get_i32(), 2);
+    /// ```
+    pub fn set_property(&self, name: &str, value: EsValueFacade) -> Result<(), EsErrorInfo> {
+        self.convertible.set_property(name, value)
+    }
+
+    /// get the keys of the own properties of an object reference, see objects::get_all_own_prop_names()
+    /// this fails if this is not an object reference (see is_object_ref())
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::objects::PropKey;
+    ///
+    /// let rt = EsRuntimeBuilder::new().eager_object_max_props(0).build();
+    /// let esvf = rt.eval_sync("let o = {a: 1}; Object.defineProperty(o, 'b', {value: 2}); o;", "test_get_all_own_prop_names.es").ok().expect("script failed");
+    /// let keys = esvf.get_all_own_prop_names(true, false).ok().expect("could not get keys");
+    /// assert_eq!(keys, vec![PropKey::String("a".to_string()), PropKey::String("b".to_string())]);
+    /// ```
+    pub fn get_all_own_prop_names(
+        &self,
+        include_non_enumerable: bool,
+        include_symbols: bool,
+    ) -> Result<Vec<objects::PropKey>, EsErrorInfo> {
+        self.convertible
+            .get_all_own_prop_names(include_non_enumerable, include_symbols)
+    }
+
+    /// call a method of an object reference with the object as this
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().eager_object_max_props(0).build();
+    /// let esvf = rt.eval_sync("({a: 1, add: function(b) {return this.a + b;}});", "test_invoke_method.es").ok().expect("script failed");
+    /// let res = esvf.invoke_method("add", vec![EsValueFacade::new_i32(2)]).ok().expect("add failed");
+    /// assert_eq!(res.get_i32(), 3);
+    /// ```
+    pub fn invoke_method(
+        &self,
+        name: &str,
+        args: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        self.convertible.invoke_method(name, args)
+    }
+
+    /// check if this facade and another facade reference the same object in the script engine
+    /// this is only the case for functions, promises and object references (see is_object_ref()) which were passed from the script engine to rust
+    /// other values (e.g. eagerly copied objects) always return false
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_sync("let f = function(){}; [f, f];", "test_same_object_as.es").ok().expect("script failed");
+    /// let arr = esvf.get_array();
+    /// assert!(arr[0].same_object_as(&arr[1]));
+    /// ```
+    pub fn same_object_as(&self, other: &EsValueFacade) -> bool {
+        match (
+            self.convertible.get_cached_object(),
+            other.convertible.get_cached_object(),
+        ) {
+            (Some((id, rti_ref)), Some((other_id, other_rti_ref))) => {
+                if !Weak::ptr_eq(rti_ref, other_rti_ref) {
+                    false
+                } else if id == other_id {
+                    true
+                } else if let Some(rt_arc) = rti_ref.upgrade() {
+                    rt_arc.do_in_es_event_queue_sync(move |_sm_rt| {
+                        let obj = spidermonkeyruntimewrapper::do_with_cached_object(
+                            id,
+                            |epr: &EsPersistentRooted| epr.get(),
+                        );
+                        let other_obj = spidermonkeyruntimewrapper::do_with_cached_object(
+                            other_id,
+                            |epr: &EsPersistentRooted| epr.get(),
+                        );
+                        obj == other_obj
+                    })
+                } else {
+                    // the runtime was dropped
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// check if the value is a reference to a string in the script engine
+    /// this is the case for strings longer than configured by EsRuntimeBuilder::eager_string_max_len()
+    /// those strings are not copied so you can't use get_string(), use string_length() and substring() instead
+    pub fn is_string_ref(&self) -> bool {
+        self.convertible.is_string_ref()
+    }
+
+    /// get the length of a string (or string reference) in UTF-16 code units, like the length property in script
+    /// this fails if this is not a string or if the runtime of a string reference was dropped
+    pub fn string_length(&self) -> Result<usize, EsErrorInfo> {
+        self.convertible.string_length()
+    }
+
+    /// get a part of a string (or string reference), for string references only that part is copied from the script engine
+    /// start and end are indexes in UTF-16 code units (like String.prototype.substring in script) and are clamped to the length of the string
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().eager_string_max_len(10).build();
+    /// let esvf = rt.eval_sync("'abc'.repeat(1000);", "test_substring.es").ok().expect("script failed");
+    /// assert!(esvf.is_string_ref());
+    /// assert_eq!(esvf.string_length().ok().unwrap(), 3000);
+    /// assert_eq!(esvf.substring(1, 4).ok().unwrap().as_str(), "bca");
+    /// ```
+    pub fn substring(&self, start: usize, end: usize) -> Result<String, EsErrorInfo> {
+        self.convertible.substring(start, end)
+    }
+
+    /// check if the value is a WeakMap
+    /// WeakMaps can't be enumerated so they are passed as a reference to the WeakMap in the script engine
+    /// you can pass them back to script or use invoke_method() to call get(), set() or has()
+    pub fn is_weakmap(&self) -> bool {
+        self.convertible.is_weakmap()
+    }
+
+    /// check if the value is a WeakSet, like WeakMaps these are passed as a reference to the WeakSet in the script engine
+    pub fn is_weakset(&self) -> bool {
+        self.convertible.is_weakset()
+    }
+
+    /// check if the value is an function
+    pub fn is_function(&self) -> bool {
+        self.convertible.is_function()
+    }
+
+    /// check if the value is undefined
+    /// null values returned from the script engine are not undefined, see is_null()
+    pub fn is_undefined(&self) -> bool {
+        self.convertible.is_undefined()
+    }
+
+    /// check if the value is null
+    pub fn is_null(&self) -> bool {
+        self.convertible.is_null()
+    }
+
+    /// format the value for logging or debugging, much like util.inspect does in node.js
+    /// nested objects and arrays deeper than depth are shown as [Object] and [Array]
+    /// functions are shown as [Function: name] and promises as Promise { <state> }
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = rt.eval_sync("({a: [1, 'two', {b: {c: true}}]});", "test_to_debug_string.es").ok().expect("script failed");
+    /// assert_eq!(esvf.to_debug_string(2).as_str(), "{ a: [ 1, 'two', { b: [Object] } ] }");
+    /// ```
+    pub fn to_debug_string(&self, depth: usize) -> String {
+        let mut out = String::new();
+        self.debug_fmt(depth, 0, &mut out);
+        out
+    }
+
+    fn debug_fmt(&self, depth: usize, level: usize, out: &mut String) {
+        if self.is_undefined() {
+            out.push_str("undefined");
+        } else if self.is_null() {
+            out.push_str("null");
+        } else if self.is_boolean() {
+            out.push_str(if self.get_boolean() { "true" } else { "false" });
+        } else if self.is_i32() {
+            out.push_str(self.get_i32().to_string().as_str());
+        } else if self.is_f64() {
+            let num = self.get_f64();
+            if num.is_nan() {
+                out.push_str("NaN");
+            } else if num.is_infinite() {
+                out.push_str(if num > 0.0 { "Infinity" } else { "-Infinity" });
+            } else {
+                out.push_str(num.to_string().as_str());
+            }
+        } else if self.is_string_ref() {
+            match (
+                self.string_length(),
+                self.substring(0, DEBUG_MAX_STRING_LEN),
+            ) {
+                (Ok(len), Ok(sub)) => {
+                    out.push_str(Self::debug_quote(sub.as_str()).as_str());
+                    if len > DEBUG_MAX_STRING_LEN {
+                        out.push_str(
+                            format!("... {} more characters", len - DEBUG_MAX_STRING_LEN).as_str(),
+                        );
+                    }
+                }
+                _ => out.push_str("[String: <unknown>]"),
+            }
+        } else if self.is_string() {
+            out.push_str(Self::debug_quote(self.get_string()).as_str());
+        } else if self.is_function() {
+            let name = self
+                .downcast_ref::<CachedJSFunction>()
+                .and_then(|func| func.get_name().ok())
+                .unwrap_or_default();
+            if name.is_empty() {
+                out.push_str("[Function (anonymous)]");
+            } else {
+                out.push_str(format!("[Function: {}]", name).as_str());
+            }
+        } else if self.is_promise() {
+            let state_res = match self.downcast_ref::<CachedJSPromise>() {
+                Some(prom) => prom.get_state(),
+                None => Ok((PromiseState::Pending, None)),
+            };
+            out.push_str("Promise { ");
+            match state_res {
+                Ok((PromiseState::Rejected, Some(res))) => {
+                    out.push_str("<rejected> ");
+                    res.debug_fmt(depth, level + 1, out);
+                }
+                Ok((_, Some(res))) => {
+                    res.debug_fmt(depth, level + 1, out);
+                }
+                Ok((_, None)) => {
+                    out.push_str("<pending>");
+                }
+                Err(_) => {
+                    out.push_str("<unknown>");
+                }
+            }
+            out.push_str(" }");
+        } else if self.is_array() {
+            let arr = self.get_array();
+            let props_opt = self.get_array_props();
+            if arr.is_empty() && props_opt.is_none() {
+                out.push_str("[]");
+            } else if level > depth {
+                out.push_str("[Array]");
+            } else {
+                out.push_str("[ ");
+                for (x, item) in arr.iter().enumerate() {
+                    if x > 0 {
+                        out.push_str(", ");
+                    }
+                    item.debug_fmt(depth, level + 1, out);
+                }
+                if let Some(props) = props_opt {
+                    if !arr.is_empty() {
+                        out.push_str(", ");
+                    }
+                    Self::debug_fmt_props(props, depth, level, out);
+                }
+                out.push_str(" ]");
+            }
+        } else if self.is_object() {
+            let map = self.get_object();
+            if map.is_empty() {
+                out.push_str("{}");
+            } else if level > depth {
+                out.push_str("[Object]");
+            } else {
+                out.push_str("{ ");
+                Self::debug_fmt_props(map, depth, level, out);
+                out.push_str(" }");
+            }
+        } else if self.is_object_ref() {
+            // the props of an object reference are not copied
+            out.push_str("[Object]");
+        } else {
+            out.push_str("[unknown]");
+        }
+    }
+
+    fn debug_fmt_props(
+        props: &HashMap<String, EsValueFacade>,
+        depth: usize,
+        level: usize,
+        out: &mut String,
+    ) {
+        // sort the keys so the output is predictable
+        let mut keys: Vec<&String> = props.keys().collect();
+        keys.sort();
+        for (x, key) in keys.into_iter().enumerate() {
+            if x > 0 {
+                out.push_str(", ");
+            }
+            if Self::debug_is_identifier(key) {
+                out.push_str(key);
+            } else {
+                out.push_str(Self::debug_quote(key).as_str());
+            }
+            out.push_str(": ");
+            props.get(key).unwrap().debug_fmt(depth, level + 1, out);
+        }
+    }
+
+    fn debug_quote(s: &str) -> String {
+        format!(
+            "'{}'",
+            s.replace('\\', "\\\\")
+                .replace('\'', "\\'")
+                .replace('\n', "\\n")
+        )
+    }
+
+    fn debug_is_identifier(s: &str) -> bool {
+        let mut chars = s.chars();
+        match chars.next() {
+            Some(c) if c.is_alphabetic() || c == '_' || c == '$' => {
+                chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+            }
+            _ => false,
+        }
+    }
+
+    /// convert this value to a script value and pass it to a closure, this is meant for passing values to raw JSAPI functions
+    /// this needs to run in the worker thread of the runtime, e.g. in SmRuntime::do_with_jsapi()
+    /// the value is rooted until the closure returns, it should not be stored outside of the closure without rooting it
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// let esvf = EsValueFacade::new_str("hello".to_string());
+    /// let is_str = rt.do_in_es_event_queue_sync(move |sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, _global| {
+    ///         esvf.with_raw_value(cx, |val| val.is_string())
+    ///     })
+    /// });
+    /// assert!(is_str);
+    /// ```
+    pub fn with_raw_value<R, C>(&self, context: *mut JSContext, consumer: C) -> R
+    where
+        C: FnOnce(HandleValue) -> R,
+    {
+        rooted!(in (context) let mut val_root = UndefinedValue());
+        self.to_es_value(context, val_root.handle_mut());
+        consumer(val_root.handle())
+    }
+
+    pub(crate) fn to_es_value(&self, context: *mut JSContext, return_val: MutableHandleValue) {
+        trace!("to_es_value.1");
+
+        if let Some(origin) = &self.origin {
+            // only use the original object if we're converting the value in the runtime it came from
+            if is_current_runtime(&origin.rti_ref) {
+                trace!("to_es_value.1 -> origin");
+                let obj = spidermonkeyruntimewrapper::do_with_cached_object(
+                    origin.cached_obj_id,
+                    |epr: &EsPersistentRooted| epr.get(),
+                );
+                let mut return_val = return_val;
+                return_val.set(ObjectValue(obj));
+                return;
+            }
+        }
+
+        self.convertible.to_js_value(context, return_val)
+    }
+}
+
+type PromiseResultContainer = Either<Result<EsValueFacade, String>, (usize, Weak<EsRuntimeInner>)>;
+type PromiseResultContainerOption = Option<PromiseResultContainer>;
+
+impl Drop for RustPromise {
+    fn drop(&mut self) {
+        // drop from map if val is None, task has not run yet and to_es_val was not called
+        let map: &mut PromiseAnswersMap = &mut PROMISE_ANSWERS.lock("EsValueFacade::drop").unwrap();
+        let id = self.id;
+        if let Some(opt) = map.get(&id) {
+            if opt.is_none() {
+                map.remove(&id);
+            }
+        }
+    }
+}
+
+impl Drop for CachedJSObject {
+    fn drop(&mut self) {
+        release_cached_object_later(&self.rti_ref, self.cached_obj_id);
+    }
+}
+
+impl Drop for CachedJSString {
+    fn drop(&mut self) {
+        release_cached_object_later(&self.rti_ref, self.cached_obj_id);
+    }
+}
+
+impl Drop for CachedJSFunction {
+    fn drop(&mut self) {
+        release_cached_object_later(&self.rti_ref, self.cached_obj_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::esruntime::tests::init_test_runtime;
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::esvaluefacade::{EsObjectBuilder, EsTypeError, EsValueConvertible, EsValueFacade};
+    use crate::jsapi_utils::objects::PropKey;
+    use crate::jsapi_utils::EsErrorInfo;
+    use mozjs::jsapi::JSContext;
+    use mozjs::jsval::Int32Value;
+    use mozjs::rust::MutableHandleValue;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn in_and_output_vars() {
+        log::info!("test: in_and_output_vars");
+
+        let rt = init_test_runtime();
+        rt.add_global_sync_function("test_op_0", |args: Vec<EsValueFacade>| {
+            let args1 = args.get(0).expect("did not get a first arg");
+            let args2 = args.get(1).expect("did not get a second arg");
+
+            let x = args1.get_i32() as f64;
+            let y = args2.get_i32() as f64;
+
+            Ok(EsValueFacade::new_f64(x / y))
+        });
+
+        rt.add_global_sync_function("test_op_1", |args: Vec<EsValueFacade>| {
+            let args1 = args.get(0).expect("did not get a first arg");
+            let args2 = args.get(1).expect("did not get a second arg");
+
+            let x = args1.get_i32();
+            let y = args2.get_i32();
+
+            Ok(EsValueFacade::new_i32(x * y))
+        });
+
+        rt.add_global_sync_function("test_op_2", |args: Vec<EsValueFacade>| {
+            let args1 = args.get(0).expect("did not get a first arg");
+            let args2 = args.get(1).expect("did not get a second arg");
+
+            let x = args1.get_i32();
+            let y = args2.get_i32();
+
+            Ok(EsValueFacade::new_bool(x > y))
+        });
+
+        rt.add_global_sync_function("test_op_3", |args: Vec<EsValueFacade>| {
+            let args1 = args.get(0).expect("did not get a first arg");
+            let args2 = args.get(1).expect("did not get a second arg");
+
+            let x = args1.get_i32();
+            let y = args2.get_i32();
+
+            let res_str = format!("{}", x * y);
+            Ok(EsValueFacade::new_str(res_str))
+        });
+
+        let res0 = rt.eval_sync("test_op_0(13, 17);", "test_vars0.es");
+        let res1 = rt.eval_sync("test_op_1(13, 17);", "test_vars1.es");
+        let res2 = rt.eval_sync("test_op_2(13, 17);", "test_vars2.es");
+        let res3 = rt.eval_sync("test_op_3(13, 17);", "test_vars3.es");
+        let esvf0 = res0.ok().expect("1 did not get a result");
+        let esvf1 = res1.ok().expect("1 did not get a result");
+        let esvf2 = res2.ok().expect("2 did not get a result");
+        let esvf3 = res3.ok().expect("3 did not get a result");
+
+        assert_eq!(esvf0.get_f64(), (13_f64 / 17_f64));
+        assert_eq!(esvf1.get_i32(), (13 * 17) as i32);
+        assert_eq!(esvf2.get_boolean(), false);
+        assert_eq!(esvf3.get_string(), format!("{}", 13 * 17).as_str());
+    }
+
+    #[test]
+    fn test_wait_for_native_prom() {
+        log::info!("test: test_wait_for_native_prom");
+
+        let rt = init_test_runtime();
+        let esvf_prom = rt
+            .eval_sync(
+                "let p = new Promise((resolve, reject) => {resolve(123);});p = p.then((v) => {return v;});p = p.then((v) => {return v;});p = p.then((v) => {return v;});p = p.then((v) => {return v;});p = p.then((v) => {return v;});p = p.then((v) => {return v;}); p;",
+                "wait_for_prom.es",
+            )
+            .ok()
+            .unwrap();
+        assert!(esvf_prom.is_promise());
+        let esvf_prom_resolved = esvf_prom
+            .get_promise_result_blocking(Duration::from_secs(60))
+            .ok()
+            .unwrap()
+            .ok()
+            .unwrap();
+
+        assert!(esvf_prom_resolved.is_i32());
+        assert_eq!(esvf_prom_resolved.get_i32().clone(), 123 as i32);
+    }
+
+    #[test]
+    fn test_wait_for_prom() {
+        log::info!("test: test_wait_for_prom");
+
+        let rt = init_test_runtime();
+        let esvf_prom = rt
+            .eval_sync(
+                "let test_wait_for_prom_prom = new Promise((resolve, reject) => {resolve(123);}); test_wait_for_prom_prom;",
+                "wait_for_prom.es",
+            )
+            .ok()
+            .unwrap();
+        assert!(esvf_prom.is_promise());
+        let esvf_prom_resolved = esvf_prom
+            .get_promise_result_blocking(Duration::from_secs(60))
+            .ok()
+            .unwrap()
+            .ok()
+            .unwrap();
+
+        assert!(esvf_prom_resolved.is_i32());
+        assert_eq!(esvf_prom_resolved.get_i32().clone(), 123 as i32);
+    }
+
+    #[test]
+    fn test_wait_for_prom2() {
+        log::info!("test: test_wait_for_prom2");
+
+        let rt = init_test_runtime();
+
+        let esvf_prom_res: Result<EsValueFacade, EsErrorInfo> = rt
+            .eval_sync(
+                "let test_wait_for_prom2_prom = new Promise((resolve, reject) => {console.log('rejecting promise with foo');reject(\"foo\");}); test_wait_for_prom2_prom;",
+                "wait_for_prom2.es",
+            );
+        if esvf_prom_res.is_err() {
+            panic!(
+                "error evaling wait_for_prom2.es : {}",
+                esvf_prom_res.err().unwrap().err_msg()
+            );
+        } else {
+            let esvf_prom = esvf_prom_res
+                .ok()
+                .expect("wait_for_prom.es did not eval ok");
+            assert!(esvf_prom.is_promise());
+            let esvf_prom_resolved = esvf_prom
+                .get_promise_result_blocking(Duration::from_secs(60))
+                .ok()
+                .unwrap()
+                .err()
+                .unwrap();
+
+            assert!(esvf_prom_resolved.is_string());
+
+            assert_eq!(esvf_prom_resolved.get_string(), "foo");
+        }
+    }
+
+    #[test]
+    fn test_wait_for_prom3() {
+        log::info!("test: test_wait_for_prom3");
+
+        let rt = init_test_runtime();
+
+        let my_slow_prom_esvf = EsValueFacade::new_promise(|| {
+            std::thread::sleep(Duration::from_secs(10));
+            Ok(EsValueFacade::new_i32(12345))
+        });
+
+        rt.eval_sync(
+            "this.p3waitmethod = function(p){return p.then((res) => {return (res * 2);});};",
+            "testp3.es",
+        )
+        .ok()
+        .expect("p3 script failed");
+        let prom_esvf_res = rt.call_sync(vec![], "p3waitmethod", vec![my_slow_prom_esvf]);
+
+        if prom_esvf_res.is_err() {
+            let err: EsErrorInfo = prom_esvf_res.err().unwrap();
+            panic!("p3 call failed: {}", err.err_msg());
+        }
+
+        let prom_esvf = prom_esvf_res.ok().unwrap();
+
+        let res = prom_esvf.get_promise_result_blocking(Duration::from_secs(2));
+        assert!(res.is_err());
+        drop(prom_esvf);
+        std::thread::sleep(Duration::from_secs(10));
+        // rt should still be ok here
+        let _ = rt.eval_sync("true;", "p3ok.es").ok().expect("p3 not ok");
+    }
+
+    #[test]
+    fn test_get_object() {
+        log::info!("test: test_get_object");
+        let rt = init_test_runtime();
+        let esvf = rt
+            .eval_sync(
+                "({a: 1, b: true, c: 'hello', d: {a: 2}});",
+                "test_get_object.es",
+            )
+            .ok()
+            .unwrap();
+
+        assert!(esvf.is_object());
+
+        let map: &HashMap<String, EsValueFacade> = esvf.get_object();
+
+        let esvf_a = map.get(&"a".to_string()).unwrap();
+
+        assert!(esvf_a.is_i32());
+        assert_eq!(esvf_a.get_i32(), 1);
+    }
+
+    #[test]
+    fn test_getset_array() {
+        log::info!("test: test_getset_array");
+        let rt = init_test_runtime();
+        let esvf = rt
+            .eval_sync("([5, 7, 9]);", "test_getset_array.es")
+            .ok()
+            .unwrap();
+
+        assert!(esvf.is_array());
+
+        let vec: &Vec<EsValueFacade> = esvf.get_array();
+
+        assert_eq!(vec.len(), 3);
+
+        let esvf_0 = vec.get(1).unwrap();
+
+        assert!(esvf_0.is_i32());
+        assert_eq!(esvf_0.get_i32(), 7);
+
+        let mut props = HashMap::new();
+        props.insert("a".to_string(), EsValueFacade::new_i32(12));
+        let new_vec = vec![
+            EsValueFacade::new_i32(8),
+            EsValueFacade::new_str("a".to_string()),
+            EsValueFacade::new_obj(props),
+        ];
+        let args = vec![EsValueFacade::new_array(new_vec)];
+        let res: Result<EsValueFacade, EsErrorInfo> = rt.call_sync(vec!["JSON"], "stringify", args);
+
+        if res.is_err() {
+            panic!("could not call stringify: {}", res.err().unwrap().err_msg());
+        }
+
+        let res_esvf = res.ok().unwrap();
+        let str = res_esvf.get_string();
+        assert_eq!(str, &"[8,\"a\",{\"a\":12}]".to_string())
+    }
+
+    #[test]
+    fn test_set_object() {
+        log::info!("test: test_set_object");
+        let rt = init_test_runtime();
+        let _esvf = rt
+            .eval_sync(
+                "this.test_set_object = function test_set_object(obj, prop){return obj[prop];};",
+                "test_set_object_1.es",
+            )
+            .ok()
+            .unwrap();
+
+        let mut map: HashMap<String, EsValueFacade> = HashMap::new();
+        map.insert(
+            "p1".to_string(),
+            EsValueFacade::new_str("hello".to_string()),
+        );
+        let obj = EsValueFacade::new_obj(map);
+
+        let res_esvf_res = rt.call_sync(
+            vec![],
+            "test_set_object",
+            vec![obj, EsValueFacade::new_str("p1".to_string())],
+        );
+
+        let res_esvf = res_esvf_res.ok().unwrap();
+        assert!(res_esvf.is_string());
+        assert_eq!(res_esvf.get_string(), "hello");
+    }
+
+    #[test]
+    fn test_with_raw_value() {
+        log::info!("test: test_with_raw_value");
+        let rt = init_test_runtime();
+        rt.eval_sync(
+            "this.test_with_raw_value_double = function(a){return a * 2;};",
+            "test_with_raw_value.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let arg = EsValueFacade::new_i32(21);
+        let res = rt.do_in_es_event_queue_sync(move |sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                rooted!(in (cx) let mut func_root = mozjs::jsval::UndefinedValue());
+                crate::jsapi_utils::objects::get_es_obj_prop_val(
+                    cx,
+                    global,
+                    "test_with_raw_value_double",
+                    func_root.handle_mut(),
+                )
+                .ok()
+                .expect("could not get function");
+                arg.with_raw_value(cx, |arg_val| {
+                    rooted!(in (cx) let mut rval = mozjs::jsval::UndefinedValue());
+                    crate::jsapi_utils::functions::call_function_value(
+                        cx,
+                        global,
+                        func_root.handle(),
+                        vec![*arg_val],
+                        rval.handle_mut(),
+                    )
+                    .ok()
+                    .expect("call failed");
+                    rval.to_int32()
+                })
+            })
+        });
+        assert_eq!(res, 42);
+    }
+
+    #[test]
+    fn test_promise_await_cancellable() {
+        log::info!("test: test_promise_await_cancellable");
+        let rt = init_test_runtime();
+        let prom_esvf = rt
+            .eval_sync(
+                "this.test_cancel_prom_resolve = null; new Promise((res) => {test_cancel_prom_resolve = res;});",
+                "test_promise_await_cancellable.es",
+            )
+            .ok()
+            .expect("script failed");
+
+        let token = crate::esvaluefacade::CancelToken::new();
+        let token2 = token.clone();
+        let canceler = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            token2.cancel();
+        });
+
+        let res =
+            prom_esvf.get_promise_result_blocking_cancellable(Duration::from_secs(10), &token);
+        assert_eq!(
+            res.err(),
+            Some(std::sync::mpsc::RecvTimeoutError::Disconnected)
+        );
+        assert!(token.is_canceled());
+        canceler.join().unwrap();
+
+        // resolving the promise after canceling is not an error in script
+        rt.eval_sync(
+            "test_cancel_prom_resolve(1);",
+            "test_promise_await_cancellable2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = prom_esvf.get_promise_result_blocking(Duration::from_secs(1));
+        assert_eq!(
+            res.err(),
+            Some(std::sync::mpsc::RecvTimeoutError::Disconnected)
+        );
+
+        // canceling a token after its wait timed out does not affect later waits
+        let prom_esvf = rt
+            .eval_sync(
+                "new Promise((res) => {test_cancel_prom_resolve = res;});",
+                "test_promise_await_cancellable3.es",
+            )
+            .ok()
+            .expect("script failed");
+        let token = crate::esvaluefacade::CancelToken::new();
+        let res =
+            prom_esvf.get_promise_result_blocking_cancellable(Duration::from_millis(50), &token);
+        assert_eq!(res.err(), Some(std::sync::mpsc::RecvTimeoutError::Timeout));
+        token.cancel();
+        rt.eval_sync(
+            "test_cancel_prom_resolve(2);",
+            "test_promise_await_cancellable4.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = prom_esvf
+            .get_promise_result_blocking(Duration::from_secs(10))
+            .ok()
+            .expect("promise timed out")
+            .ok()
+            .expect("promise was rejected");
+        assert_eq!(res.get_i32(), 2);
+    }
+
+    #[test]
+    fn test_object_ref_methods() {
+        log::info!("test: test_object_ref_methods");
+        let rt = EsRuntimeBuilder::new().eager_object_max_props(0).build();
+        let esvf = rt
+            .eval_sync(
+                "this.test_obj_ref = {count: 1, inc: function(by) {this.count += by; return this.count;}, fail: function() {throw Error('failed');}}; test_obj_ref;",
+                "test_object_ref_methods.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object_ref());
+
+        esvf.set_property("count", EsValueFacade::new_i32(10))
+            .ok()
+            .expect("could not set count");
+        let res = esvf
+            .invoke_method("inc", vec![EsValueFacade::new_i32(5)])
+            .ok()
+            .expect("inc failed");
+        assert_eq!(res.get_i32(), 15);
+        let count = esvf
+            .get_property("count")
+            .ok()
+            .expect("could not get count");
+        assert_eq!(count.get_i32(), 15);
+        let count = rt
+            .eval_sync("test_obj_ref.count;", "test_object_ref_methods2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(count.get_i32(), 15);
+
+        assert!(esvf.invoke_method("fail", vec![]).is_err());
+        assert!(esvf.invoke_method("no_such_method", vec![]).is_err());
+
+        // set_property calls setters like an assignment in script does
+        let esvf = rt
+            .eval_sync(
+                "this.test_obj_ref2 = {_v: 1, get v() {return this._v;}, set v(v) {if (v < 0) {throw Error('negative');} this._v = v * 2;}}; test_obj_ref2;",
+                "test_object_ref_methods3.es",
+            )
+            .ok()
+            .expect("script failed");
+        esvf.set_property("v", EsValueFacade::new_i32(4))
+            .ok()
+            .expect("could not set v");
+        assert_eq!(esvf.get_property("v").ok().unwrap().get_i32(), 8);
+        assert!(esvf.set_property("v", EsValueFacade::new_i32(-1)).is_err());
+        // the accessor was not replaced by a data property
+        assert_eq!(esvf.get_property("_v").ok().unwrap().get_i32(), 8);
+
+        let keys = esvf
+            .get_all_own_prop_names(false, false)
+            .ok()
+            .expect("could not get keys");
+        assert_eq!(
+            keys,
+            vec![
+                PropKey::String("_v".to_string()),
+                PropKey::String("v".to_string())
+            ]
+        );
+        assert!(EsValueFacade::new_i32(1)
+            .get_all_own_prop_names(false, false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_object_builder() {
+        log::info!("test: test_object_builder");
+        let rt = init_test_runtime();
+
+        let esvf = EsObjectBuilder::new()
+            .prop("b", EsValueFacade::new_i32(1))
+            .prop(
+                "a",
+                EsObjectBuilder::new()
+                    .prop("z", EsValueFacade::new_str("zed".to_string()))
+                    .prop("y", EsValueFacade::new_bool(true))
+                    .build(),
+            )
+            .prop("c", EsValueFacade::new_i32(2))
+            .prop("b", EsValueFacade::new_i32(3))
+            .build();
+
+        assert!(esvf.is_object());
+        let map = esvf.get_object();
+        assert_eq!(map.get("b").unwrap().get_i32(), 3);
+        assert_eq!(
+            map.get("a")
+                .unwrap()
+                .get_object()
+                .get("z")
+                .unwrap()
+                .get_string(),
+            "zed"
+        );
+
+        rt.eval_sync(
+            "this.test_object_builder_func = (obj) => {return JSON.stringify(obj);};",
+            "test_object_builder.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = rt
+            .call_sync(vec![], "test_object_builder_func", vec![esvf])
+            .ok()
+            .expect("call failed");
+        assert_eq!(
+            res.get_string(),
+            r#"{"b":3,"a":{"z":"zed","y":true},"c":2}"#
+        );
+    }
+
+    #[test]
+    fn test_object_round_trip() {
+        log::info!("test: test_object_round_trip");
+        let rt = init_test_runtime();
+        rt.add_global_sync_function("test_object_round_trip_op", |mut args| Ok(args.remove(0)));
+
+        let esvf = rt
+            .eval_sync(
+                "class TestRoundTrip {constructor(){this.a = 1;}}; let test_rt_obj = new TestRoundTrip(); let test_rt_arr = [1, 2]; \
+                [test_object_round_trip_op(test_rt_obj) === test_rt_obj, test_object_round_trip_op(test_rt_obj) instanceof TestRoundTrip, test_object_round_trip_op(test_rt_arr) === test_rt_arr];",
+                "test_object_round_trip.es",
+            )
+            .ok()
+            .expect("script failed");
+        for res in esvf.get_array() {
+            assert!(res.get_bool());
+        }
+
+        // the copy can still be read in rust
+        let esvf = rt
+            .eval_sync("({a: 1});", "test_object_round_trip2.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_object().get("a").unwrap().get_i32(), 1);
+
+        // only the top level object of a copy is cached, not the nested objects and arrays
+        let rt = EsRuntimeBuilder::new().build();
+        let cache_size = || {
+            rt.do_in_es_event_queue_sync(|_sm_rt| {
+                crate::spidermonkeyruntimewrapper::object_cache_size()
+            })
+        };
+        let size_before = cache_size();
+        let esvf = rt
+            .eval_sync(
+                "Array.from({length: 100}, (v, i) => ({i, arr: [i, i + 1]}));",
+                "test_object_round_trip3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_array().len(), 100);
+        assert_eq!(cache_size(), size_before + 1);
+    }
+
+    #[test]
+    fn test_prepped_prom() {
+        log::info!("test: test_prepped_prom");
+        let rt = init_test_runtime();
+
+        let my_prep_func = || {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(EsValueFacade::new_i32(123))
+        };
+
+        let my_bad_prep_func = || {
+            std::thread::sleep(Duration::from_secs(5));
+            Err("456".to_string())
+        };
+
+        let prom_esvf = EsValueFacade::new_promise(my_prep_func);
+        let prom_esvf_rej = EsValueFacade::new_promise(my_bad_prep_func);
+
+        rt.eval_sync("this.test_prepped_prom_func = (prom) => {return prom.then((p_res) => {return p_res + 'foo';}).catch((p_err) => {return p_err + 'bar';});};", "test_prepped_prom.es").ok().unwrap();
+
+        let p2_esvf = rt.call_sync(vec![], "test_prepped_prom_func", vec![prom_esvf]);
+        let p2_esvf_rej = rt.call_sync(vec![], "test_prepped_prom_func", vec![prom_esvf_rej]);
+
+        let res = p2_esvf
+            .ok()
+            .unwrap()
+            .get_promise_result_blocking(Duration::from_secs(10))
+            .ok()
+            .unwrap();
+
+        let res_str_esvf = res.ok().unwrap();
+
+        let res_str = res_str_esvf.get_string();
+
+        assert_eq!("123foo", res_str);
+
+        let res2 = p2_esvf_rej
+            .ok()
+            .unwrap()
+            .get_promise_result_blocking(Duration::from_secs(30))
+            .ok()
+            .unwrap();
+
+        let res_str_esvf_rej = res2.ok().unwrap(); // yes its the ok because we catch the rejection in test_prepped_prom.es, val should be bar thou
+
+        let res_str_rej = res_str_esvf_rej.get_string();
+
+        assert_eq!("456bar", res_str_rej);
+    }
+
+    #[test]
+    fn test_prepped_prom_panic() {
+        log::info!("test: test_prepped_prom_panic");
+        let rt = init_test_runtime();
+
+        let prom_esvf = EsValueFacade::new_promise(|| panic!("resolver is broken"));
+
+        rt.eval_sync("this.test_prepped_prom_panic_func = (prom) => {return prom.catch((p_err) => {return 'caught: ' + p_err;});};", "test_prepped_prom_panic.es").ok().unwrap();
+
+        let p2_esvf = rt
+            .call_sync(vec![], "test_prepped_prom_panic_func", vec![prom_esvf])
+            .ok()
+            .unwrap();
+        let res = p2_esvf
+            .get_promise_result_blocking(Duration::from_secs(10))
+            .ok()
+            .unwrap();
+
+        let res_esvf = res.ok().unwrap();
+        assert_eq!(
+            res_esvf.get_string(),
+            "caught: promise resolver panicked: resolver is broken"
+        );
+
+        // the runtime is still usable afterwards
+        let two = rt
+            .eval_sync("1 + 1;", "test_prepped_prom_panic2.es")
+            .ok()
+            .unwrap();
+        assert_eq!(two.get_i32(), 2);
+    }
+
+    #[test]
+    fn test_prepped_prom_resolve() {
+        log::info!("test: test_prepped_prom_resolve");
+        let rt = init_test_runtime();
+
+        let my_prep_func = || {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(EsValueFacade::new_i32(123))
+        };
+
+        let prom_esvf = EsValueFacade::new_promise(my_prep_func);
+
+        rt.eval_sync("this.test_prepped_prom_func = (prom) => {return prom.then((p_res) => {return p_res + 'foo';}).catch((p_err) => {return p_err + 'bar';});};", "test_prepped_prom.es").ok().unwrap();
+
+        let p2_esvf = rt.call_sync(vec![], "test_prepped_prom_func", vec![prom_esvf]);
+
+        let res = p2_esvf
+            .ok()
+            .unwrap()
+            .get_promise_result_blocking(Duration::from_secs(30))
+            .ok()
+            .unwrap();
+
+        let res_str_esvf = res.ok().unwrap();
+
+        let res_str = res_str_esvf.get_string();
+
+        assert_eq!("123foo", res_str);
+    }
+
+    #[test]
+    fn test_prepped_prom_flatten() {
+        log::info!("test: test_prepped_prom_flatten");
+        let rt = init_test_runtime();
 
-        rt_arc.do_in_es_event_queue(move |_sm_rt| {
-            spidermonkeyruntimewrapper::remove_cached_object(cached_obj_id);
+        rt.eval_sync("this.test_prepped_prom_flatten_func = (prom) => {return prom.then((p_res) => {return (p_res instanceof Promise) ? 'not flattened' : p_res + 'foo';}).catch((p_err) => {return p_err + 'bar';});};", "test_prepped_prom_flatten.es").ok().unwrap();
+
+        let prom_esvf = EsValueFacade::new_promise(|| {
+            Ok(EsValueFacade::new_promise(|| {
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(EsValueFacade::new_i32(123))
+            }))
         });
-    }
-}
+        let res = rt
+            .call_sync(vec![], "test_prepped_prom_flatten_func", vec![prom_esvf])
+            .ok()
+            .unwrap()
+            .get_promise_result_blocking(Duration::from_secs(10))
+            .ok()
+            .unwrap();
+        assert_eq!(res.ok().unwrap().get_string(), "123foo");
 
-#[cfg(test)]
-mod tests {
+        // a rejected inner promise rejects the outer promise
+        let prom_esvf_rej = EsValueFacade::new_promise(|| {
+            Ok(EsValueFacade::new_promise(|| {
+                Err("inner failed".to_string())
+            }))
+        });
+        let res = rt
+            .call_sync(
+                vec![],
+                "test_prepped_prom_flatten_func",
+                vec![prom_esvf_rej],
+            )
+            .ok()
+            .unwrap()
+            .get_promise_result_blocking(Duration::from_secs(10))
+            .ok()
+            .unwrap();
+        assert_eq!(res.ok().unwrap().get_string(), "inner failedbar");
 
-    use crate::esruntime::tests::init_test_runtime;
-    use crate::esvaluefacade::EsValueFacade;
-    use crate::jsapi_utils::EsErrorInfo;
-    use std::collections::HashMap;
-    use std::time::Duration;
+        // a Promise from script returned by the resolver is adopted too
+        let script_prom = rt
+            .eval_sync(
+                "new Promise((resolve) => {resolve(456);});",
+                "test_prepped_prom_flatten2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(script_prom.is_promise());
+        let prom_esvf_script = EsValueFacade::new_promise(move || Ok(script_prom));
+        let res = rt
+            .call_sync(
+                vec![],
+                "test_prepped_prom_flatten_func",
+                vec![prom_esvf_script],
+            )
+            .ok()
+            .unwrap()
+            .get_promise_result_blocking(Duration::from_secs(10))
+            .ok()
+            .unwrap();
+        assert_eq!(res.ok().unwrap().get_string(), "456foo");
+    }
 
     #[test]
-    #[allow(clippy::float_cmp)]
-    fn in_and_output_vars() {
-        log::info!("test: in_and_output_vars");
-
+    fn test_large_object_ref() {
+        log::info!("test: test_large_object_ref");
         let rt = init_test_runtime();
-        rt.add_global_sync_function("test_op_0", |args: Vec<EsValueFacade>| {
-            let args1 = args.get(0).expect("did not get a first arg");
-            let args2 = args.get(1).expect("did not get a second arg");
+        let esvf = rt
+            .eval_sync(
+                "let test_large_object_ref_obj = {}; for (let x = 0; x < 50000; x++) {test_large_object_ref_obj['k' + x] = x;} test_large_object_ref_obj;",
+                "test_large_object_ref.es",
+            )
+            .ok()
+            .expect("script failed");
 
-            let x = args1.get_i32() as f64;
-            let y = args2.get_i32() as f64;
+        assert!(esvf.is_object_ref());
+        assert!(!esvf.is_object());
 
-            Ok(EsValueFacade::new_f64(x / y))
-        });
+        let k123 = esvf.get_property("k123").ok().expect("could not get k123");
+        assert_eq!(k123.get_i32(), 123);
+        let k49999 = esvf
+            .get_property("k49999")
+            .ok()
+            .expect("could not get k49999");
+        assert_eq!(k49999.get_i32(), 49999);
 
-        rt.add_global_sync_function("test_op_1", |args: Vec<EsValueFacade>| {
-            let args1 = args.get(0).expect("did not get a first arg");
-            let args2 = args.get(1).expect("did not get a second arg");
+        // small objects should still be converted eagerly
+        let esvf = rt
+            .eval_sync("({a: 1, b: 2});", "test_large_object_ref2.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object());
+        assert!(!esvf.is_object_ref());
+    }
 
-            let x = args1.get_i32();
-            let y = args2.get_i32();
+    #[test]
+    fn test_eager_object_max_props() {
+        log::info!("test: test_eager_object_max_props");
+        let rt = EsRuntimeBuilder::new().eager_object_max_props(2).build();
+        let esvf = rt
+            .eval_sync("({a: 1, b: 2, c: 3});", "test_eager_object_max_props.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object_ref());
 
-            Ok(EsValueFacade::new_i32(x * y))
-        });
+        // object refs convert back to the same object
+        rt.eval_sync(
+            "this.test_eager_object_max_props_func = function(obj){return obj.a + obj.b + obj.c;};",
+            "test_eager_object_max_props2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = rt
+            .call_sync(vec![], "test_eager_object_max_props_func", vec![esvf])
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_i32(), 6);
 
-        rt.add_global_sync_function("test_op_2", |args: Vec<EsValueFacade>| {
-            let args1 = args.get(0).expect("did not get a first arg");
-            let args2 = args.get(1).expect("did not get a second arg");
+        // objects with index keys are copied too
+        let esvf = rt
+            .eval_sync("({1: 'a', b: 2});", "test_eager_object_max_props3.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object());
+        assert_eq!(esvf.get_object().get("1").unwrap().get_string(), "a");
 
-            let x = args1.get_i32();
-            let y = args2.get_i32();
+        // an object whose keys can not be enumerated is passed as a reference
+        let esvf = rt
+            .eval_sync(
+                "new Proxy({a: 1}, {ownKeys: function(){throw Error('no keys');}});",
+                "test_eager_object_max_props4.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object_ref());
+        assert_eq!(esvf.get_property("a").ok().unwrap().get_i32(), 1);
+    }
 
-            Ok(EsValueFacade::new_bool(x > y))
-        });
+    #[test]
+    fn test_string_ref() {
+        log::info!("test: test_string_ref");
+        let rt = EsRuntimeBuilder::new().eager_string_max_len(100).build();
+        let esvf = rt
+            .eval_sync(
+                "'0123456789'.repeat(100000) + 'ab\\u00e9';",
+                "test_string_ref.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_string_ref());
+        assert!(!esvf.is_string());
+        assert_eq!(esvf.string_length().ok().unwrap(), 1_000_003);
+        assert_eq!(esvf.substring(5, 15).ok().unwrap().as_str(), "5678901234");
+        assert_eq!(
+            esvf.substring(999_995, 2_000_000).ok().unwrap().as_str(),
+            "56789ab\u{e9}"
+        );
 
-        rt.add_global_sync_function("test_op_3", |args: Vec<EsValueFacade>| {
-            let args1 = args.get(0).expect("did not get a first arg");
-            let args2 = args.get(1).expect("did not get a second arg");
+        // a string ref can be passed back to the runtime
+        rt.eval_sync(
+            "this.test_string_ref_len = function(s){return s.length;};",
+            "test_string_ref2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let len = rt
+            .call_sync(vec![], "test_string_ref_len", vec![esvf])
+            .ok()
+            .expect("call failed");
+        assert_eq!(len.get_i32(), 1_000_003);
 
-            let x = args1.get_i32();
-            let y = args2.get_i32();
+        // short strings are still copied
+        let esvf = rt
+            .eval_sync("'abc';", "test_string_ref3.es")
+            .ok()
+            .expect("script failed");
+        assert!(!esvf.is_string_ref());
+        assert_eq!(esvf.string_length().ok().unwrap(), 3);
+        assert_eq!(esvf.substring(1, 2).ok().unwrap().as_str(), "b");
+        assert!(EsValueFacade::new_i32(1).string_length().is_err());
+    }
 
-            let res_str = format!("{}", x * y);
-            Ok(EsValueFacade::new_str(res_str))
-        });
+    #[test]
+    fn test_strict_string_conversion() {
+        log::info!("test: test_strict_string_conversion");
+        let rt = EsRuntimeBuilder::new()
+            .strict_string_conversion(true)
+            .build();
+        let esvf = rt
+            .eval_sync("'a\\uD800b';", "test_strict_string_conversion.es")
+            .ok()
+            .expect("script failed");
+        // the lone surrogate is kept in the runtime
+        assert!(esvf.is_string_ref());
+        assert_eq!(esvf.to_string_lossy().ok().unwrap(), "a\u{FFFD}b");
+        assert!(esvf.try_get_string().is_err());
+        assert!(esvf
+            .try_get_string_strict()
+            .err()
+            .unwrap()
+            .message
+            .contains("unpaired surrogate"));
 
-        let res0 = rt.eval_sync("test_op_0(13, 17);", "test_vars0.es");
-        let res1 = rt.eval_sync("test_op_1(13, 17);", "test_vars1.es");
-        let res2 = rt.eval_sync("test_op_2(13, 17);", "test_vars2.es");
-        let res3 = rt.eval_sync("test_op_3(13, 17);", "test_vars3.es");
-        let esvf0 = res0.ok().expect("1 did not get a result");
-        let esvf1 = res1.ok().expect("1 did not get a result");
-        let esvf2 = res2.ok().expect("2 did not get a result");
-        let esvf3 = res3.ok().expect("3 did not get a result");
+        rt.eval_sync(
+            "this.test_strict_string_code = function(s){return s.charCodeAt(1);};",
+            "test_strict_string_conversion2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let code = rt
+            .call_sync(vec![], "test_strict_string_code", vec![esvf])
+            .ok()
+            .expect("call failed");
+        assert_eq!(code.get_i32(), 0xD800);
 
-        assert_eq!(esvf0.get_f64(), (13_f64 / 17_f64));
-        assert_eq!(esvf1.get_i32(), (13 * 17) as i32);
-        assert_eq!(esvf2.get_boolean(), false);
-        assert_eq!(esvf3.get_string(), format!("{}", 13 * 17).as_str());
+        // valid strings are still copied
+        let esvf = rt
+            .eval_sync("'abc';", "test_strict_string_conversion3.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_string());
+        assert_eq!(esvf.to_string_lossy().ok().unwrap(), "abc");
+        assert_eq!(esvf.try_get_string_strict().ok().unwrap(), "abc");
+        assert!(EsValueFacade::new_i32(1).try_get_string_strict().is_err());
     }
 
     #[test]
-    fn test_wait_for_native_prom() {
-        log::info!("test: test_wait_for_native_prom");
+    fn test_str_and_char() {
+        log::info!("test: test_str_and_char");
+        let rt = init_test_runtime();
+        rt.eval_sync(
+            "this.test_str_and_char = function(a, b, c){return a + b + c + ':' + b.length;};",
+            "test_str_and_char.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let res = rt
+            .call_sync(
+                vec![],
+                "test_str_and_char",
+                vec![
+                    EsValueFacade::new_static_str("ab"),
+                    EsValueFacade::new_char('\u{1F600}'),
+                    EsValueFacade::new_cow_str(std::borrow::Cow::Borrowed("cd")),
+                ],
+            )
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_string(), "ab\u{1F600}cd:2");
 
+        let esvf = 'x'.to_es_value_facade();
+        assert!(esvf.is_string());
+        assert_eq!(esvf.get_string(), "x");
+    }
+
+    #[test]
+    fn test_same_object_as() {
+        log::info!("test: test_same_object_as");
         let rt = init_test_runtime();
-        let esvf_prom = rt
+        let esvf = rt
             .eval_sync(
-                "let p = new Promise((resolve, reject) => {resolve(123);});p = p.then((v) => {return v;});p = p.then((v) => {return v;});p = p.then((v) => {return v;});p = p.then((v) => {return v;});p = p.then((v) => {return v;});p = p.then((v) => {return v;}); p;",
-                "wait_for_prom.es",
+                "(function(){let f = () => 1; let g = () => 2; return [f, f, g, 'f', {a: 1}, {a: 1}];})();",
+                "test_same_object_as.es",
             )
             .ok()
-            .unwrap();
-        assert!(esvf_prom.is_promise());
-        let esvf_prom_resolved = esvf_prom
-            .get_promise_result_blocking(Duration::from_secs(60))
+            .expect("script failed");
+        let arr = esvf.get_array();
+        assert!(arr[0].same_object_as(&arr[1]));
+        assert!(arr[1].same_object_as(&arr[0]));
+        assert!(!arr[0].same_object_as(&arr[2]));
+        assert!(!arr[0].same_object_as(&arr[3]));
+        // eagerly copied objects have no identity
+        assert!(!arr[4].same_object_as(&arr[4]));
+
+        // object references which are passed to rust twice
+        let rt2 = EsRuntimeBuilder::new().eager_object_max_props(0).build();
+        rt2.eval_sync(
+            "this.test_same_object_as_obj = {a: 1};",
+            "test_same_object_as2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let a = rt2
+            .eval_sync("test_same_object_as_obj;", "test_same_object_as3.es")
             .ok()
-            .unwrap()
+            .expect("script failed");
+        let b = rt2
+            .eval_sync("test_same_object_as_obj;", "test_same_object_as4.es")
             .ok()
-            .unwrap();
-
-        assert!(esvf_prom_resolved.is_i32());
-        assert_eq!(esvf_prom_resolved.get_i32().clone(), 123 as i32);
+            .expect("script failed");
+        let c = rt2
+            .eval_sync("({a: 1});", "test_same_object_as5.es")
+            .ok()
+            .expect("script failed");
+        assert!(a.is_object_ref());
+        assert!(a.same_object_as(&b));
+        assert!(!a.same_object_as(&c));
     }
 
     #[test]
-    fn test_wait_for_prom() {
-        log::info!("test: test_wait_for_prom");
-
+    fn test_function_metadata() {
+        log::info!("test: test_function_metadata");
         let rt = init_test_runtime();
-        let esvf_prom = rt
+        let func_esvf = rt
             .eval_sync(
-                "let test_wait_for_prom_prom = new Promise((resolve, reject) => {resolve(123);}); test_wait_for_prom_prom;",
-                "wait_for_prom.es",
+                "(function test_function_metadata_f(a, b, c = 1, ...rest){return a;});",
+                "test_function_metadata.es",
             )
             .ok()
-            .unwrap();
-        assert!(esvf_prom.is_promise());
-        let esvf_prom_resolved = esvf_prom
-            .get_promise_result_blocking(Duration::from_secs(60))
+            .expect("script failed");
+        assert!(func_esvf.is_function());
+        assert_eq!(
+            func_esvf.function_name().ok().unwrap().as_str(),
+            "test_function_metadata_f"
+        );
+        assert_eq!(func_esvf.function_arity().ok().unwrap(), 2);
+        assert!(func_esvf
+            .function_source()
             .ok()
             .unwrap()
+            .starts_with("function test_function_metadata_f(a, b"));
+
+        let arrow_esvf = rt
+            .eval_sync("(x => x * 2);", "test_function_metadata2.es")
             .ok()
-            .unwrap();
+            .expect("script failed");
+        assert_eq!(arrow_esvf.function_name().ok().unwrap().as_str(), "");
+        assert_eq!(arrow_esvf.function_arity().ok().unwrap(), 1);
+        assert_eq!(
+            arrow_esvf.function_source().ok().unwrap().as_str(),
+            "x => x * 2"
+        );
 
-        assert!(esvf_prom_resolved.is_i32());
-        assert_eq!(esvf_prom_resolved.get_i32().clone(), 123 as i32);
+        assert!(EsValueFacade::new_i32(1).function_name().is_err());
+        assert!(EsValueFacade::new_i32(1).function_arity().is_err());
+        assert!(EsValueFacade::new_i32(1).function_source().is_err());
+
+        // a function passed back to script is the same function object
+        rt.eval_sync(
+            "this.test_function_metadata_call = function(f){return f(21);};",
+            "test_function_metadata3.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = rt
+            .call_sync(vec![], "test_function_metadata_call", vec![arrow_esvf])
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_i32(), 42);
     }
 
     #[test]
-    fn test_wait_for_prom2() {
-        log::info!("test: test_wait_for_prom2");
-
+    fn test_apply() {
+        log::info!("test: test_apply");
         let rt = init_test_runtime();
+        let func_esvf = rt
+            .eval_sync(
+                "(function(a, b){'use strict'; return this.prefix + a + b;});",
+                "test_apply.es",
+            )
+            .ok()
+            .expect("script failed");
 
-        let esvf_prom_res: Result<EsValueFacade, EsErrorInfo> = rt
+        let mut props = HashMap::new();
+        props.insert(
+            "prefix".to_string(),
+            EsValueFacade::new_str("ctx:".to_string()),
+        );
+        let res_esvf = func_esvf
+            .apply(
+                EsValueFacade::new_obj(props),
+                vec![
+                    EsValueFacade::new_str("a".to_string()),
+                    EsValueFacade::new_i32(1),
+                ],
+            )
+            .ok()
+            .expect("apply failed");
+        assert_eq!(res_esvf.get_string(), "ctx:a1");
+
+        // in strict mode this stays undefined so reading prefix throws
+        let res = func_esvf.apply(EsValueFacade::undefined(), vec![]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_construct() {
+        log::info!("test: test_construct");
+        let rt = init_test_runtime();
+        let class_esvf = rt
             .eval_sync(
-                "let test_wait_for_prom2_prom = new Promise((resolve, reject) => {console.log('rejecting promise with foo');reject(\"foo\");}); test_wait_for_prom2_prom;",
-                "wait_for_prom2.es",
-            );
-        if esvf_prom_res.is_err() {
-            panic!(
-                "error evaling wait_for_prom2.es : {}",
-                esvf_prom_res.err().unwrap().err_msg()
-            );
-        } else {
-            let esvf_prom = esvf_prom_res
-                .ok()
-                .expect("wait_for_prom.es did not eval ok");
-            assert!(esvf_prom.is_promise());
-            let esvf_prom_resolved = esvf_prom
-                .get_promise_result_blocking(Duration::from_secs(60))
-                .ok()
-                .unwrap()
-                .err()
-                .unwrap();
+                "(class TestConstructPoint {constructor(x, y){this.x = x; this.y = y; this.sum = x + y;}});",
+                "test_construct.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(class_esvf.is_function());
 
-            assert!(esvf_prom_resolved.is_string());
+        let point_esvf = class_esvf
+            .construct(vec![EsValueFacade::new_i32(3), EsValueFacade::new_i32(4)])
+            .ok()
+            .expect("construct failed");
+        assert!(point_esvf.is_object());
+        assert_eq!(point_esvf.get_object().get("sum").unwrap().get_i32(), 7);
 
-            assert_eq!(esvf_prom_resolved.get_string(), "foo");
+        // classes can not be invoked without new
+        assert!(class_esvf.invoke_function(vec![]).is_err());
+
+        // arrow functions are not constructors
+        let arrow_esvf = rt
+            .eval_sync("(() => 1);", "test_construct2.es")
+            .ok()
+            .expect("script failed");
+        assert!(arrow_esvf.construct(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_downcast_ref() {
+        struct TestDowncastData {
+            val: i32,
+        }
+
+        impl EsValueConvertible for TestDowncastData {
+            fn to_js_value(&self, _cx: *mut JSContext, rval: MutableHandleValue) {
+                let mut rval = rval;
+                rval.set(Int32Value(self.val));
+            }
         }
+
+        let esvf = TestDowncastData { val: 37 }.to_es_value_facade();
+        let data = esvf
+            .downcast_ref::<TestDowncastData>()
+            .expect("downcast failed");
+        assert_eq!(data.val, 37);
+        assert!(esvf.downcast_ref::<String>().is_none());
+
+        let esvf = EsValueFacade::new_i32(12);
+        assert_eq!(*esvf.downcast_ref::<i32>().expect("downcast failed"), 12);
+        assert!(esvf.downcast_ref::<TestDowncastData>().is_none());
+
+        // the data can still be converted to script after downcasting
+        let rt = init_test_runtime();
+        rt.eval_sync(
+            "this.test_downcast_ref_func = function(a){return a + 1;};",
+            "test_downcast_ref.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = rt
+            .call_sync(
+                vec![],
+                "test_downcast_ref_func",
+                vec![TestDowncastData { val: 41 }.to_es_value_facade()],
+            )
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_i32(), 42);
     }
 
     #[test]
-    fn test_wait_for_prom3() {
-        log::info!("test: test_wait_for_prom3");
+    fn test_nan_and_infinity() {
+        log::info!("test: test_nan_and_infinity");
+        let rt = init_test_runtime();
+
+        let esvf = rt
+            .eval_sync("NaN;", "test_nan_and_infinity.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_nan());
+        assert!(!esvf.is_finite());
+        assert!(!esvf.is_infinite());
+
+        let esvf = rt
+            .eval_sync("-Infinity;", "test_nan_and_infinity2.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_infinite());
+        assert_eq!(esvf.get_f64(), f64::NEG_INFINITY);
+
+        let esvf = rt
+            .eval_sync("12;", "test_nan_and_infinity3.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_finite());
+        assert!(!esvf.is_nan());
+
+        // values which are not a number are not finite, nan or infinite
+        let esvf = EsValueFacade::new_str("NaN".to_string());
+        assert!(!esvf.is_finite());
+        assert!(!esvf.is_nan());
+        assert!(!esvf.is_infinite());
+
+        rt.eval_sync(
+            "this.test_nan_and_infinity_describe = function(a){\
+                if (Number.isNaN(a)) return 'NaN'; \
+                if (a === Infinity) return '+Inf'; \
+                if (a === -Infinity) return '-Inf'; \
+                return typeof a;\
+            };\
+            this.test_nan_and_infinity_echo = function(a){return a;};",
+            "test_nan_and_infinity4.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        // a NaN with a non canonical bit pattern should also be a NaN in script
+        let non_canonical_nan = f64::from_bits(0xfff8_0000_0000_0001);
+        assert!(non_canonical_nan.is_nan());
+
+        for (val, expected) in vec![
+            (f64::NAN, "NaN"),
+            (non_canonical_nan, "NaN"),
+            (f64::INFINITY, "+Inf"),
+            (f64::NEG_INFINITY, "-Inf"),
+        ] {
+            let res = rt
+                .call_sync(
+                    vec![],
+                    "test_nan_and_infinity_describe",
+                    vec![EsValueFacade::new_f64(val)],
+                )
+                .ok()
+                .expect("call failed");
+            assert_eq!(res.get_string(), expected);
+
+            let res = rt
+                .call_sync(
+                    vec![],
+                    "test_nan_and_infinity_echo",
+                    vec![EsValueFacade::new_f64(val)],
+                )
+                .ok()
+                .expect("call failed");
+            assert!(res.is_f64());
+            assert_eq!(res.is_nan(), val.is_nan());
+            assert_eq!(res.is_infinite(), val.is_infinite());
+        }
+    }
 
+    #[test]
+    fn test_f64_round_trip() {
+        log::info!("test: test_f64_round_trip");
         let rt = init_test_runtime();
 
-        let my_slow_prom_esvf = EsValueFacade::new_promise(|| {
-            std::thread::sleep(Duration::from_secs(10));
-            Ok(EsValueFacade::new_i32(12345))
-        });
+        for (code, expected) in vec![
+            ("Infinity;", f64::INFINITY),
+            ("-Infinity;", f64::NEG_INFINITY),
+            ("1 / 0;", f64::INFINITY),
+            ("-0;", -0.0),
+        ] {
+            let esvf = rt
+                .eval_sync(code, "test_f64_round_trip.es")
+                .ok()
+                .expect("script failed");
+            assert!(esvf.is_f64());
+            assert!(!esvf.is_i32());
+            assert_eq!(esvf.get_f64(), expected);
+            assert_eq!(
+                esvf.get_f64().is_sign_negative(),
+                expected.is_sign_negative()
+            );
+        }
+
+        let esvf = rt
+            .eval_sync("0 / 0;", "test_f64_round_trip2.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_f64());
+        assert!(esvf.get_f64().is_nan());
 
         rt.eval_sync(
-            "this.p3waitmethod = function(p){return p.then((res) => {return (res * 2);});};",
-            "testp3.es",
+            "this.test_f64_round_trip_echo = function(a){return a;};",
+            "test_f64_round_trip3.es",
         )
         .ok()
-        .expect("p3 script failed");
-        let prom_esvf_res = rt.call_sync(vec![], "p3waitmethod", vec![my_slow_prom_esvf]);
-
-        if prom_esvf_res.is_err() {
-            let err: EsErrorInfo = prom_esvf_res.err().unwrap();
-            panic!("p3 call failed: {}", err.err_msg());
+        .expect("script failed");
+
+        for val in vec![
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            -0.0,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+        ] {
+            let res = rt
+                .call_sync(
+                    vec![],
+                    "test_f64_round_trip_echo",
+                    vec![EsValueFacade::new_f64(val)],
+                )
+                .ok()
+                .expect("call failed");
+            assert!(res.is_f64());
+            assert_eq!(res.get_f64().to_bits(), val.to_bits());
         }
 
-        let prom_esvf = prom_esvf_res.ok().unwrap();
-
-        let res = prom_esvf.get_promise_result_blocking(Duration::from_secs(2));
-        assert!(res.is_err());
-        drop(prom_esvf);
-        std::thread::sleep(Duration::from_secs(10));
-        // rt should still be ok here
-        let _ = rt.eval_sync("true;", "p3ok.es").ok().expect("p3 not ok");
+        let res = rt
+            .call_sync(
+                vec![],
+                "test_f64_round_trip_echo",
+                vec![EsValueFacade::new_f64(f64::NAN)],
+            )
+            .ok()
+            .expect("call failed");
+        assert!(res.is_f64());
+        assert!(res.get_f64().is_nan());
     }
 
     #[test]
-    fn test_get_object() {
-        log::info!("test: test_get_object");
+    fn test_to_debug_string() {
+        log::info!("test: test_to_debug_string");
         let rt = init_test_runtime();
+
         let esvf = rt
             .eval_sync(
-                "({a: 1, b: true, c: 'hello', d: {a: 2}});",
-                "test_get_object.es",
+                "({a: [1, 'two', {b: {c: true}}], 'd-e': null, f: 1.5, g: []});",
+                "test_to_debug_string.es",
             )
             .ok()
-            .unwrap();
+            .expect("script failed");
+        assert_eq!(
+            esvf.to_debug_string(2).as_str(),
+            "{ a: [ 1, 'two', { b: [Object] } ], 'd-e': null, f: 1.5, g: [] }"
+        );
+        assert_eq!(
+            esvf.to_debug_string(0).as_str(),
+            "{ a: [Array], 'd-e': null, f: 1.5, g: [] }"
+        );
 
-        assert!(esvf.is_object());
+        let esvf = rt
+            .eval_sync(
+                "[function myFunc(){}, () => {}, Promise.resolve(12), Promise.reject('oops'), new Promise(() => {})];",
+                "test_to_debug_string2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(
+            esvf.to_debug_string(2).as_str(),
+            "[ [Function: myFunc], [Function (anonymous)], Promise { 12 }, Promise { <rejected> 'oops' }, Promise { <pending> } ]"
+        );
+    }
 
-        let map: &HashMap<String, EsValueFacade> = esvf.get_object();
+    #[test]
+    fn test_null() {
+        log::info!("test: test_null");
+        let rt = init_test_runtime();
 
-        let esvf_a = map.get(&"a".to_string()).unwrap();
+        let esvf = rt
+            .eval_sync("null;", "test_null.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_null());
+        assert!(!esvf.is_undefined());
 
-        assert!(esvf_a.is_i32());
-        assert_eq!(esvf_a.get_i32(), 1);
+        rt.eval_sync(
+            "this.test_null_func = function(a){return a === null;};",
+            "test_null2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = rt
+            .call_sync(vec![], "test_null_func", vec![esvf])
+            .ok()
+            .expect("call failed");
+        assert!(res.get_boolean());
     }
 
     #[test]
-    fn test_getset_array() {
-        log::info!("test: test_getset_array");
+    fn test_array_with_props() {
+        log::info!("test: test_array_with_props");
         let rt = init_test_runtime();
+
         let esvf = rt
-            .eval_sync("([5, 7, 9]);", "test_getset_array.es")
+            .eval_sync(
+                "let arr = [1, 2, 3]; arr.meta = 'page1'; arr.total = 30; arr;",
+                "test_array_with_props.es",
+            )
             .ok()
-            .unwrap();
-
+            .expect("script failed");
         assert!(esvf.is_array());
+        assert_eq!(esvf.get_array().len(), 3);
+        let props = esvf.get_array_props().expect("no props");
+        assert_eq!(props.len(), 2);
+        assert_eq!(props.get("meta").unwrap().get_string(), "page1");
+        assert_eq!(props.get("total").unwrap().get_i32(), 30);
 
-        let vec: &Vec<EsValueFacade> = esvf.get_array();
-
-        assert_eq!(vec.len(), 3);
-
-        let esvf_0 = vec.get(1).unwrap();
+        // plain arrays have no props
+        let esvf = rt
+            .eval_sync("[1, 2];", "test_array_with_props2.es")
+            .ok()
+            .expect("script failed");
+        assert!(esvf.get_array_props().is_none());
 
-        assert!(esvf_0.is_i32());
-        assert_eq!(esvf_0.get_i32(), 7);
+        // a Proxy of an array whose keys can't be enumerated is converted without its props instead of panicking
+        let esvf = rt
+            .eval_sync(
+                "new Proxy([1, 2], {ownKeys() {throw Error('no keys');}});",
+                "test_array_with_props4.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_array().len(), 2);
+        assert!(esvf.get_array_props().is_none());
 
+        // and back to script
+        rt.eval_sync(
+            "function test_array_with_props_describe(a){return a.length + ':' + a.meta;}",
+            "test_array_with_props3.es",
+        )
+        .ok()
+        .expect("script failed");
         let mut props = HashMap::new();
-        props.insert("a".to_string(), EsValueFacade::new_i32(12));
-        let new_vec = vec![
-            EsValueFacade::new_i32(8),
-            EsValueFacade::new_str("a".to_string()),
-            EsValueFacade::new_obj(props),
-        ];
-        let args = vec![EsValueFacade::new_array(new_vec)];
-        let res: Result<EsValueFacade, EsErrorInfo> = rt.call_sync(vec!["JSON"], "stringify", args);
-
-        if res.is_err() {
-            panic!("could not call stringify: {}", res.err().unwrap().err_msg());
-        }
-
-        let res_esvf = res.ok().unwrap();
-        let str = res_esvf.get_string();
-        assert_eq!(str, &"[8,\"a\",{\"a\":12}]".to_string())
+        props.insert("meta".to_string(), EsValueFacade::new_str("x".to_string()));
+        let res = rt
+            .call_sync(
+                vec![],
+                "test_array_with_props_describe",
+                vec![EsValueFacade::new_array_with_props(
+                    vec![EsValueFacade::new_i32(1)],
+                    props,
+                )],
+            )
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_string(), "1:x");
     }
 
     #[test]
-    fn test_set_object() {
-        log::info!("test: test_set_object");
+    fn test_weakmap() {
+        log::info!("test: test_weakmap");
         let rt = init_test_runtime();
-        let _esvf = rt
+
+        let esvf = rt
             .eval_sync(
-                "this.test_set_object = function test_set_object(obj, prop){return obj[prop];};",
-                "test_set_object_1.es",
+                "this.test_weakmap_wm = new WeakMap(); this.test_weakmap_key = {}; \
+                 test_weakmap_wm.set(test_weakmap_key, 'cached'); \
+                 ({cache: test_weakmap_wm, seen: new WeakSet(), n: 1});",
+                "test_weakmap.es",
             )
             .ok()
-            .unwrap();
-
-        let mut map: HashMap<String, EsValueFacade> = HashMap::new();
-        map.insert(
-            "p1".to_string(),
-            EsValueFacade::new_str("hello".to_string()),
-        );
-        let obj = EsValueFacade::new_obj(map);
+            .expect("script failed");
+        let map = esvf.get_object();
+        assert_eq!(map.len(), 3);
+        let wm = map.get("cache").unwrap();
+        assert!(wm.is_weakmap());
+        assert!(!wm.is_weakset());
+        assert_eq!(wm.get_type_name(), "weakmap");
+        assert!(map.get("seen").unwrap().is_weakset());
+        assert!(!map.get("n").unwrap().is_weakmap());
+
+        // objects which only inherit from WeakSet.prototype are not WeakSets
+        let fake = rt
+            .eval_sync("Object.create(WeakSet.prototype);", "test_weakmap_fake.es")
+            .ok()
+            .expect("script failed");
+        assert!(!fake.is_weakset());
+        assert!(fake.is_object());
 
-        let res_esvf_res = rt.call_sync(
-            vec![],
-            "test_set_object",
-            vec![obj, EsValueFacade::new_str("p1".to_string())],
-        );
+        // the WeakMap is passed back as the same object
+        rt.eval_sync(
+            "function test_weakmap_check(wm){return (wm === test_weakmap_wm) + ':' + wm.get(test_weakmap_key);}\
+             function test_weakmap_is(wm){return wm instanceof WeakMap;}",
+            "test_weakmap2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let wm = rt
+            .eval_sync("test_weakmap_wm;", "test_weakmap3.es")
+            .ok()
+            .expect("script failed");
+        let res = rt
+            .call_sync(vec![], "test_weakmap_check", vec![wm])
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_string(), "true:cached");
 
-        let res_esvf = res_esvf_res.ok().unwrap();
-        assert!(res_esvf.is_string());
-        assert_eq!(res_esvf.get_string(), "hello");
+        let new_wm = EsValueFacade::new_weakmap();
+        assert!(new_wm.is_weakmap());
+        let res = rt
+            .call_sync(vec![], "test_weakmap_is", vec![new_wm])
+            .ok()
+            .expect("call failed");
+        assert!(res.get_boolean());
     }
 
     #[test]
-    fn test_prepped_prom() {
-        log::info!("test: test_prepped_prom");
+    fn test_try_get() {
+        log::info!("test: test_try_get");
         let rt = init_test_runtime();
 
-        let my_prep_func = || {
-            std::thread::sleep(Duration::from_secs(5));
-            Ok(EsValueFacade::new_i32(123))
-        };
+        let esvf = rt
+            .eval_sync("'abc';", "test_try_get.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.try_get_string(), Ok("abc"));
+        assert_eq!(
+            esvf.try_get_i32(),
+            Err(EsTypeError {
+                expected: "i32",
+                actual: "string"
+            })
+        );
 
-        let my_bad_prep_func = || {
-            std::thread::sleep(Duration::from_secs(5));
-            Err("456".to_string())
-        };
+        let esvf = rt
+            .eval_sync("12.5;", "test_try_get.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.try_get_f64(), Ok(12.5));
+        let err = esvf.try_get_boolean().err().expect("not an error");
+        assert_eq!(err.to_string().as_str(), "expected boolean but got f64");
 
-        let prom_esvf = EsValueFacade::new_promise(my_prep_func);
-        let prom_esvf_rej = EsValueFacade::new_promise(my_bad_prep_func);
+        let esvf = rt
+            .eval_sync("({a: 1});", "test_try_get.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.try_get_object().ok().unwrap().len(), 1);
+        assert_eq!(esvf.try_get_array().err().unwrap().actual, "object");
 
-        rt.eval_sync("this.test_prepped_prom_func = (prom) => {return prom.then((p_res) => {return p_res + 'foo';}).catch((p_err) => {return p_err + 'bar';});};", "test_prepped_prom.es").ok().unwrap();
+        let esvf = rt
+            .eval_sync("undefined;", "test_try_get.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.try_get_string().err().unwrap().actual, "undefined");
+    }
 
-        let p2_esvf = rt.call_sync(vec![], "test_prepped_prom_func", vec![prom_esvf]);
-        let p2_esvf_rej = rt.call_sync(vec![], "test_prepped_prom_func", vec![prom_esvf_rej]);
+    #[test]
+    fn test_array_buffer_transfer() {
+        log::info!("test: test_array_buffer_transfer");
+        let rt = init_test_runtime();
 
-        let res = p2_esvf
+        rt.eval_sync(
+            "function test_array_buffer_transfer_read(buf){\
+                 let arr = new Uint8Array(buf);\
+                 return (buf instanceof ArrayBuffer) + ':' + buf.byteLength + ':' + arr[0] + ',' + arr[255] + ',' + arr[4095];\
+             }",
+            "test_array_buffer_transfer.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let bytes: Vec<u8> = (0..4096).map(|i| (i % 256) as u8).collect();
+        let res = rt
+            .call_sync(
+                vec![],
+                "test_array_buffer_transfer_read",
+                vec![EsValueFacade::new_array_buffer_transfer(bytes)],
+            )
             .ok()
-            .unwrap()
-            .get_promise_result_blocking(Duration::from_secs(10))
+            .expect("call failed");
+        assert_eq!(res.get_string(), "true:4096:0,255,255");
+
+        // the Vec is dropped by the free func when the buffer is collected
+        rt.cleanup_sync();
+    }
+
+    #[test]
+    fn test_number_array() {
+        log::info!("test: test_number_array");
+        let rt = init_test_runtime();
+
+        let esvf = rt
+            .eval_sync(
+                "Array.from({length: 10000}, (v, i) => i % 2 === 0 ? i : i / 4);",
+                "test_number_array.es",
+            )
             .ok()
-            .unwrap();
+            .expect("script failed");
+        assert!(esvf.is_array());
+        assert!(esvf.downcast_ref::<super::EsNumberArray>().is_some());
 
-        let res_str_esvf = res.ok().unwrap();
+        let expected: Vec<f64> = (0..10000)
+            .map(|i| if i % 2 == 0 { i as f64 } else { i as f64 / 4.0 })
+            .collect();
+        assert_eq!(esvf.get_number_array(), expected);
 
-        let res_str = res_str_esvf.get_string();
+        // the elements are the same as those of the general path
+        let arr = esvf.get_array();
+        assert_eq!(arr.len(), 10000);
+        assert_eq!(arr[2].get_i32(), 2);
+        assert_eq!(arr[3].get_f64(), 0.75);
 
-        assert_eq!("123foo", res_str);
+        // and it can be passed back to script
+        rt.eval_sync(
+            "function test_number_array_sum(arr){return arr.reduce((a, b) => a + b, 0);}",
+            "test_number_array2.es",
+        )
+        .ok()
+        .expect("script failed");
+        let sum = rt
+            .call_sync(vec![], "test_number_array_sum", vec![esvf])
+            .ok()
+            .expect("call failed");
+        assert_eq!(sum.get_f64(), expected.iter().sum::<f64>());
 
-        let res2 = p2_esvf_rej
+        // arrays with other values use the general path
+        let mixed = rt
+            .eval_sync("[1, 'two', 3];", "test_number_array3.es")
             .ok()
-            .unwrap()
-            .get_promise_result_blocking(Duration::from_secs(30))
+            .expect("script failed");
+        assert!(mixed.downcast_ref::<super::EsNumberArray>().is_none());
+        assert_eq!(mixed.get_array()[1].get_string(), "two");
+
+        // every element is read once, also when the fast path is not used
+        let reads = rt
+            .eval_sync(
+                "this.test_number_array_reads = 0; \
+                 new Proxy([1, 2, 'three'], {get: function(target, prop){if (typeof prop === 'string' && /^\\d+$/.test(prop)) {test_number_array_reads++;} return target[prop];}});",
+                "test_number_array4.es",
+            )
             .ok()
-            .unwrap();
+            .expect("script failed");
+        assert_eq!(reads.get_array().len(), 3);
+        let count = rt
+            .eval_sync("test_number_array_reads;", "test_number_array5.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(count.get_i32(), 3);
+    }
 
-        let res_str_esvf_rej = res2.ok().unwrap(); // yes its the ok because we catch the rejection in test_prepped_prom.es, val should be bar thou
+    #[test]
+    fn test_iterable() {
+        log::info!("test: test_iterable");
+        let rt = init_test_runtime();
 
-        let res_str_rej = res_str_esvf_rej.get_string();
+        rt.eval_sync(
+            "function test_iterable_sum(it){\
+                 let s = 0;\
+                 for (const x of it) {if (x > 4) break; s += x;}\
+                 return s;\
+             }",
+            "test_iterable.es",
+        )
+        .ok()
+        .expect("script failed");
 
-        assert_eq!("456bar", res_str_rej);
+        let pulled = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let pulled2 = pulled.clone();
+        let numbers = (1..).map(move |i| {
+            pulled2.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            EsValueFacade::new_i32(i)
+        });
+        let res = rt
+            .call_sync(
+                vec![],
+                "test_iterable_sum",
+                vec![EsValueFacade::new_iterable(numbers)],
+            )
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_i32(), 10);
+        // the endless iterator was consumed lazily
+        assert_eq!(pulled.load(std::sync::atomic::Ordering::SeqCst), 5);
     }
 
     #[test]
-    fn test_prepped_prom_resolve() {
-        log::info!("test: test_prepped_prom_resolve");
-        let rt = init_test_runtime();
-
-        let my_prep_func = || {
-            std::thread::sleep(Duration::from_secs(5));
-            Ok(EsValueFacade::new_i32(123))
-        };
+    fn test_drop_cached_function_after_runtime() {
+        log::info!("test: test_drop_cached_function_after_runtime");
+        let rt = EsRuntimeBuilder::new().build();
+        let func_esvf = rt
+            .eval_sync(
+                "(function(){return 1;});",
+                "test_drop_cached_function_after_runtime.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(func_esvf.is_function());
+
+        // the facade does not keep the runtime alive, so using the function fails
+        // and dropping it should just skip releasing the cached object
+        drop(rt);
+        assert!(func_esvf.invoke_function(vec![]).is_err());
+        assert!(func_esvf.apply(EsValueFacade::undefined(), vec![]).is_err());
+        assert!(func_esvf.function_name().is_err());
+        drop(func_esvf);
+    }
 
-        let prom_esvf = EsValueFacade::new_promise(my_prep_func);
+    #[test]
+    fn test_refs_after_runtime_dropped() {
+        log::info!("test: test_refs_after_runtime_dropped");
+        let rt = EsRuntimeBuilder::new().eager_string_max_len(2).build();
+        let str_esvf = rt
+            .eval_sync("'abc';", "test_refs_after_runtime_dropped.es")
+            .ok()
+            .expect("script failed");
+        assert!(str_esvf.is_string_ref());
+        let prom_esvf = rt
+            .eval_sync("Promise.resolve(1);", "test_refs_after_runtime_dropped2.es")
+            .ok()
+            .expect("script failed");
+
+        drop(rt);
+        assert!(str_esvf.string_length().is_err());
+        assert!(str_esvf.substring(0, 1).is_err());
+        assert!(str_esvf.to_string_lossy().is_err());
+        assert_eq!(str_esvf.to_debug_string(2), "[String: <unknown>]");
+        assert_eq!(prom_esvf.to_debug_string(2), "Promise { <unknown> }");
+    }
 
-        rt.eval_sync("this.test_prepped_prom_func = (prom) => {return prom.then((p_res) => {return p_res + 'foo';}).catch((p_err) => {return p_err + 'bar';});};", "test_prepped_prom.es").ok().unwrap();
+    #[test]
+    fn test_cached_values_of_other_runtime() {
+        log::info!("test: test_cached_values_of_other_runtime");
+        let rt_a = EsRuntimeBuilder::new().build();
+        let rt_b = EsRuntimeBuilder::new().build();
+        let func_esvf = rt_a
+            .eval_sync(
+                "(function(){return 1;});",
+                "test_cached_values_of_other_runtime.es",
+            )
+            .ok()
+            .expect("script failed");
+        let prom_esvf = rt_a
+            .eval_sync(
+                "Promise.resolve(1);",
+                "test_cached_values_of_other_runtime2.es",
+            )
+            .ok()
+            .expect("script failed");
+        rt_b.eval_sync(
+            "this.test_types = function(a, b){return typeof a + ',' + typeof b;};",
+            "test_cached_values_of_other_runtime3.es",
+        )
+        .ok()
+        .expect("script failed");
 
-        let p2_esvf = rt.call_sync(vec![], "test_prepped_prom_func", vec![prom_esvf]);
+        // values of runtime a are not resolved in runtime b
+        let res = rt_b
+            .call_sync(vec![], "test_types", vec![func_esvf, prom_esvf])
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_string(), "undefined,undefined");
+    }
 
-        let res = p2_esvf
+    #[test]
+    fn test_refs_of_other_runtime() {
+        log::info!("test: test_refs_of_other_runtime");
+        let rt_a = EsRuntimeBuilder::new()
+            .eager_object_max_props(0)
+            .eager_string_max_len(2)
+            .build();
+        let rt_b = EsRuntimeBuilder::new().build();
+        let obj_esvf = rt_a
+            .eval_sync("({a: 1});", "test_refs_of_other_runtime.es")
             .ok()
-            .unwrap()
-            .get_promise_result_blocking(Duration::from_secs(30))
+            .expect("script failed");
+        assert!(obj_esvf.is_object_ref());
+        let str_esvf = rt_a
+            .eval_sync("'abc';", "test_refs_of_other_runtime2.es")
             .ok()
-            .unwrap();
+            .expect("script failed");
+        assert!(str_esvf.is_string_ref());
+        rt_b.eval_sync(
+            "this.test_types = function(a, b){return typeof a + ',' + typeof b;};",
+            "test_refs_of_other_runtime3.es",
+        )
+        .ok()
+        .expect("script failed");
 
-        let res_str_esvf = res.ok().unwrap();
+        let res = rt_b
+            .call_sync(vec![], "test_types", vec![obj_esvf, str_esvf])
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_string(), "undefined,undefined");
+    }
 
-        let res_str = res_str_esvf.get_string();
+    #[test]
+    fn test_throwing_getters_are_passed_as_ref() {
+        log::info!("test: test_throwing_getters_are_passed_as_ref");
+        let rt = init_test_runtime();
+        let esvf = rt
+            .eval_sync(
+                "let a = [1, 2]; Object.defineProperty(a, 'meta', {enumerable: true, get() {throw Error('no meta');}}); a;",
+                "test_throwing_getters_are_passed_as_ref.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object_ref());
 
-        assert_eq!("123foo", res_str);
+        let esvf = rt
+            .eval_sync(
+                "new Proxy([1, 2], {get(target, prop) {if (prop === 'length') {throw Error('no length');} return target[prop];}});",
+                "test_throwing_getters_are_passed_as_ref2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object_ref());
+
+        let esvf = rt
+            .eval_sync(
+                "({get a() {throw Error('no a');}});",
+                "test_throwing_getters_are_passed_as_ref3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.is_object_ref());
     }
 }
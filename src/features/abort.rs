@@ -0,0 +1,420 @@
+//! # AbortController and AbortSignal
+//!
+//! this feature adds the AbortController and AbortSignal classes which are used by script code to cancel an operation
+//!
+//! rust code which performs an operation that takes an AbortSignal as argument can use get_signal_id() and on_abort()
+//! to be notified when the signal is aborted
+//!
+//! ```no_run
+//! use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+//!
+//! let rt = EsRuntimeBuilder::new().build();
+//! let esvf = rt.eval_sync("let controller = new AbortController(); \
+//!     controller.signal.addEventListener('abort', () => {console.log('aborted');}); \
+//!     controller.abort('user cancelled'); \
+//!     controller.signal.reason;", "test_abort.es").ok().expect("script failed");
+//! assert_eq!(esvf.get_string(), "user cancelled");
+//! ```
+
+use crate::esruntime::EsRuntime;
+use crate::jsapi_utils::objects;
+use crate::jsapi_utils::reflection::ProxyBuilder;
+use crate::jsapi_utils::reflection::{get_obj_id_for, get_proxy, get_proxy_for, next_instance_id};
+use crate::jsapi_utils::rooting::EsPersistentRooted;
+use crate::spidermonkeyruntimewrapper::{self, SmRuntime};
+use mozjs::jsapi::JSContext;
+use mozjs::jsval::{BooleanValue, ObjectValue, UndefinedValue};
+use mozjs::rust::{HandleObject, HandleValue, MutableHandleValue};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+const SIGNAL_CLASS_NAME: &str = "AbortSignal";
+const REASON_PROP: &str = "reason";
+
+/// a callback which is called with the reason when an AbortSignal is aborted
+pub type AbortCallback = Box<dyn FnOnce(*mut JSContext, HandleValue)>;
+
+#[derive(Default)]
+struct SignalState {
+    aborted: bool,
+    // the id of a cached holder object which has the reason as a prop
+    reason_id: Option<usize>,
+    on_abort: Vec<AbortCallback>,
+}
+
+struct ControllerState {
+    signal_id: i32,
+    // the id of the cached signal object, this is kept while the controller lives so controller.signal always returns the same object
+    signal_obj_id: Option<usize>,
+}
+
+thread_local! {
+    static SIGNALS: RefCell<HashMap<i32, SignalState>> = RefCell::new(HashMap::new());
+    static CONTROLLERS: RefCell<HashMap<i32, ControllerState>> = RefCell::new(HashMap::new());
+}
+
+pub(crate) fn init(rt: &EsRuntime) {
+    rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+        sm_rt.do_with_jsapi(|_rt, cx, global| {
+            ProxyBuilder::new(vec![], SIGNAL_CLASS_NAME)
+                .readonly_property("aborted", |_cx, obj_id, mut rval| {
+                    rval.set(BooleanValue(is_aborted(obj_id)));
+                    Ok(())
+                })
+                .readonly_property(REASON_PROP, |cx, obj_id, rval| get_reason(cx, obj_id, rval))
+                .event("abort")
+                .static_method("abort", |cx, args, rval| {
+                    // create a signal which is already aborted
+                    let signal_id = next_instance_id();
+                    SIGNALS.with(|signals_rc| {
+                        signals_rc
+                            .borrow_mut()
+                            .insert(signal_id, SignalState::default());
+                    });
+                    rooted!(in (cx) let mut reason_root = UndefinedValue());
+                    if let Some(reason) = args.get(0) {
+                        reason_root.set(reason.get());
+                    }
+                    abort(cx, signal_id, reason_root.handle());
+                    new_signal_instance(cx, signal_id, rval)
+                })
+                .finalizer(|obj_id| {
+                    let state_opt =
+                        SIGNALS.with(|signals_rc| signals_rc.borrow_mut().remove(&obj_id));
+                    if let Some(state) = state_opt {
+                        if let Some(reason_id) = state.reason_id {
+                            release_later(reason_id);
+                        }
+                    }
+                })
+                .build(cx, global);
+
+            ProxyBuilder::new(vec![], "AbortController")
+                .constructor(|_cx, _args| {
+                    let obj_id = next_instance_id();
+                    let signal_id = next_instance_id();
+                    SIGNALS.with(|signals_rc| {
+                        signals_rc
+                            .borrow_mut()
+                            .insert(signal_id, SignalState::default());
+                    });
+                    CONTROLLERS.with(|controllers_rc| {
+                        controllers_rc.borrow_mut().insert(
+                            obj_id,
+                            ControllerState {
+                                signal_id,
+                                signal_obj_id: None,
+                            },
+                        );
+                    });
+                    Ok(obj_id)
+                })
+                .readonly_property("signal", |cx, obj_id, rval| {
+                    get_controller_signal(cx, obj_id, rval)
+                })
+                .method("abort", |cx, obj_id, args, _rval| {
+                    let signal_id = CONTROLLERS
+                        .with(|controllers_rc| {
+                            controllers_rc
+                                .borrow()
+                                .get(&obj_id)
+                                .map(|controller| controller.signal_id)
+                        })
+                        .ok_or_else(|| "no such AbortController".to_string())?;
+                    rooted!(in (cx) let mut reason_root = UndefinedValue());
+                    if let Some(reason) = args.get(0) {
+                        reason_root.set(reason.get());
+                    }
+                    abort(cx, signal_id, reason_root.handle());
+                    Ok(())
+                })
+                .finalizer(|obj_id| {
+                    let controller_opt = CONTROLLERS
+                        .with(|controllers_rc| controllers_rc.borrow_mut().remove(&obj_id));
+                    if let Some(controller) = controller_opt {
+                        if let Some(signal_obj_id) = controller.signal_obj_id {
+                            // the state is removed when the signal is finalized
+                            release_later(signal_obj_id);
+                        } else {
+                            // the signal was never used by script, so it will not be finalized
+                            let state_opt = SIGNALS.with(|signals_rc| {
+                                signals_rc.borrow_mut().remove(&controller.signal_id)
+                            });
+                            if let Some(reason_id) = state_opt.and_then(|state| state.reason_id) {
+                                release_later(reason_id);
+                            }
+                        }
+                    }
+                })
+                .build(cx, global);
+        })
+    });
+}
+
+/// get the id of an AbortSignal object, returns None if the object is not an AbortSignal
+/// this is useful for rust functions which accept an AbortSignal as argument
+pub fn get_signal_id(cx: *mut JSContext, obj: HandleObject) -> Option<i32> {
+    match get_proxy_for(cx, obj.get()) {
+        Some(proxy) if proxy.get_canonical_name().as_str() == SIGNAL_CLASS_NAME => {
+            Some(get_obj_id_for(cx, obj.get()))
+        }
+        _ => None,
+    }
+}
+
+/// check if the AbortSignal with the given id was aborted
+pub fn is_aborted(signal_id: i32) -> bool {
+    SIGNALS.with(|signals_rc| {
+        signals_rc
+            .borrow()
+            .get(&signal_id)
+            .map(|state| state.aborted)
+            .unwrap_or(false)
+    })
+}
+
+/// add a callback which is called with the reason when the AbortSignal with the given id is aborted
+/// if the signal was already aborted the callback is called immediately
+/// needs to run in the worker thread of the runtime
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::jsapi_utils::abort;
+/// use mozjs::jsval::UndefinedValue;
+/// use mozjs::rooted;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.do_in_es_event_queue_sync(|sm_rt| {
+///     sm_rt.do_with_jsapi(|rt, cx, global| {
+///         rooted!(in (cx) let mut signal_root = UndefinedValue());
+///         spidermonkey_runtime::jsapi_utils::eval(rt, global,
+///             "globalThis.controller = new AbortController(); controller.signal;",
+///             "test_on_abort.es", signal_root.handle_mut()).ok().expect("script failed");
+///         rooted!(in (cx) let signal_obj_root = signal_root.to_object());
+///         let signal_id = abort::get_signal_id(cx, signal_obj_root.handle()).expect("not a signal");
+///         abort::on_abort(cx, signal_id, Box::new(|_cx, _reason| {
+///             println!("operation was aborted");
+///         }));
+///     })
+/// });
+/// rt.eval_sync("controller.abort();", "test_on_abort2.es").ok().expect("script failed");
+/// ```
+pub fn on_abort(cx: *mut JSContext, signal_id: i32, callback: AbortCallback) {
+    let run_now = SIGNALS.with(|signals_rc| {
+        let signals = &mut *signals_rc.borrow_mut();
+        match signals.get_mut(&signal_id) {
+            Some(state) if !state.aborted => {
+                state.on_abort.push(callback);
+                None
+            }
+            _ => Some(callback),
+        }
+    });
+    if let Some(callback) = run_now {
+        rooted!(in (cx) let mut reason_root = UndefinedValue());
+        // the reason is undefined if the signal does not exist anymore
+        let _ = get_reason(cx, signal_id, reason_root.handle_mut());
+        callback(cx, reason_root.handle());
+    }
+}
+
+/// abort the AbortSignal with the given id, this dispatches the abort event and calls the callbacks added with on_abort()
+/// aborting a signal which was already aborted has no effect
+/// needs to run in the worker thread of the runtime
+pub fn abort(cx: *mut JSContext, signal_id: i32, reason: HandleValue) {
+    rooted!(in (cx) let mut holder_root = crate::jsapi_utils::objects::NULL_JSOBJECT);
+    objects::new_object(cx, holder_root.handle_mut());
+    objects::set_es_obj_prop_value(cx, holder_root.handle(), REASON_PROP, reason);
+
+    let callbacks_opt = SIGNALS.with(|signals_rc| {
+        let signals = &mut *signals_rc.borrow_mut();
+        match signals.get_mut(&signal_id) {
+            Some(state) if !state.aborted => {
+                state.aborted = true;
+                state.reason_id = Some(spidermonkeyruntimewrapper::register_cached_object(
+                    cx,
+                    holder_root.get(),
+                ));
+                Some(std::mem::take(&mut state.on_abort))
+            }
+            _ => None,
+        }
+    });
+
+    // the state is no longer borrowed here so listeners and callbacks may use the signal
+    if let Some(callbacks) = callbacks_opt {
+        if let Some(proxy) = get_proxy(SIGNAL_CLASS_NAME) {
+            rooted!(in (cx) let mut evt_obj_root = crate::jsapi_utils::objects::NULL_JSOBJECT);
+            objects::new_object(cx, evt_obj_root.handle_mut());
+            rooted!(in (cx) let mut type_root = UndefinedValue());
+            crate::jsapi_utils::new_es_value_from_str(cx, "abort", type_root.handle_mut());
+            objects::set_es_obj_prop_value(cx, evt_obj_root.handle(), "type", type_root.handle());
+            rooted!(in (cx) let evt_root = ObjectValue(evt_obj_root.get()));
+            proxy.dispatch_event(signal_id, "abort", cx, evt_root.handle().into());
+        }
+        for callback in callbacks {
+            callback(cx, reason);
+        }
+    }
+}
+
+fn get_reason(
+    cx: *mut JSContext,
+    signal_id: i32,
+    mut rval: MutableHandleValue,
+) -> Result<(), String> {
+    let reason_id_opt = SIGNALS.with(|signals_rc| {
+        signals_rc
+            .borrow()
+            .get(&signal_id)
+            .and_then(|state| state.reason_id)
+    });
+    match reason_id_opt {
+        Some(reason_id) => {
+            let holder = spidermonkeyruntimewrapper::do_with_cached_object(
+                reason_id,
+                |epr: &EsPersistentRooted| epr.get(),
+            );
+            rooted!(in (cx) let holder_root = holder);
+            objects::get_es_obj_prop_val(cx, holder_root.handle(), REASON_PROP, rval)
+                .map_err(|err| err.err_msg())
+        }
+        None => {
+            rval.set(UndefinedValue());
+            Ok(())
+        }
+    }
+}
+
+fn get_controller_signal(
+    cx: *mut JSContext,
+    obj_id: i32,
+    mut rval: MutableHandleValue,
+) -> Result<(), String> {
+    let (signal_id, signal_obj_id_opt) = CONTROLLERS
+        .with(|controllers_rc| {
+            controllers_rc
+                .borrow()
+                .get(&obj_id)
+                .map(|controller| (controller.signal_id, controller.signal_obj_id))
+        })
+        .ok_or_else(|| "no such AbortController".to_string())?;
+
+    if let Some(signal_obj_id) = signal_obj_id_opt {
+        let signal_obj = spidermonkeyruntimewrapper::do_with_cached_object(
+            signal_obj_id,
+            |epr: &EsPersistentRooted| epr.get(),
+        );
+        rval.set(ObjectValue(signal_obj));
+        return Ok(());
+    }
+
+    new_signal_instance(cx, signal_id, rval)?;
+    let signal_obj_id = spidermonkeyruntimewrapper::register_cached_object(cx, rval.to_object());
+    CONTROLLERS.with(|controllers_rc| {
+        if let Some(controller) = controllers_rc.borrow_mut().get_mut(&obj_id) {
+            controller.signal_obj_id = Some(signal_obj_id);
+        }
+    });
+    Ok(())
+}
+
+fn new_signal_instance(
+    cx: *mut JSContext,
+    signal_id: i32,
+    rval: MutableHandleValue,
+) -> Result<(), String> {
+    let proxy = get_proxy(SIGNAL_CLASS_NAME).expect("AbortSignal was not initialized");
+    proxy
+        .new_instance(cx, signal_id, rval)
+        .map_err(|err| err.err_msg())
+}
+
+// cached objects should not be released while the engine is finalizing objects, so we do that in a later job
+fn release_later(cached_obj_id: usize) {
+    if let Some(rt_arc) = SmRuntime::try_clone_current_esrt_inner_arc() {
+        rt_arc.do_in_es_event_queue(move |_sm_rt| {
+            spidermonkeyruntimewrapper::release_cached_object(cached_obj_id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::esruntime::tests::init_test_runtime;
+    use crate::features::abort;
+    use mozjs::jsval::UndefinedValue;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_abort_controller() {
+        log::info!("test: test_abort_controller");
+        let rt = init_test_runtime();
+        let esvf = rt
+            .eval_sync(
+                "let ac = new AbortController(); let fired = []; \
+                 ac.signal.addEventListener('abort', (evt) => {fired.push(evt.type + ':' + ac.signal.reason);}); \
+                 let before = ac.signal.aborted; \
+                 ac.abort('stop'); ac.abort('again'); \
+                 [before, ac.signal.aborted, ac.signal === ac.signal, fired.length, fired[0], AbortSignal.abort(1).aborted].join(',');",
+                "test_abort_controller.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "false,true,true,1,abort:stop,true");
+    }
+
+    #[test]
+    fn test_on_abort() {
+        log::info!("test: test_on_abort");
+        let rt = init_test_runtime();
+        let res = rt.do_in_es_event_queue_sync(|sm_rt| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in (cx) let mut signal_root = UndefinedValue());
+                crate::jsapi_utils::eval(
+                    rt,
+                    global,
+                    "globalThis.test_on_abort_ac = new AbortController(); test_on_abort_ac.signal;",
+                    "test_on_abort.es",
+                    signal_root.handle_mut(),
+                )
+                .ok()
+                .expect("script failed");
+                rooted!(in (cx) let signal_obj_root = signal_root.to_object());
+                let signal_id =
+                    abort::get_signal_id(cx, signal_obj_root.handle()).expect("not a signal");
+                assert!(!abort::is_aborted(signal_id));
+
+                let reasons = Rc::new(RefCell::new(vec![]));
+                let reasons2 = reasons.clone();
+                abort::on_abort(
+                    cx,
+                    signal_id,
+                    Box::new(move |cx, reason| {
+                        let reason_str = crate::jsapi_utils::es_value_to_str(cx, *reason)
+                            .ok()
+                            .expect("reason was not a string");
+                        reasons2.borrow_mut().push(reason_str);
+                    }),
+                );
+
+                rooted!(in (cx) let mut rval = UndefinedValue());
+                crate::jsapi_utils::eval(
+                    rt,
+                    global,
+                    "test_on_abort_ac.abort('timeout');",
+                    "test_on_abort2.es",
+                    rval.handle_mut(),
+                )
+                .ok()
+                .expect("script failed");
+
+                assert!(abort::is_aborted(signal_id));
+                let res = reasons.borrow().clone();
+                res
+            })
+        });
+        assert_eq!(res, vec!["timeout".to_string()]);
+    }
+}
@@ -7,7 +7,63 @@ use mozjs::jsapi::CallArgs;
 use mozjs::jsapi::JSContext;
 use mozjs::jsval::{JSVal, UndefinedValue};
 use mozjs::rust::HandleValue;
+use std::cell::RefCell;
 use std::str::FromStr;
+use std::sync::mpsc::Sender;
+
+/// the level at which a line was written to the console
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConsoleLevel {
+    Trace,
+    Debug,
+    Info,
+    Log,
+    Warn,
+    Error,
+    Assert,
+}
+
+/// a line written to the console by script code, e.g. by calling console.log()
+#[derive(Clone, Debug)]
+pub struct ConsoleLine {
+    pub level: ConsoleLevel,
+    pub message: String,
+}
+
+thread_local! {
+    // if set, all console lines written in the worker thread are also sent to this sink
+    static CONSOLE_SINK: RefCell<Option<Sender<ConsoleLine>>> = RefCell::new(None);
+}
+
+/// set the sink which receives all console lines written in the current thread
+/// returns the previous sink so it can be restored
+pub(crate) fn set_console_sink(sink: Option<Sender<ConsoleLine>>) -> Option<Sender<ConsoleLine>> {
+    CONSOLE_SINK.with(|rc| rc.replace(sink))
+}
+
+fn write_line(level: ConsoleLevel, message: String) {
+    // prefix the log line with the name of the runtime if it has one
+    let prefix = match jsapi_utils::current_runtime_name() {
+        Some(name) => format!("console[{}]", name),
+        None => "console".to_string(),
+    };
+    match level {
+        ConsoleLevel::Trace => log::trace!("{}: {}", prefix, message),
+        ConsoleLevel::Debug => log::debug!("{}: {}", prefix, message),
+        ConsoleLevel::Info | ConsoleLevel::Log | ConsoleLevel::Assert => {
+            log::info!("{}: {}", prefix, message)
+        }
+        ConsoleLevel::Warn => log::warn!("{}: {}", prefix, message),
+        ConsoleLevel::Error => log::error!("{}: {}", prefix, message),
+    }
+
+    CONSOLE_SINK.with(|rc| {
+        if let Some(sink) = &*rc.borrow() {
+            // the receiving end may have been dropped, that's fine
+            let _ = sink.send(ConsoleLine { level, message });
+        }
+    });
+}
 
 // todo rewrite to Proxy
 
@@ -32,13 +88,8 @@ pub(crate) fn init(rt: &EsRuntime) {
 /// see https://console.spec.whatwg.org/#formatting-specifiers
 ///
 fn parse_field(context: *mut JSContext, field: String, value: JSVal) -> String {
-    rooted!(in(context) let val_root = value);
-
-    // convert all vartypes to jsstring
-    let js_str: *mut mozjs::jsapi::JSString =
-        unsafe { mozjs::rust::ToString(context, val_root.handle()) };
-    // convert jsstring to rust string
-    let str_val = jsapi_utils::es_jsstring_to_string(context, js_str);
+    // convert all vartypes to a rust string, this does not throw for Symbols
+    let str_val = jsapi_utils::es_value_to_display_str(context, value);
 
     // return string
     parse_field_value(field, str_val)
@@ -144,7 +195,7 @@ fn parse_line2(context: *mut JSContext, args: Vec<JSVal>) -> String {
     }
     let mut args = args;
     let arg1: JSVal = args.remove(0);
-    let message = jsapi_utils::es_value_to_str(context, arg1).ok().unwrap();
+    let message = jsapi_utils::es_value_to_display_str(context, arg1);
 
     let mut output = String::new();
     let mut field_code = String::new();
@@ -178,7 +229,7 @@ unsafe extern "C" fn console_log(
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
     //
-    log::info!("console: {}", parse_line(context, argc, vp));
+    write_line(ConsoleLevel::Log, parse_line(context, argc, vp));
     true
 }
 
@@ -188,7 +239,7 @@ unsafe extern "C" fn console_debug(
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
     //
-    log::debug!("console: {}", parse_line(context, argc, vp));
+    write_line(ConsoleLevel::Debug, parse_line(context, argc, vp));
     true
 }
 
@@ -198,7 +249,7 @@ unsafe extern "C" fn console_warn(
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
     //
-    log::warn!("console: {}", parse_line(context, argc, vp));
+    write_line(ConsoleLevel::Warn, parse_line(context, argc, vp));
     true
 }
 
@@ -208,7 +259,7 @@ unsafe extern "C" fn console_info(
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
     //
-    log::info!("console: {}", parse_line(context, argc, vp));
+    write_line(ConsoleLevel::Info, parse_line(context, argc, vp));
     true
 }
 
@@ -218,7 +269,7 @@ unsafe extern "C" fn console_trace(
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
     //
-    log::trace!("console: {}", parse_line(context, argc, vp));
+    write_line(ConsoleLevel::Trace, parse_line(context, argc, vp));
     true
 }
 
@@ -228,7 +279,7 @@ unsafe extern "C" fn console_error(
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
     //
-    log::error!("console: {}", parse_line(context, argc, vp));
+    write_line(ConsoleLevel::Error, parse_line(context, argc, vp));
     true
 }
 
@@ -262,7 +313,7 @@ unsafe extern "C" fn console_assert(
     args.rval().set(UndefinedValue());
 
     if assertion {
-        log::info!("console: {}", parse_line2(context, values));
+        write_line(ConsoleLevel::Assert, parse_line2(context, values));
     }
 
     true
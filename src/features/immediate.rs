@@ -1,7 +1,7 @@
 use crate::esruntime::EsRuntime;
 use crate::jsapi_utils;
 use crate::jsapi_utils::report_exception;
-use log::{error, trace};
+use log::trace;
 use mozjs::jsval::ObjectValue;
 
 pub(crate) fn init(rt: &EsRuntime) {
@@ -53,13 +53,9 @@ pub(crate) fn init(rt: &EsRuntime) {
                         vec![],
                         rval.handle_mut(),
                     );
-                    if res.is_err() {
-                        error!(
-                            "error executing setImmediate func: {}",
-                            res.err().unwrap().err_msg()
-                        );
-                    } else {
-                        trace!("executed setImmediate function");
+                    match res {
+                        Ok(()) => trace!("executed setImmediate function"),
+                        Err(err) => crate::spidermonkeyruntimewrapper::report_uncaught_error(err),
                     }
                 });
             });
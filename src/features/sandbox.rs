@@ -0,0 +1,169 @@
+use crate::esruntime::EsRuntime;
+
+/// presets of the builtins which are available to scripts, see EsRuntimeBuilder::sandbox_profile()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SandboxProfile {
+    /// all builtins are available, this is the default
+    Full,
+    /// no dynamic code and no timers
+    /// * eval, Function and WebAssembly are removed from the global object
+    /// * the constructor property of (async/generator) functions throws instead of compiling code
+    /// * setImmediate, setTimeout, setInterval and fetch are removed from the global object
+    Minimal,
+}
+
+const MINIMAL_GLOBALS: [&str; 7] = [
+    "eval",
+    "Function",
+    "WebAssembly",
+    "setImmediate",
+    "setTimeout",
+    "setInterval",
+    "fetch",
+];
+
+// the constructors of all function types compile code from a string, replace those with a thrower
+const NO_DYNAMIC_CODE_SCRIPT: &str = "(function(){\
+    const thrower = function(){throw new EvalError('dynamic code evaluation is not allowed');};\
+    const protos = [\
+        Function.prototype,\
+        Object.getPrototypeOf(async function(){}),\
+        Object.getPrototypeOf(function*(){}),\
+        Object.getPrototypeOf(async function*(){})\
+    ];\
+    for (const proto of protos) {\
+        Object.defineProperty(proto, 'constructor', {value: thrower, writable: false, enumerable: false, configurable: false});\
+    }\
+})();";
+
+// make all own props of the global readonly and permanent, the global itself stays extensible
+const LOCK_GLOBALS_SCRIPT: &str = "(function(){\
+    for (const name of Reflect.ownKeys(globalThis)) {\
+        const desc = Object.getOwnPropertyDescriptor(globalThis, name);\
+        if ('value' in desc) {\
+            desc.writable = false;\
+        }\
+        desc.configurable = false;\
+        Object.defineProperty(globalThis, name, desc);\
+    }\
+})();";
+
+pub(crate) fn init(rt: &EsRuntime) {
+    let (profile, removed_globals, lock_globals) = rt.do_with_inner(|inner| {
+        (
+            inner.config.sandbox_profile,
+            inner.config.removed_globals.clone(),
+            inner.config.lock_globals,
+        )
+    });
+
+    let mut globals_to_remove: Vec<String> = vec![];
+
+    if profile == SandboxProfile::Minimal {
+        rt.eval_void_sync(NO_DYNAMIC_CODE_SCRIPT, "es_sandbox_minimal.es")
+            .ok()
+            .expect("could not init minimal sandbox");
+        globals_to_remove.extend(MINIMAL_GLOBALS.iter().map(|name| name.to_string()));
+    }
+
+    globals_to_remove.extend(removed_globals);
+
+    if !globals_to_remove.is_empty() {
+        // Reflect.deleteProperty does not throw for non-configurable props, not even in strict mode
+        let script: String = globals_to_remove
+            .iter()
+            .map(|name| format!("Reflect.deleteProperty(globalThis, {:?});", name))
+            .collect();
+        rt.eval_void_sync(script.as_str(), "es_sandbox_remove_globals.es")
+            .ok()
+            .expect("could not remove globals");
+    }
+
+    if lock_globals {
+        rt.eval_void_sync(LOCK_GLOBALS_SCRIPT, "es_sandbox_lock_globals.es")
+            .ok()
+            .expect("could not lock globals");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::esruntime::EsRuntime;
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::features::sandbox::SandboxProfile;
+
+    #[test]
+    fn test_sandbox_minimal() {
+        let rt = EsRuntime::builder()
+            .sandbox_profile(SandboxProfile::Minimal)
+            .remove_global("Proxy")
+            .build();
+
+        let esvf = rt
+            .eval_sync(
+                "[typeof eval, typeof Function, typeof setImmediate, typeof Proxy, typeof Math].join(',');",
+                "test_sandbox_minimal.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(
+            esvf.get_string(),
+            "undefined,undefined,undefined,undefined,object"
+        );
+
+        assert!(rt
+            .eval_sync(
+                "(function(){}).constructor('return 1;')();",
+                "test_sandbox_minimal2.es"
+            )
+            .is_err());
+        assert!(rt
+            .eval_sync(
+                "(async function(){}).constructor('return 1;');",
+                "test_sandbox_minimal3.es"
+            )
+            .is_err());
+
+        // regular functions still work
+        let esvf = rt
+            .eval_sync(
+                "(function(a){return a * 2;})(21);",
+                "test_sandbox_minimal4.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 42);
+
+        let rt = EsRuntime::builder().build();
+        let esvf = rt
+            .eval_sync("eval('1 + 1');", "test_sandbox_full.es")
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 2);
+    }
+
+    #[test]
+    fn test_sandboxed() {
+        let rt = EsRuntimeBuilder::new().sandboxed().build();
+
+        assert!(rt.eval_sync("eval('1');", "test_sandboxed.es").is_err());
+        let esvf = rt
+            .eval_sync(
+                "'use strict'; let replaced = true; try {Math = {};} catch(e) {replaced = false;} [typeof fetch, typeof setTimeout, replaced].join(',');",
+                "test_sandboxed2.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "undefined,undefined,false");
+
+        // new globals can still be added
+        let esvf = rt
+            .eval_sync(
+                "globalThis.test_sandboxed_new = 12; test_sandboxed_new;",
+                "test_sandboxed3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 12);
+    }
+}
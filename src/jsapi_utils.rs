@@ -24,27 +24,39 @@
 //!
 
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
-use crate::jsapi_utils::objects::{get_es_obj_prop_val_as_i32, get_es_obj_prop_val_as_string};
+use crate::jsapi_utils::objects::{get_es_obj_prop_val, get_es_obj_prop_val_as_string};
 use log::{debug, trace};
 use mozjs::conversions::jsstr_to_string;
 use mozjs::glue::{RUST_JSID_IS_STRING, RUST_JSID_TO_STRING};
 use mozjs::jsapi::JSContext;
 use mozjs::jsapi::JSString;
 use mozjs::jsapi::JSType;
+use mozjs::jsapi::JS_AtomizeAndPinString;
 use mozjs::jsapi::JS_ClearPendingException;
+use mozjs::jsapi::JS_DeprecatedStringHasLatin1Chars;
 use mozjs::jsapi::JS_GetPendingException;
+use mozjs::jsapi::JS_GetStringLength;
+use mozjs::jsapi::JS_GetTwoByteStringCharsAndLength;
 use mozjs::jsapi::JS_IsExceptionPending;
+use mozjs::jsapi::JS_NewDependentString;
 use mozjs::jsapi::JS_NewStringCopyN;
+use mozjs::jsapi::JS_SetPendingException;
+use mozjs::jsapi::JS_StringToId;
 use mozjs::jsapi::JS_TypeOfValue;
+use mozjs::jsapi::JS::GetSymbolDescription;
 use mozjs::jsapi::JS_GC;
-use mozjs::jsval::{StringValue, UndefinedValue};
+use mozjs::jsapi::{CurrentGlobalOrNull, ExceptionStackBehavior, HandleValueArray, JSObject};
+use mozjs::jsval::{ObjectValue, StringValue, UndefinedValue};
 use mozjs::rust::{HandleObject, HandleValue, MutableHandleValue, Runtime};
+use std::cell::{Cell, RefCell};
 use std::str;
+use std::{ptr, slice};
 
 pub mod arrays;
 pub mod big_ints;
 pub mod functions;
 pub mod handles;
+pub mod iterators;
 pub mod modules;
 pub mod objects;
 pub mod promises;
@@ -53,6 +65,8 @@ pub mod rooting;
 pub mod scripts;
 pub mod typed_arrays;
 
+pub use crate::features::abort;
+
 /// get the type of a JSVal
 /// this is the equivalent of calling ```typeof val``` in script
 pub fn get_type_of(context: *mut JSContext, val: HandleValue) -> JSType {
@@ -85,69 +99,298 @@ pub fn report_exception(cx: *mut JSContext, ex: &str) {
     };
 }
 
+/// report an exception to script
+/// if an exception is already pending its message is appended and it is set as the cause of the new exception
 pub fn report_exception2(cx: *mut JSContext, ex: String) {
-    let ex_str = format!("{}\0", ex);
+    if !unsafe { JS_IsExceptionPending(cx) } {
+        let ex_str = format!("{}\0", ex);
+        unsafe {
+            mozjs::jsapi::JS_ReportErrorUTF8(cx, ex_str.as_str().as_ptr() as *const libc::c_char)
+        };
+        return;
+    }
+
+    rooted!(in(cx) let mut cause_val = UndefinedValue());
+    unsafe {
+        JS_GetPendingException(cx, cause_val.handle_mut().into());
+        JS_ClearPendingException(cx);
+    }
+
+    let cause_msg = if cause_val.is_object() {
+        rooted!(in(cx) let cause_obj = cause_val.to_object());
+        get_es_obj_prop_val_as_string(cx, cause_obj.handle(), "message").unwrap_or_default()
+    } else {
+        es_value_to_str(cx, *cause_val).unwrap_or_default()
+    };
+
+    let ex_str = format!("{}, caused by: {}\0", ex, cause_msg);
     unsafe {
         mozjs::jsapi::JS_ReportErrorUTF8(cx, ex_str.as_str().as_ptr() as *const libc::c_char)
     };
+
+    // chain the original exception as cause of the new one
+    rooted!(in(cx) let mut ex_val = UndefinedValue());
+    if unsafe { JS_GetPendingException(cx, ex_val.handle_mut().into()) } && ex_val.is_object() {
+        rooted!(in(cx) let ex_obj = ex_val.to_object());
+        objects::set_es_obj_prop_value(cx, ex_obj.handle(), "cause", cause_val.handle());
+    }
+}
+
+/// throw an error of a builtin error class like TypeError or RangeError in script
+/// a plain Error is thrown if the global object has no constructor with the given name
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::jsapi_utils;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.do_in_es_event_queue_sync(|sm_rt| {
+///     sm_rt.do_with_jsapi(|_rt, cx, _global| {
+///         jsapi_utils::report_typed_exception(cx, "RangeError", "age should be positive");
+///         let err = jsapi_utils::take_pending_exception(cx).expect("no exception");
+///         assert_eq!(err.message.as_str(), "age should be positive");
+///     });
+/// });
+/// ```
+pub fn report_typed_exception(cx: *mut JSContext, error_class: &str, message: &str) {
+    rooted!(in(cx) let global = unsafe { CurrentGlobalOrNull(cx) });
+    rooted!(in(cx) let mut constructor_val = UndefinedValue());
+    if global.is_null()
+        || objects::get_es_obj_prop_val(
+            cx,
+            global.handle(),
+            error_class,
+            constructor_val.handle_mut(),
+        )
+        .is_err()
+        || !constructor_val.is_object()
+    {
+        report_exception(cx, message);
+        return;
+    }
+
+    rooted!(in(cx) let mut message_val = UndefinedValue());
+    new_es_value_from_str(cx, message, message_val.handle_mut());
+    let args = vec![*message_val];
+    rooted!(in(cx) let mut error_obj = ptr::null_mut::<JSObject>());
+    let construct_res = objects::new_from_constructor(
+        cx,
+        constructor_val.handle(),
+        unsafe { HandleValueArray::from_rooted_slice(&*args) },
+        error_obj.handle_mut(),
+    );
+    if construct_res.is_err() || error_obj.is_null() {
+        report_exception(cx, message);
+        return;
+    }
+
+    rooted!(in(cx) let error_val = ObjectValue(*error_obj));
+    unsafe {
+        JS_SetPendingException(
+            cx,
+            error_val.handle().into(),
+            ExceptionStackBehavior::Capture,
+        )
+    };
+}
+
+/// run the body of a native function and convert a panic into a script exception
+/// this prevents a panic from unwinding across the FFI boundary and keeps the runtime usable after a buggy callback
+/// mozjs::panic::wrap_panic is not used here because it stores the panic and resumes it later
+/// this also counts the depth of nested native calls (e.g. a native function which calls a script function which calls the native function again)
+/// and throws an error instead of running the native function when the max call depth of the runtime is exceeded
+pub fn catch_native_panic<F: FnOnce() -> bool>(
+    cx: *mut JSContext,
+    native_name: &str,
+    native: F,
+) -> bool {
+    let max_call_depth = MAX_CALL_DEPTH.with(|max| max.get());
+    if current_call_depth() >= max_call_depth {
+        report_exception2(
+            cx,
+            format!(
+                "native function {} exceeded the max call depth of {}",
+                native_name, max_call_depth
+            ),
+        );
+        return false;
+    }
+    let _depth_guard = CallDepthGuard::enter();
+
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(native)) {
+        Ok(ret) => ret,
+        Err(payload) => {
+            let msg = panic_message(&payload);
+            log::error!("native function {} panicked: {}", native_name, msg);
+            report_exception2(
+                cx,
+                format!("native function {} panicked: {}", native_name, msg),
+            );
+            false
+        }
+    }
+}
+
+thread_local! {
+    // the number of native calls which are currently running in this thread, see catch_native_panic()
+    static CALL_DEPTH: Cell<u32> = Cell::new(0);
+    static MAX_CALL_DEPTH: Cell<u32> = Cell::new(u32::MAX);
+}
+
+// decrements CALL_DEPTH when a native call is done, even if it panicked
+struct CallDepthGuard {}
+
+impl CallDepthGuard {
+    fn enter() -> Self {
+        CALL_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        CallDepthGuard {}
+    }
+}
+
+impl Drop for CallDepthGuard {
+    fn drop(&mut self) {
+        CALL_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// set the max depth of nested native calls for the current thread, see EsRuntimeBuilder::max_call_depth()
+pub(crate) fn set_max_call_depth(max_call_depth: u32) {
+    MAX_CALL_DEPTH.with(|max| max.set(max_call_depth));
+}
+
+/// get the number of nested native calls which are currently running in this thread
+pub fn current_call_depth() -> u32 {
+    CALL_DEPTH.with(|depth| depth.get())
+}
+
+thread_local! {
+    static RUNTIME_NAME: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// set the name of the runtime of the current thread, see EsRuntimeBuilder::name()
+pub(crate) fn set_runtime_name(name: Option<String>) {
+    RUNTIME_NAME.with(|rc| rc.replace(name));
+}
+
+/// get the name of the runtime of the current thread, see EsRuntimeBuilder::name()
+pub fn current_runtime_name() -> Option<String> {
+    RUNTIME_NAME.with(|rc| rc.borrow().clone())
+}
+
+/// get the message of a panic payload
+pub(crate) fn panic_message(payload: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
 }
 
 fn get_pending_exception_or_generic_err(cx: *mut JSContext, gen_err: &'static str) -> EsErrorInfo {
     if let Some(err) = crate::jsapi_utils::get_pending_exception(cx) {
         err
     } else {
-        EsErrorInfo {
-            message: gen_err.to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        }
+        EsErrorInfo::new(gen_err.to_string(), "".to_string(), 0, 0)
     }
 }
 
 /// see if there is a pending exception and return it as an EsErrorInfo
+/// the exception is cleared, this is the same as take_pending_exception()
 #[allow(dead_code)]
 pub fn get_pending_exception(context: *mut JSContext) -> Option<EsErrorInfo> {
-    trace!("report_es_ex");
+    take_pending_exception(context)
+}
 
-    if unsafe { JS_IsExceptionPending(context) } {
-        rooted!(in(context) let mut error_value = UndefinedValue());
-        if unsafe { JS_GetPendingException(context, error_value.handle_mut().into()) } {
-            let js_error_obj: *mut mozjs::jsapi::JSObject = error_value.to_object();
-            rooted!(in(context) let mut js_error_obj_root = js_error_obj);
+/// see if there is a pending exception and return it as an EsErrorInfo
+/// the exception is cleared so it will not be thrown to script or reported by the engine, use this when you handle the exception
+pub fn take_pending_exception(context: *mut JSContext) -> Option<EsErrorInfo> {
+    pending_exception_info(context, true)
+}
 
-            let message =
-                get_es_obj_prop_val_as_string(context, js_error_obj_root.handle(), "message")
-                    .ok()
-                    .unwrap();
-            let filename =
-                get_es_obj_prop_val_as_string(context, js_error_obj_root.handle(), "fileName")
-                    .ok()
-                    .unwrap();
-            let lineno =
-                get_es_obj_prop_val_as_i32(context, js_error_obj_root.handle(), "lineNumber");
-            let column =
-                get_es_obj_prop_val_as_i32(context, js_error_obj_root.handle(), "columnNumber");
-
-            let error_info: EsErrorInfo = EsErrorInfo {
-                message,
-                filename,
-                lineno,
-                column,
-            };
-
-            debug!(
-                "ex = {} in {} at {}:{}",
-                error_info.message, error_info.filename, error_info.lineno, error_info.column
-            );
+/// see if there is a pending exception and return it as an EsErrorInfo
+/// the exception is not cleared so it is still thrown to script when the current native function returns false
+pub fn peek_pending_exception(context: *mut JSContext) -> Option<EsErrorInfo> {
+    pending_exception_info(context, false)
+}
 
-            unsafe { JS_ClearPendingException(context) };
-            Some(error_info)
-        } else {
-            None
-        }
+fn pending_exception_info(context: *mut JSContext, clear: bool) -> Option<EsErrorInfo> {
+    trace!("report_es_ex");
+
+    if !unsafe { JS_IsExceptionPending(context) } {
+        return None;
+    }
+    rooted!(in(context) let mut error_value = UndefinedValue());
+    if !unsafe { JS_GetPendingException(context, error_value.handle_mut().into()) } {
+        return None;
+    }
+    // the exception is cleared while we read it, getting props of the error obj can not run script while an exception is pending
+    unsafe { JS_ClearPendingException(context) };
+
+    let error_info = es_value_to_error_info(context, error_value.handle());
+
+    debug!(
+        "ex = {} in {} at {}:{}",
+        error_info.message, error_info.filename, error_info.lineno, error_info.column
+    );
+
+    if !clear {
+        // put the exception back so it is still thrown to script
+        unsafe {
+            JS_SetPendingException(
+                context,
+                error_value.handle().into(),
+                ExceptionStackBehavior::DoNotCapture,
+            )
+        };
+    }
+    Some(error_info)
+}
+
+/// convert a thrown value (or the reason of a rejected promise) to an EsErrorInfo
+/// the message, fileName, lineNumber and columnNumber props are used if the value is an object, for other values the message is the value as string
+pub(crate) fn es_value_to_error_info(
+    context: *mut JSContext,
+    error_value: HandleValue,
+) -> EsErrorInfo {
+    if error_value.is_object() {
+        rooted!(in(context) let js_error_obj_root = error_value.to_object());
+
+        // a thrown object is not always an Error (e.g. throw {};) so all props are optional
+        EsErrorInfo::new(
+            get_error_prop_as_string(context, js_error_obj_root.handle(), "message"),
+            get_error_prop_as_string(context, js_error_obj_root.handle(), "fileName"),
+            get_error_prop_as_i32(context, js_error_obj_root.handle(), "lineNumber"),
+            get_error_prop_as_i32(context, js_error_obj_root.handle(), "columnNumber"),
+        )
     } else {
-        None
+        // a thrown primitive like throw 'oops';
+        EsErrorInfo::new(
+            es_value_to_str(context, *error_value).unwrap_or_default(),
+            "".to_string(),
+            0,
+            0,
+        )
+    }
+}
+
+// get a prop of a thrown object as String, an empty String if the prop is undefined or could not be read
+fn get_error_prop_as_string(context: *mut JSContext, obj: HandleObject, prop_name: &str) -> String {
+    rooted!(in(context) let mut rval = UndefinedValue());
+    match get_es_obj_prop_val(context, obj, prop_name, rval.handle_mut()) {
+        Ok(()) if !rval.is_undefined() => es_value_to_str(context, *rval).unwrap_or_default(),
+        _ => "".to_string(),
+    }
+}
+
+// get a prop of a thrown object as i32, 0 if the prop is not a number or could not be read
+fn get_error_prop_as_i32(context: *mut JSContext, obj: HandleObject, prop_name: &str) -> i32 {
+    rooted!(in(context) let mut rval = UndefinedValue());
+    match get_es_obj_prop_val(context, obj, prop_name, rval.handle_mut()) {
+        Ok(()) if rval.is_int32() => rval.to_int32(),
+        Ok(()) if rval.is_double() => rval.to_double() as i32,
+        _ => 0,
     }
 }
 
@@ -157,9 +400,36 @@ pub struct EsErrorInfo {
     pub filename: String,
     pub lineno: i32,
     pub column: i32,
+    /// the name of the runtime the error occurred in, see EsRuntimeBuilder::name()
+    pub runtime_name: Option<String>,
 }
 
 impl EsErrorInfo {
+    /// create a new EsErrorInfo, the runtime_name is set to the name of the runtime of the current thread (see current_runtime_name())
+    /// use this instead of a struct literal so new fields don't break your code
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::jsapi_utils::EsErrorInfo;
+    ///
+    /// let err = EsErrorInfo::new("oops".to_string(), "my_script.es".to_string(), 1, 2);
+    /// assert_eq!(err.err_msg(), "oops at my_script.es:1:2");
+    /// ```
+    pub fn new(message: String, filename: String, lineno: i32, column: i32) -> Self {
+        EsErrorInfo {
+            message,
+            filename,
+            lineno,
+            column,
+            runtime_name: current_runtime_name(),
+        }
+    }
+
+    /// set the name of the runtime the error occurred in, e.g. for errors which are created outside of the worker thread of a runtime
+    pub fn with_runtime_name(mut self, runtime_name: Option<String>) -> Self {
+        self.runtime_name = runtime_name;
+        self
+    }
+
     /// get eror as String in the form of [message] at [filename]:[lineno]:[column]
     pub fn err_msg(&self) -> String {
         format!(
@@ -176,20 +446,26 @@ impl Clone for EsErrorInfo {
             filename: self.filename.clone(),
             lineno: self.lineno,
             column: self.column,
+            runtime_name: self.runtime_name.clone(),
         }
     }
 }
 
 /// eval a piece of source code in the engine
+/// ret_val is set to the completion value of the script, like the result of eval() in script this is the value of the last expression statement
+/// declarations have no completion value so "let x = 1; x + 1; let y = 3;" results in 2, a script without expression statements results in undefined
 pub fn eval(
     runtime: &Runtime,
     scope: HandleObject,
     code: &str,
     file_name: &str,
-    ret_val: MutableHandleValue,
+    mut ret_val: MutableHandleValue,
 ) -> Result<(), EsErrorInfo> {
     let context = runtime.cx();
 
+    // a reused handle should not keep its previous value when the script has no completion value
+    ret_val.set(UndefinedValue());
+
     let eval_res = runtime.evaluate_script(scope, code, file_name, 0, ret_val);
 
     if eval_res.is_ok() {
@@ -199,12 +475,12 @@ pub fn eval(
         if let Some(ex) = ex_opt {
             Err(ex)
         } else {
-            Err(EsErrorInfo {
-                message: "unknown error while evalling".to_string(),
-                filename: file_name.to_string(),
-                lineno: 0,
-                column: 0,
-            })
+            Err(EsErrorInfo::new(
+                "unknown error while evalling".to_string(),
+                file_name.to_string(),
+                0,
+                0,
+            ))
         }
     }
 }
@@ -236,7 +512,77 @@ pub fn es_value_to_str(
     }
 }
 
-/// convert a JSString to a rust string
+/// convert a StringValue to a rust string, unpaired surrogates are replaced by U+FFFD
+/// this is the same as es_value_to_str, use es_value_to_str_strict if the data should not be altered
+pub fn es_value_to_str_lossy(
+    context: *mut JSContext,
+    val: mozjs::jsapi::Value,
+) -> Result<String, &'static str> {
+    es_value_to_str(context, val)
+}
+
+/// convert a StringValue to a rust string, this fails if the string contains an unpaired surrogate
+pub fn es_value_to_str_strict(
+    context: *mut JSContext,
+    val: mozjs::jsapi::Value,
+) -> Result<String, &'static str> {
+    if val.is_string() {
+        es_jsstring_to_string_strict(context, val.to_string())
+    } else {
+        Err("value was not a String")
+    }
+}
+
+/// convert any value to a rust string like String(val) does in script, this never throws
+/// Symbols become `Symbol(desc)` and BigInts become their decimal representation (without the n suffix)
+/// if converting an object throws (e.g. its toString method throws) the exception is cleared and `[object]` is returned
+/// use es_value_to_str if the value should be a String
+pub fn es_value_to_display_str(context: *mut JSContext, val: mozjs::jsapi::Value) -> String {
+    if val.is_string() {
+        return es_jsstring_to_string(context, val.to_string());
+    }
+    if val.is_symbol() {
+        rooted!(in (context) let symbol_root = val.to_symbol());
+        let desc = unsafe { GetSymbolDescription(symbol_root.handle().into()) };
+        return if desc.is_null() {
+            "Symbol()".to_string()
+        } else {
+            format!("Symbol({})", es_jsstring_to_string(context, desc))
+        };
+    }
+    rooted!(in (context) let val_root = val);
+    rooted!(in (context) let str_root = unsafe { mozjs::rust::ToString(context, val_root.handle()) });
+    if str_root.is_null() {
+        if let Some(err) = take_pending_exception(context) {
+            debug!("es_value_to_display_str failed: {}", err.err_msg());
+        }
+        "[object]".to_string()
+    } else {
+        es_jsstring_to_string(context, *str_root)
+    }
+}
+
+/// convert a JSString to a rust string, this fails if the string contains an unpaired surrogate
+pub fn es_jsstring_to_string_strict(
+    context: *mut JSContext,
+    js_string: *mut JSString,
+) -> Result<String, &'static str> {
+    unsafe {
+        // latin1 strings can't contain surrogates
+        if JS_DeprecatedStringHasLatin1Chars(js_string) {
+            return Ok(jsstr_to_string(context, js_string));
+        }
+        let mut length = 0;
+        let chars = JS_GetTwoByteStringCharsAndLength(context, ptr::null(), js_string, &mut length);
+        if chars.is_null() {
+            return Err("could not get the chars of the String");
+        }
+        String::from_utf16(slice::from_raw_parts(chars, length))
+            .map_err(|_| "String contained an unpaired surrogate")
+    }
+}
+
+/// convert a JSString to a rust string, unpaired surrogates are replaced by U+FFFD
 pub fn es_jsstring_to_string(
     context: *mut JSContext,
     js_string: *mut mozjs::jsapi::JSString,
@@ -244,6 +590,37 @@ pub fn es_jsstring_to_string(
     unsafe { jsstr_to_string(context, js_string) }
 }
 
+/// get the length of a JSString in UTF-16 code units, this is the same as the length property in script
+pub fn es_jsstring_length(js_string: *mut JSString) -> usize {
+    unsafe { JS_GetStringLength(js_string) }
+}
+
+/// convert a part of a JSString to a rust string without converting the whole JSString
+/// start and end are indexes in UTF-16 code units (like String.prototype.substring in script) and are clamped to the length of the string
+/// this fails if the substring could not be created (e.g. when out of memory), the pending exception is then cleared and returned as error
+pub fn es_jsstring_substring(
+    context: *mut JSContext,
+    js_string: *mut JSString,
+    start: usize,
+    end: usize,
+) -> Result<String, EsErrorInfo> {
+    let len = es_jsstring_length(js_string);
+    let end = std::cmp::min(end, len);
+    let start = std::cmp::min(start, end);
+
+    rooted!(in (context) let str_root = js_string);
+    rooted!(in (context) let sub_root = unsafe {
+        JS_NewDependentString(context, str_root.handle().into(), start, end - start)
+    });
+    if sub_root.get().is_null() {
+        return Err(get_pending_exception_or_generic_err(
+            context,
+            "could not create substring",
+        ));
+    }
+    Ok(es_jsstring_to_string(context, *sub_root))
+}
+
 // convert a PropertyKey or JSID to String
 pub fn es_jsid_to_string(context: *mut JSContext, id: mozjs::jsapi::HandleId) -> String {
     assert!(unsafe { RUST_JSID_IS_STRING(id) });
@@ -251,6 +628,26 @@ pub fn es_jsid_to_string(context: *mut JSContext, id: mozjs::jsapi::HandleId) ->
     es_jsstring_to_string(context, *id_str)
 }
 
+/// convert a str to a PropertyKey or JSID, the string is atomized and pinned so it is best used for names that are used often
+/// this fails if the string could not be atomized (e.g. when out of memory)
+pub fn es_str_to_jsid(
+    context: *mut JSContext,
+    s: &str,
+    ret_val: mozjs::rust::MutableHandleId,
+) -> Result<(), EsErrorInfo> {
+    let s_nt = format!("{}\0", s);
+    rooted!(in(context) let str_root = unsafe { JS_AtomizeAndPinString(context, s_nt.as_ptr() as *const libc::c_char) });
+    if str_root.is_null()
+        || !unsafe { JS_StringToId(context, str_root.handle().into(), ret_val.into()) }
+    {
+        return Err(get_pending_exception_or_generic_err(
+            context,
+            "could not convert str to jsid",
+        ));
+    }
+    Ok(())
+}
+
 /// call the garbage collector
 pub fn gc(context: *mut JSContext) {
     unsafe {
@@ -324,6 +721,55 @@ mod tests {
         assert_eq!(res.as_str(), "i am eval");
     }
 
+    #[test]
+    fn test_eval_completion_value() {
+        let rt = init_test_runtime();
+        // None if the completion value is undefined
+        let eval_i32 = |code: &str| {
+            let esvf = rt
+                .eval_sync(code, "test_eval_completion_value.es")
+                .ok()
+                .expect("script failed");
+            if esvf.is_undefined() {
+                None
+            } else {
+                Some(esvf.get_i32())
+            }
+        };
+
+        // trailing expression
+        assert_eq!(eval_i32("let test_cv_a = 1; test_cv_a + 1"), Some(2));
+        assert_eq!(eval_i32("let test_cv_b = 1; test_cv_b + 1;"), Some(2));
+        // trailing declaration
+        assert_eq!(
+            eval_i32("let test_cv_c = 1; test_cv_c + 1; let test_cv_d = 3;"),
+            Some(2)
+        );
+        assert_eq!(eval_i32("1; function test_cv_f() {}"), Some(1));
+        assert_eq!(eval_i32("let test_cv_e = 1;"), None);
+        // empty script
+        assert_eq!(eval_i32(""), None);
+        assert_eq!(eval_i32("// just a comment"), None);
+
+        // a reused handle does not keep its previous value
+        let reset = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in(cx) let mut rval = mozjs::jsval::Int32Value(123));
+                jsapi_utils::eval(
+                    rt,
+                    global,
+                    "",
+                    "test_eval_completion_value2.es",
+                    rval.handle_mut(),
+                )
+                .ok()
+                .expect("script failed");
+                rval.is_undefined()
+            })
+        });
+        assert!(reset);
+    }
+
     #[test]
     fn test_report_exception() {
         use log::trace;
@@ -362,4 +808,170 @@ mod tests {
 
         assert_eq!(res, "quibus is not defined");
     }
+
+    #[test]
+    fn test_report_exception_keeps_pending() {
+        let rt = init_test_runtime();
+        let res = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                jsapi_utils::report_exception2(cx, "root cause".to_string());
+                jsapi_utils::report_exception2(cx, "getter failed".to_string());
+                get_pending_exception(cx).expect("no pending exception")
+            })
+        });
+
+        assert_eq!(res.message, "getter failed, caused by: root cause");
+    }
+
+    #[test]
+    fn test_peek_and_take_pending_exception() {
+        let rt = init_test_runtime();
+        let res = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                jsapi_utils::report_exception2(cx, "peek me".to_string());
+                let peeked = jsapi_utils::peek_pending_exception(cx).expect("no pending exception");
+                let peeked_again =
+                    jsapi_utils::peek_pending_exception(cx).expect("exception was cleared");
+                let taken = jsapi_utils::take_pending_exception(cx).expect("exception was cleared");
+                let after_take = jsapi_utils::peek_pending_exception(cx);
+                (
+                    peeked.message,
+                    peeked_again.message,
+                    taken.message,
+                    after_take.is_none(),
+                )
+            })
+        });
+
+        assert_eq!(res.0, "peek me");
+        assert_eq!(res.1, "peek me");
+        assert_eq!(res.2, "peek me");
+        assert!(res.3);
+    }
+
+    #[test]
+    fn test_peek_pending_exception_thrown_object() {
+        let rt = init_test_runtime();
+        let res = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in(cx) let mut thrown_root = UndefinedValue());
+                jsapi_utils::eval(
+                    rt,
+                    global,
+                    "({lineNumber: 12, get fileName(){throw Error('no file');}});",
+                    "test_peek_pending_exception_thrown_object.es",
+                    thrown_root.handle_mut(),
+                )
+                .ok()
+                .expect("script failed");
+                unsafe {
+                    mozjs::jsapi::JS_SetPendingException(
+                        cx,
+                        thrown_root.handle().into(),
+                        mozjs::jsapi::ExceptionStackBehavior::DoNotCapture,
+                    )
+                };
+                let peeked = jsapi_utils::peek_pending_exception(cx).expect("no pending exception");
+                let taken = jsapi_utils::take_pending_exception(cx).expect("exception was cleared");
+                (
+                    peeked.message,
+                    peeked.filename,
+                    peeked.lineno,
+                    taken.lineno,
+                    jsapi_utils::peek_pending_exception(cx).is_none(),
+                )
+            })
+        });
+
+        // a throwing getter or missing message does not fail peeking the exception
+        assert_eq!(res.0, "");
+        assert_eq!(res.1, "");
+        assert_eq!(res.2, 12);
+        assert_eq!(res.3, 12);
+        assert!(res.4);
+    }
+
+    #[test]
+    fn test_es_value_to_str_strict() {
+        let rt = init_test_runtime();
+        let res = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in(cx) let mut rval = UndefinedValue());
+                jsapi_utils::eval(
+                    rt,
+                    global,
+                    "'a\\uD800b';",
+                    "test_es_value_to_str_strict.es",
+                    rval.handle_mut(),
+                )
+                .ok()
+                .expect("script failed");
+                (
+                    jsapi_utils::es_value_to_str_lossy(cx, *rval).ok().unwrap(),
+                    jsapi_utils::es_value_to_str_strict(cx, *rval).is_err(),
+                )
+            })
+        });
+
+        assert_eq!(res.0, "a\u{FFFD}b");
+        assert!(res.1);
+    }
+
+    #[test]
+    fn test_es_value_to_display_str() {
+        let rt = init_test_runtime();
+        let res = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                let mut ret = vec![];
+                for code in &[
+                    "Symbol('my_desc');",
+                    "Symbol();",
+                    "12345678901234567890123n;",
+                    "({toString: function(){throw Error('no');}});",
+                    "[1, 'a'];",
+                ] {
+                    rooted!(in(cx) let mut rval = UndefinedValue());
+                    jsapi_utils::eval(
+                        rt,
+                        global,
+                        code,
+                        "test_es_value_to_display_str.es",
+                        rval.handle_mut(),
+                    )
+                    .ok()
+                    .expect("script failed");
+                    ret.push(jsapi_utils::es_value_to_display_str(cx, *rval));
+                    assert!(get_pending_exception(cx).is_none());
+                }
+                ret
+            })
+        });
+
+        assert_eq!(
+            res,
+            vec![
+                "Symbol(my_desc)",
+                "Symbol()",
+                "12345678901234567890123",
+                "[object]",
+                "1,a"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_es_str_to_jsid() {
+        log::info!("test: test_es_str_to_jsid");
+        let rt = init_test_runtime();
+        let res = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                rooted!(in(cx) let mut id_root = mozjs::jsapi::PropertyKey::default());
+                jsapi_utils::es_str_to_jsid(cx, "test_es_str_to_jsid", id_root.handle_mut())
+                    .ok()
+                    .expect("could not convert str to jsid");
+                jsapi_utils::es_jsid_to_string(cx, id_root.handle().into())
+            })
+        });
+        assert_eq!(res, "test_es_str_to_jsid");
+    }
 }
@@ -1,11 +1,12 @@
 use crate::jsapi_utils::{
-    get_pending_exception, get_pending_exception_or_generic_err, EsErrorInfo,
+    es_jsstring_to_string, get_pending_exception, get_pending_exception_or_generic_err, EsErrorInfo,
 };
 use log::trace;
 use mozjs::conversions::{
     ConversionBehavior, ConversionResult, FromJSValConvertible, ToJSValConvertible,
 };
 use mozjs::glue::int_to_jsid;
+use mozjs::glue::{RUST_JSID_IS_STRING, RUST_JSID_TO_STRING};
 use mozjs::jsapi::GetArrayLength;
 use mozjs::jsapi::IsArray;
 use mozjs::jsapi::JSContext;
@@ -14,8 +15,10 @@ use mozjs::jsapi::JS_GetPropertyById;
 use mozjs::jsapi::JS_SetElement;
 use mozjs::jsapi::NewArrayObject;
 use mozjs::jsapi::JS::HandleValueArray;
-use mozjs::jsval::JSVal;
-use mozjs::rust::{HandleObject, HandleValue, MutableHandleObject, MutableHandleValue};
+use mozjs::jsapi::JSITER_OWNONLY;
+use mozjs::jsval::{JSVal, UndefinedValue};
+use mozjs::rust::jsapi_wrapped::GetPropertyKeys;
+use mozjs::rust::{HandleObject, HandleValue, IdVector, MutableHandleObject, MutableHandleValue};
 
 /// convert an Array to a Vec<T>, should work for all which impl the FromJSValConvertible trait like:
 /// bool
@@ -270,6 +273,26 @@ pub fn get_array_element(
     Ok(())
 }
 
+/// read all elements of an Array as numbers in one pass, returns None if an element is not a number (or a hole)
+/// this is faster than converting every element separately for large arrays of numbers
+pub fn get_number_array(
+    context: *mut JSContext,
+    arr_obj: HandleObject,
+) -> Result<Option<Vec<f64>>, EsErrorInfo> {
+    let arr_len = get_array_length(context, arr_obj)?;
+    let mut numbers = Vec::with_capacity(arr_len as usize);
+    // a single root is reused for all elements
+    rooted!(in (context) let mut element_root = UndefinedValue());
+    for idx in 0..arr_len {
+        get_array_element(context, arr_obj, idx, element_root.handle_mut())?;
+        if !element_root.is_number() {
+            return Ok(None);
+        }
+        numbers.push(element_root.to_number());
+    }
+    Ok(Some(numbers))
+}
+
 /// create a new array obj
 pub fn new_array(context: *mut JSContext, ret_val: MutableHandleObject) {
     let arguments_value_array = HandleValueArray::new();
@@ -286,6 +309,37 @@ pub fn new_array2(context: *mut JSContext, items: Vec<JSVal>, ret_val: MutableHa
     ret_val.set(res);
 }
 
+/// get the names of the own enumerable properties of an array which are not an index, e.g. for `let a = [1, 2]; a.meta = 'x';` this returns vec!["meta"]
+/// this fails when the keys can not be enumerated (e.g. the ownKeys trap of a Proxy throws), the pending exception is then cleared and returned as error
+pub fn get_array_named_prop_names(
+    context: *mut JSContext,
+    arr_obj: HandleObject,
+) -> Result<Vec<String>, EsErrorInfo> {
+    let mut ids = unsafe { IdVector::new(context) };
+
+    if !unsafe { GetPropertyKeys(context, arr_obj, JSITER_OWNONLY, ids.handle_mut()) } {
+        return Err(get_pending_exception_or_generic_err(
+            context,
+            "could not get the property keys of the array",
+        ));
+    }
+
+    let mut ret: Vec<String> = vec![];
+
+    for x in 0..ids.len() {
+        rooted!(in(context) let id = ids[x]);
+        // indexes are int ids, only very large indexes are string ids
+        if unsafe { RUST_JSID_IS_STRING(id.handle().into()) } {
+            rooted!(in(context) let id_str = unsafe{RUST_JSID_TO_STRING(id.handle().into())});
+            let prop_name = es_jsstring_to_string(context, *id_str);
+            if prop_name.parse::<u32>().is_err() {
+                ret.push(prop_name);
+            }
+        }
+    }
+    Ok(ret)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::jsapi_utils::arrays::{
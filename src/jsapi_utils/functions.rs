@@ -30,6 +30,7 @@ use mozjs::rust::{
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ptr;
+use std::rc::Rc;
 
 /// compile a function
 pub fn compile_function(
@@ -131,12 +132,12 @@ pub fn call_function_name2(
         Err(err)
     } else {
         trace!("call_function_name2 -> unknown err");
-        Err(EsErrorInfo {
-            message: "unknown error".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        })
+        Err(EsErrorInfo::new(
+            "unknown error".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
     }
 }
 
@@ -234,12 +235,12 @@ pub fn call_function2(
     } else if let Some(err) = get_pending_exception(context) {
         Err(err)
     } else {
-        Err(EsErrorInfo {
-            message: "unknown error".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        })
+        Err(EsErrorInfo::new(
+            "unknown error".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
     }
 }
 
@@ -264,12 +265,63 @@ pub fn call_function_value2(
     } else if let Some(err) = get_pending_exception(context) {
         Err(err)
     } else {
-        Err(EsErrorInfo {
-            message: "unknown error".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        })
+        Err(EsErrorInfo::new(
+            "unknown error".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
+    }
+}
+
+/// call a function value with an explicit this value and a rooted arguments array, like Reflect.apply() does
+pub fn call_function_value_with_this(
+    context: *mut JSContext,
+    this_val: HandleValue,
+    function_val: HandleValue,
+    args: HandleValueArray,
+    ret_val: MutableHandleValue,
+) -> Result<(), EsErrorInfo> {
+    if unsafe {
+        mozjs::jsapi::Call(
+            context,
+            this_val.into(),
+            function_val.into(),
+            &args,
+            ret_val.into(),
+        )
+    } {
+        Ok(())
+    } else if let Some(err) = get_pending_exception(context) {
+        Err(err)
+    } else {
+        Err(EsErrorInfo::new(
+            "unknown error".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
+    }
+}
+
+/// construct a new object by calling a constructor function value with new and a rooted arguments array
+pub fn construct_function_value(
+    context: *mut JSContext,
+    constructor_val: HandleValue,
+    args: HandleValueArray,
+    ret_val: MutableHandleObject,
+) -> Result<(), EsErrorInfo> {
+    if unsafe { mozjs::jsapi::Construct1(context, constructor_val.into(), &args, ret_val.into()) } {
+        Ok(())
+    } else if let Some(err) = get_pending_exception(context) {
+        Err(err)
+    } else {
+        Err(EsErrorInfo::new(
+            "unknown error".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
     }
 }
 
@@ -340,12 +392,12 @@ pub fn call_namespace_function_name2(
         let val: JSVal = *new_subscope_root.handle();
 
         if !val.is_object() {
-            return Err(EsErrorInfo {
-                message: format!("{} was not an object.", obj_name),
-                column: 0,
-                lineno: 0,
-                filename: "".to_string(),
-            });
+            return Err(EsErrorInfo::new(
+                format!("{} was not an object.", obj_name),
+                "".to_string(),
+                0,
+                0,
+            ));
         }
 
         sub_scope = val.to_object();
@@ -380,23 +432,56 @@ pub fn define_native_function(
     obj: HandleObject,
     function_name: &str,
     native_function: JSNative,
+) -> *mut JSFunction {
+    define_native_function_with(cx, obj, function_name, 1, 0, native_function)
+
+    //https://developer.mozilla.org/en-US/docs/Mozilla/Projects/SpiderMonkey/JSAPI_reference/JS_DefineFunction
+}
+
+/// define a new native function on an object with a given arity and property attributes
+/// nargs is reflected by the length property of the function, attrs are the JSPROP_* flags of the property (e.g. JSPROP_READONLY)
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::jsapi_utils::functions::define_native_function_with;
+/// use mozjs::jsapi::{CallArgs, JSContext, JSPROP_READONLY};
+/// use mozjs::jsval::Int32Value;
+///
+/// unsafe extern "C" fn my_native(_cx: *mut JSContext, argc: u32, vp: *mut mozjs::jsapi::Value) -> bool {
+///     let args = CallArgs::from_vp(vp, argc);
+///     args.rval().set(Int32Value(argc as i32));
+///     true
+/// }
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.do_in_es_event_queue_sync(|sm_rt| {
+///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+///         define_native_function_with(cx, global, "myNative", 3, JSPROP_READONLY as u32, Some(my_native));
+///     });
+/// });
+/// let esvf = rt.eval_sync("myNative.length;", "test_define_native_function_with.es").ok().expect("script failed");
+/// assert_eq!(esvf.get_i32(), 3);
+/// ```
+pub fn define_native_function_with(
+    cx: *mut JSContext,
+    obj: HandleObject,
+    function_name: &str,
+    nargs: u32,
+    attrs: u32,
+    native_function: JSNative,
 ) -> *mut JSFunction {
     let n = format!("{}\0", function_name);
 
-    let ret: *mut JSFunction = unsafe {
+    unsafe {
         JS_DefineFunction(
             cx,
             obj.into(),
             n.as_ptr() as *const libc::c_char,
             native_function,
-            1,
-            0,
+            nargs,
+            attrs,
         )
-    };
-
-    ret
-
-    //https://developer.mozilla.org/en-US/docs/Mozilla/Projects/SpiderMonkey/JSAPI_reference/JS_DefineFunction
+    }
 }
 
 /// define a new native function on an object
@@ -477,16 +562,19 @@ static CALLBACK_CLASS_OPS: JSClassOps = JSClassOps {
 };
 
 unsafe extern "C" fn call_callback(cx: *mut JSContext, argc: u32, vp: *mut JSVal) -> bool {
-    let args = CallArgs::from_vp(vp, argc);
-    let callback_obj: *mut JSObject = args.callee();
-    let callback_id = callback_obj as usize;
-    trace!("call callback id: {}", callback_id);
-
-    CALLBACKS.with(|callbacks_rc| {
-        let callbacks = &mut *callbacks_rc.borrow_mut();
-        if callbacks.contains_key(&callback_id) {
+    crate::jsapi_utils::catch_native_panic(cx, "call_callback", || {
+        let args = CallArgs::from_vp(vp, argc);
+        let callback_obj: *mut JSObject = args.callee();
+        let callback_id = callback_obj as usize;
+        trace!("call callback id: {}", callback_id);
+
+        // clone the callback so the map is not borrowed while it runs, it may create or finalize other callbacks
+        let callback_opt = CALLBACKS.with(|callbacks_rc| {
+            let callbacks = &*callbacks_rc.borrow();
+            callbacks.get(&callback_id).cloned()
+        });
+        if let Some(callback) = callback_opt {
             trace!("found callback");
-            let callback = callbacks.get(&callback_id).unwrap();
             let mut args_vec = vec![];
             for x in 0..args.argc_ {
                 args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
@@ -542,7 +630,7 @@ pub type Callback =
     dyn Fn(*mut JSContext, Vec<HandleValue>, MutableHandleValue) -> Result<(), String> + 'static;
 
 thread_local! {
-    static CALLBACKS: RefCell<HashMap<usize, Box<Callback>>> = RefCell::new(HashMap::new());
+    static CALLBACKS: RefCell<HashMap<usize, Rc<Callback>>> = RefCell::new(HashMap::new());
 }
 
 /// create a new callback function based on a closure
@@ -606,7 +694,7 @@ where
     CALLBACKS.with(move |callbacks_rc| {
         let callbacks = &mut *callbacks_rc.borrow_mut();
         trace!("inserting callback with id {}", callback_id);
-        callbacks.insert(callback_id, Box::new(callback));
+        callbacks.insert(callback_id, Rc::new(callback));
     });
 
     true
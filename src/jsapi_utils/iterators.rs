@@ -0,0 +1,339 @@
+use crate::esvaluefacade::{EsValueConvertible, EsValueFacade};
+use crate::jsapi_utils::functions::new_callback;
+use crate::jsapi_utils::objects::{new_object, set_es_obj_prop_value, NULL_JSOBJECT};
+use mozjs::glue::RUST_SYMBOL_TO_JSID;
+use mozjs::jsapi::JSContext;
+use mozjs::jsapi::JS_DefinePropertyById2;
+use mozjs::jsapi::JS::{GetWellKnownSymbol, SymbolCode};
+use mozjs::jsval::ObjectValue;
+use mozjs::rust::{HandleObject, MutableHandleValue};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+/// the producer of the values of an iterator, it is called for every call to next()
+/// return Some(val) for the next value or None when done
+pub type IteratorNext = Box<dyn FnMut() -> Option<EsValueFacade> + 'static>;
+
+/// the producer of the values of an async iterator, it is called for every call to next()
+/// return Ok(Some(val)) for the next value, Ok(None) when done or Err to reject the Promise returned by next()
+pub type AsyncIteratorNext =
+    Box<dyn FnMut() -> Result<Option<EsValueFacade>, String> + Send + 'static>;
+
+/// create a new object which implements the async iterator protocol so script can use it in a for await loop
+/// every call to next() runs the producer in a helper thread and returns a Promise which resolves to {value, done}
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+/// use spidermonkey_runtime::jsapi_utils::iterators::new_async_iterator;
+/// use spidermonkey_runtime::jsapi_utils::objects::set_es_obj_prop_value;
+/// use mozjs::jsval::UndefinedValue;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.do_in_es_event_queue_sync(|sm_rt| {
+///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+///         let mut x = 0;
+///         rooted!(in (cx) let mut iter_val = UndefinedValue());
+///         new_async_iterator(cx, Box::new(move || {
+///             x += 1;
+///             Ok(if x <= 3 { Some(EsValueFacade::new_i32(x)) } else { None })
+///         }), iter_val.handle_mut());
+///         set_es_obj_prop_value(cx, global, "myIter", iter_val.handle());
+///     });
+/// });
+/// rt.eval_sync("(async function(){for await (const x of myIter) {console.log(x);}})();", "new_async_iterator.es")
+///     .ok().expect("script failed");
+/// ```
+pub fn new_async_iterator(cx: *mut JSContext, next: AsyncIteratorNext, rval: MutableHandleValue) {
+    new_async_iterator2(cx, Arc::new(Mutex::new(next)), rval)
+}
+
+/// create a new object which implements the iterator protocol so script can use it in a for of loop
+/// the producer is called lazily for every call to next(), so values are only produced when script asks for them
+/// the producer is dropped when the iterator is garbage collected
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+/// use spidermonkey_runtime::jsapi_utils::iterators::new_iterator;
+/// use spidermonkey_runtime::jsapi_utils::objects::set_es_obj_prop_value;
+/// use mozjs::jsval::UndefinedValue;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.do_in_es_event_queue_sync(|sm_rt| {
+///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+///         let mut x = 0;
+///         rooted!(in (cx) let mut iter_val = UndefinedValue());
+///         new_iterator(cx, Box::new(move || {
+///             x += 1;
+///             if x <= 3 { Some(EsValueFacade::new_i32(x)) } else { None }
+///         }), iter_val.handle_mut());
+///         set_es_obj_prop_value(cx, global, "myIter", iter_val.handle());
+///     });
+/// });
+/// rt.eval_sync("for (const x of myIter) {console.log(x);}", "new_iterator.es")
+///     .ok().expect("script failed");
+/// ```
+pub fn new_iterator(cx: *mut JSContext, next: IteratorNext, rval: MutableHandleValue) {
+    new_iterator2(cx, Rc::new(RefCell::new(next)), rval)
+}
+
+fn new_iterator2(
+    cx: *mut JSContext,
+    next: Rc<RefCell<IteratorNext>>,
+    mut rval: MutableHandleValue,
+) {
+    rooted!(in (cx) let mut iter_obj = NULL_JSOBJECT);
+    new_object(cx, iter_obj.handle_mut());
+
+    let next_producer = next.clone();
+    rooted!(in (cx) let mut next_func = NULL_JSOBJECT);
+    new_callback(cx, next_func.handle_mut(), move |cx, _args, rval| {
+        let next_res = {
+            let producer = &mut *next_producer.borrow_mut();
+            producer()
+        };
+        let mut props = HashMap::new();
+        match next_res {
+            Some(val) => {
+                props.insert("value".to_string(), val);
+                props.insert("done".to_string(), EsValueFacade::new_bool(false));
+            }
+            None => {
+                props.insert("value".to_string(), EsValueFacade::undefined());
+                props.insert("done".to_string(), EsValueFacade::new_bool(true));
+            }
+        }
+        EsValueFacade::new_obj(props).to_es_value(cx, rval);
+        Ok(())
+    });
+    rooted!(in (cx) let next_func_val = ObjectValue(*next_func));
+    set_es_obj_prop_value(cx, iter_obj.handle(), "next", next_func_val.handle());
+
+    // [Symbol.iterator]() returns an iterator which shares the producer of this one
+    rooted!(in (cx) let mut sym_func = NULL_JSOBJECT);
+    new_callback(cx, sym_func.handle_mut(), move |cx, _args, rval| {
+        new_iterator2(cx, next.clone(), rval);
+        Ok(())
+    });
+    rooted!(in (cx) let sym_func_val = ObjectValue(*sym_func));
+    define_well_known_symbol_prop(
+        cx,
+        iter_obj.handle(),
+        SymbolCode::iterator,
+        sym_func_val.handle(),
+    );
+
+    rval.set(ObjectValue(*iter_obj));
+}
+
+/// create a new async iterator which yields the items of a rust stream, like the Receiver of a channel
+/// the stream is read in a helper thread so it may block while waiting for the next item, the iterator is done when the stream ends
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+/// use spidermonkey_runtime::jsapi_utils::iterators::new_async_iterator_from_stream;
+/// use spidermonkey_runtime::jsapi_utils::objects::set_es_obj_prop_value;
+/// use mozjs::jsval::UndefinedValue;
+/// use std::sync::mpsc::channel;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// let (tx, rx) = channel();
+/// rt.do_in_es_event_queue_sync(move |sm_rt| {
+///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+///         rooted!(in (cx) let mut iter_val = UndefinedValue());
+///         new_async_iterator_from_stream(cx, rx.into_iter(), iter_val.handle_mut());
+///         set_es_obj_prop_value(cx, global, "events", iter_val.handle());
+///     });
+/// });
+/// rt.eval_sync("(async function(){for await (const evt of events) {console.log(evt);}})();", "new_async_iterator_from_stream.es")
+///     .ok().expect("script failed");
+/// tx.send(EsValueFacade::new_str("hello".to_string())).expect("send failed");
+/// // dropping the sender ends the for await loop
+/// drop(tx);
+/// ```
+pub fn new_async_iterator_from_stream<S>(cx: *mut JSContext, stream: S, rval: MutableHandleValue)
+where
+    S: Iterator<Item = EsValueFacade> + Send + 'static,
+{
+    let mut stream = stream;
+    new_async_iterator(cx, Box::new(move || Ok(stream.next())), rval)
+}
+
+fn new_async_iterator2(
+    cx: *mut JSContext,
+    next: Arc<Mutex<AsyncIteratorNext>>,
+    mut rval: MutableHandleValue,
+) {
+    rooted!(in (cx) let mut iter_obj = NULL_JSOBJECT);
+    new_object(cx, iter_obj.handle_mut());
+
+    let next_producer = next.clone();
+    rooted!(in (cx) let mut next_func = NULL_JSOBJECT);
+    new_callback(cx, next_func.handle_mut(), move |cx, _args, rval| {
+        let next_producer = next_producer.clone();
+        let prom_esvf = EsValueFacade::new_promise(move || {
+            let next_res = {
+                let producer = &mut *next_producer.lock().unwrap();
+                producer()?
+            };
+            let mut props = HashMap::new();
+            match next_res {
+                Some(val) => {
+                    props.insert("value".to_string(), val);
+                    props.insert("done".to_string(), EsValueFacade::new_bool(false));
+                }
+                None => {
+                    props.insert("value".to_string(), EsValueFacade::undefined());
+                    props.insert("done".to_string(), EsValueFacade::new_bool(true));
+                }
+            }
+            Ok(EsValueFacade::new_obj(props))
+        });
+        prom_esvf.to_es_value(cx, rval);
+        Ok(())
+    });
+    rooted!(in (cx) let next_func_val = ObjectValue(*next_func));
+    set_es_obj_prop_value(cx, iter_obj.handle(), "next", next_func_val.handle());
+
+    // [Symbol.asyncIterator]() returns an iterator which shares the producer of this one
+    rooted!(in (cx) let mut sym_func = NULL_JSOBJECT);
+    new_callback(cx, sym_func.handle_mut(), move |cx, _args, rval| {
+        new_async_iterator2(cx, next.clone(), rval);
+        Ok(())
+    });
+    rooted!(in (cx) let sym_func_val = ObjectValue(*sym_func));
+    define_well_known_symbol_prop(
+        cx,
+        iter_obj.handle(),
+        SymbolCode::asyncIterator,
+        sym_func_val.handle(),
+    );
+
+    rval.set(ObjectValue(*iter_obj));
+}
+
+/// get the id of a well known Symbol like Symbol.asyncIterator so it can be used as a property key
+pub fn get_well_known_symbol_id(
+    cx: *mut JSContext,
+    symbol_code: SymbolCode,
+    rval: mozjs::rust::MutableHandleId,
+) {
+    unsafe {
+        let symbol = GetWellKnownSymbol(cx, symbol_code);
+        RUST_SYMBOL_TO_JSID(symbol, rval.into());
+    }
+}
+
+/// define a property of an object keyed by a well known Symbol like Symbol.asyncIterator
+pub fn define_well_known_symbol_prop(
+    cx: *mut JSContext,
+    obj: HandleObject,
+    symbol_code: SymbolCode,
+    prop_val: mozjs::rust::HandleValue,
+) {
+    rooted!(in (cx) let mut id_root = mozjs::jsapi::PropertyKey::default());
+    get_well_known_symbol_id(cx, symbol_code, id_root.handle_mut());
+    unsafe {
+        JS_DefinePropertyById2(
+            cx,
+            obj.into(),
+            id_root.handle().into(),
+            prop_val.into(),
+            mozjs::jsapi::JSPROP_ENUMERATE as u32,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::esruntime::tests::init_test_runtime;
+    use crate::esvaluefacade::EsValueFacade;
+    use crate::jsapi_utils::iterators::{new_async_iterator, new_async_iterator_from_stream};
+    use crate::jsapi_utils::objects::set_es_obj_prop_value;
+    use mozjs::jsval::UndefinedValue;
+    use std::time::Duration;
+
+    #[test]
+    fn test_async_iterator() {
+        log::info!("test: test_async_iterator");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let mut x = 0;
+                rooted!(in (cx) let mut iter_val = UndefinedValue());
+                new_async_iterator(
+                    cx,
+                    Box::new(move || {
+                        x += 1;
+                        Ok(if x <= 3 {
+                            Some(EsValueFacade::new_i32(x))
+                        } else {
+                            None
+                        })
+                    }),
+                    iter_val.handle_mut(),
+                );
+                set_es_obj_prop_value(cx, global, "test_async_iter", iter_val.handle());
+            });
+        });
+
+        let prom_esvf = rt
+            .eval_sync(
+                "(async function(){let r = []; for await (const x of test_async_iter) {r.push(x);} return r.join(',');})();",
+                "test_async_iterator.es",
+            )
+            .ok()
+            .expect("script failed");
+        let res = prom_esvf
+            .get_promise_result_blocking(Duration::from_secs(5))
+            .ok()
+            .expect("promise timed out")
+            .ok()
+            .expect("promise was rejected");
+        assert_eq!(res.get_string(), "1,2,3");
+    }
+
+    #[test]
+    fn test_async_iterator_from_stream() {
+        log::info!("test: test_async_iterator_from_stream");
+        let rt = init_test_runtime();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        rt.do_in_es_event_queue_sync(move |sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                rooted!(in (cx) let mut iter_val = UndefinedValue());
+                new_async_iterator_from_stream(cx, rx.into_iter(), iter_val.handle_mut());
+                set_es_obj_prop_value(cx, global, "test_stream_iter", iter_val.handle());
+            });
+        });
+
+        let prom_esvf = rt
+            .eval_sync(
+                "(async function(){let r = []; for await (const x of test_stream_iter) {r.push(x);} return r.join(',');})();",
+                "test_async_iterator_from_stream.es",
+            )
+            .ok()
+            .expect("script failed");
+
+        std::thread::spawn(move || {
+            for x in &["a", "b", "c"] {
+                std::thread::sleep(Duration::from_millis(10));
+                tx.send(EsValueFacade::new_str(x.to_string()))
+                    .expect("send failed");
+            }
+        });
+
+        let res = prom_esvf
+            .get_promise_result_blocking(Duration::from_secs(5))
+            .ok()
+            .expect("promise timed out")
+            .ok()
+            .expect("promise was rejected");
+        assert_eq!(res.get_string(), "a,b,c");
+    }
+}
@@ -1,4 +1,5 @@
 use crate::esruntime::EsRuntime;
+use crate::esvaluefacade::EsValueFacade;
 use crate::jsapi_utils;
 use crate::jsapi_utils::objects::NULL_JSOBJECT;
 use crate::jsapi_utils::rooting::EsPersistentRooted;
@@ -51,6 +52,8 @@ pub fn compile_module(
     trace!("compile_module: {}", file_name);
     trace!("{}", src);
 
+    jsapi_utils::scripts::check_script_size(src, file_name)?;
+
     let src_vec: Vec<u16> = src.encode_utf16().collect();
     let options = unsafe { mozjs::rust::CompileOptionsWrapper::new(context, file_name, 1) };
     let mut source = transform_u16_to_source_text(&src_vec);
@@ -66,12 +69,12 @@ pub fn compile_module(
         if let Some(err) = get_pending_exception(context) {
             return Err(err);
         }
-        return Err(EsErrorInfo {
-            message: "CompileModule failed unknown".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        });
+        return Err(EsErrorInfo::new(
+            "CompileModule failed unknown".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ));
     }
 
     trace!("SetModulePrivate: {}", file_name);
@@ -97,12 +100,12 @@ pub fn compile_module(
         if let Some(err) = get_pending_exception(context) {
             return Err(err);
         }
-        return Err(EsErrorInfo {
-            message: "ModuleInstantiate failed unknown".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        });
+        return Err(EsErrorInfo::new(
+            "ModuleInstantiate failed unknown".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ));
     }
 
     trace!("ModuleEvaluate: {}", file_name);
@@ -118,12 +121,12 @@ pub fn compile_module(
         if let Some(err) = get_pending_exception(context) {
             return Err(err);
         }
-        return Err(EsErrorInfo {
-            message: "ModuleEvaluate failed unknown".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        });
+        return Err(EsErrorInfo::new(
+            "ModuleEvaluate failed unknown".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ));
     }
 
     Ok(compiled_module)
@@ -139,12 +142,30 @@ thread_local! {
 fn init_module_cache() -> LruCache<String, EsPersistentRooted> {
     let ct = SM_RT.with(|sm_rt_rc| {
         let sm_rt = &*sm_rt_rc.borrow();
-        sm_rt.clone_esrt_inner().module_cache_size
+        sm_rt.clone_esrt_inner().config.module_cache_size
     });
 
     LruCache::new(ct)
 }
 
+/// resize the module cache of the current runtime, the least recently used modules are evicted when shrinking
+/// this needs to run in the worker thread of the event queue
+pub(crate) fn set_module_cache_size(size: usize) {
+    MODULE_CACHE.with(|cache_rc| {
+        let cache = &mut *cache_rc.borrow_mut();
+        cache.resize(size);
+    });
+}
+
+/// get the number of modules in the module cache of the current runtime
+#[cfg(test)]
+pub(crate) fn module_cache_len() -> usize {
+    MODULE_CACHE.with(|cache_rc| {
+        let cache = &*cache_rc.borrow();
+        cache.len()
+    })
+}
+
 fn get_path_from_module_private(cx: *mut JSContext, reference_private: RawHandleValue) -> String {
     if !reference_private.is_undefined() {
         rooted!(in (cx) let private_obj_root = reference_private.to_object_or_null());
@@ -168,166 +189,212 @@ unsafe extern "C" fn module_dynamic_import(
     specifier: RawHandle<*mut JSString>,
     promise: RawHandle<*mut JSObject>,
 ) -> bool {
-    // see sequence here in c
-    // https://github.com/mozilla/gecko-dev/blob/master/js/src/shell/ModuleLoader.cpp
+    crate::jsapi_utils::catch_native_panic(cx, "module_dynamic_import", || {
+        // see sequence here in c
+        // https://github.com/mozilla/gecko-dev/blob/master/js/src/shell/ModuleLoader.cpp
 
-    trace!("module_dynamic_import called");
+        trace!("module_dynamic_import called");
 
-    rooted!(in (cx) let mut closure_root = NULL_JSOBJECT);
-    jsapi_utils::objects::new_object(cx, closure_root.handle_mut());
+        rooted!(in (cx) let mut closure_root = NULL_JSOBJECT);
+        jsapi_utils::objects::new_object(cx, closure_root.handle_mut());
 
-    rooted!(in (cx) let promise_val_root = ObjectValue(*promise));
-    rooted!(in (cx) let specifier_val_root = StringValue(&**specifier));
-    jsapi_utils::objects::set_es_obj_prop_value(
-        cx,
-        closure_root.handle(),
-        "promise",
-        promise_val_root.handle(),
-    );
-    jsapi_utils::objects::set_es_obj_prop_value_raw(
-        cx,
-        closure_root.handle().into(),
-        "reference_private",
-        reference_private,
-    );
-    jsapi_utils::objects::set_es_obj_prop_value(
-        cx,
-        closure_root.handle(),
-        "specifier",
-        specifier_val_root.handle(),
-    );
+        rooted!(in (cx) let promise_val_root = ObjectValue(*promise));
+        rooted!(in (cx) let specifier_val_root = StringValue(&**specifier));
+        jsapi_utils::objects::set_es_obj_prop_value(
+            cx,
+            closure_root.handle(),
+            "promise",
+            promise_val_root.handle(),
+        );
+        jsapi_utils::objects::set_es_obj_prop_value_raw(
+            cx,
+            closure_root.handle().into(),
+            "reference_private",
+            reference_private,
+        );
+        jsapi_utils::objects::set_es_obj_prop_value(
+            cx,
+            closure_root.handle(),
+            "specifier",
+            specifier_val_root.handle(),
+        );
 
-    let file_name = jsapi_utils::es_jsstring_to_string(cx, *specifier);
-    let ref_path = get_path_from_module_private(cx, reference_private);
+        let file_name = jsapi_utils::es_jsstring_to_string(cx, *specifier);
+        let ref_path = get_path_from_module_private(cx, reference_private);
 
-    trace!(
-        "module_dynamic_import called: {} from ref: {}",
-        file_name,
-        ref_path
-    );
+        trace!(
+            "module_dynamic_import called: {} from ref: {}",
+            file_name,
+            ref_path
+        );
 
-    let closure_id = register_cached_object(cx, *closure_root);
-    let rt_arc = SmRuntime::clone_current_esrt_inner_arc();
+        let closure_id = register_cached_object(cx, *closure_root);
+        let rt_arc = SmRuntime::clone_current_esrt_inner_arc();
 
-    // todo if the module is already cache we could just run an async job via
-    // rt_arc.do_in_spidermonkey_runtime_thread
-    // instead of stepping into a different thread
+        let is_cached = MODULE_CACHE.with(|cache_rc| {
+            let cache = &*cache_rc.borrow();
+            cache.contains(&file_name)
+        });
 
-    let load_task = move || {
-        trace!(
-            "module_dynamic_import: {}, load_task running",
-            file_name.as_str()
-        );
-        // load mod code here (in helper thread)
-        let script: Option<Script> = if let Some(loader) = &rt_arc.module_source_loader {
+        // in memory modules are loaded here in the worker thread so we don't need a helper task
+        let sync_script: Option<Script> = if is_cached {
+            None
+        } else if let Some(loader) = &rt_arc.config.sync_module_source_loader {
             loader(file_name.as_str(), ref_path.as_str())
         } else {
             None
         };
 
-        trace!(
-            "module_dynamic_import: {}, load_task: loaded",
-            file_name.as_str()
-        );
+        if is_cached || sync_script.is_some() {
+            trace!(
+                "module_dynamic_import: {} was cached or loaded sync, finish in event queue",
+                file_name.as_str()
+            );
+            // the import hook should return before the promise is resolved so we still finish the import in a new job
+            rt_arc.do_in_es_event_queue(move |sm_rt| {
+                finish_dynamic_import(sm_rt, closure_id, file_name, sync_script);
+            });
+            return true;
+        }
 
-        rt_arc.do_in_es_event_queue(move |sm_rt| {
-            // compile module / get from cache here
-            // resolve or reject promise here (in event queue)
+        let load_task = move || {
             trace!(
-                "module_dynamic_import: {}, load_task: back in do_in_spidermonkey_runtime_thread",
+                "module_dynamic_import: {}, load_task running",
                 file_name.as_str()
             );
-            sm_rt.do_with_jsapi(|_rt, cx, _global| {
-                // check if was cached async
-                // todo replace with a bool
+            // load mod code here (in helper thread)
+            // a panicking loader should reject the import instead of killing the helper thread
+            let script: Option<Script> = if let Some(loader) = rt_arc.get_module_source_loader() {
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    loader(file_name.as_str(), ref_path.as_str())
+                }))
+                .unwrap_or_else(|payload| {
+                    log::error!(
+                        "module loader panicked for {}: {}",
+                        file_name,
+                        jsapi_utils::panic_message(&payload)
+                    );
+                    None
+                })
+            } else {
+                None
+            };
 
-                trace!(
-                    "module_dynamic_import: {}, load_task: back in do_in_spidermonkey_runtime_thread, check cache",
-                    file_name.as_str()
-                );
+            trace!(
+                "module_dynamic_import: {}, load_task: loaded",
+                file_name.as_str()
+            );
 
-                let is_cached = MODULE_CACHE.with(|cache_rc| {
-                    let cache = &*cache_rc.borrow();
-                    cache.contains(&file_name)
-                });
+            rt_arc.do_in_es_event_queue(move |sm_rt| {
+                finish_dynamic_import(sm_rt, closure_id, file_name, script);
+            });
+        };
+        EsRuntime::add_helper_task(load_task);
 
-                let closure_epr = crate::spidermonkeyruntimewrapper::remove_cached_object(closure_id);
-                rooted!(in (cx) let closure_root = closure_epr.get());
-                rooted!(in (cx) let mut promise_val_root = NullValue());
-                rooted!(in (cx) let mut specifier_val_root = NullValue());
-                rooted!(in (cx) let mut reference_private_val_root = NullValue());
-                jsapi_utils::objects::get_es_obj_prop_val(cx, closure_root.handle(), "promise", promise_val_root.handle_mut()).ok().expect("could not get promise prop from closure");
-                jsapi_utils::objects::get_es_obj_prop_val(cx, closure_root.handle(), "specifier", specifier_val_root.handle_mut()).ok().expect("could not get specifier prop from closure");
-                jsapi_utils::objects::get_es_obj_prop_val(cx, closure_root.handle(), "reference_private", reference_private_val_root.handle_mut()).ok().expect("could not get reference_private prop from closure");
-                rooted!(in (cx) let mut promise_root = promise_val_root.to_object());
-                rooted!(in (cx) let mut specifier_root = specifier_val_root.to_string());
+        true
+    })
+}
 
+/// compile a dynamically imported module (or get it from the cache) and resolve or reject the promise of the import
+/// this runs in the worker thread of the event queue
+fn finish_dynamic_import(
+    sm_rt: &SmRuntime,
+    closure_id: usize,
+    file_name: String,
+    script: Option<Script>,
+) {
+    // compile module / get from cache here
+    // resolve or reject promise here (in event queue)
+    trace!(
+        "module_dynamic_import: {}, load_task: back in do_in_spidermonkey_runtime_thread",
+        file_name.as_str()
+    );
+    sm_rt.do_with_jsapi(|_rt, cx, _global| {
+        // check if was cached async
+        // todo replace with a bool
 
-                if is_cached {
-                    // resolve promise
-                    trace!("dyn module {} was cached, finish import", file_name.as_str());
-                    FinishDynamicModuleImport_NoTLA(cx, DynamicImportStatus::Ok , reference_private_val_root.handle().into(), specifier_root.handle().into(), promise_root.handle().into());
+        trace!(
+            "module_dynamic_import: {}, load_task: back in do_in_spidermonkey_runtime_thread, check cache",
+            file_name.as_str()
+        );
 
-                } else if let Some(script_code) = script {
+        let is_cached = MODULE_CACHE.with(|cache_rc| {
+            let cache = &*cache_rc.borrow();
+            cache.contains(&file_name)
+        });
 
-                    trace!("dyn module {} was loaded, compile", file_name.as_str());
+        let closure_epr = crate::spidermonkeyruntimewrapper::remove_cached_object(closure_id);
+        rooted!(in (cx) let closure_root = closure_epr.get());
+        rooted!(in (cx) let mut promise_val_root = NullValue());
+        rooted!(in (cx) let mut specifier_val_root = NullValue());
+        rooted!(in (cx) let mut reference_private_val_root = NullValue());
+        jsapi_utils::objects::get_es_obj_prop_val(cx, closure_root.handle(), "promise", promise_val_root.handle_mut()).ok().expect("could not get promise prop from closure");
+        jsapi_utils::objects::get_es_obj_prop_val(cx, closure_root.handle(), "specifier", specifier_val_root.handle_mut()).ok().expect("could not get specifier prop from closure");
+        jsapi_utils::objects::get_es_obj_prop_val(cx, closure_root.handle(), "reference_private", reference_private_val_root.handle_mut()).ok().expect("could not get reference_private prop from closure");
+        rooted!(in (cx) let mut promise_root = promise_val_root.to_object());
+        rooted!(in (cx) let mut specifier_root = specifier_val_root.to_string());
 
-                    let compiled_mod_obj_res = compile_module(
-                        cx,
-                        script_code.get_code(),
-                        script_code.get_path(),
-                    );
 
-                    if let Ok(compiled_mod_obj) = compiled_mod_obj_res {
-                        MODULE_CACHE.with(|cache_rc| {
-                            let cache = &mut *cache_rc.borrow_mut();
-                            let mod_epr = EsPersistentRooted::new_from_obj(cx, compiled_mod_obj);
-                            cache.put(file_name.clone(), mod_epr);
-                        });
-
-                        trace!("dyn module {} was loaded, compiled and cached, finish", file_name.as_str());
-
-                        FinishDynamicModuleImport_NoTLA(cx, DynamicImportStatus::Ok, reference_private_val_root.handle().into(), specifier_root.handle().into(), promise_root.handle().into());
-
-                    } else {
-                        // reject promise
-
-                        trace!("dyn module {} was not compiled ok, rejecting promise", file_name.as_str());
-
-                        let err_str= format!("module failed to compile: {}", compiled_mod_obj_res.err().unwrap().err_msg());
-                        rooted!(in (cx) let mut prom_reject_val = UndefinedValue());
-                        jsapi_utils::new_es_value_from_str(cx, err_str.as_str(), prom_reject_val.handle_mut());
-
-                        trace!("rejecting dynamic module promise: failed {}", err_str);
-                        jsapi_utils::promises::reject_promise(
-                            cx,
-                            promise_root.handle(),
-                            prom_reject_val.handle(),
-                        )
-                            .ok()
-                            .expect("promise rejection failed / 1");
-                    }
-                } else {
-                    // reject promise
-                    let err_str= format!("module not found: {}", file_name);
-                    trace!("rejecting dynamic module promise: failed {}", err_str);
-                    rooted!(in (cx) let mut prom_reject_val = UndefinedValue());
-                    jsapi_utils::new_es_value_from_str(cx, err_str.as_str(), prom_reject_val.handle_mut());
-
-                    jsapi_utils::promises::reject_promise(
-                        cx,
-                        promise_root.handle(),
-                        prom_reject_val.handle(),
-                    )
-                        .ok()
-                        .expect("promise rejection failed / 2");
-                }
-            });
-        });
-    };
-    EsRuntime::add_helper_task(load_task);
+        if is_cached {
+            // resolve promise
+            trace!("dyn module {} was cached, finish import", file_name.as_str());
+            FinishDynamicModuleImport_NoTLA(cx, DynamicImportStatus::Ok , reference_private_val_root.handle().into(), specifier_root.handle().into(), promise_root.handle().into());
+
+        } else if let Some(script_code) = script {
 
-    true
+            trace!("dyn module {} was loaded, compile", file_name.as_str());
+
+            let compiled_mod_obj_res = compile_module(
+                cx,
+                script_code.get_code(),
+                script_code.get_path(),
+            );
+
+            if let Ok(compiled_mod_obj) = compiled_mod_obj_res {
+                MODULE_CACHE.with(|cache_rc| {
+                    let cache = &mut *cache_rc.borrow_mut();
+                    let mod_epr = EsPersistentRooted::new_from_obj(cx, compiled_mod_obj);
+                    cache.put(file_name.clone(), mod_epr);
+                });
+
+                trace!("dyn module {} was loaded, compiled and cached, finish", file_name.as_str());
+
+                FinishDynamicModuleImport_NoTLA(cx, DynamicImportStatus::Ok, reference_private_val_root.handle().into(), specifier_root.handle().into(), promise_root.handle().into());
+
+            } else {
+                // reject promise
+
+                trace!("dyn module {} was not compiled ok, rejecting promise", file_name.as_str());
+
+                let err_str= format!("module failed to compile: {}", compiled_mod_obj_res.err().unwrap().err_msg());
+                rooted!(in (cx) let mut prom_reject_val = UndefinedValue());
+                jsapi_utils::new_es_value_from_str(cx, err_str.as_str(), prom_reject_val.handle_mut());
+
+                trace!("rejecting dynamic module promise: failed {}", err_str);
+                jsapi_utils::promises::reject_promise(
+                    cx,
+                    promise_root.handle(),
+                    prom_reject_val.handle(),
+                )
+                    .ok()
+                    .expect("promise rejection failed / 1");
+            }
+        } else {
+            // reject promise
+            let err_str= format!("module not found: {}", file_name);
+            trace!("rejecting dynamic module promise: failed {}", err_str);
+            rooted!(in (cx) let mut prom_reject_val = UndefinedValue());
+            jsapi_utils::new_es_value_from_str(cx, err_str.as_str(), prom_reject_val.handle_mut());
+
+            jsapi_utils::promises::reject_promise(
+                cx,
+                promise_root.handle(),
+                prom_reject_val.handle(),
+            )
+                .ok()
+                .expect("promise rejection failed / 2");
+        }
+    });
 }
 
 unsafe extern "C" fn set_module_metadata(
@@ -335,23 +402,54 @@ unsafe extern "C" fn set_module_metadata(
     private_value: RawHandleValue,
     meta_object: RawHandleObject,
 ) -> bool {
-    // the goal here is to set the "url" prop on meta_object which is the full_path prop of private_value
-    // i think :)
+    crate::jsapi_utils::catch_native_panic(cx, "set_module_metadata", || {
+        // the goal here is to set the "url" prop on meta_object which is the full_path prop of private_value
+        // i think :)
 
-    // lets just see what we get here first
-    let path = get_path_from_module_private(cx, private_value);
+        // lets just see what we get here first
+        let path = get_path_from_module_private(cx, private_value);
 
-    rooted!(in (cx) let mut path_root = UndefinedValue());
-    jsapi_utils::new_es_value_from_str(cx, path.as_str(), path_root.handle_mut());
+        rooted!(in (cx) let mut path_root = UndefinedValue());
+        jsapi_utils::new_es_value_from_str(cx, path.as_str(), path_root.handle_mut());
 
-    jsapi_utils::objects::set_es_obj_prop_value_raw(
-        cx,
-        meta_object,
-        "url",
-        path_root.handle().into(),
-    );
+        jsapi_utils::objects::set_es_obj_prop_value_raw(
+            cx,
+            meta_object,
+            "url",
+            path_root.handle().into(),
+        );
+
+        let es_rt_inner = SmRuntime::clone_current_esrt_inner_arc();
+        if let Some(populator) = &es_rt_inner.config.import_meta_populator {
+            let mut meta_builder = MetaBuilder { props: vec![] };
+            populator(path.as_str(), &mut meta_builder);
+            for (name, value) in meta_builder.props {
+                rooted!(in (cx) let mut value_root = UndefinedValue());
+                value.to_es_value(cx, value_root.handle_mut());
+                jsapi_utils::objects::set_es_obj_prop_value_raw(
+                    cx,
+                    meta_object,
+                    name.as_str(),
+                    value_root.handle().into(),
+                );
+            }
+        }
+
+        true
+    })
+}
+
+/// used to add properties to the import.meta object of a module, see EsRuntimeBuilder::import_meta_populator()
+pub struct MetaBuilder {
+    props: Vec<(String, EsValueFacade)>,
+}
 
-    true
+impl MetaBuilder {
+    /// add a property to import.meta
+    pub fn set(&mut self, name: &str, value: EsValueFacade) -> &mut Self {
+        self.props.push((name.to_string(), value));
+        self
+    }
 }
 
 /// native function used a import function for module loading
@@ -359,6 +457,19 @@ unsafe extern "C" fn import_module(
     cx: *mut JSContext,
     reference_private: RawHandleValue,
     specifier: RawHandle<*mut JSString>,
+) -> *mut JSObject {
+    let mut module: *mut JSObject = ptr::null_mut();
+    crate::jsapi_utils::catch_native_panic(cx, "import_module", || {
+        module = import_module2(cx, reference_private, specifier);
+        true
+    });
+    module
+}
+
+unsafe fn import_module2(
+    cx: *mut JSContext,
+    reference_private: RawHandleValue,
+    specifier: RawHandle<*mut JSString>,
 ) -> *mut JSObject {
     let file_name = jsapi_utils::es_jsstring_to_string(cx, *specifier);
     let ref_path = get_path_from_module_private(cx, reference_private);
@@ -383,7 +494,14 @@ unsafe extern "C" fn import_module(
     let module_code_opt: Option<Script> = SM_RT.with(|sm_rt_rc| {
         let sm_rt = sm_rt_rc.borrow();
         let es_rt_inner = sm_rt.clone_esrt_inner();
-        if let Some(module_source_loader) = &es_rt_inner.module_source_loader {
+        let sync_res = if let Some(sync_loader) = &es_rt_inner.config.sync_module_source_loader {
+            sync_loader(file_name.as_str(), ref_path.as_str())
+        } else {
+            None
+        };
+        if sync_res.is_some() {
+            sync_res
+        } else if let Some(module_source_loader) = es_rt_inner.get_module_source_loader() {
             module_source_loader(file_name.as_str(), ref_path.as_str())
         } else {
             None
@@ -1,5 +1,6 @@
 use crate::jsapi_utils::{
-    es_jsstring_to_string, es_value_to_str, get_pending_exception, EsErrorInfo,
+    es_jsstring_to_string, es_value_to_display_str, es_value_to_str, get_pending_exception,
+    get_pending_exception_or_generic_err, EsErrorInfo,
 };
 use log::trace;
 use mozjs::glue::RUST_JSID_IS_STRING;
@@ -13,27 +14,45 @@ use mozjs::jsapi::JSObject;
 use mozjs::jsapi::JS_DeepFreezeObject;
 use mozjs::jsapi::JS_DefineProperty;
 use mozjs::jsapi::JS_FreezeObject;
+use mozjs::jsapi::JS_GetClass;
 use mozjs::jsapi::JS_GetConstructor;
 use mozjs::jsapi::JS_GetProperty;
 use mozjs::jsapi::JS_GetPrototype;
+use mozjs::jsapi::JS_IdToValue;
 use mozjs::jsapi::JS_NewObjectWithGivenProto;
 use mozjs::jsapi::JS_NewPlainObject;
-use mozjs::jsapi::JSITER_OWNONLY;
+use mozjs::jsapi::JS_SetProperty;
+use mozjs::jsapi::JS::GetSymbolDescription;
+use mozjs::jsapi::{JSITER_HIDDEN, JSITER_OWNONLY, JSITER_SYMBOLS};
 use mozjs::jsval::{JSVal, ObjectValue, UndefinedValue};
 use mozjs::rust::jsapi_wrapped::GetPropertyKeys;
 use mozjs::rust::{
     HandleObject, HandleValue, IdVector, IntoHandle, MutableHandleObject, MutableHandleValue,
 };
+use std::ffi::CStr;
 use std::ptr;
 
 pub const NULL_JSOBJECT: *mut JSObject = 0 as *mut JSObject;
 
 /// get a namespace object and create any part that is not yet defined
+/// this panics if the namespace could not be defined, see try_get_or_define_namespace() for a version which returns an error
 pub fn get_or_define_namespace(
     context: *mut JSContext,
     global: HandleObject,
     namespace: Vec<&str>,
 ) -> *mut JSObject {
+    try_get_or_define_namespace(context, global, namespace)
+        .ok()
+        .expect("could not define namespace")
+}
+
+/// get a namespace object and create any part that is not yet defined
+/// this fails if a part of the namespace exists but is not an object or if a part could not be defined (e.g. because its parent is frozen)
+pub fn try_get_or_define_namespace(
+    context: *mut JSContext,
+    global: HandleObject,
+    namespace: Vec<&str>,
+) -> Result<*mut JSObject, EsErrorInfo> {
     // todo refactor to rval
 
     trace!("get_or_define_package");
@@ -49,38 +68,44 @@ pub fn get_or_define_namespace(
             cur_obj_root.handle(),
             name,
             sub_val_root.handle_mut(),
-        )
-        .ok()
-        .unwrap();
+        )?;
 
         if sub_val_root.is_null_or_undefined() {
             trace!("get_or_define_package, loop step: {} is null, create", name);
             // create
 
             rooted!(in(context) let mut new_obj_root = NULL_JSOBJECT);
-
-            define_new_object(
-                context,
-                cur_obj_root.handle(),
-                name,
-                new_obj_root.handle_mut(),
-            );
-
-            get_es_obj_prop_val(
-                context,
-                cur_obj_root.handle(),
-                name,
-                sub_val_root.handle_mut(),
-            )
-            .ok()
-            .unwrap();
-
-            assert!(!sub_val_root.is_null_or_undefined());
+            new_object(context, new_obj_root.handle_mut());
+            sub_val_root.set(ObjectValue(*new_obj_root));
+
+            let prop_name_str = format!("{}\0", name);
+            let ok = unsafe {
+                JS_DefineProperty(
+                    context,
+                    cur_obj_root.handle().into(),
+                    prop_name_str.as_ptr() as *const libc::c_char,
+                    sub_val_root.handle().into(),
+                    mozjs::jsapi::JSPROP_ENUMERATE as u32,
+                )
+            };
+            if !ok {
+                return Err(get_pending_exception_or_generic_err(
+                    context,
+                    "could not define namespace",
+                ));
+            }
+        } else if !sub_val_root.is_object() {
+            return Err(EsErrorInfo::new(
+                format!("namespace part {} is not an object", name),
+                "".to_string(),
+                0,
+                0,
+            ));
         }
         cur_obj_root.handle_mut().set(sub_val_root.to_object());
     }
 
-    *cur_obj_root
+    Ok(*cur_obj_root)
 }
 
 pub fn define_new_object(
@@ -178,6 +203,38 @@ pub fn get_es_obj_prop_val_as_i32(
     val.to_int32()
 }
 
+/// util method to quickly get a property of a JSObject as f64, this works for both int and double values
+pub fn get_es_obj_prop_val_as_f64(
+    context: *mut JSContext,
+    obj: HandleObject,
+    prop_name: &str,
+) -> f64 {
+    rooted!(in (context) let mut rval = UndefinedValue());
+    let res = get_es_obj_prop_val(context, obj, prop_name, rval.handle_mut());
+    if res.is_err() {
+        panic!("{}", res.err().unwrap().message);
+    }
+
+    let val: JSVal = *rval;
+    val.to_number()
+}
+
+/// util method to quickly get a property of a JSObject as bool
+pub fn get_es_obj_prop_val_as_bool(
+    context: *mut JSContext,
+    obj: HandleObject,
+    prop_name: &str,
+) -> bool {
+    rooted!(in (context) let mut rval = UndefinedValue());
+    let res = get_es_obj_prop_val(context, obj, prop_name, rval.handle_mut());
+    if res.is_err() {
+        panic!("{}", res.err().unwrap().message);
+    }
+
+    let val: JSVal = *rval;
+    val.to_boolean()
+}
+
 /// create a new object in the engine
 pub fn new_object(context: *mut JSContext, ret_val: MutableHandleObject) {
     let mut ret_val = ret_val;
@@ -190,6 +247,23 @@ pub fn new_object_value(context: *mut JSContext, ret_val: MutableHandleValue) {
     ret_val.set(unsafe { ObjectValue(JS_NewPlainObject(context)) });
 }
 
+/// create a new WeakMap in the engine
+pub fn new_weakmap(context: *mut JSContext, ret_val: MutableHandleObject) {
+    let mut ret_val = ret_val;
+    ret_val.set(unsafe { mozjs::jsapi::JS::NewWeakMapObject(context) });
+}
+
+/// check if an object is a WeakMap
+pub fn object_is_weakmap(obj: *mut JSObject) -> bool {
+    unsafe { mozjs::jsapi::JS::IsWeakMapObject(obj) }
+}
+
+/// check if an object is a WeakSet, this checks the class of the object so objects which only inherit from WeakSet.prototype are not WeakSets
+pub fn object_is_weakset(obj: *mut JSObject) -> bool {
+    let class = unsafe { JS_GetClass(obj) };
+    !class.is_null() && unsafe { CStr::from_ptr((*class).name) }.to_bytes() == b"WeakSet"
+}
+
 /// freeze an Object
 pub fn freeze_object(context: *mut JSContext, obj: HandleObject) -> Result<(), EsErrorInfo> {
     let res = unsafe { JS_FreezeObject(context, obj.into()) };
@@ -198,12 +272,12 @@ pub fn freeze_object(context: *mut JSContext, obj: HandleObject) -> Result<(), E
     } else if let Some(err) = crate::jsapi_utils::get_pending_exception(context) {
         Err(err)
     } else {
-        Err(EsErrorInfo {
-            message: "unknown error".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        })
+        Err(EsErrorInfo::new(
+            "unknown error".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
     }
 }
 
@@ -215,12 +289,12 @@ pub fn deep_freeze_object(context: *mut JSContext, obj: HandleObject) -> Result<
     } else if let Some(err) = crate::jsapi_utils::get_pending_exception(context) {
         Err(err)
     } else {
-        Err(EsErrorInfo {
-            message: "unknown error".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        })
+        Err(EsErrorInfo::new(
+            "unknown error".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
     }
 }
 
@@ -336,6 +410,104 @@ pub fn get_js_obj_prop_names(context: *mut JSContext, obj: HandleObject) -> Vec<
     ret
 }
 
+/// the key of an own property of an object, see get_all_own_prop_names()
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropKey {
+    /// a String key, index keys (e.g. of arrays) are also returned as String
+    String(String),
+    /// a Symbol key with the description of the Symbol, None for a Symbol without description
+    Symbol(Option<String>),
+}
+
+/// get the keys of the own properties of an object including the ones get_js_obj_prop_names() skips
+/// non-enumerable properties are included if include_non_enumerable is true
+/// symbol keys are included if include_symbols is true
+/// this fails when the keys can not be enumerated (e.g. the ownKeys trap of a Proxy throws), the pending exception is then cleared and returned as error
+pub fn get_all_own_prop_names(
+    context: *mut JSContext,
+    obj: HandleObject,
+    include_non_enumerable: bool,
+    include_symbols: bool,
+) -> Result<Vec<PropKey>, EsErrorInfo> {
+    let mut flags = JSITER_OWNONLY;
+    if include_non_enumerable {
+        flags |= JSITER_HIDDEN;
+    }
+    if include_symbols {
+        flags |= JSITER_SYMBOLS;
+    }
+
+    let mut ids = unsafe { IdVector::new(context) };
+
+    if !unsafe { GetPropertyKeys(context, obj, flags, ids.handle_mut()) } {
+        return Err(get_pending_exception_or_generic_err(
+            context,
+            "could not get property keys",
+        ));
+    }
+
+    let mut ret: Vec<PropKey> = vec![];
+
+    for x in 0..ids.len() {
+        // ids may also be ints (e.g. array indexes) or symbols so convert them to a value first
+        rooted!(in(context) let mut id_val = UndefinedValue());
+        if !unsafe { JS_IdToValue(context, ids[x], id_val.handle_mut().into()) } {
+            return Err(get_pending_exception_or_generic_err(
+                context,
+                "could not convert property key",
+            ));
+        }
+        if id_val.is_symbol() {
+            rooted!(in(context) let symbol_root = id_val.to_symbol());
+            let desc = unsafe { GetSymbolDescription(symbol_root.handle().into()) };
+            ret.push(PropKey::Symbol(if desc.is_null() {
+                None
+            } else {
+                Some(es_jsstring_to_string(context, desc))
+            }));
+        } else {
+            ret.push(PropKey::String(es_value_to_display_str(context, *id_val)));
+        }
+    }
+    Ok(ret)
+}
+
+/// get the names of the own enumerable properties of an object if it has no more than max_count of those
+/// returns None (without converting any name to a String) if the object has more properties
+/// this fails when the keys can not be enumerated (e.g. the ownKeys trap of a Proxy throws), the pending exception is then cleared and returned as error
+pub fn get_js_obj_prop_names_max(
+    context: *mut JSContext,
+    obj: HandleObject,
+    max_count: usize,
+) -> Result<Option<Vec<String>>, EsErrorInfo> {
+    let mut ids = unsafe { IdVector::new(context) };
+
+    if !unsafe { GetPropertyKeys(context, obj, JSITER_OWNONLY, ids.handle_mut()) } {
+        return Err(get_pending_exception_or_generic_err(
+            context,
+            "could not get property keys",
+        ));
+    }
+
+    if ids.len() > max_count {
+        return Ok(None);
+    }
+
+    let mut ret: Vec<String> = Vec::with_capacity(ids.len());
+    for x in 0..ids.len() {
+        // index keys (e.g. {1: 'a'}) are ints so convert every id to a value first
+        rooted!(in(context) let mut id_val = UndefinedValue());
+        if !unsafe { JS_IdToValue(context, ids[x], id_val.handle_mut().into()) } {
+            return Err(get_pending_exception_or_generic_err(
+                context,
+                "could not convert property key",
+            ));
+        }
+        ret.push(es_value_to_display_str(context, *id_val));
+    }
+    Ok(Some(ret))
+}
+
 /// set a property of an object
 #[allow(dead_code)]
 pub fn set_es_obj_prop_value_raw(
@@ -376,6 +548,37 @@ pub fn set_es_obj_prop_value(
     }
 }
 
+/// assign a property of an object like `obj.name = val` does in script
+/// unlike set_es_obj_prop_value this calls setters and the set trap of a Proxy instead of defining a data property
+pub fn assign_es_obj_prop_value(
+    context: *mut JSContext,
+    obj: HandleObject,
+    prop_name: &str,
+    prop_val: HandleValue,
+) -> Result<(), EsErrorInfo> {
+    let prop_name_str = format!("{}\0", prop_name);
+    let ok = unsafe {
+        JS_SetProperty(
+            context,
+            obj.into(),
+            prop_name_str.as_ptr() as *const libc::c_char,
+            prop_val.into(),
+        )
+    };
+    if ok {
+        Ok(())
+    } else if let Some(err) = get_pending_exception(context) {
+        Err(err)
+    } else {
+        Err(EsErrorInfo::new(
+            format!("could not set property {}", prop_name),
+            "".to_string(),
+            0,
+            0,
+        ))
+    }
+}
+
 /// set a property of an object
 #[allow(dead_code)]
 pub fn set_es_obj_prop_val_permanent(
@@ -403,7 +606,9 @@ mod tests {
     use crate::jsapi_utils;
     use crate::jsapi_utils::objects::NULL_JSOBJECT;
     use crate::jsapi_utils::objects::{
-        get_es_obj_prop_val, get_js_obj_prop_names, get_or_define_namespace,
+        get_all_own_prop_names, get_es_obj_prop_val, get_es_obj_prop_val_as_bool,
+        get_es_obj_prop_val_as_f64, get_es_obj_prop_val_as_i32, get_js_obj_prop_names,
+        get_or_define_namespace, PropKey,
     };
     use crate::jsapi_utils::{es_value_to_str, get_pending_exception};
     use crate::spidermonkeyruntimewrapper::SmRuntime;
@@ -478,6 +683,38 @@ mod tests {
         assert_eq!(test_vec.get(2).unwrap(), &"3".to_string());
     }
 
+    #[test]
+    fn test_get_es_obj_prop_val_as() {
+        log::info!("test: test_get_es_obj_prop_val_as");
+        let rt = init_test_runtime();
+
+        let res = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in(cx) let mut rval = UndefinedValue());
+                jsapi_utils::eval(
+                    rt,
+                    global,
+                    "({ratio: 0.75, count: 3, enabled: true, disabled: false})",
+                    "test_get_es_obj_prop_val_as.es",
+                    rval.handle_mut(),
+                )
+                .ok()
+                .expect("script failed");
+                rooted!(in(cx) let jso_root = rval.to_object());
+
+                (
+                    get_es_obj_prop_val_as_f64(cx, jso_root.handle(), "ratio"),
+                    get_es_obj_prop_val_as_f64(cx, jso_root.handle(), "count"),
+                    get_es_obj_prop_val_as_i32(cx, jso_root.handle(), "count"),
+                    get_es_obj_prop_val_as_bool(cx, jso_root.handle(), "enabled"),
+                    get_es_obj_prop_val_as_bool(cx, jso_root.handle(), "disabled"),
+                )
+            })
+        });
+
+        assert_eq!(res, (0.75, 3.0, 3, true, false));
+    }
+
     #[test]
     fn test_get_js_obj_prop_names_x() {
         for _x in 0..10 {
@@ -524,6 +761,77 @@ mod tests {
         assert_eq!(test_vec.get(2).unwrap(), &"c".to_string());
     }
 
+    #[test]
+    fn test_get_all_own_prop_names() {
+        log::info!("test: test_get_all_own_prop_names");
+        let rt = init_test_runtime();
+
+        let names = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in(cx) let mut rval = UndefinedValue());
+                jsapi_utils::eval(
+                    rt,
+                    global,
+                    "(function(){let o = {a: 1}; Object.defineProperty(o, 'hidden', {value: 2, enumerable: false}); \
+                     o[Symbol('tag')] = 3; o[Symbol()] = 4; o['Symbol(tag)'] = 5; return o;})();",
+                    "test_get_all_own_prop_names.es",
+                    rval.handle_mut(),
+                )
+                .ok()
+                .expect("script failed");
+                rooted!(in (cx) let obj_root = rval.to_object());
+
+                (
+                    get_js_obj_prop_names(cx, obj_root.handle()),
+                    get_all_own_prop_names(cx, obj_root.handle(), false, false)
+                        .ok()
+                        .expect("could not get keys"),
+                    get_all_own_prop_names(cx, obj_root.handle(), true, false)
+                        .ok()
+                        .expect("could not get keys"),
+                    get_all_own_prop_names(cx, obj_root.handle(), true, true)
+                        .ok()
+                        .expect("could not get keys"),
+                )
+            })
+        });
+
+        let s = |name: &str| PropKey::String(name.to_string());
+
+        assert_eq!(names.0, vec!["a", "Symbol(tag)"]);
+        assert_eq!(names.1, vec![s("a"), s("Symbol(tag)")]);
+        assert_eq!(names.2, vec![s("a"), s("hidden"), s("Symbol(tag)")]);
+        assert_eq!(
+            names.3,
+            vec![
+                s("a"),
+                s("hidden"),
+                s("Symbol(tag)"),
+                PropKey::Symbol(Some("tag".to_string())),
+                PropKey::Symbol(None)
+            ]
+        );
+
+        let err = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in(cx) let mut rval = UndefinedValue());
+                jsapi_utils::eval(
+                    rt,
+                    global,
+                    "new Proxy({}, {ownKeys: function(){throw Error('no keys');}});",
+                    "test_get_all_own_prop_names2.es",
+                    rval.handle_mut(),
+                )
+                .ok()
+                .expect("script failed");
+                rooted!(in (cx) let obj_root = rval.to_object());
+                get_all_own_prop_names(cx, obj_root.handle(), true, true)
+            })
+        });
+        assert!(err.is_err());
+        assert!(err.err().unwrap().message.contains("no keys"));
+    }
+
     #[test]
     fn test_get_or_define_package() {
         log::info!("test: test_get_or_define_package");
@@ -551,6 +859,49 @@ mod tests {
         assert_eq!(json, expect);
     }
 
+    #[test]
+    fn test_try_get_or_define_namespace() {
+        log::info!("test: test_try_get_or_define_namespace");
+        let rt = init_test_runtime();
+        rt.eval_sync(
+            "globalThis.test_try_get_or_define_namespace = {num: 1, frozen: Object.freeze({})};",
+            "test_try_get_or_define_namespace.es",
+        )
+        .ok()
+        .expect("script failed");
+        let errs = rt.do_in_es_event_queue_sync(|sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                (
+                    try_get_or_define_namespace(
+                        cx,
+                        global,
+                        vec!["test_try_get_or_define_namespace", "num", "a"],
+                    )
+                    .err(),
+                    try_get_or_define_namespace(
+                        cx,
+                        global,
+                        vec!["test_try_get_or_define_namespace", "frozen", "a"],
+                    )
+                    .err(),
+                    try_get_or_define_namespace(
+                        cx,
+                        global,
+                        vec!["test_try_get_or_define_namespace", "b"],
+                    )
+                    .is_ok(),
+                )
+            })
+        });
+        assert!(errs
+            .0
+            .expect("num is not an object")
+            .message
+            .contains("num"));
+        assert!(errs.1.is_some());
+        assert!(errs.2);
+    }
+
     #[test]
     fn test_get_obj_props() {
         log::info!("test: test_get_obj_props");
@@ -1,5 +1,8 @@
 use crate::jsapi_utils::objects::NULL_JSOBJECT;
+use crate::jsapi_utils::rooting::EsPersistentRooted;
+use crate::jsapi_utils::{functions, objects};
 use crate::jsapi_utils::{get_pending_exception, EsErrorInfo};
+use hirofa_utils::eventloop::EventLoop;
 use mozjs::jsapi::AddPromiseReactions;
 use mozjs::jsapi::GetPromiseResult;
 use mozjs::jsapi::GetPromiseState;
@@ -7,17 +10,25 @@ use mozjs::jsapi::HandleObject as RawHandleObject;
 use mozjs::jsapi::IsPromiseObject;
 use mozjs::jsapi::JSContext;
 use mozjs::jsapi::JSObject;
+use mozjs::jsapi::PromiseRejectionHandlingState;
 use mozjs::jsapi::PromiseState;
 use mozjs::jsapi::SetPromiseRejectionTrackerCallback;
 use mozjs::jsapi::StackFormat;
 use mozjs::jsval::JSVal;
+use mozjs::jsval::UndefinedValue;
 use mozjs::rust::jsapi_wrapped::NewPromiseObject;
 use mozjs::rust::jsapi_wrapped::RejectPromise;
 use mozjs::rust::jsapi_wrapped::ResolvePromise;
-use mozjs::rust::{HandleObject, HandleValue, MutableHandleValue};
+use mozjs::rust::{HandleObject, HandleValue, MutableHandleObject, MutableHandleValue};
+use std::cell::RefCell;
 use std::os::raw::c_void;
 use std::ptr;
 
+thread_local! {
+    // promises which were rejected without a rejection handler, they are reported if no handler was added when report_unhandled_rejections() runs
+    static UNHANDLED_REJECTIONS: RefCell<Vec<EsPersistentRooted>> = RefCell::new(vec![]);
+}
+
 /// Returns true if the given object is an unwrapped PromiseObject, false otherwise.
 pub fn object_is_promise(obj: HandleObject) -> bool {
     object_is_promise_raw(obj.into())
@@ -137,7 +148,88 @@ pub fn new_promise_with_exe(context: *mut JSContext, executor: HandleObject) ->
     unsafe { NewPromiseObject(context, executor) }
 }
 
+/// create a new Promise and the functions to resolve or reject it
+/// this is the rust equivalent of the script
+/// ```javascript
+/// let {promise, resolve, reject} = Promise.withResolvers();
+/// ```
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::jsapi_utils::promises::new_promise_with_resolvers;
+/// use spidermonkey_runtime::jsapi_utils::objects::NULL_JSOBJECT;
+/// use mozjs::rooted;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.do_in_es_event_queue_sync(|sm_rt| {
+///     sm_rt.do_with_jsapi(|_rt, cx, _global| {
+///         rooted!(in (cx) let mut promise_root = NULL_JSOBJECT);
+///         rooted!(in (cx) let mut resolve_root = NULL_JSOBJECT);
+///         rooted!(in (cx) let mut reject_root = NULL_JSOBJECT);
+///         new_promise_with_resolvers(cx, promise_root.handle_mut(), resolve_root.handle_mut(), reject_root.handle_mut())
+///             .ok()
+///             .expect("could not create promise");
+///         // store the resolve and reject functions (e.g. in the object cache) and call one of them later
+///     });
+/// });
+/// ```
+pub fn new_promise_with_resolvers(
+    context: *mut JSContext,
+    promise: MutableHandleObject,
+    resolve: MutableHandleObject,
+    reject: MutableHandleObject,
+) -> Result<(), EsErrorInfo> {
+    // the executor is called synchronously by NewPromiseObject, it stores the resolving functions in a holder object
+    rooted!(in (context) let mut holder_root = NULL_JSOBJECT);
+    objects::new_object(context, holder_root.handle_mut());
+    let holder: *mut JSObject = *holder_root;
+
+    rooted!(in (context) let mut executor_root = NULL_JSOBJECT);
+    functions::new_callback(
+        context,
+        executor_root.handle_mut(),
+        move |cx, args: Vec<HandleValue>, _rval| {
+            if args.len() < 2 {
+                return Err("promise executor expects 2 arguments".to_string());
+            }
+            let holder_handle = unsafe { HandleObject::from_marked_location(&holder) };
+            objects::set_es_obj_prop_value(cx, holder_handle, "resolve", args[0]);
+            objects::set_es_obj_prop_value(cx, holder_handle, "reject", args[1]);
+            Ok(())
+        },
+    );
+
+    let mut promise = promise;
+    promise.set(new_promise_with_exe(context, executor_root.handle()));
+    if promise.get().is_null() {
+        return Err(get_pending_exception(context).unwrap_or_else(|| {
+            EsErrorInfo::new("could not create promise".to_string(), "".to_string(), 0, 0)
+        }));
+    }
+
+    let mut resolve = resolve;
+    let mut reject = reject;
+    rooted!(in (context) let mut func_val_root = UndefinedValue());
+    objects::get_es_obj_prop_val(
+        context,
+        holder_root.handle(),
+        "resolve",
+        func_val_root.handle_mut(),
+    )?;
+    resolve.set(func_val_root.to_object());
+    objects::get_es_obj_prop_val(
+        context,
+        holder_root.handle(),
+        "reject",
+        func_val_root.handle_mut(),
+    )?;
+    reject.set(func_val_root.to_object());
+
+    Ok(())
+}
+
 /// resolve a Promise with a given resolution value
+/// if the resolution value is a Promise or thenable the Promise adopts its state like resolve() does in script
 pub fn resolve_promise(
     context: *mut JSContext,
     promise: HandleObject,
@@ -149,12 +241,12 @@ pub fn resolve_promise(
     } else if let Some(err) = get_pending_exception(context) {
         Err(err)
     } else {
-        Err(EsErrorInfo {
-            message: "unknown error resolving promise".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        })
+        Err(EsErrorInfo::new(
+            "unknown error resolving promise".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
     }
 }
 
@@ -170,12 +262,12 @@ pub fn reject_promise(
     } else if let Some(err) = get_pending_exception(context) {
         Err(err)
     } else {
-        Err(EsErrorInfo {
-            message: "unknown error rejecting promise".to_string(),
-            filename: "".to_string(),
-            lineno: 0,
-            column: 0,
-        })
+        Err(EsErrorInfo::new(
+            "unknown error rejecting promise".to_string(),
+            "".to_string(),
+            0,
+            0,
+        ))
     }
 }
 
@@ -184,10 +276,14 @@ mod tests {
     use crate::esruntime::tests::init_test_runtime;
     use crate::jsapi_utils;
     use crate::jsapi_utils::get_pending_exception;
-    use crate::jsapi_utils::promises::object_is_promise;
+    use crate::jsapi_utils::objects::NULL_JSOBJECT;
+    use crate::jsapi_utils::promises::{
+        get_promise_result, get_promise_state, new_promise_with_resolvers, object_is_promise,
+    };
     use crate::jsapi_utils::tests::test_with_sm_rt;
     use log::trace;
-    use mozjs::jsval::UndefinedValue;
+    use mozjs::jsapi::PromiseState;
+    use mozjs::jsval::{Int32Value, ObjectValue, UndefinedValue};
 
     #[test]
     fn test_x() {
@@ -230,6 +326,50 @@ mod tests {
         assert_eq!(res, true);
     }
 
+    #[test]
+    fn test_new_promise_with_resolvers() {
+        log::info!("test: test_new_promise_with_resolvers");
+        let res = test_with_sm_rt(|sm_rt| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                rooted!(in (cx) let mut promise_root = NULL_JSOBJECT);
+                rooted!(in (cx) let mut resolve_root = NULL_JSOBJECT);
+                rooted!(in (cx) let mut reject_root = NULL_JSOBJECT);
+                new_promise_with_resolvers(
+                    cx,
+                    promise_root.handle_mut(),
+                    resolve_root.handle_mut(),
+                    reject_root.handle_mut(),
+                )
+                .ok()
+                .expect("could not create promise");
+                assert!(object_is_promise(promise_root.handle()));
+                assert_eq!(
+                    get_promise_state(promise_root.handle()),
+                    PromiseState::Pending
+                );
+
+                rooted!(in (cx) let resolve_val_root = ObjectValue(*resolve_root));
+                rooted!(in (cx) let mut rval = UndefinedValue());
+                jsapi_utils::functions::call_function_value(
+                    cx,
+                    global,
+                    resolve_val_root.handle(),
+                    vec![Int32Value(123)],
+                    rval.handle_mut(),
+                )
+                .ok()
+                .expect("resolve failed");
+
+                assert_eq!(
+                    get_promise_state(promise_root.handle()),
+                    PromiseState::Fulfilled
+                );
+                get_promise_result(promise_root.handle()).to_int32()
+            })
+        });
+        assert_eq!(res, 123);
+    }
+
     #[test]
     fn test_not_instance_of_promise() {
         log::info!("test: test_not_instance_of_promise");
@@ -274,7 +414,8 @@ mod tests {
     }
 }
 
-/// this initializes a default rejectiontracker which logs when a promise was rejected which did not have a rejection handler
+/// this initializes a default rejectiontracker which reports when a promise was rejected which did not have a rejection handler
+/// the rejection is passed to the error callback of the runtime (see EsRuntimeBuilder::error_callback()) unless a handler is added in the same job
 pub fn init_rejection_tracker(cx: *mut JSContext) {
     unsafe {
         SetPromiseRejectionTrackerCallback(cx, Some(promise_rejection_tracker), ptr::null_mut())
@@ -284,18 +425,54 @@ pub fn init_rejection_tracker(cx: *mut JSContext) {
 unsafe extern "C" fn promise_rejection_tracker(
     cx: *mut JSContext,
     _muted_errors: bool,
-    _promise: mozjs::jsapi::HandleObject,
-    _state: mozjs::jsapi::PromiseRejectionHandlingState,
+    promise: mozjs::jsapi::HandleObject,
+    state: mozjs::jsapi::PromiseRejectionHandlingState,
     _data: *mut c_void,
 ) {
+    if state == PromiseRejectionHandlingState::Handled {
+        // a handler was added to a promise which was rejected before
+        UNHANDLED_REJECTIONS.with(|rc| {
+            rc.borrow_mut().retain(|epr| epr.get() != promise.get());
+        });
+        return;
+    }
+
     capture_stack!(in (cx) let stack);
     let str_stack = stack
         .unwrap()
         .as_string(None, StackFormat::SpiderMonkey)
         .unwrap();
 
-    log::error!(
+    log::debug!(
         "promise without rejection handler was rejected from:\n{}",
         str_stack
     );
+
+    UNHANDLED_REJECTIONS.with(|rc| {
+        rc.borrow_mut()
+            .push(EsPersistentRooted::new_from_obj(cx, promise.get()))
+    });
+    // script may still add a handler in the current job, e.g. Promise.reject(err).catch(...);
+    EventLoop::add_local_void(report_unhandled_rejections);
+}
+
+// pass the reasons of the promises which are still unhandled to the error callback of the runtime
+fn report_unhandled_rejections() {
+    let rejected: Vec<EsPersistentRooted> =
+        UNHANDLED_REJECTIONS.with(|rc| rc.borrow_mut().drain(..).collect());
+    if rejected.is_empty() {
+        return;
+    }
+    crate::spidermonkeyruntimewrapper::SM_RT.with(|sm_rt_rc| {
+        let sm_rt = &*sm_rt_rc.borrow();
+        sm_rt.do_with_jsapi(|_rt, cx, _global| {
+            for epr in rejected {
+                rooted!(in (cx) let prom_root = epr.get());
+                rooted!(in (cx) let reason_root = get_promise_result(prom_root.handle()));
+                let mut err = crate::jsapi_utils::es_value_to_error_info(cx, reason_root.handle());
+                err.message = format!("unhandled promise rejection: {}", err.message);
+                crate::spidermonkeyruntimewrapper::report_uncaught_error(err);
+            }
+        })
+    });
 }
@@ -79,7 +79,9 @@
 //! ```
 //!
 
+use crate::esvaluefacade::EsValueFacade;
 use crate::jsapi_utils;
+use crate::jsapi_utils::iterators::AsyncIteratorNext;
 use crate::jsapi_utils::objects::NULL_JSOBJECT;
 use crate::jsapi_utils::rooting::EsPersistentRooted;
 use crate::jsapi_utils::{es_jsid_to_string, report_exception2, EsErrorInfo};
@@ -95,21 +97,50 @@ use mozjs::jsapi::JSObject;
 use mozjs::jsapi::JSCLASS_FOREGROUND_FINALIZE;
 use mozjs::jsval::{ObjectValue, UndefinedValue};
 use mozjs::rust::{HandleObject, HandleValue, MutableHandleValue};
+use std::any::Any;
 use std::borrow::Borrow;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
 use std::ptr::replace;
+use std::rc::Rc;
 use std::sync::Arc;
 
 pub type Constructor = Box<dyn Fn(*mut JSContext, Vec<HandleValue>) -> Result<i32, String>>;
-pub type Setter = Box<dyn Fn(*mut JSContext, i32, HandleValue) -> Result<(), String>>;
+pub type Setter = Box<dyn Fn(*mut JSContext, i32, HandleValue) -> Result<(), SetterError>>;
 pub type Getter = Box<dyn Fn(*mut JSContext, i32, MutableHandleValue) -> Result<(), String>>;
 pub type Method =
     Box<dyn Fn(*mut JSContext, i32, Vec<HandleValue>, MutableHandleValue) -> Result<(), String>>;
-pub type StaticSetter = Box<dyn Fn(*mut JSContext, HandleValue) -> Result<(), String>>;
+pub type StaticSetter = Box<dyn Fn(*mut JSContext, HandleValue) -> Result<(), SetterError>>;
 pub type StaticGetter = Box<dyn Fn(*mut JSContext, MutableHandleValue) -> Result<(), String>>;
 pub type StaticMethod =
     Box<dyn Fn(*mut JSContext, Vec<HandleValue>, MutableHandleValue) -> Result<(), String>>;
+pub type DynamicMethodFilter = Box<dyn Fn(i32, &str) -> bool>;
+/// the error of a setter of a proxy, a TypeError or RangeError is thrown to script as an error of that class
+/// a String converts to SetterError::Error so a setter can return `Err(msg.into())`
+#[derive(Clone, Debug, PartialEq)]
+pub enum SetterError {
+    Error(String),
+    TypeError(String),
+    RangeError(String),
+}
+
+impl From<String> for SetterError {
+    fn from(message: String) -> Self {
+        SetterError::Error(message)
+    }
+}
+
+impl From<&str> for SetterError {
+    fn from(message: &str) -> Self {
+        SetterError::Error(message.to_string())
+    }
+}
+
+/// the well known Symbols like Symbol.iterator or Symbol.toStringTag, see ProxyBuilder::symbol_method()
+pub use mozjs::jsapi::JS::SymbolCode as WellKnownSymbol;
+pub type DynamicMethod = Box<
+    dyn Fn(*mut JSContext, i32, &str, Vec<HandleValue>, MutableHandleValue) -> Result<bool, String>,
+>;
 
 /// create a class def in the runtime which constructs and calls methods in a rust proxy
 pub struct Proxy {
@@ -117,18 +148,33 @@ pub struct Proxy {
     pub class_name: &'static str,
     constructor: Option<Constructor>,
     finalizer: Option<Box<dyn Fn(i32)>>,
-    properties: HashMap<&'static str, (Getter, Setter)>,
+    on_instance_created: Option<Box<dyn Fn(i32)>>,
+    callable: Option<Method>,
+    properties: HashMap<&'static str, (Getter, Option<Setter>)>,
+    property_flags: HashMap<&'static str, PropertyFlags>,
 
     // todo add cx as second arg to methods
     methods: HashMap<&'static str, Method>,
+    // keyed by the name of the function, e.g. "[Symbol.iterator]"
+    symbol_methods: HashMap<String, (WellKnownSymbol, Method)>,
     native_methods: HashMap<&'static str, JSNative>,
+    method_arities: HashMap<&'static str, u32>,
+    dynamic_methods: Option<(DynamicMethodFilter, DynamicMethod)>,
     events: HashSet<&'static str>,
     event_listeners: RefCell<HashMap<i32, HashMap<&'static str, Vec<EsPersistentRooted>>>>,
-    static_properties: HashMap<&'static str, (StaticGetter, StaticSetter)>,
+    static_properties: HashMap<&'static str, (StaticGetter, Option<StaticSetter>)>,
     static_methods: HashMap<&'static str, StaticMethod>,
+    // keyed by the name of the function, e.g. "[Symbol.hasInstance]"
+    static_symbol_methods: HashMap<String, (WellKnownSymbol, StaticMethod)>,
     static_native_methods: HashMap<&'static str, JSNative>,
+    static_method_arities: HashMap<&'static str, u32>,
     static_events: HashSet<&'static str>,
     static_event_listeners: RefCell<HashMap<&'static str, Vec<EsPersistentRooted>>>,
+    lock_members: bool,
+    has_instance_data: bool,
+    parent: Option<String>,
+    // the prototype of instances, this is also the prototype property of the constructor
+    prototype: Option<EsPersistentRooted>,
 }
 
 /// the builder struct for Proxy
@@ -137,20 +183,81 @@ pub struct ProxyBuilder {
     pub class_name: &'static str,
     constructor: Option<Constructor>,
     finalizer: Option<Box<dyn Fn(i32)>>,
-    properties: HashMap<&'static str, (Getter, Setter)>,
+    on_instance_created: Option<Box<dyn Fn(i32)>>,
+    callable: Option<Method>,
+    properties: HashMap<&'static str, (Getter, Option<Setter>)>,
+    property_flags: HashMap<&'static str, PropertyFlags>,
     methods: HashMap<&'static str, Method>,
+    symbol_methods: Vec<(WellKnownSymbol, Method)>,
+    symbol_values: Vec<(WellKnownSymbol, EsValueFacade)>,
     native_methods: HashMap<&'static str, JSNative>,
+    method_arities: HashMap<&'static str, u32>,
+    dynamic_methods: Option<(DynamicMethodFilter, DynamicMethod)>,
     events: HashSet<&'static str>,
-    static_properties: HashMap<&'static str, (StaticGetter, StaticSetter)>,
+    static_properties: HashMap<&'static str, (StaticGetter, Option<StaticSetter>)>,
     static_methods: HashMap<&'static str, StaticMethod>,
+    static_symbol_methods: Vec<(WellKnownSymbol, StaticMethod)>,
     static_native_methods: HashMap<&'static str, JSNative>,
+    static_method_arities: HashMap<&'static str, u32>,
     static_events: HashSet<&'static str>,
+    lock_members: bool,
+    has_instance_data: bool,
+    parent: Option<&'static str>,
+}
+
+/// the flags of a property of a proxy instance, see ProxyBuilder::property_with_flags()
+/// by default properties are configurable (unless the members are locked) and not enumerable
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PropertyFlags {
+    /// if true the property shows up in Object.keys() and for..in loops
+    pub enumerable: bool,
+    /// if false the property can not be deleted or redefined
+    pub configurable: bool,
+}
+
+impl Default for PropertyFlags {
+    fn default() -> Self {
+        PropertyFlags {
+            enumerable: false,
+            configurable: true,
+        }
+    }
+}
+
+impl PropertyFlags {
+    fn attrs(&self) -> u32 {
+        let mut attrs = 0;
+        if self.enumerable {
+            attrs |= mozjs::jsapi::JSPROP_ENUMERATE as u32;
+        }
+        if !self.configurable {
+            attrs |= mozjs::jsapi::JSPROP_PERMANENT as u32;
+        }
+        attrs
+    }
+}
+
+/// metadata of a registered Proxy class, see list_proxies()
+#[derive(Clone, Debug)]
+pub struct ProxyInfo {
+    pub canonical_name: String,
+    pub constructable: bool,
+    pub callable: bool,
+    pub methods: Vec<String>,
+    pub properties: Vec<String>,
+    pub events: Vec<String>,
+    pub static_methods: Vec<String>,
+    pub static_properties: Vec<String>,
+    pub static_events: Vec<String>,
 }
 
 thread_local! {
     static PROXY_INSTANCE_IDS: RefCell<HashMap<usize, i32>> = RefCell::new(HashMap::new());
     static PROXY_INSTANCE_CLASSNAMES: RefCell<HashMap<i32, String>> = RefCell::new(HashMap::new());
     static PROXIES: RefCell<HashMap<String, Arc<Proxy>>> = RefCell::new(HashMap::new());
+    // the data of instances which were constructed by a constructor_with_data, by obj_id
+    static PROXY_INSTANCE_DATA: RefCell<HashMap<i32, Rc<dyn Any>>> = RefCell::new(HashMap::new());
+    static NEXT_PROXY_INSTANCE_ID: Cell<i32> = Cell::new(1);
 }
 
 /// find a ref to a proxy, use full canonical name as key, needs to run in the workerthread of the event queue
@@ -179,6 +286,94 @@ pub fn get_proxy(canonical_name: &str) -> Option<Arc<Proxy>> {
     })
 }
 
+/// create a new instance of a registered proxy class from rust, this is the same as calling new_instance() on the proxy returned by get_proxy()
+/// returns an error if there is no proxy with that canonical name, needs to run in the workerthread of the event queue
+pub fn new_instance(
+    cx: *mut JSContext,
+    canonical_name: &str,
+    obj_id: i32,
+    rval: MutableHandleValue,
+) -> Result<(), EsErrorInfo> {
+    match get_proxy(canonical_name) {
+        Some(proxy) => proxy.new_instance(cx, obj_id, rval),
+        None => Err(EsErrorInfo::new(
+            format!("no proxy class found for {}", canonical_name),
+            "".to_string(),
+            0,
+            0,
+        )),
+    }
+}
+
+/// get info about all proxies which are registered in this runtime, needs to run in the workerthread of the event queue
+/// the result is sorted by canonical name
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::jsapi_utils::reflection::list_proxies;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// let infos = rt.do_in_es_event_queue_sync(|_sm_rt| list_proxies());
+/// for info in infos {
+///     println!("{} methods: {:?}", info.canonical_name, info.methods);
+/// }
+/// ```
+pub fn list_proxies() -> Vec<ProxyInfo> {
+    PROXIES.with(|rc: &RefCell<HashMap<String, Arc<Proxy>>>| {
+        let map: &HashMap<String, Arc<Proxy>> = &*rc.borrow();
+        let mut infos: Vec<ProxyInfo> = map.values().map(|proxy| proxy.get_info()).collect();
+        infos.sort_by(|a, b| a.canonical_name.cmp(&b.canonical_name));
+        infos
+    })
+}
+
+/// the length of a proxy method if no arity was declared
+const DEFAULT_METHOD_ARITY: u32 = 1;
+
+/// the attrs for properties and methods of a proxy
+fn member_attrs(lock_members: bool) -> u32 {
+    if lock_members {
+        (mozjs::jsapi::JSPROP_READONLY | mozjs::jsapi::JSPROP_PERMANENT) as u32
+    } else {
+        0
+    }
+}
+
+/// define a method of a proxy (or proxy instance) on an object
+fn define_member_function(
+    cx: *mut JSContext,
+    obj: HandleObject,
+    function_name: &str,
+    nargs: u32,
+    native_function: JSNative,
+    lock_members: bool,
+) -> *mut mozjs::jsapi::JSFunction {
+    crate::jsapi_utils::functions::define_native_function_with(
+        cx,
+        obj,
+        function_name,
+        nargs,
+        member_attrs(lock_members),
+        native_function,
+    )
+}
+
+/// the attrs for getter/setter properties of a proxy, accessors can not be readonly so only permanent is set
+fn accessor_attrs(lock_members: bool) -> u32 {
+    if lock_members {
+        mozjs::jsapi::JSPROP_PERMANENT as u32
+    } else {
+        (mozjs::jsapi::JSPROP_PERMANENT & mozjs::jsapi::JSPROP_GETTER & mozjs::jsapi::JSPROP_SETTER)
+            as u32
+    }
+}
+
+fn sorted_names<'a, I: Iterator<Item = &'a &'static str>>(names: I) -> Vec<String> {
+    let mut ret: Vec<String> = names.map(|name| name.to_string()).collect();
+    ret.sort();
+    ret
+}
+
 impl Proxy {
     fn new(cx: *mut JSContext, scope: HandleObject, builder: &mut ProxyBuilder) -> Arc<Self> {
         let mut ret = Proxy {
@@ -186,16 +381,28 @@ impl Proxy {
             class_name: builder.class_name,
             constructor: unsafe { replace(&mut builder.constructor, None) },
             finalizer: unsafe { replace(&mut builder.finalizer, None) },
+            on_instance_created: builder.on_instance_created.take(),
+            callable: builder.callable.take(),
             properties: HashMap::new(),
+            property_flags: builder.property_flags.drain().collect(),
             methods: HashMap::new(),
+            symbol_methods: HashMap::new(),
             native_methods: HashMap::new(),
+            method_arities: builder.method_arities.drain().collect(),
+            dynamic_methods: builder.dynamic_methods.take(),
             events: HashSet::new(),
             event_listeners: RefCell::new(HashMap::new()),
             static_properties: HashMap::new(),
             static_methods: HashMap::new(),
+            static_symbol_methods: HashMap::new(),
             static_native_methods: HashMap::new(),
+            static_method_arities: builder.static_method_arities.drain().collect(),
             static_events: HashSet::new(),
             static_event_listeners: RefCell::new(HashMap::new()),
+            lock_members: builder.lock_members,
+            has_instance_data: builder.has_instance_data,
+            parent: builder.parent.take().map(|parent| parent.to_string()),
+            prototype: None,
         };
 
         builder.properties.drain().all(|e| {
@@ -208,6 +415,15 @@ impl Proxy {
             true
         });
 
+        for (symbol_code, method) in builder.symbol_methods.drain(..) {
+            ret.symbol_methods.insert(
+                well_known_symbol_function_name(cx, symbol_code),
+                (symbol_code, method),
+            );
+        }
+        let symbol_values: Vec<(WellKnownSymbol, EsValueFacade)> =
+            builder.symbol_values.drain(..).collect();
+
         builder.native_methods.drain().all(|e| {
             ret.native_methods.insert(e.0, e.1);
             true
@@ -228,6 +444,13 @@ impl Proxy {
             true
         });
 
+        for (symbol_code, method) in builder.static_symbol_methods.drain(..) {
+            ret.static_symbol_methods.insert(
+                well_known_symbol_function_name(cx, symbol_code),
+                (symbol_code, method),
+            );
+        }
+
         builder.static_native_methods.drain().all(|e| {
             ret.static_native_methods.insert(e.0, e.1);
             true
@@ -264,6 +487,11 @@ impl Proxy {
             cname_root.handle(),
         );
 
+        ret.init_prototype(
+            cx,
+            unsafe { mozjs::rust::HandleObject::from_marked_location(&(func as *mut JSObject)) },
+            symbol_values,
+        );
         ret.init_static_properties(cx, unsafe {
             mozjs::rust::HandleObject::from_marked_location(&(func as *mut JSObject))
         });
@@ -294,6 +522,43 @@ impl Proxy {
         }
     }
 
+    fn get_method_arity(&self, name: &str) -> u32 {
+        self.method_arities
+            .get(name)
+            .cloned()
+            .unwrap_or(DEFAULT_METHOD_ARITY)
+    }
+
+    fn get_property_flags(&self, name: &str) -> PropertyFlags {
+        self.property_flags.get(name).cloned().unwrap_or_default()
+    }
+
+    fn get_static_method_arity(&self, name: &str) -> u32 {
+        self.static_method_arities
+            .get(name)
+            .cloned()
+            .unwrap_or(DEFAULT_METHOD_ARITY)
+    }
+
+    /// get the metadata of this proxy class
+    pub fn get_info(&self) -> ProxyInfo {
+        ProxyInfo {
+            canonical_name: self.get_canonical_name(),
+            constructable: self.constructor.is_some(),
+            callable: self.callable.is_some(),
+            methods: sorted_names(self.methods.keys().chain(self.native_methods.keys())),
+            properties: sorted_names(self.properties.keys()),
+            events: sorted_names(self.events.iter()),
+            static_methods: sorted_names(
+                self.static_methods
+                    .keys()
+                    .chain(self.static_native_methods.keys()),
+            ),
+            static_properties: sorted_names(self.static_properties.keys()),
+            static_events: sorted_names(self.static_events.iter()),
+        }
+    }
+
     /// instantiate a new instance of the proxy class
     pub fn new_instance(
         &self,
@@ -301,8 +566,15 @@ impl Proxy {
         obj_id: i32,
         mut return_handle: MutableHandleValue,
     ) -> Result<(), EsErrorInfo> {
-        let obj_instance: *mut JSObject =
-            unsafe { mozjs::jsapi::JS_NewObject(cx, &ES_PROXY_CLASS) };
+        let class: &JSClass = if self.callable.is_some() {
+            &ES_PROXY_CALLABLE_CLASS
+        } else {
+            &ES_PROXY_CLASS
+        };
+        rooted!(in (cx) let proto_root = self.get_prototype());
+        let obj_instance: *mut JSObject = unsafe {
+            mozjs::jsapi::JS_NewObjectWithGivenProto(cx, class, proto_root.handle().into())
+        };
 
         rooted!(in (cx) let obj_instance_root = obj_instance);
         rooted!(in (cx) let mut pname_root = UndefinedValue());
@@ -339,10 +611,15 @@ impl Proxy {
 
         return_handle.set(ObjectValue(obj_instance));
 
+        if let Some(on_instance_created) = &self.on_instance_created {
+            on_instance_created(obj_id);
+        }
+
         Ok(())
     }
 
     /// dispatch an event for a specific instance of the proxy class
+    /// a listener which throws does not stop the other listeners, its error is passed to the error callback of the runtime (see EsRuntimeBuilder::error_callback())
     pub fn dispatch_event(
         &self,
         obj_id: i32,
@@ -353,6 +630,73 @@ impl Proxy {
         dispatch_event_for_proxy(cx, self, obj_id, event_name, event_obj);
     }
 
+    /// dispatch an event for a specific instance of the proxy class and collect the return values of the listeners
+    /// this fails with the error of the first listener which throws, the listeners after it are not called
+    pub fn dispatch_event_collect(
+        &self,
+        obj_id: i32,
+        event_name: &str,
+        cx: *mut JSContext,
+        event_obj: mozjs::jsapi::HandleValue,
+    ) -> Result<Vec<EsValueFacade>, EsErrorInfo> {
+        call_event_listeners(cx, self, obj_id, event_name, event_obj, false)
+            .map(|(results, _canceled)| results)
+    }
+
+    /// dispatch an event for a specific instance of the proxy class which may be canceled by a listener
+    /// a listener cancels the event by returning false or by calling preventDefault() on an event object which has a defaultPrevented property,
+    /// the listeners after it are not called
+    /// returns false if the event was canceled
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    /// use mozjs::jsval::UndefinedValue;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let proxy = ProxyBuilder::new(vec![], "Door")
+    ///         .constructor(|_cx, _args| Ok(1))
+    ///         .event("close")
+    ///         .build(cx, global);
+    ///         sm_rt.eval("let d = new Door(); d.addEventListener('close', () => false);", "dispatch_cancelable_event.es")
+    ///             .ok().expect("script failed");
+    ///         rooted!(in (cx) let evt_root = UndefinedValue());
+    ///         let not_canceled = proxy.dispatch_cancelable_event(1, "close", cx, evt_root.handle().into())
+    ///             .ok().expect("listener failed");
+    ///         assert!(!not_canceled);
+    ///     })
+    /// });
+    /// ```
+    pub fn dispatch_cancelable_event(
+        &self,
+        obj_id: i32,
+        event_name: &str,
+        cx: *mut JSContext,
+        event_obj: mozjs::jsapi::HandleValue,
+    ) -> Result<bool, EsErrorInfo> {
+        call_event_listeners(cx, self, obj_id, event_name, event_obj, true)
+            .map(|(_results, canceled)| !canceled)
+    }
+
+    /// get the number of listeners for an event of a specific instance of the proxy class
+    pub fn listener_count(&self, obj_id: i32, event_name: &str) -> usize {
+        let pel = &*self.event_listeners.borrow();
+        pel.get(&obj_id)
+            .and_then(|obj_map| obj_map.get(event_name))
+            .map(|listener_vec| listener_vec.len())
+            .unwrap_or(0)
+    }
+
+    /// remove all event listeners of a specific instance of the proxy class
+    /// this releases the listener functions right away instead of when the instance is garbage collected
+    /// this should not be called from a listener of the same proxy class while an event is being dispatched
+    pub fn clear_event_listeners(&self, obj_id: i32) {
+        let pel = &mut *self.event_listeners.borrow_mut();
+        pel.remove(&obj_id);
+    }
+
     /// dispatch a static event for the proxy class
     pub fn dispatch_static_event(
         &self,
@@ -363,9 +707,109 @@ impl Proxy {
         dispatch_static_event_for_proxy(cx, self, event_name, event_obj);
     }
 
+    fn init_prototype(
+        &mut self,
+        cx: *mut JSContext,
+        func: HandleObject,
+        symbol_values: Vec<(WellKnownSymbol, EsValueFacade)>,
+    ) {
+        rooted!(in (cx) let mut proto_root = NULL_JSOBJECT);
+        if let Some(parent_name) = &self.parent {
+            let parent = get_proxy(parent_name.as_str()).unwrap_or_else(|| {
+                panic!(
+                    "proxy {} extends {} which was not built",
+                    self.get_canonical_name(),
+                    parent_name
+                )
+            });
+            rooted!(in (cx) let parent_proto_root = parent.get_prototype());
+            crate::jsapi_utils::objects::new_object_from_prototype(
+                cx,
+                parent_proto_root.handle(),
+                proto_root.handle_mut(),
+            )
+            .ok()
+            .expect("could not create prototype");
+        } else {
+            crate::jsapi_utils::objects::new_object(cx, proto_root.handle_mut());
+        }
+
+        // the methods are shared by all instances, the method trampoline gets the obj_id from this
+        for name in sorted_names(self.methods.keys()) {
+            define_member_function(
+                cx,
+                proto_root.handle(),
+                name.as_str(),
+                self.get_method_arity(name.as_str()),
+                Some(proxy_instance_method),
+                self.lock_members,
+            );
+        }
+
+        // the name of a function with a Symbol key is "[Symbol.xxx]", the method trampoline uses that to find the method
+        for (symbol_code, _method) in self.symbol_methods.values() {
+            rooted!(in (cx) let mut id_root = mozjs::jsapi::PropertyKey::default());
+            jsapi_utils::iterators::get_well_known_symbol_id(
+                cx,
+                *symbol_code,
+                id_root.handle_mut(),
+            );
+            unsafe {
+                mozjs::jsapi::JS_DefineFunctionById(
+                    cx,
+                    proto_root.handle().into(),
+                    id_root.handle().into(),
+                    Some(proxy_instance_method),
+                    0,
+                    member_attrs(self.lock_members),
+                );
+            }
+        }
+
+        for (symbol_code, value) in symbol_values {
+            rooted!(in (cx) let mut id_root = mozjs::jsapi::PropertyKey::default());
+            jsapi_utils::iterators::get_well_known_symbol_id(cx, symbol_code, id_root.handle_mut());
+            rooted!(in (cx) let mut value_root = UndefinedValue());
+            value.to_es_value(cx, value_root.handle_mut());
+            unsafe {
+                mozjs::jsapi::JS_DefinePropertyById2(
+                    cx,
+                    proto_root.handle().into(),
+                    id_root.handle().into(),
+                    value_root.handle().into(),
+                    member_attrs(self.lock_members),
+                );
+            }
+        }
+
+        rooted!(in (cx) let proto_val_root = ObjectValue(*proto_root));
+        crate::jsapi_utils::objects::set_es_obj_prop_val_permanent(
+            cx,
+            func,
+            "prototype",
+            proto_val_root.handle(),
+        );
+        rooted!(in (cx) let func_val_root = ObjectValue(func.get()));
+        crate::jsapi_utils::objects::set_es_obj_prop_val_permanent(
+            cx,
+            proto_root.handle(),
+            "constructor",
+            func_val_root.handle(),
+        );
+
+        self.prototype = Some(EsPersistentRooted::new_from_obj(cx, *proto_root));
+    }
+
+    fn get_prototype(&self) -> *mut JSObject {
+        self.prototype
+            .as_ref()
+            .map(|proto| proto.get())
+            .unwrap_or(ptr::null_mut())
+    }
+
     fn init_static_properties(&self, cx: *mut JSContext, func: HandleObject) {
         // this is actually how static_props should work, not instance props.. they should be resolved from the proxy_op
-        for prop_name in self.static_properties.keys() {
+        for (prop_name, prop) in &self.static_properties {
             // https://doc.servo.org/mozjs/jsapi/fn.JS_DefineProperty1.html
             // mozjs::jsapi::JS_DefineProperty1
             // todo move this to jsapi_utils::object
@@ -377,10 +821,12 @@ impl Proxy {
                     func.into(),
                     n.as_ptr() as *const libc::c_char,
                     Some(proxy_static_getter),
-                    Some(proxy_static_setter),
-                    (mozjs::jsapi::JSPROP_PERMANENT
-                        & mozjs::jsapi::JSPROP_GETTER
-                        & mozjs::jsapi::JSPROP_SETTER) as u32,
+                    if prop.1.is_some() {
+                        Some(proxy_static_setter)
+                    } else {
+                        None
+                    },
+                    accessor_attrs(self.lock_members),
                 )
             };
             assert!(ok);
@@ -391,12 +837,33 @@ impl Proxy {
         trace!("init static methods for {}", self.class_name);
         for method_name in self.static_methods.keys() {
             trace!("init static method {} for {}", method_name, self.class_name);
-            crate::jsapi_utils::functions::define_native_function(
+            define_member_function(
                 cx,
                 func,
                 method_name,
+                self.get_static_method_arity(method_name),
                 Some(proxy_static_method),
+                self.lock_members,
+            );
+        }
+        // like the symbol methods of instances these are found by their name "[Symbol.xxx]"
+        for (symbol_code, _method) in self.static_symbol_methods.values() {
+            rooted!(in (cx) let mut id_root = mozjs::jsapi::PropertyKey::default());
+            jsapi_utils::iterators::get_well_known_symbol_id(
+                cx,
+                *symbol_code,
+                id_root.handle_mut(),
             );
+            unsafe {
+                mozjs::jsapi::JS_DefineFunctionById(
+                    cx,
+                    func.into(),
+                    id_root.handle().into(),
+                    Some(proxy_static_method),
+                    0,
+                    member_attrs(self.lock_members),
+                );
+            }
         }
         for native_method_name in self.static_native_methods.keys() {
             trace!(
@@ -409,32 +876,40 @@ impl Proxy {
                 .get(native_method_name)
                 .cloned()
                 .unwrap();
-            crate::jsapi_utils::functions::define_native_function(
+            define_member_function(
                 cx,
                 func,
                 native_method_name,
+                self.get_static_method_arity(native_method_name),
                 method,
+                self.lock_members,
             );
         }
     }
     fn init_static_events(&self, cx: *mut JSContext, func: HandleObject) {
-        crate::jsapi_utils::functions::define_native_function(
+        define_member_function(
             cx,
             func,
             "addEventListener",
+            2,
             Some(proxy_static_add_event_listener),
+            self.lock_members,
         );
-        crate::jsapi_utils::functions::define_native_function(
+        define_member_function(
             cx,
             func,
             "removeEventListener",
+            2,
             Some(proxy_static_remove_event_listener),
+            self.lock_members,
         );
-        crate::jsapi_utils::functions::define_native_function(
+        define_member_function(
             cx,
             func,
             "dispatchEvent",
+            2,
             Some(proxy_static_dispatch_event),
+            self.lock_members,
         );
     }
 }
@@ -461,14 +936,26 @@ impl ProxyBuilder {
             class_name,
             constructor: None,
             finalizer: None,
+            on_instance_created: None,
+            callable: None,
             properties: HashMap::new(),
+            property_flags: HashMap::new(),
             methods: HashMap::new(),
+            symbol_methods: vec![],
+            symbol_values: vec![],
             native_methods: HashMap::new(),
+            method_arities: HashMap::new(),
+            dynamic_methods: None,
             events: HashSet::new(),
             static_properties: HashMap::new(),
             static_methods: HashMap::new(),
+            static_symbol_methods: vec![],
             static_native_methods: HashMap::new(),
+            static_method_arities: HashMap::new(),
             static_events: HashSet::new(),
+            lock_members: false,
+            parent: None,
+            has_instance_data: false,
         }
     }
 
@@ -501,6 +988,57 @@ impl ProxyBuilder {
         self
     }
 
+    /// function to call when the Proxy is constructed, unlike constructor() this returns the rust object of the instance
+    /// the object is stored by the runtime and dropped when the instance is garbage collected, so you don't need a finalizer for it
+    /// the obj_id of the instance is generated by the runtime, use with_instance_data() to get the object in methods, getters and setters
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::{with_instance_data, ProxyBuilder};
+    /// use mozjs::jsval::Int32Value;
+    /// use std::any::Any;
+    ///
+    /// struct Counter {
+    ///     start: i32,
+    /// }
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyCounter")
+    ///         .constructor_with_data(|_cx, _args| {
+    ///             let data: Box<dyn Any> = Box::new(Counter { start: 10 });
+    ///             Ok(data)
+    ///         })
+    ///         .method("getStart", |_cx, obj_id, _args, mut rval| {
+    ///             let start = with_instance_data(obj_id, |counter: &Counter| counter.start)
+    ///                 .ok_or_else(|| "no counter found".to_string())?;
+    ///             rval.set(Int32Value(start));
+    ///             Ok(())
+    ///         })
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// let esvf = rt.eval_sync("new com.mybiz.MyCounter().getStart();",
+    ///     "test_jsapi_proxy_constructor_with_data.es")
+    ///     .ok().expect("script failed");
+    /// assert_eq!(esvf.get_i32(), 10);
+    /// ```
+    pub fn constructor_with_data<C>(&mut self, constructor: C) -> &mut Self
+    where
+        C: Fn(*mut JSContext, Vec<HandleValue>) -> Result<Box<dyn Any>, String> + 'static,
+    {
+        self.has_instance_data = true;
+        self.constructor(move |cx, args| {
+            let data = constructor(cx, args)?;
+            let obj_id = next_instance_id();
+            PROXY_INSTANCE_DATA.with(|data_rc| {
+                data_rc.borrow_mut().insert(obj_id, Rc::from(data));
+            });
+            Ok(obj_id)
+        })
+    }
+
     /// this closure is called when the instance of the Proxy is garbage collected,
     /// you should use this to cleanup any instances you may have created in rust
     pub fn finalizer<F>(&mut self, finalizer: F) -> &mut Self
@@ -511,88 +1049,585 @@ impl ProxyBuilder {
         self
     }
 
-    /// add a getter and setter
-    pub fn property<G, S>(&mut self, name: &'static str, getter: G, setter: S) -> &mut Self
-    where
-        G: Fn(*mut JSContext, i32, MutableHandleValue) -> Result<(), String> + 'static,
-        S: Fn(*mut JSContext, i32, HandleValue) -> Result<(), String> + 'static,
-    {
-        self.properties
-            .insert(name, (Box::new(getter), Box::new(setter)));
-        self
-    }
-
-    /// add a static getter and setter
-    pub fn static_property<G, S>(&mut self, name: &'static str, getter: G, setter: S) -> &mut Self
+    /// this closure is called with the obj_id right after a new instance of the Proxy was created,
+    /// both for instances constructed in script and for instances created from rust with new_instance()
+    /// this is the counterpart of finalizer(), use it to register the instance in your own bookkeeping
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyTrackedClass")
+    ///         .constructor(|_cx, _args| {
+    ///             Ok(1)
+    ///         })
+    ///         .on_instance_created(|id: i32| {
+    ///             println!("instance {} was created", id);
+    ///         })
+    ///         .finalizer(|id: i32| {
+    ///             println!("instance {} was finalized", id);
+    ///         })
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// ```
+    pub fn on_instance_created<F>(&mut self, on_instance_created: F) -> &mut Self
     where
-        G: Fn(*mut JSContext, MutableHandleValue) -> Result<(), String> + 'static,
-        S: Fn(*mut JSContext, HandleValue) -> Result<(), String> + 'static,
+        F: Fn(i32) + 'static,
     {
-        self.static_properties
-            .insert(name, (Box::new(getter), Box::new(setter)));
+        self.on_instance_created = Some(Box::new(on_instance_created));
         self
     }
 
-    /// add a method to the proxy class
-    /// thse can be called from js as if it were members of the instance
+    /// make the instances of the proxy callable like a function, e.g. `let i = new MyClass(); i(1, 2);`
+    /// the closure is called with the id of the instance and the arguments of the call
     /// # Example
     /// ```no_run
     /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
     /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    /// use mozjs::jsval::Int32Value;
     ///
     /// let rt = EsRuntimeBuilder::new().build();
     /// rt.do_in_es_event_queue_sync(|sm_rt| {
     ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
-    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyClass")
-    ///         .constructor(|cx, args| {
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyCallableClass")
+    ///         .constructor(|_cx, _args| {
     ///             Ok(1)
     ///         })
-    ///         .method("doSomething", |_cx, obj_id, _args, _rval|{
-    ///             println!("do something for obj: {}", obj_id);
+    ///         .callable(|_cx, _obj_id, args, mut rval| {
+    ///             rval.set(Int32Value(args.len() as i32));
     ///             Ok(())
     ///         })
     ///         .build(cx, global);
     ///     })
     /// });
-    /// rt.eval_sync("let i = new com.mybiz.MyClass(); i.doSomething();",
-    ///     "test_jsapi_proxy_method.es")
+    /// let esvf = rt.eval_sync("let i = new com.mybiz.MyCallableClass(); i(1, 2, 3);",
+    ///     "test_jsapi_proxy_callable.es")
     ///     .ok().expect("script failed");
+    /// assert_eq!(esvf.get_i32(), 3);
     /// ```
-    pub fn method<M>(&mut self, name: &'static str, method: M) -> &mut Self
+    pub fn callable<C>(&mut self, callable: C) -> &mut Self
     where
-        M: Fn(*mut JSContext, i32, Vec<HandleValue>, MutableHandleValue) -> Result<(), String>
+        C: Fn(*mut JSContext, i32, Vec<HandleValue>, MutableHandleValue) -> Result<(), String>
             + 'static,
     {
-        self.methods.insert(name, Box::new(method));
-        self
-    }
-
-    /// add a native method
-    pub fn native_method<M>(&mut self, name: &'static str, method: JSNative) -> &mut Self {
-        self.native_methods.insert(name, method);
+        self.callable = Some(Box::new(callable));
         self
     }
 
-    /// add a static method
-    pub fn static_method<M>(&mut self, name: &'static str, method: M) -> &mut Self
+    /// add a getter and setter
+    /// when the setter returns a SetterError::TypeError or SetterError::RangeError script gets an error of that class
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::{ProxyBuilder, SetterError};
+    /// use mozjs::jsval::Int32Value;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+    ///         let _proxy_arc = ProxyBuilder::new(vec![], "Person")
+    ///             .constructor(|_cx, _args| Ok(1))
+    ///             .property("age",
+    ///                 |_cx, _obj_id, mut rval| {
+    ///                     rval.set(Int32Value(30));
+    ///                     Ok(())
+    ///                 },
+    ///                 |_cx, _obj_id, val| {
+    ///                     if val.is_int32() && val.to_int32() < 0 {
+    ///                         return Err(SetterError::RangeError("age should not be negative".to_string()));
+    ///                     }
+    ///                     Ok(())
+    ///                 })
+    ///             .build(cx, global);
+    ///     })
+    /// });
+    /// let esvf = rt.eval_sync("try {new Person().age = -1;} catch(ex) {ex.name;}", "test_property_range_error.es")
+    ///     .ok().expect("script failed");
+    /// assert_eq!(esvf.get_string(), "RangeError");
+    /// ```
+    pub fn property<G, S>(&mut self, name: &'static str, getter: G, setter: S) -> &mut Self
     where
-        M: Fn(*mut JSContext, Vec<HandleValue>, MutableHandleValue) -> Result<(), String> + 'static,
+        G: Fn(*mut JSContext, i32, MutableHandleValue) -> Result<(), String> + 'static,
+        S: Fn(*mut JSContext, i32, HandleValue) -> Result<(), SetterError> + 'static,
     {
-        self.static_methods.insert(name, Box::new(method));
+        self.properties
+            .insert(name, (Box::new(getter), Some(Box::new(setter))));
         self
     }
 
-    /// add a static native method
-    pub fn static_native_method(&mut self, name: &'static str, method: JSNative) -> &mut Self {
-        self.static_native_methods.insert(name, method);
+    /// add a getter without a setter
+    /// assigning a value to the property will throw a TypeError in strict mode and is ignored otherwise
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    /// use mozjs::jsval::Int32Value;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyReadOnlyClass")
+    ///             .constructor(|_cx, _args| Ok(1))
+    ///             .readonly_property("answer", |_cx, _obj_id, mut rval| {
+    ///                 rval.set(Int32Value(42));
+    ///                 Ok(())
+    ///             })
+    ///             .build(cx, global);
+    ///     })
+    /// });
+    /// let esvf = rt.eval_sync("'use strict'; let i = new com.mybiz.MyReadOnlyClass(); i.answer;",
+    ///     "test_jsapi_proxy_readonly.es")
+    ///     .ok().expect("script failed");
+    /// assert_eq!(esvf.get_i32(), 42);
+    /// ```
+    pub fn readonly_property<G>(&mut self, name: &'static str, getter: G) -> &mut Self
+    where
+        G: Fn(*mut JSContext, i32, MutableHandleValue) -> Result<(), String> + 'static,
+    {
+        self.properties.insert(name, (Box::new(getter), None));
         self
     }
 
-    /// create the proxy class, please not that this can only be used once on a builder
-    pub fn build(&mut self, cx: *mut JSContext, scope: HandleObject) -> Arc<Proxy> {
-        Proxy::new(cx, scope, self)
-    }
-
+    /// add a getter and setter with flags which determine if the property is enumerable and configurable
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::{ProxyBuilder, PropertyFlags};
+    /// use mozjs::jsval::Int32Value;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyEnumerableClass")
+    ///             .constructor(|_cx, _args| Ok(1))
+    ///             .property_with_flags("size", PropertyFlags { enumerable: true, configurable: false },
+    ///                 |_cx, _obj_id, mut rval| {
+    ///                     rval.set(Int32Value(3));
+    ///                     Ok(())
+    ///                 },
+    ///                 |_cx, _obj_id, _val| Ok(()))
+    ///             .build(cx, global);
+    ///     })
+    /// });
+    /// let esvf = rt.eval_sync("Object.keys(new com.mybiz.MyEnumerableClass()).join(',');",
+    ///     "test_jsapi_proxy_property_with_flags.es")
+    ///     .ok().expect("script failed");
+    /// assert_eq!(esvf.get_string(), "size");
+    /// ```
+    pub fn property_with_flags<G, S>(
+        &mut self,
+        name: &'static str,
+        flags: PropertyFlags,
+        getter: G,
+        setter: S,
+    ) -> &mut Self
+    where
+        G: Fn(*mut JSContext, i32, MutableHandleValue) -> Result<(), String> + 'static,
+        S: Fn(*mut JSContext, i32, HandleValue) -> Result<(), SetterError> + 'static,
+    {
+        self.property_flags.insert(name, flags);
+        self.property(name, getter, setter)
+    }
+
+    /// add a getter without a setter with flags which determine if the property is enumerable and configurable
+    pub fn readonly_property_with_flags<G>(
+        &mut self,
+        name: &'static str,
+        flags: PropertyFlags,
+        getter: G,
+    ) -> &mut Self
+    where
+        G: Fn(*mut JSContext, i32, MutableHandleValue) -> Result<(), String> + 'static,
+    {
+        self.property_flags.insert(name, flags);
+        self.readonly_property(name, getter)
+    }
+
+    /// add a static getter and setter
+    pub fn static_property<G, S>(&mut self, name: &'static str, getter: G, setter: S) -> &mut Self
+    where
+        G: Fn(*mut JSContext, MutableHandleValue) -> Result<(), String> + 'static,
+        S: Fn(*mut JSContext, HandleValue) -> Result<(), SetterError> + 'static,
+    {
+        self.static_properties
+            .insert(name, (Box::new(getter), Some(Box::new(setter))));
+        self
+    }
+
+    /// add a static getter without a setter
+    /// assigning a value to the property will throw a TypeError in strict mode and is ignored otherwise
+    pub fn static_readonly_property<G>(&mut self, name: &'static str, getter: G) -> &mut Self
+    where
+        G: Fn(*mut JSContext, MutableHandleValue) -> Result<(), String> + 'static,
+    {
+        self.static_properties
+            .insert(name, (Box::new(getter), None));
+        self
+    }
+
+    /// add a getter which returns a Promise, the getter is run in a helper thread and the Promise is resolved with its result
+    /// this is useful for properties which are slow to get (e.g. loaded from a database), in script you can use await obj.prop
+    /// because the getter does not run in the worker thread of the runtime it does not get a JSContext
+    /// the property has no setter
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global| {
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyRecord")
+    ///             .constructor(|_cx, _args| Ok(1))
+    ///             .property_async_getter("data", |obj_id| {
+    ///                 // load data for obj_id here
+    ///                 Ok(EsValueFacade::new_str(format!("data of {}", obj_id)))
+    ///             })
+    ///             .build(cx, global);
+    ///     })
+    /// });
+    /// rt.eval_sync("(async function(){let r = new com.mybiz.MyRecord(); let d = await r.data; console.log(d);})();",
+    ///     "test_jsapi_proxy_async_getter.es")
+    ///     .ok().expect("script failed");
+    /// ```
+    pub fn property_async_getter<G>(&mut self, name: &'static str, getter: G) -> &mut Self
+    where
+        G: Fn(i32) -> Result<EsValueFacade, String> + Send + Sync + 'static,
+    {
+        let getter = Arc::new(getter);
+        self.readonly_property(name, move |cx, obj_id, rval| {
+            let getter = getter.clone();
+            let prom_esvf = EsValueFacade::new_promise(move || getter(obj_id));
+            prom_esvf.to_es_value(cx, rval);
+            Ok(())
+        })
+    }
+
+    /// add a method to the proxy class
+    /// thse can be called from js as if it were members of the instance
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyClass")
+    ///         .constructor(|cx, args| {
+    ///             Ok(1)
+    ///         })
+    ///         .method("doSomething", |_cx, obj_id, _args, _rval|{
+    ///             println!("do something for obj: {}", obj_id);
+    ///             Ok(())
+    ///         })
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// rt.eval_sync("let i = new com.mybiz.MyClass(); i.doSomething();",
+    ///     "test_jsapi_proxy_method.es")
+    ///     .ok().expect("script failed");
+    /// ```
+    pub fn method<M>(&mut self, name: &'static str, method: M) -> &mut Self
+    where
+        M: Fn(*mut JSContext, i32, Vec<HandleValue>, MutableHandleValue) -> Result<(), String>
+            + 'static,
+    {
+        self.methods.insert(name, Box::new(method));
+        self
+    }
+
+    /// add a method with a declared arity, the arity is reflected by the length property of the method
+    pub fn method_with_arity<M>(&mut self, name: &'static str, nargs: u32, method: M) -> &mut Self
+    where
+        M: Fn(*mut JSContext, i32, Vec<HandleValue>, MutableHandleValue) -> Result<(), String>
+            + 'static,
+    {
+        self.method_arities.insert(name, nargs);
+        self.method(name, method)
+    }
+
+    /// add a method keyed by a well known Symbol, e.g. Symbol.iterator to make the instances iterable in a for of loop
+    /// the method is defined on the prototype of the proxy like the other methods
+    /// Symbols which are looked up on the constructor (like Symbol.hasInstance) should be added with static_symbol_method()
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::{ProxyBuilder, WellKnownSymbol};
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyRange")
+    ///         .constructor(|_cx, _args| Ok(1))
+    ///         .symbol_method(WellKnownSymbol::iterator, |cx, _obj_id, _args, rval| {
+    ///             let mut x = 0;
+    ///             spidermonkey_runtime::jsapi_utils::iterators::new_iterator(cx, Box::new(move || {
+    ///                 x += 1;
+    ///                 if x <= 3 { Some(EsValueFacade::new_i32(x)) } else { None }
+    ///             }), rval);
+    ///             Ok(())
+    ///         })
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// rt.eval_sync("for (const x of new com.mybiz.MyRange()) {console.log(x);}",
+    ///     "test_jsapi_proxy_symbol_method.es")
+    ///     .ok().expect("script failed");
+    /// ```
+    pub fn symbol_method<M>(&mut self, symbol: WellKnownSymbol, method: M) -> &mut Self
+    where
+        M: Fn(*mut JSContext, i32, Vec<HandleValue>, MutableHandleValue) -> Result<(), String>
+            + 'static,
+    {
+        self.symbol_methods.push((symbol, Box::new(method)));
+        self
+    }
+
+    /// add a value keyed by a well known Symbol to the prototype of the proxy, e.g. a String for Symbol.toStringTag
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::{ProxyBuilder, WellKnownSymbol};
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyTagged")
+    ///         .constructor(|_cx, _args| Ok(1))
+    ///         .symbol_value(WellKnownSymbol::toStringTag, EsValueFacade::new_str("MyTagged".to_string()))
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// let esvf = rt.eval_sync("Object.prototype.toString.call(new com.mybiz.MyTagged());",
+    ///     "test_jsapi_proxy_symbol_value.es")
+    ///     .ok().expect("script failed");
+    /// assert_eq!(esvf.get_string(), "[object MyTagged]");
+    /// ```
+    pub fn symbol_value(&mut self, symbol: WellKnownSymbol, value: EsValueFacade) -> &mut Self {
+        self.symbol_values.push((symbol, value));
+        self
+    }
+
+    /// add a method which returns an async iterator, script can use the result in a for await loop
+    /// the method returns the producer of the values, every call to next() runs it in a helper thread
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyResultSet")
+    ///         .constructor(|_cx, _args| Ok(1))
+    ///         .async_iterator_method("rows", |_cx, _obj_id, _args| {
+    ///             let mut row = 0;
+    ///             Ok(Box::new(move || {
+    ///                 // fetch the next row here
+    ///                 row += 1;
+    ///                 Ok(if row <= 10 { Some(EsValueFacade::new_i32(row)) } else { None })
+    ///             }))
+    ///         })
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// rt.eval_sync("(async function(){for await (const row of new com.mybiz.MyResultSet().rows()) {console.log(row);}})();",
+    ///     "test_jsapi_proxy_async_iterator_method.es")
+    ///     .ok().expect("script failed");
+    /// ```
+    pub fn async_iterator_method<M>(&mut self, name: &'static str, method: M) -> &mut Self
+    where
+        M: Fn(*mut JSContext, i32, Vec<HandleValue>) -> Result<AsyncIteratorNext, String> + 'static,
+    {
+        self.method(name, move |cx, obj_id, args, rval| {
+            let next = method(cx, obj_id, args)?;
+            jsapi_utils::iterators::new_async_iterator(cx, next, rval);
+            Ok(())
+        })
+    }
+
+    /// add a handler for methods whose names are not known when building the proxy
+    /// the filter is consulted when script gets a member of an instance which is not a registered property or method,
+    /// if it returns true a method with that name is defined on the instance which calls the handler
+    /// the handler returns Ok(true) if it handled the call or Ok(false) if it did not, which results in an error in script
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyDynamicClass")
+    ///         .constructor(|_cx, _args| Ok(1))
+    ///         .dynamic_method_handler(
+    ///             |_obj_id, name| name.starts_with("do"),
+    ///             |_cx, obj_id, name, _args, _rval| {
+    ///                 println!("{} for obj: {}", name, obj_id);
+    ///                 Ok(true)
+    ///             },
+    ///         )
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// rt.eval_sync("let i = new com.mybiz.MyDynamicClass(); i.doSomething();",
+    ///     "test_jsapi_proxy_dynamic_method_handler.es")
+    ///     .ok().expect("script failed");
+    /// ```
+    pub fn dynamic_method_handler<F, M>(&mut self, filter: F, handler: M) -> &mut Self
+    where
+        F: Fn(i32, &str) -> bool + 'static,
+        M: Fn(
+                *mut JSContext,
+                i32,
+                &str,
+                Vec<HandleValue>,
+                MutableHandleValue,
+            ) -> Result<bool, String>
+            + 'static,
+    {
+        self.dynamic_methods = Some((Box::new(filter), Box::new(handler)));
+        self
+    }
+
+    /// add a native method
+    pub fn native_method<M>(&mut self, name: &'static str, method: JSNative) -> &mut Self {
+        self.native_methods.insert(name, method);
+        self
+    }
+
+    /// add a static method
+    pub fn static_method<M>(&mut self, name: &'static str, method: M) -> &mut Self
+    where
+        M: Fn(*mut JSContext, Vec<HandleValue>, MutableHandleValue) -> Result<(), String> + 'static,
+    {
+        self.static_methods.insert(name, Box::new(method));
+        self
+    }
+
+    /// add a static method with a declared arity, the arity is reflected by the length property of the method
+    pub fn static_method_with_arity<M>(
+        &mut self,
+        name: &'static str,
+        nargs: u32,
+        method: M,
+    ) -> &mut Self
+    where
+        M: Fn(*mut JSContext, Vec<HandleValue>, MutableHandleValue) -> Result<(), String> + 'static,
+    {
+        self.static_method_arities.insert(name, nargs);
+        self.static_method(name, method)
+    }
+
+    /// add a static method keyed by a well known Symbol, the method is defined on the constructor of the proxy
+    /// e.g. a method for Symbol.hasInstance customizes the instanceof operator for the class
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::{ProxyBuilder, WellKnownSymbol};
+    /// use mozjs::jsval::BooleanValue;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyDuck")
+    ///         .constructor(|_cx, _args| Ok(1))
+    ///         .static_symbol_method(WellKnownSymbol::hasInstance, |_cx, args, rval| {
+    ///             rval.set(BooleanValue(args[0].is_object()));
+    ///             Ok(())
+    ///         })
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// let esvf = rt.eval_sync("({quack: true}) instanceof com.mybiz.MyDuck;",
+    ///     "test_jsapi_proxy_static_symbol_method.es")
+    ///     .ok().expect("script failed");
+    /// assert!(esvf.get_boolean());
+    /// ```
+    pub fn static_symbol_method<M>(&mut self, symbol: WellKnownSymbol, method: M) -> &mut Self
+    where
+        M: Fn(*mut JSContext, Vec<HandleValue>, MutableHandleValue) -> Result<(), String> + 'static,
+    {
+        self.static_symbol_methods.push((symbol, Box::new(method)));
+        self
+    }
+
+    /// add a static native method
+    pub fn static_native_method(&mut self, name: &'static str, method: JSNative) -> &mut Self {
+        self.static_native_methods.insert(name, method);
+        self
+    }
+
+    /// define the methods and properties of the proxy (and its instances) as readonly and permanent
+    /// so script can not overwrite or delete them, defaults to false
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _proxy = ProxyBuilder::new(vec!["com", "mybiz"], "MyLockedClass")
+    ///         .static_method("doIt", |_cx, _args, _rval| Ok(()))
+    ///         .lock_members(true)
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// rt.eval_sync("com.mybiz.MyLockedClass.doIt = null; com.mybiz.MyLockedClass.doIt();",
+    ///     "test_jsapi_proxy_lock_members.es")
+    ///     .ok().expect("script failed");
+    /// ```
+    pub fn lock_members(&mut self, lock: bool) -> &mut Self {
+        self.lock_members = lock;
+        self
+    }
+
+    /// let this proxy class extend another proxy class, the parent proxy needs to be built first
+    /// the prototype of the class links to the prototype of the parent so instanceof works for the parent class,
+    /// properties and methods of the parent can be used on instances of this class, static members are not inherited
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use spidermonkey_runtime::jsapi_utils::reflection::ProxyBuilder;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.do_with_jsapi(|_rt, cx, global|{
+    ///         let _animal = ProxyBuilder::new(vec!["zoo"], "Animal")
+    ///         .constructor(|_cx, _args| Ok(1))
+    ///         .method("breathe", |_cx, _obj_id, _args, _rval| Ok(()))
+    ///         .build(cx, global);
+    ///         let _dog = ProxyBuilder::new(vec!["zoo"], "Dog")
+    ///         .constructor(|_cx, _args| Ok(2))
+    ///         .extends("zoo.Animal")
+    ///         .method("bark", |_cx, _obj_id, _args, _rval| Ok(()))
+    ///         .build(cx, global);
+    ///     })
+    /// });
+    /// rt.eval_sync("let d = new zoo.Dog(); d.bark(); d.breathe(); d instanceof zoo.Animal;",
+    ///     "test_jsapi_proxy_extends.es")
+    ///     .ok().expect("script failed");
+    /// ```
+    pub fn extends(&mut self, parent_canonical_name: &'static str) -> &mut Self {
+        self.parent = Some(parent_canonical_name);
+        self
+    }
+
+    /// create the proxy class, please not that this can only be used once on a builder
+    pub fn build(&mut self, cx: *mut JSContext, scope: HandleObject) -> Arc<Proxy> {
+        Proxy::new(cx, scope, self)
+    }
+
     /// define an event_type for this proxy class
     pub fn event(&mut self, evt_type: &'static str) -> &mut Self {
         self.events.insert(evt_type);
@@ -617,166 +1652,1081 @@ mod tests {
     use mozjs::rust::HandleValue;
 
     #[test]
-    fn test_proxy() {
-        log::info!("test_proxy");
+    fn test_proxy() {
+        log::info!("test_proxy");
+        let rt = init_test_runtime();
+
+        rt.do_with_inner(|inner| {
+            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.do_with_jsapi(|_rt, cx, global| {
+                    let _proxy_arc = ProxyBuilder::new(vec![],"TestClass1")
+                        .constructor(|cx: *mut JSContext, args: Vec<HandleValue>| {
+                            // this will run in the event queue workerthread so global is rooted here
+                            debug!("proxytest: construct");
+                            let name = if !args.is_empty() {
+                                let hv = args.get(0).unwrap();
+                                es_value_to_str(cx, **hv).ok().unwrap()
+                            } else {
+                                "NoName".to_string()
+                            };
+                            debug!("proxytest: construct with name {}", name);
+                            Ok(1)
+                        })
+                        .property("foo", |_cx, obj_id, mut rval| {
+                            debug!("proxy.get foo {}", obj_id);
+                            rval.set(Int32Value(123));
+                            Ok(())
+                        }, |_cx, obj_id, _val| {
+                            debug!("proxy.set foo {}", obj_id);
+                            Ok(())
+                        })
+                        .property("bar", |_cx, _obj_id, mut rval| {
+                            rval.set(Int32Value(456));
+                            Ok(())
+                        }, |_cx, _obj_id, _val| {
+                            Ok(())
+                        })
+                        .finalizer(|id: i32| {
+                            debug!("proxytest: finalize id {}", id);
+                        })
+                        .method("methodA", |_cx, obj_id, args, _rval| {
+                            trace!("proxy.methodA called for obj {} with {} args", obj_id, args.len());
+                            Ok(())
+                        })
+                        .method("methodB", |_cx, obj_id, args, _rval| {
+                            trace!("proxy.methodB called for obj {} with {} args", obj_id, args.len());
+                            Ok(())
+                        })
+                        .event("saved")
+                        .build(cx, global);
+                    let esvf = sm_rt
+                        .eval(
+                            "// create a new instance of your Proxy\n\
+                                      let tp_obj = new TestClass1('bar'); \n\
+                                      // you can set props that are not proxied \n\
+                                      tp_obj.abc = 1; console.log('tp_obj.abc = %s', tp_obj.abc); \n\
+                                      // test you getter and setter\n\
+                                      let tp_i = tp_obj.foo; tp_obj.foo = 987; \n\
+                                      // test your method\n\
+                                      tp_obj.methodA(1, 2, 3);tp_obj.methodA(1, 2, 3);tp_obj.methodA(1, 2, 3); \n\
+                                      tp_obj.methodB(true); \n\
+                                      // add an event listener\n\
+                                      tp_obj.addEventListener('saved', (evt) => {console.log('tp_obj was saved');}); \n\
+                                      // dispatch an event from script\n\
+                                      tp_obj.dispatchEvent('saved', {}); \n\
+                                      // allow you object to be GCed\n\
+                                      tp_obj = null; tp_i;",
+                            "test_proxy.es",
+                        )
+                        .ok()
+                        .unwrap();
+                    assert_eq!(123, esvf.get_i32());
+                });
+            });
+            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.cleanup();
+            });
+        });
+    }
+
+    #[test]
+    fn test_static_proxy() {
+        log::info!("test_static_proxy");
+        let rt = init_test_runtime();
+
+        rt.do_with_inner(|inner| {
+            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.do_with_jsapi(|_rt, cx, global| {
+                    let _proxy_arc = ProxyBuilder::new(vec![],"TestClass2")
+                        .static_property("foo", |_cx, mut rval| {
+                            debug!("static_proxy.get foo");
+                            rval.set(Int32Value(123));
+                            Ok(())
+                        }, |_cx, _val| {
+                            debug!("static_proxy.set foo");
+                            Ok(())
+                        })
+                        .static_property("bar", |_cx, mut rval| {
+                            rval.set(Int32Value(456));
+                            Ok(())
+                        }, |_cx, _val| {
+                            Ok(())
+                        })
+                        .static_method("methodA", |_cx, args, _rval| {
+                            trace!("static_proxy.methodA called with {} args", args.len());
+                            Ok(())
+                        })
+                        .static_method("methodB", |_cx, args, _rval| {
+                            trace!("static_proxy.methodB called with {} args", args.len());
+                            Ok(())
+                        })
+                        .static_event("saved")
+                        .build(cx, global);
+                    let esvf = sm_rt
+                        .eval(
+                            "// you can set props that are not proxied \n\
+                                      TestClass2.abc = 1; console.log('TestClass2.abc = %s', TestClass2.abc); \n\
+                                      // test you getter and setter\n\
+                                      let tsp_i = TestClass2.foo; TestClass2.foo = 987; \n\
+                                      // test your method\n\
+                                      TestClass2.methodA(1, 2, 3);TestClass2.methodA(1, 2, 3);TestClass2.methodA(1, 2, 3); \n\
+                                      TestClass2.methodB(true); \n\
+                                      // add an event listener\n\
+                                      TestClass2.addEventListener('saved', (evt) => {console.log('TestClass2 was saved');}); \n\
+                                      // dispatch an event from script\n\
+                                      TestClass2.dispatchEvent('saved', {}); tsp_i;",
+                            "test_static_proxy.es",
+                        )
+                        .ok()
+                        .unwrap();
+                    assert_eq!(123, esvf.get_i32());
+                });
+            });
+            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.cleanup();
+            });
+        });
+    }
+
+    #[test]
+    fn test_proxy_nonconstructable() {
+        log::info!("test_proxy_nonconstructable");
+        let rt = init_test_runtime();
+
+        rt.do_with_inner(|inner| {
+            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.do_with_jsapi(|_rt, cx, global| {
+                    let _proxy_arc = ProxyBuilder::new(vec![], "TestClass3")
+                        .method("test", |_cx, _obj_id, _args, _rval| Ok(()))
+                        .build(cx, global);
+                    let _err = sm_rt
+                        .eval(
+                            "let t = new TestClass3();",
+                            "test_proxy_nonconstructable.es",
+                        )
+                        .err()
+                        .expect("well that should have failed");
+                });
+            });
+            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.cleanup();
+            });
+        });
+    }
+
+    #[test]
+    fn test_list_proxies() {
+        log::info!("test_list_proxies");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy_arc = ProxyBuilder::new(vec!["test", "info"], "TestClass4")
+                    .constructor(|_cx, _args| Ok(1))
+                    .property(
+                        "foo",
+                        |_cx, _obj_id, _rval| Ok(()),
+                        |_cx, _obj_id, _val| Ok(()),
+                    )
+                    .method("methodB", |_cx, _obj_id, _args, _rval| Ok(()))
+                    .method("methodA", |_cx, _obj_id, _args, _rval| Ok(()))
+                    .event("saved")
+                    .static_method("create", |_cx, _args, _rval| Ok(()))
+                    .static_event("created")
+                    .build(cx, global);
+            });
+        });
+
+        let infos = rt.list_proxies_sync();
+
+        let info = infos
+            .iter()
+            .find(|info| info.canonical_name.eq("test.info.TestClass4"))
+            .expect("proxy not listed");
+        assert!(info.constructable);
+        assert_eq!(info.methods, vec!["methodA", "methodB"]);
+        assert_eq!(info.properties, vec!["foo"]);
+        assert_eq!(info.events, vec!["saved"]);
+        assert_eq!(info.static_methods, vec!["create"]);
+        assert!(info.static_properties.is_empty());
+        assert_eq!(info.static_events, vec!["created"]);
+
+        let console_info = infos
+            .iter()
+            .find(|info| info.canonical_name.eq("console"))
+            .expect("console not listed");
+        assert!(!console_info.constructable);
+        assert!(console_info.static_methods.contains(&"log".to_string()));
+    }
+
+    #[test]
+    fn test_proxy_lock_members() {
+        log::info!("test_proxy_lock_members");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _locked = ProxyBuilder::new(vec![], "TestClass5")
+                    .constructor(|_cx, _args| Ok(1))
+                    .method("methodA", |_cx, _obj_id, _args, mut rval| {
+                        rval.set(Int32Value(5));
+                        Ok(())
+                    })
+                    .property(
+                        "foo",
+                        |_cx, _obj_id, mut rval| {
+                            rval.set(Int32Value(6));
+                            Ok(())
+                        },
+                        |_cx, _obj_id, _val| Ok(()),
+                    )
+                    .lock_members(true)
+                    .build(cx, global);
+                let _unlocked = ProxyBuilder::new(vec![], "TestClass6")
+                    .constructor(|_cx, _args| Ok(1))
+                    .method("methodA", |_cx, _obj_id, _args, _rval| Ok(()))
+                    .build(cx, global);
+            });
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let tc5 = new TestClass5(); tc5.methodA = function(){return 1;}; delete tc5.methodA; delete tc5.foo; tc5.methodA() + tc5.foo;",
+                "test_proxy_lock_members.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 11);
+
+        let res = rt.eval_sync(
+            "(function(){'use strict'; let tc5b = new TestClass5(); tc5b.methodA = null;})();",
+            "test_proxy_lock_members2.es",
+        );
+        assert!(res.is_err());
+
+        let esvf = rt
+            .eval_sync(
+                "let tc6 = new TestClass6(); tc6.methodA = function(){return 1;}; tc6.methodA();",
+                "test_proxy_lock_members3.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 1);
+    }
+
+    #[test]
+    fn test_proxy_callable() {
+        log::info!("test_proxy_callable");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy_arc = ProxyBuilder::new(vec![], "TestClass7")
+                    .constructor(|_cx, _args| Ok(7))
+                    .callable(|_cx, obj_id, args, mut rval| {
+                        assert_eq!(obj_id, 7);
+                        let mut total = 0;
+                        for arg in args {
+                            assert!(arg.is_int32());
+                            total += arg.to_int32();
+                        }
+                        rval.set(Int32Value(total));
+                        Ok(())
+                    })
+                    .build(cx, global);
+            });
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let tc7 = new TestClass7(); typeof tc7 === 'function' ? tc7(1, 2, 3) : -1;",
+                "test_proxy_callable.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 6);
+    }
+
+    #[test]
+    fn test_proxy_readonly_property() {
+        log::info!("test_proxy_readonly_property");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy = ProxyBuilder::new(vec![], "TestClass9")
+                    .constructor(|_cx, _args| Ok(1))
+                    .readonly_property("foo", |_cx, _obj_id, mut rval| {
+                        rval.set(Int32Value(7));
+                        Ok(())
+                    })
+                    .static_readonly_property("bar", |_cx, mut rval| {
+                        rval.set(Int32Value(8));
+                        Ok(())
+                    })
+                    .build(cx, global);
+            });
+        });
+
+        // sloppy mode ignores the assignment
+        let esvf = rt
+            .eval_sync(
+                "let tc9 = new TestClass9(); tc9.foo = 1; TestClass9.bar = 1; tc9.foo + TestClass9.bar;",
+                "test_proxy_readonly_property.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_i32(), 15);
+
+        // strict mode throws
+        let res = rt.eval_sync(
+            "(function(){'use strict'; let tc9b = new TestClass9(); tc9b.foo = 1;})();",
+            "test_proxy_readonly_property2.es",
+        );
+        assert!(res.is_err());
+        let res = rt.eval_sync(
+            "(function(){'use strict'; TestClass9.bar = 1;})();",
+            "test_proxy_readonly_property3.es",
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_proxy_constructor_with_data() {
+        log::info!("test_proxy_constructor_with_data");
+        let rt = init_test_runtime();
+
+        struct TestData {
+            name: String,
+            dropped: Arc<std::sync::atomic::AtomicBool>,
+        }
+        impl Drop for TestData {
+            fn drop(&mut self) {
+                self.dropped
+                    .store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let dropped2 = dropped.clone();
+
+        rt.do_in_es_event_queue_sync(move |sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy = ProxyBuilder::new(vec![], "TestClass11")
+                    .constructor_with_data(move |cx, args| {
+                        let name = es_value_to_str(cx, *args[0]).ok().expect("not a string");
+                        let data: Box<dyn std::any::Any> = Box::new(TestData {
+                            name,
+                            dropped: dropped2.clone(),
+                        });
+                        Ok(data)
+                    })
+                    .method("getName", |cx, obj_id, _args, rval| {
+                        let name = with_instance_data(obj_id, |data: &TestData| data.name.clone())
+                            .ok_or_else(|| "no data".to_string())?;
+                        crate::jsapi_utils::new_es_value_from_str(cx, name.as_str(), rval);
+                        Ok(())
+                    })
+                    .method("getWrongType", |_cx, obj_id, _args, mut rval| {
+                        let found = with_instance_data(obj_id, |_data: &String| ()).is_some();
+                        rval.set(mozjs::jsval::BooleanValue(found));
+                        Ok(())
+                    })
+                    .build(cx, global);
+            });
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "(function(){let tc11 = new TestClass11('abc'); let tc11b = new TestClass11('def'); return tc11.getName() + tc11b.getName() + tc11.getWrongType();})();",
+                "test_proxy_constructor_with_data.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "abcdeffalse");
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                crate::jsapi_utils::gc(cx);
+            });
+        });
+        assert!(dropped.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_proxy_property_flags() {
+        log::info!("test_proxy_property_flags");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let enumerable = PropertyFlags {
+                    enumerable: true,
+                    configurable: true,
+                };
+                let _proxy = ProxyBuilder::new(vec![], "TestClass12")
+                    .constructor(|_cx, _args| Ok(1))
+                    .readonly_property_with_flags("a", enumerable, |_cx, _obj_id, mut rval| {
+                        rval.set(mozjs::jsval::Int32Value(1));
+                        Ok(())
+                    })
+                    .readonly_property("b", |_cx, _obj_id, mut rval| {
+                        rval.set(mozjs::jsval::Int32Value(2));
+                        Ok(())
+                    })
+                    .property_with_flags(
+                        "c",
+                        enumerable,
+                        |_cx, _obj_id, mut rval| {
+                            rval.set(mozjs::jsval::Int32Value(3));
+                            Ok(())
+                        },
+                        |_cx, _obj_id, _val| Ok(()),
+                    )
+                    .method("m", |_cx, _obj_id, _args, _rval| Ok(()))
+                    .build(cx, global);
+            });
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "(function(){let tc12 = new TestClass12(); let names = Object.getOwnPropertyNames(tc12); \
+                 let for_in = []; for (let p in tc12) {for_in.push(p);} \
+                 return Object.keys(tc12).join(',') + '|' + for_in.join(',') + '|' + names.includes('b') + \
+                 Object.getOwnPropertyNames(TestClass12.prototype).includes('m');})();",
+                "test_proxy_property_flags.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "a,c|a,c|truetrue");
+    }
+
+    #[test]
+    fn test_proxy_async_getter() {
+        log::info!("test_proxy_async_getter");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy = ProxyBuilder::new(vec![], "TestClass10")
+                    .constructor(|_cx, _args| Ok(10))
+                    .property_async_getter("data", |obj_id| {
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                        Ok(crate::esvaluefacade::EsValueFacade::new_i32(obj_id * 2))
+                    })
+                    .build(cx, global);
+            });
+        });
+
+        let prom_esvf = rt
+            .eval_sync(
+                "(async function(){let tc10 = new TestClass10(); let d = await tc10.data; return d + 1;})();",
+                "test_proxy_async_getter.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(prom_esvf.is_promise());
+        let res = prom_esvf
+            .get_promise_result_blocking(std::time::Duration::from_secs(5))
+            .ok()
+            .expect("promise timed out")
+            .ok()
+            .expect("promise was rejected");
+        assert_eq!(res.get_i32(), 21);
+    }
+
+    #[test]
+    fn test_proxy_async_iterator_method() {
+        log::info!("test_proxy_async_iterator_method");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy = ProxyBuilder::new(vec![], "TestClass13")
+                    .constructor(|_cx, _args| Ok(13))
+                    .async_iterator_method("range", |_cx, obj_id, _args| {
+                        let mut x = 0;
+                        Ok(Box::new(move || {
+                            x += 1;
+                            Ok(if x <= 3 {
+                                Some(crate::esvaluefacade::EsValueFacade::new_i32(obj_id + x))
+                            } else {
+                                None
+                            })
+                        }))
+                    })
+                    .build(cx, global);
+            });
+        });
+
+        let prom_esvf = rt
+            .eval_sync(
+                "(async function(){let r = []; for await (const x of new TestClass13().range()) {r.push(x);} return r.join(',');})();",
+                "test_proxy_async_iterator_method.es",
+            )
+            .ok()
+            .expect("script failed");
+        let res = prom_esvf
+            .get_promise_result_blocking(std::time::Duration::from_secs(5))
+            .ok()
+            .expect("promise timed out")
+            .ok()
+            .expect("promise was rejected");
+        assert_eq!(res.get_string(), "14,15,16");
+    }
+
+    #[test]
+    fn test_proxy_dynamic_method_handler() {
+        log::info!("test_proxy_dynamic_method_handler");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy = ProxyBuilder::new(vec![], "TestClass14")
+                    .constructor(|_cx, _args| Ok(14))
+                    .method("fixed", |_cx, _obj_id, _args, mut rval| {
+                        rval.set(Int32Value(1));
+                        Ok(())
+                    })
+                    .dynamic_method_handler(
+                        |_obj_id, name| name != "notAMethod",
+                        |cx, obj_id, name, args, rval| {
+                            if name == "unsupported" {
+                                return Ok(false);
+                            }
+                            let arg = es_value_to_str(cx, *args[0]).ok().unwrap();
+                            crate::jsapi_utils::new_es_value_from_str(
+                                cx,
+                                format!("{}:{}:{}", name, obj_id, arg).as_str(),
+                                rval,
+                            );
+                            Ok(true)
+                        },
+                    )
+                    .build(cx, global);
+            });
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let tc14 = new TestClass14(); [tc14.anythingGoes('a'), tc14.fixed(), typeof tc14.notAMethod].join(',');",
+                "test_proxy_dynamic_method_handler.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "anythingGoes:14:a,1,undefined");
+
+        let err = rt
+            .eval_sync(
+                "new TestClass14().unsupported('b');",
+                "test_proxy_dynamic_method_handler2.es",
+            )
+            .err()
+            .expect("unsupported method should fail");
+        assert!(err.message.contains("method unsupported is not supported"));
+    }
+
+    #[test]
+    fn test_proxy_extends() {
+        log::info!("test_proxy_extends");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _animal = ProxyBuilder::new(vec!["zoo"], "TestClass15")
+                    .constructor(|_cx, _args| Ok(15))
+                    .readonly_property("legs", |_cx, _obj_id, mut rval| {
+                        rval.set(Int32Value(4));
+                        Ok(())
+                    })
+                    .method("describe", |cx, obj_id, _args, rval| {
+                        crate::jsapi_utils::new_es_value_from_str(
+                            cx,
+                            format!("animal {}", obj_id).as_str(),
+                            rval,
+                        );
+                        Ok(())
+                    })
+                    .method("speak", |cx, _obj_id, _args, rval| {
+                        crate::jsapi_utils::new_es_value_from_str(cx, "...", rval);
+                        Ok(())
+                    })
+                    .build(cx, global);
+                let _dog = ProxyBuilder::new(vec!["zoo"], "TestClass16")
+                    .constructor(|_cx, _args| Ok(16))
+                    .extends("zoo.TestClass15")
+                    .method("speak", |cx, _obj_id, _args, rval| {
+                        crate::jsapi_utils::new_es_value_from_str(cx, "woof", rval);
+                        Ok(())
+                    })
+                    .build(cx, global);
+            });
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let tc16 = new zoo.TestClass16(); let tc15 = new zoo.TestClass15(); \
+                [tc16 instanceof zoo.TestClass16, tc16 instanceof zoo.TestClass15, tc15 instanceof zoo.TestClass16, \
+                tc16.speak(), tc16.describe(), tc16.legs, tc15.speak()].join(',');",
+                "test_proxy_extends.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "true,true,false,woof,animal 16,4,...");
+    }
+
+    #[test]
+    fn test_proxy_cancelable_event() {
+        log::info!("test_proxy_cancelable_event");
+        let rt = init_test_runtime();
+
+        let res = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let proxy = ProxyBuilder::new(vec![], "TestClass17")
+                    .constructor(|_cx, _args| Ok(17))
+                    .event("close")
+                    .build(cx, global);
+
+                sm_rt
+                    .eval(
+                        "globalThis.test_tc17_calls = []; let tc17 = new TestClass17(); \
+                        tc17.addEventListener('close', () => {test_tc17_calls.push('a'); return 1;}); \
+                        tc17.addEventListener('close', () => {test_tc17_calls.push('b'); return false;}); \
+                        tc17.addEventListener('close', () => {test_tc17_calls.push('c');});",
+                        "test_proxy_cancelable_event.es",
+                    )
+                    .ok()
+                    .expect("script failed");
+
+                rooted!(in (cx) let evt_root = mozjs::jsval::UndefinedValue());
+                let not_canceled = proxy
+                    .dispatch_cancelable_event(17, "close", cx, evt_root.handle().into())
+                    .ok()
+                    .expect("listener failed");
+                let calls = sm_rt
+                    .eval("test_tc17_calls.join(',');", "test_proxy_cancelable_event2.es")
+                    .ok()
+                    .expect("script failed")
+                    .get_string()
+                    .to_string();
+
+                let results = proxy
+                    .dispatch_event_collect(17, "close", cx, evt_root.handle().into())
+                    .ok()
+                    .expect("listener failed");
+                let results: Vec<String> = results
+                    .iter()
+                    .map(|esvf| {
+                        if esvf.is_i32() {
+                            esvf.get_i32().to_string()
+                        } else if esvf.is_boolean() {
+                            esvf.get_boolean().to_string()
+                        } else {
+                            "undefined".to_string()
+                        }
+                    })
+                    .collect();
+
+                (not_canceled, calls, results)
+            })
+        });
+
+        assert!(!res.0);
+        // the listener after the canceling listener was not called
+        assert_eq!(res.1, "a,b");
+        assert_eq!(res.2, vec!["1", "false", "undefined"]);
+    }
+
+    #[test]
+    fn test_proxy_setter_typed_errors() {
+        log::info!("test_proxy_setter_typed_errors");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy_arc = ProxyBuilder::new(vec![], "TestClass18")
+                    .constructor(|_cx, _args| Ok(18))
+                    .property(
+                        "age",
+                        |_cx, _obj_id, mut rval| {
+                            rval.set(mozjs::jsval::Int32Value(18));
+                            Ok(())
+                        },
+                        |_cx, _obj_id, val| {
+                            if !val.is_int32() {
+                                Err(SetterError::TypeError("age should be a number".to_string()))
+                            } else if val.to_int32() < 0 {
+                                Err(SetterError::RangeError(
+                                    "age should not be negative".to_string(),
+                                ))
+                            } else if val.to_int32() > 200 {
+                                Err("age is not realistic".into())
+                            } else {
+                                Ok(())
+                            }
+                        },
+                    )
+                    .build(cx, global);
+            })
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let tc18 = new TestClass18(); let res = []; \
+                for (let val of [-1, 'old', 300, 20]) { \
+                    try {tc18.age = val; res.push('ok');} catch(ex) {res.push(ex.name + ':' + ex.message);} \
+                } \
+                res.join('|');",
+                "test_proxy_setter_typed_errors.es",
+            )
+            .ok()
+            .expect("script failed");
+        let res = esvf.get_string();
+        let parts: Vec<&str> = res.split('|').collect();
+        assert_eq!(
+            parts[0],
+            "RangeError:setter age failed: age should not be negative"
+        );
+        assert_eq!(
+            parts[1],
+            "TypeError:setter age failed: age should be a number"
+        );
+        assert!(parts[2].starts_with("Error:setter age failed"));
+        assert_eq!(parts[3], "ok");
+    }
+
+    #[test]
+    fn test_proxy_shared_methods() {
+        log::info!("test_proxy_shared_methods");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let next_id = std::cell::Cell::new(0);
+                let _proxy_arc = ProxyBuilder::new(vec![], "TestClass19")
+                    .constructor(move |_cx, _args| {
+                        next_id.set(next_id.get() + 1);
+                        Ok(next_id.get())
+                    })
+                    .method("getId", |_cx, obj_id, _args, mut rval| {
+                        rval.set(mozjs::jsval::Int32Value(obj_id));
+                        Ok(())
+                    })
+                    .build(cx, global);
+            })
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "(function(){let instances = []; for (let x = 0; x < 1000; x++) {instances.push(new TestClass19());} \
+                 let first = instances[0]; \
+                 let shared = instances.every((i) => i.getId === first.getId && !Object.prototype.hasOwnProperty.call(i, 'getId')); \
+                 let ids_ok = instances.every((i, idx) => i.getId() === idx + 1); \
+                 return [shared, ids_ok, first.getId === TestClass19.prototype.getId].join(',');})();",
+                "test_proxy_shared_methods.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "true,true,true");
+    }
+
+    #[test]
+    fn test_proxy_throwing_listener() {
+        log::info!("test_proxy_throwing_listener");
+        let errors = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let errors2 = errors.clone();
+        let rt = crate::esruntimebuilder::EsRuntimeBuilder::new()
+            .error_callback(Box::new(move |err| {
+                errors2.lock().unwrap().push(err.message);
+            }))
+            .build();
+
+        let calls = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let proxy = ProxyBuilder::new(vec![], "TestClass20")
+                    .constructor(|_cx, _args| Ok(20))
+                    .event("change")
+                    .build(cx, global);
+
+                sm_rt
+                    .eval(
+                        "globalThis.test_tc20_calls = []; let tc20 = new TestClass20(); \
+                        tc20.addEventListener('change', () => {throw Error('listener failed');}); \
+                        tc20.addEventListener('change', () => {test_tc20_calls.push('b');}); \
+                        tc20.dispatchEvent('change', {});",
+                        "test_proxy_throwing_listener.es",
+                    )
+                    .ok()
+                    .expect("script failed");
+
+                rooted!(in (cx) let evt_root = mozjs::jsval::UndefinedValue());
+                proxy.dispatch_event(20, "change", cx, evt_root.handle().into());
+
+                sm_rt
+                    .eval(
+                        "test_tc20_calls.join(',');",
+                        "test_proxy_throwing_listener2.es",
+                    )
+                    .ok()
+                    .expect("script failed")
+                    .get_string()
+                    .to_string()
+            })
+        });
+
+        // the listener after the throwing listener was called for both dispatches
+        assert_eq!(calls, "b,b");
+        let errors = &*errors.lock().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("listener failed"));
+    }
+
+    #[test]
+    fn test_proxy_instance_from_rust() {
+        log::info!("test_proxy_instance_from_rust");
+        let rt = init_test_runtime();
+
+        let instance = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy_arc = ProxyBuilder::new(vec!["zoo"], "TestClass21")
+                    .constructor(|_cx, _args| Ok(1))
+                    .method("describe", |cx, obj_id, _args, rval| {
+                        crate::jsapi_utils::new_es_value_from_str(
+                            cx,
+                            format!("instance {}", obj_id).as_str(),
+                            rval,
+                        );
+                        Ok(())
+                    })
+                    .build(cx, global);
+
+                rooted!(in (cx) let mut rval = mozjs::jsval::UndefinedValue());
+                assert!(new_instance(cx, "zoo.NoSuchClass", 1, rval.handle_mut()).is_err());
+                assert!(EsValueFacade::new_proxy_instance(cx, "zoo.NoSuchClass", 1).is_err());
+
+                let instance = EsValueFacade::new_proxy_instance(cx, "zoo.TestClass21", 21)
+                    .ok()
+                    .expect("could not create instance");
+
+                // the same instance is passed every time
+                rooted!(in (cx) let mut first_root = mozjs::jsval::UndefinedValue());
+                rooted!(in (cx) let mut second_root = mozjs::jsval::UndefinedValue());
+                instance.to_es_value(cx, first_root.handle_mut());
+                instance.to_es_value(cx, second_root.handle_mut());
+                assert_eq!(first_root.to_object(), second_root.to_object());
+
+                instance
+            })
+        });
+
+        rt.eval_sync(
+            "function test_tc21_describe(obj){return (obj instanceof zoo.TestClass21) + ':' + obj.describe();}",
+            "test_proxy_instance_from_rust.es",
+        )
+        .ok()
+        .expect("script failed");
+        let res = rt
+            .call_sync(vec![], "test_tc21_describe", vec![instance])
+            .ok()
+            .expect("call failed");
+        assert_eq!(res.get_string(), "true:instance 21");
+    }
+
+    #[test]
+    fn test_proxy_clear_event_listeners() {
+        log::info!("test_proxy_clear_event_listeners");
+        let rt = init_test_runtime();
+
+        let counts = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let next_id = std::cell::Cell::new(21);
+                let proxy = ProxyBuilder::new(vec![], "TestClass22")
+                    .constructor(move |_cx, _args| {
+                        next_id.set(next_id.get() + 1);
+                        Ok(next_id.get())
+                    })
+                    .event("open")
+                    .event("close")
+                    .build(cx, global);
+
+                sm_rt
+                    .eval(
+                        "globalThis.test_tc22_calls = 0; let tc22a = new TestClass22(); let tc22b = new TestClass22(); \
+                        tc22a.addEventListener('open', () => {test_tc22_calls++;}); \
+                        tc22a.addEventListener('open', () => {test_tc22_calls++;}); \
+                        tc22a.addEventListener('close', () => {test_tc22_calls++;}); \
+                        tc22b.addEventListener('open', () => {test_tc22_calls++;});",
+                        "test_proxy_clear_event_listeners.es",
+                    )
+                    .ok()
+                    .expect("script failed");
+
+                let before = (
+                    proxy.listener_count(22, "open"),
+                    proxy.listener_count(22, "close"),
+                    proxy.listener_count(23, "open"),
+                );
+                proxy.clear_event_listeners(22);
+                let after = (
+                    proxy.listener_count(22, "open"),
+                    proxy.listener_count(22, "close"),
+                    proxy.listener_count(23, "open"),
+                );
+
+                rooted!(in (cx) let evt_root = mozjs::jsval::UndefinedValue());
+                proxy.dispatch_event(22, "open", cx, evt_root.handle().into());
+                let calls = sm_rt
+                    .eval("test_tc22_calls;", "test_proxy_clear_event_listeners2.es")
+                    .ok()
+                    .expect("script failed")
+                    .get_i32();
+                (before, after, calls)
+            })
+        });
+
+        assert_eq!(counts.0, (2, 1, 1));
+        // only the listeners of the cleared instance were removed
+        assert_eq!(counts.1, (0, 0, 1));
+        assert_eq!(counts.2, 0);
+    }
+
+    #[test]
+    fn test_proxy_symbol_members() {
+        log::info!("test_proxy_symbol_members");
+        let rt = init_test_runtime();
+
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                ProxyBuilder::new(vec![], "TestClass24")
+                    .constructor(|_cx, _args| Ok(24))
+                    .symbol_value(
+                        WellKnownSymbol::toStringTag,
+                        EsValueFacade::new_str("TestClass24".to_string()),
+                    )
+                    .symbol_method(WellKnownSymbol::iterator, |cx, obj_id, _args, rval| {
+                        let mut x = 0;
+                        jsapi_utils::iterators::new_iterator(
+                            cx,
+                            Box::new(move || {
+                                x += 1;
+                                if x <= 3 {
+                                    Some(EsValueFacade::new_i32(obj_id + x))
+                                } else {
+                                    None
+                                }
+                            }),
+                            rval,
+                        );
+                        Ok(())
+                    })
+                    .build(cx, global);
+            })
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let tc24 = new TestClass24(); Object.prototype.toString.call(tc24) + ':' + [...tc24].join(',');",
+                "test_proxy_symbol_members.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "[object TestClass24]:25,26,27");
+    }
+
+    #[test]
+    fn test_proxy_static_symbol_method() {
+        log::info!("test_proxy_static_symbol_method");
         let rt = init_test_runtime();
 
-        rt.do_with_inner(|inner| {
-            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
-                sm_rt.do_with_jsapi(|_rt, cx, global| {
-                    let _proxy_arc = ProxyBuilder::new(vec![],"TestClass1")
-                        .constructor(|cx: *mut JSContext, args: Vec<HandleValue>| {
-                            // this will run in the event queue workerthread so global is rooted here
-                            debug!("proxytest: construct");
-                            let name = if !args.is_empty() {
-                                let hv = args.get(0).unwrap();
-                                es_value_to_str(cx, **hv).ok().unwrap()
-                            } else {
-                                "NoName".to_string()
-                            };
-                            debug!("proxytest: construct with name {}", name);
-                            Ok(1)
-                        })
-                        .property("foo", |_cx, obj_id, mut rval| {
-                            debug!("proxy.get foo {}", obj_id);
-                            rval.set(Int32Value(123));
-                            Ok(())
-                        }, |_cx, obj_id, _val| {
-                            debug!("proxy.set foo {}", obj_id);
-                            Ok(())
-                        })
-                        .property("bar", |_cx, _obj_id, mut rval| {
-                            rval.set(Int32Value(456));
-                            Ok(())
-                        }, |_cx, _obj_id, _val| {
-                            Ok(())
-                        })
-                        .finalizer(|id: i32| {
-                            debug!("proxytest: finalize id {}", id);
-                        })
-                        .method("methodA", |_cx, obj_id, args, _rval| {
-                            trace!("proxy.methodA called for obj {} with {} args", obj_id, args.len());
-                            Ok(())
-                        })
-                        .method("methodB", |_cx, obj_id, args, _rval| {
-                            trace!("proxy.methodB called for obj {} with {} args", obj_id, args.len());
-                            Ok(())
-                        })
-                        .event("saved")
-                        .build(cx, global);
-                    let esvf = sm_rt
-                        .eval(
-                            "// create a new instance of your Proxy\n\
-                                      let tp_obj = new TestClass1('bar'); \n\
-                                      // you can set props that are not proxied \n\
-                                      tp_obj.abc = 1; console.log('tp_obj.abc = %s', tp_obj.abc); \n\
-                                      // test you getter and setter\n\
-                                      let tp_i = tp_obj.foo; tp_obj.foo = 987; \n\
-                                      // test your method\n\
-                                      tp_obj.methodA(1, 2, 3);tp_obj.methodA(1, 2, 3);tp_obj.methodA(1, 2, 3); \n\
-                                      tp_obj.methodB(true); \n\
-                                      // add an event listener\n\
-                                      tp_obj.addEventListener('saved', (evt) => {console.log('tp_obj was saved');}); \n\
-                                      // dispatch an event from script\n\
-                                      tp_obj.dispatchEvent('saved', {}); \n\
-                                      // allow you object to be GCed\n\
-                                      tp_obj = null; tp_i;",
-                            "test_proxy.es",
-                        )
-                        .ok()
-                        .unwrap();
-                    assert_eq!(123, esvf.get_i32());
-                });
-            });
-            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
-                sm_rt.cleanup();
-            });
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                ProxyBuilder::new(vec![], "TestClass25")
+                    .constructor(|_cx, _args| Ok(25))
+                    .static_symbol_method(WellKnownSymbol::hasInstance, |_cx, args, rval| {
+                        rval.set(mozjs::jsval::BooleanValue(args[0].is_object()));
+                        Ok(())
+                    })
+                    .build(cx, global);
+            })
         });
+
+        let esvf = rt
+            .eval_sync(
+                "({}) instanceof TestClass25 && !(1 instanceof TestClass25) && TestClass25[Symbol.hasInstance].name === '[Symbol.hasInstance]';",
+                "test_proxy_static_symbol_method.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.get_boolean());
     }
 
     #[test]
-    fn test_static_proxy() {
-        log::info!("test_static_proxy");
+    fn test_proxy_on_instance_created() {
+        log::info!("test_proxy_on_instance_created");
         let rt = init_test_runtime();
 
-        rt.do_with_inner(|inner| {
-            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
-                sm_rt.do_with_jsapi(|_rt, cx, global| {
-                    let _proxy_arc = ProxyBuilder::new(vec![],"TestClass2")
-                        .static_property("foo", |_cx, mut rval| {
-                            debug!("static_proxy.get foo");
-                            rval.set(Int32Value(123));
-                            Ok(())
-                        }, |_cx, _val| {
-                            debug!("static_proxy.set foo");
-                            Ok(())
-                        })
-                        .static_property("bar", |_cx, mut rval| {
-                            rval.set(Int32Value(456));
-                            Ok(())
-                        }, |_cx, _val| {
-                            Ok(())
-                        })
-                        .static_method("methodA", |_cx, args, _rval| {
-                            trace!("static_proxy.methodA called with {} args", args.len());
-                            Ok(())
-                        })
-                        .static_method("methodB", |_cx, args, _rval| {
-                            trace!("static_proxy.methodB called with {} args", args.len());
-                            Ok(())
-                        })
-                        .static_event("saved")
-                        .build(cx, global);
-                    let esvf = sm_rt
-                        .eval(
-                            "// you can set props that are not proxied \n\
-                                      TestClass2.abc = 1; console.log('TestClass2.abc = %s', TestClass2.abc); \n\
-                                      // test you getter and setter\n\
-                                      let tsp_i = TestClass2.foo; TestClass2.foo = 987; \n\
-                                      // test your method\n\
-                                      TestClass2.methodA(1, 2, 3);TestClass2.methodA(1, 2, 3);TestClass2.methodA(1, 2, 3); \n\
-                                      TestClass2.methodB(true); \n\
-                                      // add an event listener\n\
-                                      TestClass2.addEventListener('saved', (evt) => {console.log('TestClass2 was saved');}); \n\
-                                      // dispatch an event from script\n\
-                                      TestClass2.dispatchEvent('saved', {}); tsp_i;",
-                            "test_static_proxy.es",
-                        )
-                        .ok()
-                        .unwrap();
-                    assert_eq!(123, esvf.get_i32());
-                });
-            });
-            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
-                sm_rt.cleanup();
-            });
+        let created = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let created = Rc::new(RefCell::new(vec![]));
+                let created2 = created.clone();
+                let next_id = std::cell::Cell::new(30);
+                let proxy = ProxyBuilder::new(vec![], "TestClass23")
+                    .constructor(move |_cx, _args| {
+                        next_id.set(next_id.get() + 1);
+                        Ok(next_id.get())
+                    })
+                    .on_instance_created(move |obj_id| {
+                        created2.borrow_mut().push(obj_id);
+                    })
+                    .build(cx, global);
+
+                sm_rt
+                    .eval(
+                        "let tc23a = new TestClass23(); let tc23b = new TestClass23();",
+                        "test_proxy_on_instance_created.es",
+                    )
+                    .ok()
+                    .expect("script failed");
+
+                rooted!(in (cx) let mut inst_root = mozjs::jsval::UndefinedValue());
+                proxy
+                    .new_instance(cx, 40, inst_root.handle_mut())
+                    .ok()
+                    .expect("new_instance failed");
+
+                let ret = created.borrow().clone();
+                ret
+            })
         });
+
+        assert_eq!(created, vec![31, 32, 40]);
     }
 
     #[test]
-    fn test_proxy_nonconstructable() {
-        log::info!("test_proxy_nonconstructable");
+    fn test_proxy_method_arity() {
+        log::info!("test_proxy_method_arity");
         let rt = init_test_runtime();
 
-        rt.do_with_inner(|inner| {
-            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
-                sm_rt.do_with_jsapi(|_rt, cx, global| {
-                    let _proxy_arc = ProxyBuilder::new(vec![], "TestClass3")
-                        .method("test", |_cx, _obj_id, _args, _rval| Ok(()))
-                        .build(cx, global);
-                    let _err = sm_rt
-                        .eval(
-                            "let t = new TestClass3();",
-                            "test_proxy_nonconstructable.es",
-                        )
-                        .err()
-                        .expect("well that should have failed");
-                });
-            });
-            inner.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
-                sm_rt.cleanup();
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|_rt, cx, global| {
+                let _proxy_arc = ProxyBuilder::new(vec![], "TestClass8")
+                    .constructor(|_cx, _args| Ok(1))
+                    .method_with_arity("methodA", 3, |_cx, _obj_id, _args, _rval| Ok(()))
+                    .method("methodB", |_cx, _obj_id, _args, _rval| Ok(()))
+                    .static_method_with_arity("create", 2, |_cx, _args, _rval| Ok(()))
+                    .build(cx, global);
             });
         });
+
+        let esvf = rt
+            .eval_sync(
+                "let tc8 = new TestClass8(); [tc8.methodA.length, tc8.methodB.length, TestClass8.create.length, tc8.addEventListener.length];",
+                "test_proxy_method_arity.es",
+            )
+            .ok()
+            .expect("script failed");
+        let lengths: Vec<i32> = esvf.get_array().iter().map(|e| e.get_i32()).collect();
+        assert_eq!(lengths, vec![3, 1, 2, 2]);
     }
 }
 
@@ -784,7 +2734,7 @@ static ES_PROXY_CLASS_CLASS_OPS: JSClassOps = JSClassOps {
     addProperty: None,
     delProperty: None,
     enumerate: None,
-    newEnumerate: None,
+    newEnumerate: Some(proxy_instance_new_enumerate),
     resolve: Some(proxy_instance_resolve),
     mayResolve: None,
     finalize: Some(proxy_instance_finalize),
@@ -803,6 +2753,77 @@ static ES_PROXY_CLASS: JSClass = JSClass {
     oOps: ptr::null(),
 };
 
+static ES_PROXY_CALLABLE_CLASS_CLASS_OPS: JSClassOps = JSClassOps {
+    addProperty: None,
+    delProperty: None,
+    enumerate: None,
+    newEnumerate: Some(proxy_instance_new_enumerate),
+    resolve: Some(proxy_instance_resolve),
+    mayResolve: None,
+    finalize: Some(proxy_instance_finalize),
+    call: Some(proxy_instance_call),
+    hasInstance: None,
+    construct: None,
+    trace: None,
+};
+
+static ES_PROXY_CALLABLE_CLASS: JSClass = JSClass {
+    name: b"EsCallableProxy\0" as *const u8 as *const libc::c_char,
+    flags: JSCLASS_FOREGROUND_FINALIZE,
+    cOps: &ES_PROXY_CALLABLE_CLASS_CLASS_OPS as *const JSClassOps,
+    spec: ptr::null(),
+    ext: ptr::null(),
+    oOps: ptr::null(),
+};
+
+/// add the names of the members of a proxy instance to properties when the instance is enumerated
+/// the members are defined lazily by proxy_instance_resolve so without this they would not be enumerated
+unsafe extern "C" fn proxy_instance_new_enumerate(
+    cx: *mut JSContext,
+    obj: mozjs::jsapi::HandleObject,
+    properties: mozjs::jsapi::MutableHandleIdVector,
+    enumerable_only: bool,
+) -> bool {
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_instance_new_enumerate", || {
+        trace!("reflection::new_enumerate");
+
+        let obj_handle = jsapi_utils::handles::from_raw_handle(obj);
+        if let Some(instance_proxy) = get_proxy_for(cx, obj_handle.get()) {
+            let chain = get_proxy_chain(&instance_proxy);
+            let mut names: Vec<&str> = vec![];
+            for proxy in &chain {
+                names.extend(
+                    proxy
+                        .properties
+                        .keys()
+                        .filter(|name| {
+                            !enumerable_only || proxy.get_property_flags(name).enumerable
+                        })
+                        .cloned(),
+                );
+                // methods are not listed because they are members of the prototype
+                if !enumerable_only {
+                    names.extend(proxy.native_methods.keys());
+                }
+            }
+            names.sort_unstable();
+            names.dedup();
+
+            for name in names {
+                rooted!(in (cx) let mut id_root = mozjs::jsapi::PropertyKey::default());
+                if let Err(err) = jsapi_utils::es_str_to_jsid(cx, name, id_root.handle_mut()) {
+                    report_exception2(cx, err.err_msg());
+                    return false;
+                }
+                if !mozjs::glue::AppendToIdVector(properties, id_root.handle().into()) {
+                    return false;
+                }
+            }
+        }
+        true
+    })
+}
+
 /// resolvea property, this means if we know how to handle a prop we define that prop ob the instance obj
 unsafe extern "C" fn proxy_instance_resolve(
     cx: *mut JSContext,
@@ -825,17 +2846,27 @@ unsafe extern "C" fn proxy_instance_resolve(
     if let Ok(class_name) = class_name_res {
         PROXIES.with(|proxies_rc| {
             let proxies = &*proxies_rc.borrow();
-            if let Some(proxy) = proxies.get(class_name.as_str()) {
-                trace!("check proxy {} for {}", class_name, prop_name);
+            let instance_proxy = match proxies.get(class_name.as_str()) {
+                Some(proxy) => proxy,
+                None => return,
+            };
+            // members of the proxies which are extended are defined on the instance as well
+            for proxy in get_proxy_chain(instance_proxy) {
+                if *resolved {
+                    break;
+                }
+                trace!("check proxy {} for {}", proxy.class_name, prop_name);
 
                 if prop_name.as_str().eq("addEventListener") {
                     trace!("define addEventListener");
 
-                    crate::jsapi_utils::functions::define_native_function(
+                    define_member_function(
                         cx,
                         obj_handle,
                         "addEventListener",
+                        2,
                         Some(proxy_instance_add_event_listener),
+                        proxy.lock_members,
                     );
 
                     *resolved = true;
@@ -843,11 +2874,13 @@ unsafe extern "C" fn proxy_instance_resolve(
                 } else if prop_name.as_str().eq("removeEventListener") {
                     trace!("define removeEventListener");
 
-                    crate::jsapi_utils::functions::define_native_function(
+                    define_member_function(
                         cx,
                         obj_handle,
                         "removeEventListener",
+                        2,
                         Some(proxy_instance_remove_event_listener),
+                        proxy.lock_members,
                     );
 
                     *resolved = true;
@@ -855,16 +2888,18 @@ unsafe extern "C" fn proxy_instance_resolve(
                 } else if prop_name.as_str().eq("dispatchEvent") {
                     trace!("define dispatchEvent");
 
-                    crate::jsapi_utils::functions::define_native_function(
+                    define_member_function(
                         cx,
                         obj_handle,
                         "dispatchEvent",
+                        2,
                         Some(proxy_instance_dispatch_event),
+                        proxy.lock_members,
                     );
 
                     *resolved = true;
                     trace!("resolved dispatchEvent {}", prop_name);
-                } else if proxy.properties.contains_key(prop_name.as_str()) {
+                } else if let Some(prop) = proxy.properties.get(prop_name.as_str()) {
                     trace!(
                         "define prop for proxy {} for name {}",
                         class_name,
@@ -879,10 +2914,13 @@ unsafe extern "C" fn proxy_instance_resolve(
                         obj,
                         n.as_ptr() as *const libc::c_char,
                         Some(proxy_instance_getter),
-                        Some(proxy_instance_setter),
-                        (mozjs::jsapi::JSPROP_PERMANENT
-                            & mozjs::jsapi::JSPROP_GETTER
-                            & mozjs::jsapi::JSPROP_SETTER) as u32,
+                        if prop.1.is_some() {
+                            Some(proxy_instance_setter)
+                        } else {
+                            None
+                        },
+                        accessor_attrs(proxy.lock_members)
+                            | proxy.get_property_flags(prop_name.as_str()).attrs(),
                     );
                     if !ok {
                         panic!("could not define prop");
@@ -892,21 +2930,9 @@ unsafe extern "C" fn proxy_instance_resolve(
 
                     trace!("resolved prop {}", prop_name);
                 } else if proxy.methods.contains_key(prop_name.as_str()) {
-                    trace!(
-                        "define method for proxy {} for name {}",
-                        class_name,
-                        prop_name
-                    );
-
-                    crate::jsapi_utils::functions::define_native_function(
-                        cx,
-                        obj_handle,
-                        prop_name.as_str(),
-                        Some(proxy_instance_method),
-                    );
-
-                    *resolved = true;
-                    trace!("resolved method {}", prop_name);
+                    // methods are defined once on the prototype of the proxy and shared by all instances
+                    trace!("method {} is found on the prototype", prop_name);
+                    break;
                 } else if proxy.native_methods.contains_key(prop_name.as_str()) {
                     trace!(
                         "define native method for proxy {} for name {}",
@@ -920,15 +2946,42 @@ unsafe extern "C" fn proxy_instance_resolve(
                         .cloned()
                         .unwrap();
 
-                    crate::jsapi_utils::functions::define_native_function(
+                    define_member_function(
                         cx,
                         obj_handle,
                         prop_name.as_str(),
+                        proxy.get_method_arity(prop_name.as_str()),
                         method,
+                        proxy.lock_members,
                     );
 
                     *resolved = true;
                     trace!("resolved native method {}", prop_name);
+                } else if let Some((filter, _handler)) = &proxy.dynamic_methods {
+                    // the obj id itself is never a dynamic method, getting it would resolve it again
+                    if prop_name.as_str() == PROXY_PROP_OBJ_ID {
+                        continue;
+                    }
+                    let obj_id = get_obj_id_for(cx, obj_handle.get());
+                    if filter(obj_id, prop_name.as_str()) {
+                        trace!(
+                            "define dynamic method for proxy {} for name {}",
+                            class_name,
+                            prop_name
+                        );
+
+                        define_member_function(
+                            cx,
+                            obj_handle,
+                            prop_name.as_str(),
+                            0,
+                            Some(proxy_instance_method),
+                            proxy.lock_members,
+                        );
+
+                        *resolved = true;
+                        trace!("resolved dynamic method {}", prop_name);
+                    }
                 }
             }
         });
@@ -942,67 +2995,72 @@ unsafe extern "C" fn proxy_instance_getter(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("reflection::getter");
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_instance_getter", || {
+        trace!("reflection::getter");
 
-    let args = CallArgs::from_vp(vp, argc);
-    let thisv: mozjs::jsapi::Value = *args.thisv();
-
-    if thisv.is_object() {
-        if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
-            let obj_handle = mozjs::rust::HandleObject::from_marked_location(&thisv.to_object());
+        let args = CallArgs::from_vp(vp, argc);
+        let thisv: mozjs::jsapi::Value = *args.thisv();
 
-            trace!("reflection::getter get for cn:{}", proxy.class_name);
+        if thisv.is_object() {
+            if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
+                let obj_handle =
+                    mozjs::rust::HandleObject::from_marked_location(&thisv.to_object());
 
-            let callee: *mut JSObject = args.callee();
-            let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
-                cx,
-                HandleObject::from_marked_location(&callee),
-                "name",
-            );
-            if let Ok(prop_name) = prop_name_res {
-                // lovely the name here is "get [propname]"
-                trace!(
-                    "reflection::getter get {} for cn:{}",
-                    prop_name,
-                    proxy.class_name
-                );
+                trace!("reflection::getter get for cn:{}", proxy.class_name);
 
-                // get obj id
-                let obj_id = crate::jsapi_utils::objects::get_es_obj_prop_val_as_i32(
+                let callee: *mut JSObject = args.callee();
+                let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
                     cx,
-                    obj_handle,
-                    PROXY_PROP_OBJ_ID,
+                    HandleObject::from_marked_location(&callee),
+                    "name",
                 );
+                if let Ok(prop_name) = prop_name_res {
+                    // lovely the name here is "get [propname]"
+                    trace!(
+                        "reflection::getter get {} for cn:{}",
+                        prop_name,
+                        proxy.class_name
+                    );
 
-                trace!(
-                    "reflection::getter get {} for cn:{} for obj_id {}",
-                    prop_name,
-                    proxy.class_name,
-                    obj_id
-                );
+                    // get obj id
+                    let obj_id = crate::jsapi_utils::objects::get_es_obj_prop_val_as_i32(
+                        cx,
+                        obj_handle,
+                        PROXY_PROP_OBJ_ID,
+                    );
 
-                let p_name = &prop_name[4..];
+                    trace!(
+                        "reflection::getter get {} for cn:{} for obj_id {}",
+                        prop_name,
+                        proxy.class_name,
+                        obj_id
+                    );
 
-                if let Some(prop) = proxy.properties.get(p_name) {
-                    rooted!(in (cx) let mut rval = UndefinedValue());
-                    let js_val_res = prop.0(cx, obj_id, rval.handle_mut());
-                    trace!("got val for getter");
-                    match js_val_res {
-                        Ok(()) => {
-                            args.rval().set(rval.get());
-                        }
-                        Err(js_err) => {
-                            let s = format!("method {} failed\ncaused by: {}", p_name, js_err);
-                            report_exception2(cx, s);
-                            return false;
+                    let p_name = &prop_name[4..];
+                    let proxy = find_in_proxy_chain(&proxy, |p| p.properties.contains_key(p_name))
+                        .unwrap_or(proxy);
+
+                    if let Some(prop) = proxy.properties.get(p_name) {
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        let js_val_res = prop.0(cx, obj_id, rval.handle_mut());
+                        trace!("got val for getter");
+                        match js_val_res {
+                            Ok(()) => {
+                                args.rval().set(rval.get());
+                            }
+                            Err(js_err) => {
+                                let s = format!("method {} failed\ncaused by: {}", p_name, js_err);
+                                report_exception2(cx, s);
+                                return false;
+                            }
                         }
                     }
                 }
             }
         }
-    }
 
-    true
+        true
+    })
 }
 
 unsafe extern "C" fn proxy_static_getter(
@@ -1010,51 +3068,75 @@ unsafe extern "C" fn proxy_static_getter(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("reflection::static_getter");
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_static_getter", || {
+        trace!("reflection::static_getter");
 
-    let args = CallArgs::from_vp(vp, argc);
-    let thisv: mozjs::jsapi::Value = *args.thisv();
+        let args = CallArgs::from_vp(vp, argc);
+        let thisv: mozjs::jsapi::Value = *args.thisv();
 
-    if thisv.is_object() {
-        if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
-            trace!("reflection::static_getter get for cn:{}", proxy.class_name);
+        if thisv.is_object() {
+            if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
+                trace!("reflection::static_getter get for cn:{}", proxy.class_name);
 
-            let callee: *mut JSObject = args.callee();
-            let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
-                cx,
-                HandleObject::from_marked_location(&callee),
-                "name",
-            );
-            if let Ok(prop_name) = prop_name_res {
-                // lovely the name here is "get [propname]"
-                trace!(
-                    "reflection::static_getter get {} for cn:{}",
-                    prop_name,
-                    proxy.class_name
+                let callee: *mut JSObject = args.callee();
+                let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
+                    cx,
+                    HandleObject::from_marked_location(&callee),
+                    "name",
                 );
+                if let Ok(prop_name) = prop_name_res {
+                    // lovely the name here is "get [propname]"
+                    trace!(
+                        "reflection::static_getter get {} for cn:{}",
+                        prop_name,
+                        proxy.class_name
+                    );
 
-                let p_name = &prop_name[4..];
+                    let p_name = &prop_name[4..];
 
-                if let Some(prop) = proxy.static_properties.get(p_name) {
-                    rooted!(in (cx) let mut rval = UndefinedValue());
-                    let js_val_res = prop.0(cx, rval.handle_mut());
-                    trace!("got val for static_getter");
-                    match js_val_res {
-                        Ok(()) => {
-                            args.rval().set(rval.get());
-                        }
-                        Err(js_err) => {
-                            let s = format!("getter {} failed\ncaused by: {}", p_name, js_err);
-                            report_exception2(cx, s);
-                            return false;
+                    if let Some(prop) = proxy.static_properties.get(p_name) {
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        let js_val_res = prop.0(cx, rval.handle_mut());
+                        trace!("got val for static_getter");
+                        match js_val_res {
+                            Ok(()) => {
+                                args.rval().set(rval.get());
+                            }
+                            Err(js_err) => {
+                                let s = format!("getter {} failed\ncaused by: {}", p_name, js_err);
+                                report_exception2(cx, s);
+                                return false;
+                            }
                         }
                     }
                 }
             }
         }
-    }
 
-    true
+        true
+    })
+}
+
+// generate a new id for a Proxy instance of which the id is managed by this crate
+pub(crate) fn next_instance_id() -> i32 {
+    NEXT_PROXY_INSTANCE_ID.with(|next_id| {
+        let id = next_id.get();
+        next_id.set(id.wrapping_add(1));
+        id
+    })
+}
+
+/// get the rust object of a Proxy instance which was created by a constructor_with_data
+/// returns None if there is no object for the obj_id or if it is not a T
+/// use a RefCell or Mutex in your object if you need to alter it
+pub fn with_instance_data<T, R, C>(obj_id: i32, consumer: C) -> Option<R>
+where
+    T: 'static,
+    C: FnOnce(&T) -> R,
+{
+    // clone the Rc so the consumer may construct or finalize other instances
+    let data_opt = PROXY_INSTANCE_DATA.with(|data_rc| data_rc.borrow().get(&obj_id).cloned());
+    data_opt.and_then(|data| data.downcast_ref::<T>().map(consumer))
 }
 
 /// retrieve the object ID for an instance of a Proxy class
@@ -1078,7 +3160,39 @@ pub fn get_proxy_for(cx: *mut JSContext, obj: *mut JSObject) -> Option<Arc<Proxy
         });
     }
 
-    None
+    None
+}
+
+/// get a proxy and the proxies it extends, the proxy itself comes first
+fn get_proxy_chain(proxy: &Arc<Proxy>) -> Vec<Arc<Proxy>> {
+    let mut chain = vec![proxy.clone()];
+    while let Some(parent) = chain
+        .last()
+        .unwrap()
+        .parent
+        .as_ref()
+        .and_then(|parent_name| get_proxy(parent_name.as_str()))
+    {
+        chain.push(parent);
+    }
+    chain
+}
+
+// get the name a function gets when it is defined with a well known Symbol as key, e.g. "[Symbol.iterator]"
+fn well_known_symbol_function_name(cx: *mut JSContext, symbol_code: WellKnownSymbol) -> String {
+    rooted!(in (cx) let symbol_root = unsafe { mozjs::jsapi::JS::GetWellKnownSymbol(cx, symbol_code) });
+    let desc = unsafe { mozjs::jsapi::JS::GetSymbolDescription(symbol_root.handle().into()) };
+    format!("[{}]", jsapi_utils::es_jsstring_to_string(cx, desc))
+}
+
+/// find the proxy which declares a member in the chain of a proxy, see get_proxy_chain()
+fn find_in_proxy_chain<P>(proxy: &Arc<Proxy>, predicate: P) -> Option<Arc<Proxy>>
+where
+    P: Fn(&Proxy) -> bool,
+{
+    get_proxy_chain(proxy)
+        .into_iter()
+        .find(|chain_proxy| predicate(chain_proxy))
 }
 
 fn get_static_proxy_for(cx: *mut JSContext, obj: *mut JSObject) -> Option<Arc<Proxy>> {
@@ -1103,53 +3217,75 @@ unsafe extern "C" fn proxy_instance_setter(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("reflection::setter");
-
-    let args = CallArgs::from_vp(vp, argc);
-    let this_val: mozjs::jsapi::Value = *args.thisv();
-
-    if this_val.is_object() {
-        if let Some(proxy) = get_proxy_for(cx, this_val.to_object()) {
-            trace!("reflection::setter get for cn:{}", &proxy.class_name);
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_instance_setter", || {
+        trace!("reflection::setter");
 
-            let callee: *mut JSObject = args.callee();
-            let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
-                cx,
-                HandleObject::from_marked_location(&callee),
-                "name",
-            );
-            if let Ok(prop_name) = prop_name_res {
-                // lovely the name here is "set [propname]"
-                trace!("reflection::setter set {}", prop_name);
+        let args = CallArgs::from_vp(vp, argc);
+        let this_val: mozjs::jsapi::Value = *args.thisv();
 
-                // get obj id
-                let obj_id = get_obj_id_for(cx, this_val.to_object());
+        if this_val.is_object() {
+            if let Some(proxy) = get_proxy_for(cx, this_val.to_object()) {
+                trace!("reflection::setter get for cn:{}", &proxy.class_name);
 
-                trace!(
-                    "reflection::setter set {} for for obj_id {}",
-                    prop_name,
-                    obj_id
+                let callee: *mut JSObject = args.callee();
+                let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
+                    cx,
+                    HandleObject::from_marked_location(&callee),
+                    "name",
                 );
+                if let Ok(prop_name) = prop_name_res {
+                    // lovely the name here is "set [propname]"
+                    trace!("reflection::setter set {}", prop_name);
 
-                // strip "set " from propname
-                let p_name = &prop_name[4..];
+                    // get obj id
+                    let obj_id = get_obj_id_for(cx, this_val.to_object());
 
-                if let Some(prop) = proxy.properties.get(p_name) {
-                    let val = HandleValue::from_marked_location(&args.index(0).get());
+                    trace!(
+                        "reflection::setter set {} for for obj_id {}",
+                        prop_name,
+                        obj_id
+                    );
 
-                    trace!("reflection::setter setting val");
-                    let js_val_res = prop.1(cx, obj_id, val);
-                    if let Err(js_err) = js_val_res {
-                        let s = format!("setter {} failed\ncaused by: {}", p_name, js_err);
-                        report_exception2(cx, s);
-                        return false;
+                    // strip "set " from propname
+                    let p_name = &prop_name[4..];
+                    let proxy = find_in_proxy_chain(&proxy, |p| p.properties.contains_key(p_name))
+                        .unwrap_or(proxy);
+
+                    if let Some(setter) = proxy
+                        .properties
+                        .get(p_name)
+                        .and_then(|prop| prop.1.as_ref())
+                    {
+                        let val = HandleValue::from_marked_location(&args.index(0).get());
+
+                        trace!("reflection::setter setting val");
+                        let js_val_res = setter(cx, obj_id, val);
+                        if let Err(js_err) = js_val_res {
+                            report_setter_error(cx, p_name, js_err);
+                            return false;
+                        }
                     }
                 }
             }
         }
-    }
 
-    true
+        true
+    })
+}
+
+// throw the error of a setter to script, see ProxyBuilder::property()
+fn report_setter_error(cx: *mut JSContext, p_name: &str, js_err: SetterError) {
+    let (error_class, message) = match js_err {
+        SetterError::Error(message) => {
+            let s = format!("setter {} failed\ncaused by: {}", p_name, message);
+            report_exception2(cx, s);
+            return;
+        }
+        SetterError::TypeError(message) => ("TypeError", message),
+        SetterError::RangeError(message) => ("RangeError", message),
+    };
+    let s = format!("setter {} failed: {}", p_name, message);
+    jsapi_utils::report_typed_exception(cx, error_class, s.as_str());
 }
 
 unsafe extern "C" fn proxy_static_setter(
@@ -1157,44 +3293,49 @@ unsafe extern "C" fn proxy_static_setter(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("reflection::static_setter");
-
-    let args = CallArgs::from_vp(vp, argc);
-    let this_val: mozjs::jsapi::Value = *args.thisv();
-
-    if this_val.is_object() {
-        if let Some(proxy) = get_static_proxy_for(cx, this_val.to_object()) {
-            trace!("reflection::static_setter get for cn:{}", &proxy.class_name);
-
-            let callee: *mut JSObject = args.callee();
-            let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
-                cx,
-                HandleObject::from_marked_location(&callee),
-                "name",
-            );
-            if let Ok(prop_name) = prop_name_res {
-                // lovely the name here is "set [propname]"
-                trace!("reflection::static_setter set {}", prop_name);
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_static_setter", || {
+        trace!("reflection::static_setter");
 
-                // strip "set " from propname
-                let p_name = &prop_name[4..];
+        let args = CallArgs::from_vp(vp, argc);
+        let this_val: mozjs::jsapi::Value = *args.thisv();
 
-                if let Some(prop) = proxy.static_properties.get(p_name) {
-                    let val = HandleValue::from_marked_location(&args.index(0).get());
+        if this_val.is_object() {
+            if let Some(proxy) = get_static_proxy_for(cx, this_val.to_object()) {
+                trace!("reflection::static_setter get for cn:{}", &proxy.class_name);
 
-                    trace!("reflection::static_setter setting val");
-                    let js_val_res = prop.1(cx, val);
-                    if let Err(js_err) = js_val_res {
-                        let s = format!("setter {} failed\ncaused by: {}", p_name, js_err);
-                        report_exception2(cx, s);
-                        return false;
+                let callee: *mut JSObject = args.callee();
+                let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
+                    cx,
+                    HandleObject::from_marked_location(&callee),
+                    "name",
+                );
+                if let Ok(prop_name) = prop_name_res {
+                    // lovely the name here is "set [propname]"
+                    trace!("reflection::static_setter set {}", prop_name);
+
+                    // strip "set " from propname
+                    let p_name = &prop_name[4..];
+
+                    if let Some(setter) = proxy
+                        .static_properties
+                        .get(p_name)
+                        .and_then(|prop| prop.1.as_ref())
+                    {
+                        let val = HandleValue::from_marked_location(&args.index(0).get());
+
+                        trace!("reflection::static_setter setting val");
+                        let js_val_res = setter(cx, val);
+                        if let Err(js_err) = js_val_res {
+                            report_setter_error(cx, p_name, js_err);
+                            return false;
+                        }
                     }
                 }
             }
         }
-    }
 
-    true
+        true
+    })
 }
 
 unsafe extern "C" fn proxy_static_add_event_listener(
@@ -1202,47 +3343,49 @@ unsafe extern "C" fn proxy_static_add_event_listener(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("add_static_event_listener");
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_static_add_event_listener", || {
+        trace!("add_static_event_listener");
 
-    if argc >= 2 {
-        let args = CallArgs::from_vp(vp, argc);
-        let type_handle_val = args.index(0);
-        let listener_handle_val = *args.index(1);
+        if argc >= 2 {
+            let args = CallArgs::from_vp(vp, argc);
+            let type_handle_val = args.index(0);
+            let listener_handle_val = *args.index(1);
 
-        let listener_obj: *mut JSObject = listener_handle_val.to_object();
+            let listener_obj: *mut JSObject = listener_handle_val.to_object();
 
-        let listener_epr = EsPersistentRooted::new_from_obj(cx, listener_obj);
-        let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
-            .ok()
-            .unwrap();
+            let listener_epr = EsPersistentRooted::new_from_obj(cx, listener_obj);
+            let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
+                .ok()
+                .unwrap();
 
-        let thisv: mozjs::jsapi::Value = *args.thisv();
+            let thisv: mozjs::jsapi::Value = *args.thisv();
 
-        if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
-            if proxy.static_events.contains(&type_str.as_str()) {
-                // we need this so we can get a &'static str
-                let type_str = &&(*(*proxy.static_events.get(type_str.as_str()).unwrap()));
+            if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
+                if proxy.static_events.contains(&type_str.as_str()) {
+                    // we need this so we can get a &'static str
+                    let type_str = &&(*(*proxy.static_events.get(type_str.as_str()).unwrap()));
 
-                let obj_map = &mut *proxy.static_event_listeners.borrow_mut();
+                    let obj_map = &mut *proxy.static_event_listeners.borrow_mut();
 
-                if !obj_map.contains_key(type_str) {
-                    obj_map.insert(type_str, vec![]);
-                }
+                    if !obj_map.contains_key(type_str) {
+                        obj_map.insert(type_str, vec![]);
+                    }
 
-                let listener_vec = obj_map.get_mut(type_str).unwrap();
-                listener_vec.push(listener_epr);
+                    let listener_vec = obj_map.get_mut(type_str).unwrap();
+                    listener_vec.push(listener_epr);
+                } else {
+                    trace!(
+                        "add_static_event_listener -> static event not defined: {}",
+                        type_str
+                    );
+                }
             } else {
-                trace!(
-                    "add_static_event_listener -> static event not defined: {}",
-                    type_str
-                );
+                trace!("add_static_event_listener -> no proxy found for obj");
             }
-        } else {
-            trace!("add_static_event_listener -> no proxy found for obj");
         }
-    }
 
-    true
+        true
+    })
 }
 
 unsafe extern "C" fn proxy_static_remove_event_listener(
@@ -1250,42 +3393,44 @@ unsafe extern "C" fn proxy_static_remove_event_listener(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("remove_static_event_listener");
-    if argc >= 2 {
-        let args = CallArgs::from_vp(vp, argc);
-        let type_handle_val = args.index(0);
-        let listener_handle_val = *args.index(1);
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_static_remove_event_listener", || {
+        trace!("remove_static_event_listener");
+        if argc >= 2 {
+            let args = CallArgs::from_vp(vp, argc);
+            let type_handle_val = args.index(0);
+            let listener_handle_val = *args.index(1);
 
-        let listener_obj: *mut JSObject = listener_handle_val.to_object();
+            let listener_obj: *mut JSObject = listener_handle_val.to_object();
 
-        let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
-            .ok()
-            .unwrap();
+            let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
+                .ok()
+                .unwrap();
 
-        let thisv: mozjs::jsapi::Value = *args.thisv();
+            let thisv: mozjs::jsapi::Value = *args.thisv();
 
-        if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
-            if proxy.static_events.contains(&type_str.as_str()) {
-                // we need this so we can get a &'static str
-                let type_str = &&(*(*proxy.static_events.get(type_str.as_str()).unwrap()));
+            if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
+                if proxy.static_events.contains(&type_str.as_str()) {
+                    // we need this so we can get a &'static str
+                    let type_str = &&(*(*proxy.static_events.get(type_str.as_str()).unwrap()));
 
-                let obj_map = &mut *proxy.static_event_listeners.borrow_mut();
+                    let obj_map = &mut *proxy.static_event_listeners.borrow_mut();
 
-                if obj_map.contains_key(type_str) {
-                    let listener_vec = obj_map.get_mut(type_str).unwrap();
-                    for x in 0..listener_vec.len() {
-                        let epr = listener_vec.get(x).unwrap();
-                        if epr.get() == listener_obj {
-                            trace!("remove static event listener for {}", type_str);
-                            listener_vec.remove(x);
-                            break;
+                    if obj_map.contains_key(type_str) {
+                        let listener_vec = obj_map.get_mut(type_str).unwrap();
+                        for x in 0..listener_vec.len() {
+                            let epr = listener_vec.get(x).unwrap();
+                            if epr.get() == listener_obj {
+                                trace!("remove static event listener for {}", type_str);
+                                listener_vec.remove(x);
+                                break;
+                            }
                         }
                     }
                 }
             }
         }
-    }
-    true
+        true
+    })
 }
 
 unsafe extern "C" fn proxy_static_dispatch_event(
@@ -1293,28 +3438,35 @@ unsafe extern "C" fn proxy_static_dispatch_event(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("dispatch_static_event");
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_static_dispatch_event", || {
+        trace!("dispatch_static_event");
 
-    if argc >= 2 {
-        let args = CallArgs::from_vp(vp, argc);
-        let type_handle_val = args.index(0);
-        let evt_obj_handle_val = args.index(1);
+        if argc >= 2 {
+            let args = CallArgs::from_vp(vp, argc);
+            let type_handle_val = args.index(0);
+            let evt_obj_handle_val = args.index(1);
 
-        let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
-            .ok()
-            .unwrap();
+            let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
+                .ok()
+                .unwrap();
 
-        let thisv: mozjs::jsapi::Value = *args.thisv();
+            let thisv: mozjs::jsapi::Value = *args.thisv();
 
-        if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
-            if proxy.static_events.contains(&type_str.as_str()) {
-                let type_str = &&(*(*proxy.static_events.get(type_str.as_str()).unwrap()));
+            if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
+                if proxy.static_events.contains(&type_str.as_str()) {
+                    let type_str = &&(*(*proxy.static_events.get(type_str.as_str()).unwrap()));
 
-                dispatch_static_event_for_proxy(cx, proxy.borrow(), type_str, evt_obj_handle_val);
+                    dispatch_static_event_for_proxy(
+                        cx,
+                        proxy.borrow(),
+                        type_str,
+                        evt_obj_handle_val,
+                    );
+                }
             }
         }
-    }
-    true
+        true
+    })
 }
 
 unsafe extern "C" fn proxy_instance_add_event_listener(
@@ -1322,46 +3474,48 @@ unsafe extern "C" fn proxy_instance_add_event_listener(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("add_event_listener");
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_instance_add_event_listener", || {
+        trace!("add_event_listener");
 
-    if argc >= 2 {
-        let args = CallArgs::from_vp(vp, argc);
-        let type_handle_val = args.index(0);
-        let listener_handle_val = *args.index(1);
+        if argc >= 2 {
+            let args = CallArgs::from_vp(vp, argc);
+            let type_handle_val = args.index(0);
+            let listener_handle_val = *args.index(1);
 
-        let listener_obj: *mut JSObject = listener_handle_val.to_object();
+            let listener_obj: *mut JSObject = listener_handle_val.to_object();
 
-        let listener_epr = EsPersistentRooted::new_from_obj(cx, listener_obj);
-        let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
-            .ok()
-            .unwrap();
+            let listener_epr = EsPersistentRooted::new_from_obj(cx, listener_obj);
+            let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
+                .ok()
+                .unwrap();
 
-        let thisv: mozjs::jsapi::Value = *args.thisv();
+            let thisv: mozjs::jsapi::Value = *args.thisv();
 
-        let obj_id = get_obj_id_for(cx, thisv.to_object());
+            let obj_id = get_obj_id_for(cx, thisv.to_object());
 
-        if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
-            if proxy.events.contains(&type_str.as_str()) {
-                // we need this so we can get a &'static str
-                let type_str = &&(*(*proxy.events.get(type_str.as_str()).unwrap()));
+            if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
+                if proxy.events.contains(&type_str.as_str()) {
+                    // we need this so we can get a &'static str
+                    let type_str = &&(*(*proxy.events.get(type_str.as_str()).unwrap()));
 
-                let pel = &mut *proxy.event_listeners.borrow_mut();
-                pel.entry(obj_id).or_insert_with(HashMap::new);
-                let obj_map = pel.get_mut(&obj_id).unwrap();
+                    let pel = &mut *proxy.event_listeners.borrow_mut();
+                    pel.entry(obj_id).or_insert_with(HashMap::new);
+                    let obj_map = pel.get_mut(&obj_id).unwrap();
 
-                if !obj_map.contains_key(type_str) {
-                    obj_map.insert(type_str, vec![]);
-                }
+                    if !obj_map.contains_key(type_str) {
+                        obj_map.insert(type_str, vec![]);
+                    }
 
-                let listener_vec = obj_map.get_mut(type_str).unwrap();
-                listener_vec.push(listener_epr);
-            } else {
-                trace!("add_event_listener -> event not defined: {}", type_str);
+                    let listener_vec = obj_map.get_mut(type_str).unwrap();
+                    listener_vec.push(listener_epr);
+                } else {
+                    trace!("add_event_listener -> event not defined: {}", type_str);
+                }
             }
         }
-    }
 
-    true
+        true
+    })
 }
 
 unsafe extern "C" fn proxy_instance_remove_event_listener(
@@ -1369,48 +3523,50 @@ unsafe extern "C" fn proxy_instance_remove_event_listener(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("remove_event_listener");
-    if argc >= 2 {
-        let args = CallArgs::from_vp(vp, argc);
-        let type_handle_val = args.index(0);
-        let listener_handle_val = *args.index(1);
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_instance_remove_event_listener", || {
+        trace!("remove_event_listener");
+        if argc >= 2 {
+            let args = CallArgs::from_vp(vp, argc);
+            let type_handle_val = args.index(0);
+            let listener_handle_val = *args.index(1);
 
-        let listener_obj: *mut JSObject = listener_handle_val.to_object();
+            let listener_obj: *mut JSObject = listener_handle_val.to_object();
 
-        let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
-            .ok()
-            .unwrap();
+            let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
+                .ok()
+                .unwrap();
 
-        let thisv: mozjs::jsapi::Value = *args.thisv();
+            let thisv: mozjs::jsapi::Value = *args.thisv();
 
-        let obj_id = get_obj_id_for(cx, thisv.to_object());
+            let obj_id = get_obj_id_for(cx, thisv.to_object());
 
-        if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
-            if proxy.events.contains(&type_str.as_str()) {
-                // we need this so we can get a &'static str
-                let type_str = &&(*(*proxy.events.get(type_str.as_str()).unwrap()));
+            if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
+                if proxy.events.contains(&type_str.as_str()) {
+                    // we need this so we can get a &'static str
+                    let type_str = &&(*(*proxy.events.get(type_str.as_str()).unwrap()));
 
-                let pel = &mut *proxy.event_listeners.borrow_mut();
+                    let pel = &mut *proxy.event_listeners.borrow_mut();
 
-                if pel.contains_key(&obj_id) {
-                    let obj_map = pel.get_mut(&obj_id).unwrap();
+                    if pel.contains_key(&obj_id) {
+                        let obj_map = pel.get_mut(&obj_id).unwrap();
 
-                    if obj_map.contains_key(type_str) {
-                        let listener_vec = obj_map.get_mut(type_str).unwrap();
-                        for x in 0..listener_vec.len() {
-                            let epr = listener_vec.get(x).unwrap();
-                            if epr.get() == listener_obj {
-                                trace!("remove event listener for {}", type_str);
-                                listener_vec.remove(x);
-                                break;
+                        if obj_map.contains_key(type_str) {
+                            let listener_vec = obj_map.get_mut(type_str).unwrap();
+                            for x in 0..listener_vec.len() {
+                                let epr = listener_vec.get(x).unwrap();
+                                if epr.get() == listener_obj {
+                                    trace!("remove event listener for {}", type_str);
+                                    listener_vec.remove(x);
+                                    break;
+                                }
                             }
                         }
                     }
                 }
             }
         }
-    }
-    true
+        true
+    })
 }
 
 unsafe extern "C" fn proxy_instance_dispatch_event(
@@ -1418,33 +3574,141 @@ unsafe extern "C" fn proxy_instance_dispatch_event(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("dispatch_event");
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_instance_dispatch_event", || {
+        trace!("dispatch_event");
 
-    if argc >= 2 {
-        let args = CallArgs::from_vp(vp, argc);
-        let type_handle_val = args.index(0);
-        let evt_obj_handle_val = args.index(1);
+        if argc >= 2 {
+            let args = CallArgs::from_vp(vp, argc);
+            let type_handle_val = args.index(0);
+            let evt_obj_handle_val = args.index(1);
 
-        let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
-            .ok()
-            .unwrap();
+            let type_str = crate::jsapi_utils::es_value_to_str(cx, *type_handle_val)
+                .ok()
+                .unwrap();
 
-        let thisv: mozjs::jsapi::Value = *args.thisv();
+            let thisv: mozjs::jsapi::Value = *args.thisv();
+
+            let obj_id = get_obj_id_for(cx, thisv.to_object());
+
+            if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
+                if proxy.events.contains(&type_str.as_str()) {
+                    let type_str = &&(*(*proxy.events.get(type_str.as_str()).unwrap()));
+
+                    dispatch_event_for_proxy(
+                        cx,
+                        proxy.borrow(),
+                        obj_id,
+                        type_str,
+                        evt_obj_handle_val,
+                    );
+                }
+            }
+        }
+        true
+    })
+}
 
-        let obj_id = get_obj_id_for(cx, thisv.to_object());
+/// what to do when an event listener throws
+#[derive(Clone, Copy, PartialEq)]
+enum ListenerErrorPolicy {
+    /// pass the error to the error callback of the runtime and call the next listener, the return values are not collected
+    Report,
+    /// stop calling listeners and fail with the error, the return values are collected
+    Fail,
+}
 
-        if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
-            if proxy.events.contains(&type_str.as_str()) {
-                let type_str = &&(*(*proxy.events.get(type_str.as_str()).unwrap()));
+/// call the listeners for an event of an instance and return their return values and if the event was canceled
+/// if cancelable is true no more listeners are called after a listener canceled the event, see Proxy::dispatch_cancelable_event()
+fn call_event_listeners(
+    cx: *mut JSContext,
+    proxy: &Proxy,
+    obj_id: i32,
+    evt_type: &str,
+    evt_obj: mozjs::jsapi::HandleValue,
+    cancelable: bool,
+) -> Result<(Vec<EsValueFacade>, bool), EsErrorInfo> {
+    let pel = &*proxy.event_listeners.borrow();
+    match pel.get(&obj_id).and_then(|obj_map| obj_map.get(evt_type)) {
+        Some(listener_vec) => call_listeners(
+            cx,
+            listener_vec,
+            evt_obj,
+            ListenerErrorPolicy::Fail,
+            cancelable,
+        ),
+        None => Ok((vec![], false)),
+    }
+}
 
-                dispatch_event_for_proxy(cx, proxy.borrow(), obj_id, type_str, evt_obj_handle_val);
+/// call listeners with the event obj as single argument
+/// returns the return values of the listeners (if the error_policy collects them) and if the event was canceled
+fn call_listeners(
+    cx: *mut JSContext,
+    listener_vec: &[EsPersistentRooted],
+    evt_obj: mozjs::jsapi::HandleValue,
+    error_policy: ListenerErrorPolicy,
+    cancelable: bool,
+) -> Result<(Vec<EsValueFacade>, bool), EsErrorInfo> {
+    let mut results = vec![];
+    rooted!(in (cx) let this_obj = NULL_JSOBJECT);
+    for listener_epr in listener_vec {
+        rooted!(in (cx) let mut ret_val = UndefinedValue());
+        rooted!(in (cx) let function_val = ObjectValue(listener_epr.get()));
+        let call_res = crate::jsapi_utils::functions::call_function_value(
+            cx,
+            this_obj.handle(),
+            function_val.handle(),
+            vec![*evt_obj],
+            ret_val.handle_mut(),
+        );
+        match (call_res, error_policy) {
+            (Ok(()), ListenerErrorPolicy::Fail) => {
+                let result = EsValueFacade::new_v(cx, ret_val.handle());
+                let canceled = cancelable && is_canceled(cx, &result, evt_obj);
+                results.push(result);
+                if canceled {
+                    return Ok((results, true));
+                }
+            }
+            (Ok(()), ListenerErrorPolicy::Report) => {}
+            (Err(err), ListenerErrorPolicy::Fail) => return Err(err),
+            (Err(err), ListenerErrorPolicy::Report) => {
+                crate::spidermonkeyruntimewrapper::report_uncaught_error(err);
             }
         }
     }
-    true
+    Ok((results, false))
+}
+
+/// check if a listener canceled an event by returning false or by calling preventDefault()
+fn is_canceled(
+    cx: *mut JSContext,
+    result: &EsValueFacade,
+    evt_obj: mozjs::jsapi::HandleValue,
+) -> bool {
+    if result.is_boolean() && !result.get_boolean() {
+        return true;
+    }
+    if evt_obj.is_object() {
+        rooted!(in (cx) let evt_obj_root = evt_obj.to_object());
+        rooted!(in (cx) let mut prevented_root = UndefinedValue());
+        if crate::jsapi_utils::objects::get_es_obj_prop_val(
+            cx,
+            evt_obj_root.handle(),
+            "defaultPrevented",
+            prevented_root.handle_mut(),
+        )
+        .is_ok()
+        {
+            return prevented_root.is_boolean() && prevented_root.to_boolean();
+        }
+    }
+    false
 }
 
 // proxy can call this from Proxy::dispatch_event with esvf.to_es_val()
+/// call the listeners for an event of an instance
+/// a listener which throws does not stop the other listeners, its error is passed to the error callback of the runtime
 fn dispatch_event_for_proxy(
     cx: *mut JSContext,
     proxy: &Proxy,
@@ -1453,31 +3717,15 @@ fn dispatch_event_for_proxy(
     evt_obj: mozjs::jsapi::HandleValue,
 ) {
     let pel = &*proxy.event_listeners.borrow();
-    if let Some(obj_map) = pel.get(&obj_id) {
-        if let Some(listener_vec) = obj_map.get(evt_type) {
-            rooted!(in (cx) let mut ret_val = UndefinedValue());
-            // todo this_obj should be the proxy obj..
-            rooted!(in (cx) let this_obj = NULL_JSOBJECT);
-            // since evt_obj is already rooted here we don;t need the auto_root macro, we can just use call_method_value()
-
-            for listener_epr in listener_vec {
-                let mut args_vec = vec![];
-                args_vec.push(*evt_obj);
-                let func_obj = listener_epr.get();
-                // todo why do we only have a call_method by val and not by HandleObject?
-                // the whole rooting func here could be avoided
-                rooted!(in (cx) let function_val = ObjectValue(func_obj));
-                crate::jsapi_utils::functions::call_function_value(
-                    cx,
-                    this_obj.handle(),
-                    function_val.handle(),
-                    args_vec,
-                    ret_val.handle_mut(),
-                )
-                .ok()
-                .unwrap();
-            }
-        }
+    if let Some(listener_vec) = pel.get(&obj_id).and_then(|obj_map| obj_map.get(evt_type)) {
+        // listener errors are reported so this never fails
+        let _ = call_listeners(
+            cx,
+            listener_vec,
+            evt_obj,
+            ListenerErrorPolicy::Report,
+            false,
+        );
     }
 }
 
@@ -1490,28 +3738,14 @@ fn dispatch_static_event_for_proxy(
     let obj_map = &*proxy.static_event_listeners.borrow();
 
     if let Some(listener_vec) = obj_map.get(evt_type) {
-        rooted!(in (cx) let mut ret_val = UndefinedValue());
-        // todo this_obj should be the proxy obj..
-        rooted!(in (cx) let this_obj = NULL_JSOBJECT);
-        // since evt_obj is already rooted here we don;t need the auto_root macro, we can just use call_method_value()
-
-        for listener_epr in listener_vec {
-            let mut args_vec = vec![];
-            args_vec.push(*evt_obj);
-            let func_obj = listener_epr.get();
-            // todo why do we only have a call_method by val and not by HandleObject?
-            // the whole rooting func here could be avoided
-            rooted!(in (cx) let function_val = ObjectValue(func_obj));
-            crate::jsapi_utils::functions::call_function_value(
-                cx,
-                this_obj.handle(),
-                function_val.handle(),
-                args_vec,
-                ret_val.handle_mut(),
-            )
-            .ok()
-            .unwrap();
-        }
+        // listener errors are reported so this never fails
+        let _ = call_listeners(
+            cx,
+            listener_vec,
+            evt_obj,
+            ListenerErrorPolicy::Report,
+            false,
+        );
     }
 }
 
@@ -1520,112 +3754,205 @@ unsafe extern "C" fn proxy_instance_method(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("reflection::method");
-
-    let args = CallArgs::from_vp(vp, argc);
-    let thisv: mozjs::jsapi::Value = *args.thisv();
-
-    if thisv.is_object() {
-        if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
-            trace!("reflection::method for cn:{}", &proxy.class_name);
-
-            let callee: *mut JSObject = args.callee();
-            let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
-                cx,
-                HandleObject::from_marked_location(&callee),
-                "name",
-            );
-            if let Ok(prop_name) = prop_name_res {
-                // lovely the name here is "get [propname]"
-                trace!("reflection::method {}", prop_name);
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_instance_method", || {
+        trace!("reflection::method");
 
-                // get obj id
-                let obj_id = get_obj_id_for(cx, thisv.to_object());
-
-                trace!("reflection::method {} for for obj_id {}", prop_name, obj_id);
+        let args = CallArgs::from_vp(vp, argc);
+        let thisv: mozjs::jsapi::Value = *args.thisv();
 
-                let p_name = prop_name.as_str();
+        if thisv.is_object() {
+            if let Some(proxy) = get_proxy_for(cx, thisv.to_object()) {
+                trace!("reflection::method for cn:{}", &proxy.class_name);
 
-                if let Some(prop) = proxy.methods.get(p_name) {
-                    trace!("got method for method");
+                let callee: *mut JSObject = args.callee();
+                let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
+                    cx,
+                    HandleObject::from_marked_location(&callee),
+                    "name",
+                );
+                if let Ok(prop_name) = prop_name_res {
+                    // lovely the name here is "get [propname]"
+                    trace!("reflection::method {}", prop_name);
+
+                    // get obj id
+                    let obj_id = get_obj_id_for(cx, thisv.to_object());
+
+                    trace!("reflection::method {} for for obj_id {}", prop_name, obj_id);
+
+                    let p_name = prop_name.as_str();
+                    let proxy = find_in_proxy_chain(&proxy, |p| {
+                        p.methods.contains_key(p_name) || p.symbol_methods.contains_key(p_name)
+                    })
+                    .unwrap_or(proxy);
+
+                    let method_opt = proxy.methods.get(p_name).or_else(|| {
+                        proxy
+                            .symbol_methods
+                            .get(p_name)
+                            .map(|(_symbol_code, method)| method)
+                    });
+                    if let Some(prop) = method_opt {
+                        trace!("got method for method");
+
+                        let mut args_vec = vec![];
+                        for x in 0..args.argc_ {
+                            args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
+                        }
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        let js_val_res = prop(cx, obj_id, args_vec, rval.handle_mut());
+                        match js_val_res {
+                            Ok(()) => {
+                                args.rval().set(rval.get());
+                            }
+                            Err(js_err) => {
+                                let s = format!("method {} failed\ncaused by: {}", p_name, js_err);
+                                report_exception2(cx, s);
+                                return false;
+                            }
+                        }
+                    } else if let Some((_filter, handler)) = &proxy.dynamic_methods {
+                        trace!("got dynamic method handler for method");
 
-                    let mut args_vec = vec![];
-                    for x in 0..args.argc_ {
-                        args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
-                    }
-                    rooted!(in (cx) let mut rval = UndefinedValue());
-                    let js_val_res = prop(cx, obj_id, args_vec, rval.handle_mut());
-                    match js_val_res {
-                        Ok(()) => {
-                            args.rval().set(rval.get());
+                        let mut args_vec = vec![];
+                        for x in 0..args.argc_ {
+                            args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
                         }
-                        Err(js_err) => {
-                            let s = format!("method {} failed\ncaused by: {}", p_name, js_err);
-                            report_exception2(cx, s);
-                            return false;
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        match handler(cx, obj_id, p_name, args_vec, rval.handle_mut()) {
+                            Ok(true) => {
+                                args.rval().set(rval.get());
+                            }
+                            Ok(false) => {
+                                let s = format!("method {} is not supported", p_name);
+                                report_exception2(cx, s);
+                                return false;
+                            }
+                            Err(js_err) => {
+                                let s = format!("method {} failed\ncaused by: {}", p_name, js_err);
+                                report_exception2(cx, s);
+                                return false;
+                            }
                         }
                     }
                 }
             }
         }
-    }
 
-    true
+        true
+    })
 }
 
-unsafe extern "C" fn proxy_static_method(
+unsafe extern "C" fn proxy_instance_call(
     cx: *mut JSContext,
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("reflection::static_method");
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_instance_call", || {
+        trace!("reflection::call");
 
-    let args = CallArgs::from_vp(vp, argc);
-    let thisv: mozjs::jsapi::Value = *args.thisv();
+        let args = CallArgs::from_vp(vp, argc);
+        // the callee is the instance itself
+        let callee: *mut JSObject = args.callee();
 
-    if thisv.is_object() {
-        if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
-            trace!("reflection::static_method for cn:{}", &proxy.class_name);
+        if let Some(proxy) = get_proxy_for(cx, callee) {
+            if let Some(callable) = &proxy.callable {
+                let obj_id = get_obj_id_for(cx, callee);
 
-            let callee: *mut JSObject = args.callee();
-            let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
-                cx,
-                HandleObject::from_marked_location(&callee),
-                "name",
-            );
-            if let Ok(prop_name) = prop_name_res {
-                // lovely the name here is "get [propname]"
-                trace!("reflection::static_method {}", prop_name);
+                trace!(
+                    "reflection::call for cn:{} obj_id {}",
+                    &proxy.class_name,
+                    obj_id
+                );
+
+                let mut args_vec = vec![];
+                for x in 0..args.argc_ {
+                    args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
+                }
+                rooted!(in (cx) let mut rval = UndefinedValue());
+                let js_val_res = callable(cx, obj_id, args_vec, rval.handle_mut());
+                return match js_val_res {
+                    Ok(()) => {
+                        args.rval().set(rval.get());
+                        true
+                    }
+                    Err(js_err) => {
+                        let s =
+                            format!("call of {} failed\ncaused by: {}", proxy.class_name, js_err);
+                        report_exception2(cx, s);
+                        false
+                    }
+                };
+            }
+        }
 
-                let p_name = prop_name.as_str();
+        report_exception2(cx, "object is not callable".to_string());
+        false
+    })
+}
 
-                if let Some(prop) = proxy.static_methods.get(p_name) {
-                    trace!("got method for static_method");
+unsafe extern "C" fn proxy_static_method(
+    cx: *mut JSContext,
+    argc: u32,
+    vp: *mut mozjs::jsapi::Value,
+) -> bool {
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_static_method", || {
+        trace!("reflection::static_method");
 
-                    let mut args_vec = vec![];
-                    for x in 0..args.argc_ {
-                        args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
-                    }
+        let args = CallArgs::from_vp(vp, argc);
+        let thisv: mozjs::jsapi::Value = *args.thisv();
 
-                    rooted!(in (cx) let mut rval = UndefinedValue());
-                    let js_val_res = prop(cx, args_vec, rval.handle_mut());
-                    match js_val_res {
-                        Ok(()) => {
-                            args.rval().set(rval.get());
+        if thisv.is_object() {
+            if let Some(proxy) = get_static_proxy_for(cx, thisv.to_object()) {
+                trace!("reflection::static_method for cn:{}", &proxy.class_name);
+
+                let callee: *mut JSObject = args.callee();
+                let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
+                    cx,
+                    HandleObject::from_marked_location(&callee),
+                    "name",
+                );
+                if let Ok(prop_name) = prop_name_res {
+                    // lovely the name here is "get [propname]"
+                    trace!("reflection::static_method {}", prop_name);
+
+                    let p_name = prop_name.as_str();
+
+                    let method_opt = proxy.static_methods.get(p_name).or_else(|| {
+                        proxy
+                            .static_symbol_methods
+                            .get(p_name)
+                            .map(|(_symbol_code, method)| method)
+                    });
+                    if let Some(prop) = method_opt {
+                        trace!("got method for static_method");
+
+                        let mut args_vec = vec![];
+                        for x in 0..args.argc_ {
+                            args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
                         }
-                        Err(js_err) => {
-                            let s =
-                                format!("static method {} failed\ncaused by: {}", p_name, js_err);
-                            report_exception2(cx, s);
-                            return false;
+
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        let js_val_res = prop(cx, args_vec, rval.handle_mut());
+                        match js_val_res {
+                            Ok(()) => {
+                                args.rval().set(rval.get());
+                            }
+                            Err(js_err) => {
+                                let s = format!(
+                                    "static method {} failed\ncaused by: {}",
+                                    p_name, js_err
+                                );
+                                report_exception2(cx, s);
+                                return false;
+                            }
                         }
                     }
                 }
             }
         }
-    }
 
-    true
+        true
+    })
 }
 
 unsafe extern "C" fn proxy_instance_finalize(_fop: *mut JSFreeOp, object: *mut JSObject) {
@@ -1646,7 +3973,33 @@ unsafe extern "C" fn proxy_instance_finalize(_fop: *mut JSFreeOp, object: *mut J
     trace!("finalize id {} of type {}", proxy_instance_id, cn);
     if let Some(proxy) = get_proxy(cn.as_str()) {
         if let Some(finalizer) = &proxy.finalizer {
-            finalizer(proxy_instance_id);
+            // we can't throw an exception here, but we should not unwind across the FFI boundary either
+            let finalize_res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                finalizer(proxy_instance_id)
+            }));
+            if let Err(payload) = finalize_res {
+                log::error!(
+                    "finalizer of {} panicked: {}",
+                    cn,
+                    jsapi_utils::panic_message(&payload)
+                );
+            }
+        }
+
+        if proxy.has_instance_data {
+            let data_opt =
+                PROXY_INSTANCE_DATA.with(|data_rc| data_rc.borrow_mut().remove(&proxy_instance_id));
+            // the Drop of the data is user code, which should not unwind across the FFI boundary either
+            let drop_res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+                drop(data_opt);
+            }));
+            if let Err(payload) = drop_res {
+                log::error!(
+                    "dropping instance data of {} panicked: {}",
+                    cn,
+                    jsapi_utils::panic_message(&payload)
+                );
+            }
         }
 
         // clear event listeners
@@ -1663,59 +4016,61 @@ unsafe extern "C" fn proxy_construct(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    trace!("reflection::construct");
+    crate::jsapi_utils::catch_native_panic(cx, "proxy_construct", || {
+        trace!("reflection::construct");
 
-    let args = CallArgs::from_vp(vp, argc);
+        let args = CallArgs::from_vp(vp, argc);
 
-    rooted!(in (cx) let constructor_root = args.calleev().to_object());
+        rooted!(in (cx) let constructor_root = args.calleev().to_object());
 
-    let class_name = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
-        cx,
-        constructor_root.handle(),
-        PROXY_PROP_CLASS_NAME,
-    )
-    .ok()
-    .unwrap();
-    trace!("reflection::construct cn={}", class_name);
-
-    if let Some(proxy) = get_proxy(class_name.as_str()) {
-        trace!("constructing proxy {}", class_name);
-        if let Some(constructor) = &proxy.constructor {
-            trace!("constructing proxy constructor {}", class_name);
-
-            let mut args_vec = vec![];
-            for x in 0..args.argc_ {
-                args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
-            }
+        let class_name = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
+            cx,
+            constructor_root.handle(),
+            PROXY_PROP_CLASS_NAME,
+        )
+        .ok()
+        .unwrap();
+        trace!("reflection::construct cn={}", class_name);
 
-            let obj_id_res = constructor(cx, args_vec);
+        if let Some(proxy) = get_proxy(class_name.as_str()) {
+            trace!("constructing proxy {}", class_name);
+            if let Some(constructor) = &proxy.constructor {
+                trace!("constructing proxy constructor {}", class_name);
 
-            if obj_id_res.is_ok() {
-                let obj_id = obj_id_res.ok().unwrap();
-                let rval = jsapi_utils::handles::from_raw_handle_mut(args.rval());
-                let res = proxy.new_instance(cx, obj_id, rval);
-                match res {
-                    Ok(_) => return true,
-                    Err(js_err) => {
-                        let err_str = format!("new_instance failed: {}", js_err.err_msg());
-                        report_exception2(cx, err_str);
-                        return false;
+                let mut args_vec = vec![];
+                for x in 0..args.argc_ {
+                    args_vec.push(HandleValue::from_marked_location(&*args.get(x)));
+                }
+
+                let obj_id_res = constructor(cx, args_vec);
+
+                if obj_id_res.is_ok() {
+                    let obj_id = obj_id_res.ok().unwrap();
+                    let rval = jsapi_utils::handles::from_raw_handle_mut(args.rval());
+                    let res = proxy.new_instance(cx, obj_id, rval);
+                    match res {
+                        Ok(_) => return true,
+                        Err(js_err) => {
+                            let err_str = format!("new_instance failed: {}", js_err.err_msg());
+                            report_exception2(cx, err_str);
+                            return false;
+                        }
                     }
+                } else {
+                    let err_str = format!("constructor failed: {}", obj_id_res.err().unwrap());
+                    report_exception2(cx, err_str);
+
+                    return false;
                 }
             } else {
-                let err_str = format!("constructor failed: {}", obj_id_res.err().unwrap());
-                report_exception2(cx, err_str);
+                report_exception2(cx, format!("class '{}' is not constructable", class_name));
 
                 return false;
             }
-        } else {
-            report_exception2(cx, format!("class '{}' is not constructable", class_name));
-
-            return false;
         }
-    }
 
-    report_exception2(cx, format!("class '{}' not found", class_name));
+        report_exception2(cx, format!("class '{}' not found", class_name));
 
-    false
+        false
+    })
 }
@@ -3,10 +3,11 @@ use mozjs::jsapi::Heap;
 use mozjs::jsapi::JSContext;
 use mozjs::jsapi::JSObject;
 use mozjs::jsapi::{AddRawValueRoot, RemoveRawValueRoot};
-use mozjs::jsval::{JSVal, ObjectValue};
+use mozjs::jsval::{JSVal, ObjectValue, UndefinedValue};
 
 use mozjs::rust::Runtime;
 use std::ffi::CString;
+use std::ptr;
 
 /// the EsPersistentRooted struct is used to keep an Object rooted while there are no references to it in the script Runtime
 /// the root will be released when this struct is dropped
@@ -14,6 +15,7 @@ pub struct EsPersistentRooted {
     /// The underlying `JSObject`.
     heap_obj: Box<Heap<*mut JSObject>>,
     permanent_js_root: Box<Heap<JSVal>>,
+    rooted: bool,
 }
 
 impl Default for EsPersistentRooted {
@@ -27,6 +29,7 @@ impl EsPersistentRooted {
         EsPersistentRooted {
             heap_obj: Box::new(Heap::default()),
             permanent_js_root: Box::new(Heap::default()),
+            rooted: false,
         }
     }
 
@@ -46,6 +49,7 @@ impl EsPersistentRooted {
     /// # Safety
     /// be safe :)
     pub unsafe fn init(&mut self, cx: *mut JSContext, js_obj: *mut JSObject) {
+        assert!(!self.rooted, "EsPersistentRooted was already initialized");
         self.heap_obj.set(js_obj);
         self.permanent_js_root.set(ObjectValue(js_obj));
         let c_str = CString::new("EsPersistentRooted::root").unwrap();
@@ -55,15 +59,38 @@ impl EsPersistentRooted {
             self.permanent_js_root.get_unsafe(),
             c_str.as_ptr() as *const i8
         ));
+        self.rooted = true;
+    }
+
+    /// release the root without dropping the allocated heap values
+    /// after calling this the instance may be initialized again with init()
+    pub fn release(&mut self) {
+        if self.rooted {
+            unsafe {
+                let cx = Runtime::get();
+                trace!("EsPersistentRooted -> RemoveRawValueRoot");
+                RemoveRawValueRoot(cx, self.permanent_js_root.get_unsafe());
+            }
+            self.rooted = false;
+        }
+        self.heap_obj.set(ptr::null_mut());
+        self.permanent_js_root.set(UndefinedValue());
+    }
+
+    /// check if this instance currently roots an object
+    pub fn is_rooted(&self) -> bool {
+        self.rooted
     }
 }
 
 impl Drop for EsPersistentRooted {
     fn drop(&mut self) {
-        unsafe {
-            let cx = Runtime::get();
-            trace!("EsPersistentRooted -> RemoveRawValueRoot");
-            RemoveRawValueRoot(cx, self.permanent_js_root.get_unsafe());
+        if self.rooted {
+            unsafe {
+                let cx = Runtime::get();
+                trace!("EsPersistentRooted -> RemoveRawValueRoot");
+                RemoveRawValueRoot(cx, self.permanent_js_root.get_unsafe());
+            }
         }
     }
 }
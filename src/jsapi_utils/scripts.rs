@@ -9,6 +9,7 @@ use crate::jsapi_utils;
 use crate::jsapi_utils::EsErrorInfo;
 use mozjs::jsapi::JSContext;
 use mozjs::jsapi::JSScript;
+use std::cell::Cell;
 
 use mozjs::rust::{
     transform_u16_to_source_text, HandleScript, MutableHandleScript, MutableHandleValue,
@@ -83,23 +84,125 @@ pub fn execute_script(
         return if let Some(err) = err_opt {
             Err(err)
         } else {
-            Err(EsErrorInfo {
-                message: "unknown error while executing script occured".to_string(),
-                filename: "execute_script".to_string(),
-                lineno: 0,
-                column: 0,
-            })
+            Err(EsErrorInfo::new(
+                "unknown error while executing script occured".to_string(),
+                "execute_script".to_string(),
+                0,
+                0,
+            ))
         };
     }
 
     Ok(())
 }
 
+/// decode the bytes of a script source to a String
+/// a UTF-8 or UTF-16 BOM is stripped, UTF-16 (LE or BE) without a BOM is detected by looking at the zero bytes of the first char
+/// all other sources are decoded as UTF-8
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::jsapi_utils::scripts::decode_source_bytes;
+///
+/// let src = decode_source_bytes(b"\xEF\xBB\xBFlet a = 1;", "my_script.es").ok().expect("decode failed");
+/// assert_eq!(src.as_str(), "let a = 1;");
+/// ```
+pub fn decode_source_bytes(bytes: &[u8], file_name: &str) -> Result<String, EsErrorInfo> {
+    let decode_err = |message: String| EsErrorInfo::new(message, file_name.to_string(), 0, 0);
+
+    let (utf16_le, utf16_bytes) = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8(bytes[3..].to_vec())
+            .map_err(|e| decode_err(format!("invalid UTF-8 source: {}", e)));
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        (true, &bytes[2..])
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        (false, &bytes[2..])
+    } else if bytes.len() >= 2 && bytes[0] != 0 && bytes[1] == 0 {
+        (true, bytes)
+    } else if bytes.len() >= 2 && bytes[0] == 0 && bytes[1] != 0 {
+        (false, bytes)
+    } else {
+        return String::from_utf8(bytes.to_vec())
+            .map_err(|e| decode_err(format!("invalid UTF-8 source: {}", e)));
+    };
+
+    if utf16_bytes.len() % 2 != 0 {
+        return Err(decode_err(
+            "invalid UTF-16 source: odd number of bytes".to_string(),
+        ));
+    }
+
+    let units: Vec<u16> = utf16_bytes
+        .chunks(2)
+        .map(|pair| {
+            if utf16_le {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect();
+
+    String::from_utf16(&units).map_err(|e| decode_err(format!("invalid UTF-16 source: {}", e)))
+}
+
+thread_local! {
+    static MAX_SCRIPT_BYTES: Cell<usize> = Cell::new(usize::MAX);
+}
+
+/// set the max size of script sources for the current thread, see EsRuntimeBuilder::max_script_bytes()
+pub(crate) fn set_max_script_bytes(max_script_bytes: usize) {
+    MAX_SCRIPT_BYTES.with(|max| max.set(max_script_bytes));
+}
+
+/// check if a source does not exceed the max script size of the runtime of the current thread, see EsRuntimeBuilder::max_script_bytes()
+pub(crate) fn check_script_size(code: &str, file_name: &str) -> Result<(), EsErrorInfo> {
+    let max_script_bytes = MAX_SCRIPT_BYTES.with(|max| max.get());
+    if code.len() > max_script_bytes {
+        return Err(EsErrorInfo::new(
+            format!(
+                "script is {} bytes which exceeds the max script size of {} bytes",
+                code.len(),
+                max_script_bytes
+            ),
+            file_name.to_string(),
+            0,
+            0,
+        ));
+    }
+    Ok(())
+}
+
+/// get the file name and line number of the script which is currently running
+/// this is useful in native functions to see which script called them, returns None if no script is running
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::jsapi_utils::scripts::get_scripted_caller;
+/// use mozjs::jsapi::CallArgs;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.do_in_es_event_queue_sync(|sm_rt| {
+///     sm_rt.add_global_function("audited_function", |cx, args: CallArgs| {
+///         if let Some((file_name, lineno)) = get_scripted_caller(cx) {
+///             println!("audited_function called from {}:{}", file_name, lineno);
+///         }
+///         args.rval().set(mozjs::jsval::UndefinedValue());
+///         true
+///     });
+/// });
+/// ```
+pub fn get_scripted_caller(context: *mut JSContext) -> Option<(String, u32)> {
+    match unsafe { mozjs::rust::describe_scripted_caller(context) } {
+        Ok(caller) => Some((caller.filename, caller.line)),
+        Err(_) => None,
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::esruntime::tests::init_test_runtime;
     use crate::jsapi_utils;
-    use crate::jsapi_utils::scripts::{compile_script, execute_script};
+    use crate::jsapi_utils::scripts::{compile_script, decode_source_bytes, execute_script};
     use crate::spidermonkeyruntimewrapper::SmRuntime;
     use log::debug;
     use mozjs::jsapi::JSScript;
@@ -143,4 +246,48 @@ pub mod tests {
             });
         });
     }
+
+    #[test]
+    fn test_decode_source_bytes() {
+        let src = "let a = 'h\u{e9}llo';";
+
+        assert_eq!(
+            decode_source_bytes(src.as_bytes(), "test.es").ok().unwrap(),
+            src
+        );
+
+        let mut utf8_bom = vec![0xEF, 0xBB, 0xBF];
+        utf8_bom.extend_from_slice(src.as_bytes());
+        assert_eq!(decode_source_bytes(&utf8_bom, "test.es").ok().unwrap(), src);
+
+        let le: Vec<u8> = src
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes().to_vec())
+            .collect();
+        let be: Vec<u8> = src
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes().to_vec())
+            .collect();
+
+        assert_eq!(decode_source_bytes(&le, "test.es").ok().unwrap(), src);
+        assert_eq!(decode_source_bytes(&be, "test.es").ok().unwrap(), src);
+
+        let mut le_bom = vec![0xFF, 0xFE];
+        le_bom.extend_from_slice(&le);
+        assert_eq!(decode_source_bytes(&le_bom, "test.es").ok().unwrap(), src);
+
+        let mut be_bom = vec![0xFE, 0xFF];
+        be_bom.extend_from_slice(&be);
+        assert_eq!(decode_source_bytes(&be_bom, "test.es").ok().unwrap(), src);
+
+        let err = decode_source_bytes(&[0x61, 0x62, 0xC3, 0x28], "invalid.es")
+            .err()
+            .expect("invalid UTF-8 should fail");
+        assert_eq!(err.filename.as_str(), "invalid.es");
+
+        // unpaired surrogate
+        assert!(decode_source_bytes(&[0xFF, 0xFE, 0x00, 0xD8], "invalid.es").is_err());
+        // odd number of bytes
+        assert!(decode_source_bytes(&[0xFF, 0xFE, 0x61], "invalid.es").is_err());
+    }
 }
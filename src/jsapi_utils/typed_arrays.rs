@@ -2,6 +2,7 @@ use log::trace;
 use mozjs::jsapi::JSContext;
 use mozjs::jsapi::JSObject;
 use mozjs::rust::{HandleObject, MutableHandleObject};
+use std::os::raw::c_void;
 
 // https://doc.servo.org/mozjs/jsapi/fn.JS_IsInt8Array.html
 // https://doc.servo.org/mozjs/jsapi/fn.JS_IsInt16Array.html
@@ -131,6 +132,30 @@ macro_rules! typed_array {
     };
 }
 
+/// create a new ArrayBuffer which uses the memory of a Vec<u8> without copying it
+/// the Vec is owned by the engine from now on and dropped when the ArrayBuffer is garbage collected
+pub fn new_array_buffer_from_vec(cx: *mut JSContext, bytes: Vec<u8>, mut ret: MutableHandleObject) {
+    let len = bytes.len();
+    let mut bytes = Box::new(bytes);
+    let contents = bytes.as_mut_ptr() as *mut c_void;
+    let user_data = Box::into_raw(bytes) as *mut c_void;
+    ret.set(unsafe {
+        mozjs::jsapi::NewExternalArrayBuffer(
+            cx,
+            len,
+            contents,
+            Some(free_array_buffer_vec),
+            user_data,
+        )
+    });
+}
+
+// free func for new_array_buffer_from_vec, user_data is the boxed Vec which owns the contents
+unsafe extern "C" fn free_array_buffer_vec(_contents: *mut c_void, user_data: *mut c_void) {
+    trace!("free_array_buffer_vec");
+    drop(Box::from_raw(user_data as *mut Vec<u8>));
+}
+
 typed_array!(
     Int8Array,
     JS_IsInt8Array,
@@ -110,6 +110,7 @@ pub mod esreflection;
 pub mod esruntime;
 pub mod esruntimebuilder;
 pub mod esruntimeinner;
+pub mod esserde;
 pub mod esvaluefacade;
 mod features;
 pub mod jsapi_utils;
@@ -1,3 +1,4 @@
+use crate::esruntime::{GcPhase, PromiseJob, RealmOptionsCallback};
 use crate::esruntimeinner::EsRuntimeInner;
 use crate::esvaluefacade::EsValueFacade;
 use crate::jsapi_utils;
@@ -10,26 +11,33 @@ use mozjs::glue::{CreateJobQueue, JobQueueTraps};
 use mozjs::jsapi::CallArgs;
 use mozjs::jsapi::JSAutoRealm;
 use mozjs::jsapi::JSContext;
+use mozjs::jsapi::JSGCStatus;
 use mozjs::jsapi::JSObject;
 use mozjs::jsapi::JS_NewGlobalObject;
-use mozjs::jsapi::NewArrayObject;
+use mozjs::jsapi::JS_SetGCCallback;
 use mozjs::jsapi::OnNewGlobalHookOption;
 use mozjs::jsapi::SetJobQueue;
 use mozjs::jsapi::JS::HandleValueArray;
-use mozjs::jsval::{ObjectValue, UndefinedValue};
+use mozjs::jsval::{JSVal, ObjectValue, UndefinedValue};
 use mozjs::panic::wrap_panic;
 use mozjs::rust::wrappers::JS_CallFunctionValue;
 use mozjs::rust::Runtime;
 use mozjs::rust::SIMPLE_GLOBAL_CLASS;
 use mozjs::rust::{HandleObject, JSEngine};
 use mozjs::rust::{JSEngineHandle, RealmOptions};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::ops::Deref;
 use std::os::raw::c_void;
 use std::ptr;
 use std::rc::Rc;
 use std::str;
 use std::sync::{Arc, Weak};
+use std::thread::ThreadId;
+use std::time::Instant;
 
 lazy_static! {
     static ref ENGINE_HANDLE_PRODUCER: EventLoop = EventLoop::new();
@@ -53,6 +61,10 @@ pub struct SmRuntime {
     runtime: mozjs::rust::Runtime,
     global_obj: *mut JSObject,
     pub(crate) opt_esrt_inner: Option<Weak<EsRuntimeInner>>,
+    // copied from the EsRuntimeInner so converting a value does not need to upgrade opt_esrt_inner
+    pub(crate) strict_string_conversion: bool,
+    pub(crate) eager_object_max_props: usize,
+    pub(crate) eager_string_max_len: usize,
 }
 
 thread_local! {
@@ -60,6 +72,21 @@ thread_local! {
     /// this only exists for the worker thread of the MicroTaskManager
     pub(crate) static SM_RT: RefCell<SmRuntime> = RefCell::new(SmRuntime::new());
     static GLOBAL_OPS: RefCell<HashMap<&'static str, Box<GlobalOp>>> = RefCell::new(HashMap::new());
+    // the number of promise jobs which were enqueued in the current turn of the event queue
+    static MICROTASKS_THIS_TURN: Cell<usize> = Cell::new(0);
+    // the promise jobs which were added as microtasks but have not run yet
+    static MICROTASK_QUEUE: RefCell<VecDeque<Rc<PromiseJobCallback>>> = RefCell::new(VecDeque::new());
+    // the moment the current garbage collection began
+    static GC_STARTED: Cell<Option<Instant>> = Cell::new(None);
+    // the error of the last eval, call or module load per thread which called it, see EsRuntime::take_last_error()
+    static LAST_ERRORS: RefCell<HashMap<ThreadId, EsErrorInfo>> = RefCell::new(HashMap::new());
+    // the closure which alters the RealmOptions of the SmRuntime which is created in this thread, see EsRuntimeBuilder::realm_options()
+    static REALM_OPTIONS_CALLBACK: RefCell<Option<Arc<RealmOptionsCallback>>> = RefCell::new(None);
+}
+
+/// set the closure which alters the RealmOptions of the SmRuntime of the current thread, this needs to be called before SM_RT is first used
+pub(crate) fn set_realm_options_callback(callback: Option<Arc<RealmOptionsCallback>>) {
+    REALM_OPTIONS_CALLBACK.with(|rc| rc.replace(callback));
 }
 
 impl SmRuntime {
@@ -78,6 +105,41 @@ impl SmRuntime {
         })
     }
 
+    /// get the data of a type which was stored with EsRuntime::set_context_data()
+    pub fn get_context_data<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.clone_esrt_inner().get_context_data()
+    }
+
+    /// get the data of a type which was stored with EsRuntime::set_context_data() for the runtime of the current thread
+    /// this is meant for native functions like the ones added with EsRuntime::add_global_sync_function()
+    pub fn get_current_context_data<T: Any + Send + Sync>() -> Option<Arc<T>> {
+        Self::clone_current_esrt_inner_arc().get_context_data()
+    }
+
+    /// get the application state of a type which was set with EsRuntimeBuilder::app_data(), this is the same as get_context_data()
+    pub fn app_data<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.get_context_data()
+    }
+
+    /// get the application state of a type of the runtime of the current thread which was set with EsRuntimeBuilder::app_data()
+    /// this is meant for native functions like the ones added with EsRuntime::add_global_sync_function()
+    pub fn current_app_data<T: Any + Send + Sync>() -> Option<Arc<T>> {
+        Self::get_current_context_data()
+    }
+
+    /// like clone_current_esrt_inner_arc() but returns None instead of panicking when the SmRuntime is being initialized or dropped
+    /// this is useful for callbacks from the engine like finalizers
+    pub(crate) fn try_clone_current_esrt_inner_arc() -> Option<Arc<EsRuntimeInner>> {
+        SM_RT.with(|sm_rt_rc| {
+            sm_rt_rc.try_borrow().ok().and_then(|sm_rt| {
+                sm_rt
+                    .opt_esrt_inner
+                    .as_ref()
+                    .and_then(|weak_inner| weak_inner.upgrade())
+            })
+        })
+    }
+
     /// add a function to the global object
     /// this function will be callable from javascript just by using func_name();
     /// # Example
@@ -97,7 +159,33 @@ impl SmRuntime {
     /// let esvf = rt.eval_sync("my_function();", "test_add_global_function_example.es").ok().expect("test_add_global_function_example failed");
     /// assert_eq!(esvf.get_i32(), 480);
     /// ```
+    /// the function is not enumerable so it does not show up in e.g. `for (k in globalThis)`, use add_global_function_with_attrs to change that
     pub fn add_global_function<F>(&self, name: &'static str, func: F)
+    where
+        F: Fn(*mut JSContext, CallArgs) -> bool + Send + 'static,
+    {
+        self.add_global_function_with_attrs(name, 0, func)
+    }
+
+    /// add a global function like add_global_function() with the given JSPROP_* flags for the property of the global object
+    /// e.g. JSPROP_ENUMERATE to make the function enumerable or JSPROP_PERMANENT to make it non configurable
+    /// # Example
+    /// ```no_run
+    /// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use mozjs::jsval::Int32Value;
+    /// use mozjs::jsapi::{CallArgs, JSPROP_ENUMERATE, JSPROP_PERMANENT};
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.do_in_es_event_queue_sync(|sm_rt| {
+    ///     sm_rt.add_global_function_with_attrs("my_function", (JSPROP_ENUMERATE | JSPROP_PERMANENT) as u32, |_cx, args: CallArgs| {
+    ///         args.rval().set(Int32Value(480));
+    ///         true
+    ///     });
+    /// });
+    /// let esvf = rt.eval_sync("Object.keys(globalThis).includes('my_function');", "test_add_global_function_with_attrs.es").ok().expect("script failed");
+    /// assert!(esvf.get_boolean());
+    /// ```
+    pub fn add_global_function_with_attrs<F>(&self, name: &'static str, attrs: u32, func: F)
     where
         F: Fn(*mut JSContext, CallArgs) -> bool + Send + 'static,
     {
@@ -108,15 +196,30 @@ impl SmRuntime {
 
         self.do_with_jsapi(|_rt, cx, global| {
             // reg function
-            jsapi_utils::functions::define_native_function(
+            jsapi_utils::functions::define_native_function_with(
                 cx,
                 global,
                 name,
+                1,
+                attrs,
                 Some(global_op_native_method),
             );
         })
     }
 
+    /// get the file name of the script which is currently running, e.g. the script which called a global function
+    /// returns None if no script is running
+    pub fn current_script_name(&self) -> Option<String> {
+        self.current_script_location()
+            .map(|(file_name, _lineno)| file_name)
+    }
+
+    /// get the file name and line number of the script which is currently running
+    /// returns None if no script is running
+    pub fn current_script_location(&self) -> Option<(String, u32)> {
+        self.do_with_jsapi(|_rt, cx, _global| jsapi_utils::scripts::get_scripted_caller(cx))
+    }
+
     /// construct a new SmRuntime, this should only be called from the worker thread of the EsEventQueue
     /// here we actualy construct a new Runtime
     fn new() -> Self {
@@ -127,7 +230,10 @@ impl SmRuntime {
 
         let context = runtime.cx();
         let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
-        let c_option = RealmOptions::default();
+        let mut c_option = RealmOptions::default();
+        if let Some(callback) = REALM_OPTIONS_CALLBACK.with(|rc| rc.borrow_mut().take()) {
+            callback(&mut *c_option);
+        }
 
         let global_obj;
 
@@ -145,14 +251,50 @@ impl SmRuntime {
             runtime,
             global_obj,
             opt_esrt_inner: None,
+            strict_string_conversion: false,
+            eager_object_max_props: usize::MAX,
+            eager_string_max_len: usize::MAX,
         };
 
+        ret.init_global_this();
         ret.init_promise_callbacks();
         ret.init_import_callbacks();
 
         ret
     }
 
+    /// set the gc callback if the EsRuntime was built with an on_gc closure
+    pub(crate) fn init_gc_callback(&self) {
+        if self.clone_esrt_inner().config.on_gc.is_some() {
+            self.do_with_jsapi(|_rt, cx, _global| unsafe {
+                JS_SetGCCallback(cx, Some(gc_callback), ptr::null_mut());
+            });
+        }
+    }
+
+    fn init_global_this(&self) {
+        // globalThis is normally resolved as a standard class, make sure it exists for scripts which depend on it
+        self.do_with_jsapi(|_rt, cx, global| {
+            rooted!(in (cx) let mut global_this_root = UndefinedValue());
+            let res = jsapi_utils::objects::get_es_obj_prop_val(
+                cx,
+                global,
+                "globalThis",
+                global_this_root.handle_mut(),
+            );
+            if res.is_err() || !global_this_root.is_object() {
+                debug!("globalThis was not resolved, defining it");
+                rooted!(in (cx) let global_val_root = ObjectValue(global.get()));
+                jsapi_utils::objects::set_es_obj_prop_value(
+                    cx,
+                    global,
+                    "globalThis",
+                    global_val_root.handle(),
+                );
+            }
+        });
+    }
+
     fn init_promise_callbacks(&self) {
         // this tells JSAPI how to schedule jobs for Promises
 
@@ -186,6 +328,89 @@ impl SmRuntime {
         })
     }
 
+    /// resolve a function by name and cache it together with the object it is a member of
+    /// returns the ids of the cached this object and the cached function
+    pub(crate) fn prepare_call(
+        &self,
+        obj_names: Vec<&str>,
+        func_name: &str,
+    ) -> Result<(usize, usize), EsErrorInfo> {
+        self.do_with_jsapi(|_rt, cx, global| {
+            trace!(
+                "smrt.prepare_call {} in thread {}",
+                func_name,
+                thread_id::get()
+            );
+
+            rooted!(in (cx) let mut scope = *global);
+            for obj_name in obj_names {
+                rooted!(in (cx) let mut sub_val = UndefinedValue());
+                jsapi_utils::objects::get_es_obj_prop_val(
+                    cx,
+                    scope.handle(),
+                    obj_name,
+                    sub_val.handle_mut(),
+                )?;
+                if !sub_val.is_object() {
+                    return Err(EsErrorInfo::new(
+                        format!("{} was not an object.", obj_name),
+                        "".to_string(),
+                        0,
+                        0,
+                    ));
+                }
+                scope.set(sub_val.to_object());
+            }
+
+            rooted!(in (cx) let mut func_val = UndefinedValue());
+            jsapi_utils::objects::get_es_obj_prop_val(
+                cx,
+                scope.handle(),
+                func_name,
+                func_val.handle_mut(),
+            )?;
+            if !jsapi_utils::functions::value_is_function(cx, func_val.handle()) {
+                return Err(EsErrorInfo::new(
+                    format!("{} is not a function", func_name),
+                    "".to_string(),
+                    0,
+                    0,
+                ));
+            }
+
+            let this_id = register_cached_object(cx, *scope);
+            let func_id = register_cached_object(cx, func_val.to_object());
+            Ok((this_id, func_id))
+        })
+    }
+
+    /// call a function which was cached by prepare_call
+    pub(crate) fn call_prepared(
+        &self,
+        this_id: usize,
+        func_id: usize,
+        arguments: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsErrorInfo> {
+        self.do_with_jsapi(|_rt, cx, _global| {
+            trace!("smrt.call_prepared in thread {}", thread_id::get());
+
+            rooted!(in (cx) let this_obj = do_with_cached_object(this_id, |epr| epr.get()));
+            rooted!(in (cx) let func_val = do_with_cached_object(func_id, |epr| ObjectValue(epr.get())));
+            rooted!(in (cx) let mut rval = UndefinedValue());
+
+            do_with_rooted_esvf_vec(cx, arguments, |hva| {
+                jsapi_utils::functions::call_function_value2(
+                    cx,
+                    this_obj.handle(),
+                    func_val.handle(),
+                    hva,
+                    rval.handle_mut(),
+                )?;
+                Ok(EsValueFacade::new_v(cx, rval.handle()))
+            })
+        })
+    }
+
     /// load and execute a script module
     pub fn load_module(&self, module_src: &str, module_file_name: &str) -> Result<(), EsErrorInfo> {
         trace!(
@@ -210,10 +435,14 @@ impl SmRuntime {
     pub fn eval(&self, eval_code: &str, file_name: &str) -> Result<EsValueFacade, EsErrorInfo> {
         trace!("smrt.eval {} in thread {}", file_name, thread_id::get());
 
+        jsapi_utils::scripts::check_script_size(eval_code, file_name)?;
+
+        let eval_code = self.prepare_eval_code(eval_code);
+
         self.do_with_jsapi(|rt, cx, global| {
             rooted!(in (cx) let mut rval = UndefinedValue());
             let eval_res: Result<(), EsErrorInfo> =
-                jsapi_utils::eval(rt, global, eval_code, file_name, rval.handle_mut());
+                jsapi_utils::eval(rt, global, &eval_code, file_name, rval.handle_mut());
 
             if eval_res.is_ok() {
                 Ok(EsValueFacade::new_v(cx, rval.handle()))
@@ -223,6 +452,24 @@ impl SmRuntime {
         })
     }
 
+    /// compile a piece of script without running it, this returns the syntax error if the script does not compile
+    pub fn check_syntax(&self, code: &str, file_name: &str) -> Result<(), EsErrorInfo> {
+        trace!(
+            "smrt.check_syntax {} in thread {}",
+            file_name,
+            thread_id::get()
+        );
+
+        jsapi_utils::scripts::check_script_size(code, file_name)?;
+
+        let code = self.prepare_eval_code(code);
+
+        self.do_with_jsapi(|_rt, cx, _global| {
+            rooted!(in (cx) let mut script_root = ptr::null_mut::<mozjs::jsapi::JSScript>());
+            jsapi_utils::scripts::compile_script(cx, &code, file_name, script_root.handle_mut())
+        })
+    }
+
     /// eval a piece of script and ignore the result
     pub fn eval_void(&self, eval_code: &str, file_name: &str) -> Result<(), EsErrorInfo> {
         trace!(
@@ -231,10 +478,14 @@ impl SmRuntime {
             thread_id::get()
         );
 
+        jsapi_utils::scripts::check_script_size(eval_code, file_name)?;
+
+        let eval_code = self.prepare_eval_code(eval_code);
+
         self.do_with_jsapi(|rt, cx, global| {
             rooted!(in (cx) let mut rval = UndefinedValue());
             let eval_res: Result<(), EsErrorInfo> =
-                jsapi_utils::eval(rt, global, eval_code, file_name, rval.handle_mut());
+                jsapi_utils::eval(rt, global, &eval_code, file_name, rval.handle_mut());
 
             if eval_res.is_ok() {
                 Ok(())
@@ -244,6 +495,22 @@ impl SmRuntime {
         })
     }
 
+    /// prepend "use strict" to the code if the runtime was built with force_strict_mode
+    fn prepare_eval_code<'a>(&self, eval_code: &'a str) -> Cow<'a, str> {
+        let force_strict_mode = self
+            .opt_esrt_inner
+            .as_ref()
+            .and_then(|weak| weak.upgrade())
+            .map(|inner| inner.config.force_strict_mode)
+            .unwrap_or(false);
+        if force_strict_mode {
+            // the void statement makes sure the completion value is not "use strict" for scripts without expressions
+            Cow::Owned(format!("\"use strict\";void 0;{}", eval_code))
+        } else {
+            Cow::Borrowed(eval_code)
+        }
+    }
+
     /// run the cleanup function and run the garbage collector
     /// this also fires a pre-cleanup event in script so scripts can do a cleanup before the garbage collector runs
     pub fn cleanup(&self) {
@@ -373,33 +640,40 @@ unsafe extern "C" fn global_op_native_method(
     argc: u32,
     vp: *mut mozjs::jsapi::Value,
 ) -> bool {
-    // todo get name from callee, get global op, invoke
-
-    let args = CallArgs::from_vp(vp, argc);
-    let callee: *mut JSObject = args.callee();
-    let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
-        cx,
-        HandleObject::from_marked_location(&callee),
-        "name",
-    );
-    if let Ok(prop_name) = prop_name_res {
-        return GLOBAL_OPS.with(|global_ops_rc| {
-            let global_ops = &*global_ops_rc.borrow();
-            let boxed_op = global_ops
-                .get(prop_name.as_str())
-                .expect("could not find op");
-            boxed_op(cx, args)
-        });
-    }
+    crate::jsapi_utils::catch_native_panic(cx, "global_op_native_method", || {
+        // todo get name from callee, get global op, invoke
+
+        let args = CallArgs::from_vp(vp, argc);
+        let callee: *mut JSObject = args.callee();
+        let prop_name_res = crate::jsapi_utils::objects::get_es_obj_prop_val_as_string(
+            cx,
+            HandleObject::from_marked_location(&callee),
+            "name",
+        );
+        if let Ok(prop_name) = prop_name_res {
+            return GLOBAL_OPS.with(|global_ops_rc| {
+                let global_ops = &*global_ops_rc.borrow();
+                let boxed_op = global_ops
+                    .get(prop_name.as_str())
+                    .expect("could not find op");
+                boxed_op(cx, args)
+            });
+        }
 
-    false
+        false
+    })
 }
 
 thread_local! {
 // store epr in Box because https://doc.servo.org/mozjs_sys/jsgc/struct.Heap.html#method.boxed
     static OBJECT_CACHE: RefCell<AutoIdMap<EsPersistentRooted>> = RefCell::new(AutoIdMap::new());
+    // released EsPersistentRooted instances which may be reused by register_cached_object
+    static OBJECT_CACHE_POOL: RefCell<Vec<EsPersistentRooted>> = RefCell::new(vec![]);
 }
 
+/// the max number of released EsPersistentRooted instances we keep around for reuse
+const OBJECT_CACHE_POOL_MAX_SIZE: usize = 256;
+
 pub(crate) fn do_with_rooted_esvf_vec<R, C>(
     context: *mut JSContext,
     vec: Vec<EsValueFacade>,
@@ -418,17 +692,133 @@ where
     }
 
     trace!("sm_rt::do_with_rooted_esvf_vec, init hva");
+    // the values are rooted by the vec so the hva does not need to be copied into an array object
     let arguments_value_array = unsafe { HandleValueArray::from_rooted_slice(&*values) };
-    // root the hva itself
-    trace!("sm_rt::do_with_rooted_esvf_vec, root hva");
-    rooted!(in(context) let _argument_object = unsafe { NewArrayObject(context, &arguments_value_array) });
     trace!("sm_rt::do_with_rooted_esvf_vec, run consumer");
     consumer(arguments_value_array)
 }
 
+/// a reusable buffer for the arguments of function calls, see do_with_args_buffer()
+/// the values in the buffer are rooted for as long as the buffer exists
+pub struct ArgsBuffer<'a> {
+    context: *mut JSContext,
+    values: &'a mut Vec<JSVal>,
+}
+
+impl<'a> ArgsBuffer<'a> {
+    /// replace the contents of the buffer with the values of the EsValueFacades
+    /// the memory of the buffer is kept so filling it again with the same number of args does not allocate
+    pub fn fill(&mut self, args: &[EsValueFacade]) {
+        self.values.clear();
+        for esvf in args {
+            rooted!(in (self.context) let mut val_root = UndefinedValue());
+            esvf.to_es_value(self.context, val_root.handle_mut());
+            self.values.push(*val_root);
+        }
+    }
+
+    /// replace the contents of the buffer with raw values
+    pub fn fill_values(&mut self, args: &[JSVal]) {
+        self.values.clear();
+        self.values.extend_from_slice(args);
+    }
+
+    /// replace a single argument and keep the others, e.g. for a loop where only one argument changes between calls
+    /// if index is beyond the end of the buffer the buffer is padded with undefined
+    pub fn set(&mut self, index: usize, arg: &EsValueFacade) {
+        rooted!(in (self.context) let mut val_root = UndefinedValue());
+        arg.to_es_value(self.context, val_root.handle_mut());
+        self.set_value(index, *val_root);
+    }
+
+    /// replace a single argument with a raw value, see set()
+    pub fn set_value(&mut self, index: usize, arg: JSVal) {
+        if index >= self.values.len() {
+            self.values.resize(index + 1, UndefinedValue());
+        }
+        self.values[index] = arg;
+    }
+
+    /// get the contents of the buffer as a HandleValueArray which can be passed to e.g. functions::call_function_value2()
+    /// the returned value borrows the buffer so the buffer can not be filled again while it is in use
+    pub fn handle_value_array(&self) -> ArgsBufferValues<'_> {
+        ArgsBufferValues {
+            hva: unsafe { HandleValueArray::from_rooted_slice(&*self.values) },
+            _buffer: PhantomData,
+        }
+    }
+
+    /// the number of values in the buffer
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// check if the buffer contains no values
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// the contents of an ArgsBuffer as a HandleValueArray, see ArgsBuffer::handle_value_array()
+/// dereference it to pass the HandleValueArray to a call, e.g. `*buffer.handle_value_array()`
+pub struct ArgsBufferValues<'b> {
+    hva: HandleValueArray,
+    _buffer: PhantomData<&'b Vec<JSVal>>,
+}
+
+impl<'b> Deref for ArgsBufferValues<'b> {
+    type Target = HandleValueArray;
+
+    fn deref(&self) -> &HandleValueArray {
+        &self.hva
+    }
+}
+
+/// run a closure with a rooted argument buffer which can be reused for many calls instead of rooting a new vec for every call
+/// capacity is the number of values the buffer can hold before it needs to grow
+/// # Example
+/// ```no_run
+/// use spidermonkey_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use spidermonkey_runtime::esvaluefacade::EsValueFacade;
+/// use spidermonkey_runtime::jsapi_utils;
+/// use spidermonkey_runtime::spidermonkeyruntimewrapper::do_with_args_buffer;
+/// use mozjs::jsval::UndefinedValue;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.do_in_es_event_queue_sync(|sm_rt| {
+///     sm_rt.do_with_jsapi(|rt, cx, global| {
+///         rooted!(in (cx) let mut func_root = UndefinedValue());
+///         jsapi_utils::eval(rt, global, "(function(a, b){return a + b;});", "test_args_buffer.es", func_root.handle_mut())
+///             .ok().expect("script failed");
+///         do_with_args_buffer(cx, 2, |buffer| {
+///             for x in 0..1000 {
+///                 buffer.fill(&[EsValueFacade::new_i32(x), EsValueFacade::new_i32(1)]);
+///                 rooted!(in (cx) let mut rval = UndefinedValue());
+///                 jsapi_utils::functions::call_function_value2(cx, global, func_root.handle(), *buffer.handle_value_array(), rval.handle_mut())
+///                     .ok().expect("call failed");
+///             }
+///         });
+///     });
+/// });
+/// ```
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn do_with_args_buffer<R, C>(context: *mut JSContext, capacity: usize, consumer: C) -> R
+where
+    C: FnOnce(&mut ArgsBuffer) -> R,
+{
+    auto_root!(in (context) let mut values = Vec::with_capacity(capacity));
+    let mut buffer = ArgsBuffer {
+        context,
+        values: &mut *values,
+    };
+    consumer(&mut buffer)
+}
+
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub fn register_cached_object(context: *mut JSContext, obj: *mut JSObject) -> usize {
-    let mut epr = EsPersistentRooted::default();
+    let mut epr = OBJECT_CACHE_POOL
+        .with(|pool_rc| pool_rc.borrow_mut().pop())
+        .unwrap_or_default();
     unsafe { epr.init(context, obj) };
     OBJECT_CACHE.with(|object_cache_rc| {
         let map = &mut *object_cache_rc.borrow_mut();
@@ -463,6 +853,30 @@ pub fn remove_cached_object(id: usize) -> EsPersistentRooted {
     })
 }
 
+/// remove an object from the cache and release it's root
+/// unlike remove_cached_object this does not return the EsPersistentRooted so it may be reused for a new cached object
+pub fn release_cached_object(id: usize) {
+    trace!("release cached obj with id {}", id);
+    let mut epr = remove_cached_object(id);
+    epr.release();
+    OBJECT_CACHE_POOL.with(|pool_rc| {
+        let pool = &mut *pool_rc.borrow_mut();
+        if pool.len() < OBJECT_CACHE_POOL_MAX_SIZE {
+            pool.push(epr);
+        }
+    });
+}
+
+#[cfg(test)]
+pub(crate) fn object_cache_pool_size() -> usize {
+    OBJECT_CACHE_POOL.with(|pool_rc| pool_rc.borrow().len())
+}
+
+#[cfg(test)]
+pub(crate) fn object_cache_size() -> usize {
+    OBJECT_CACHE.with(|object_cache_rc| object_cache_rc.borrow().len())
+}
+
 impl Drop for SmRuntime {
     fn drop(&mut self) {
         trace!("dropping SmRuntime in thread {}", thread_id::get());
@@ -487,38 +901,199 @@ unsafe extern "C" fn enqueue_promise_job(
     wrap_panic(&mut || {
         trace!("enqueue a job");
 
-        let cb = PromiseJobCallback::new(cx, job.get());
-
-        let task = move || {
-            SM_RT.with(move |rc| {
-                trace!("running a job");
+        let esrt_inner = SmRuntime::clone_current_esrt_inner_arc();
+        if let Some(job_scheduler) = &esrt_inner.config.job_scheduler {
+            let cached_id = register_cached_object(cx, job.get());
+            job_scheduler(PromiseJob::new(Arc::downgrade(&esrt_inner), cached_id));
+            result = true;
+            return;
+        }
 
-                let sm_rt = &*rc.borrow();
+        let run_now = MICROTASKS_THIS_TURN.with(|count| {
+            if count.get() < esrt_inner.config.max_microtasks_per_turn {
+                count.set(count.get() + 1);
+                true
+            } else {
+                false
+            }
+        });
 
-                sm_rt.do_with_jsapi(|_rt, cx, _global| {
-                    trace!("calling cb.call");
-                    let call_res = cb.call(cx, HandleObject::null());
-                    trace!("checking cb.call res");
-                    if call_res.is_err() {
-                        debug!("job failed");
-                        if let Some(err) = jsapi_utils::get_pending_exception(cx) {
-                            panic!(
-                                "job failed {}:{}:{} -> {}",
-                                err.filename, err.lineno, err.column, err.message
-                            );
-                        }
-                    }
-                });
-                trace!("job ran ok");
+        if run_now {
+            let cb = PromiseJobCallback::new(cx, job.get());
+            MICROTASK_QUEUE.with(|queue_rc| queue_rc.borrow_mut().push_back(cb));
+            // every local task runs the oldest job in the queue, jobs may already have been run by drain_microtasks()
+            EventLoop::add_local_void(|| {
+                run_next_microtask();
             });
-        };
-
-        EventLoop::add_local_void(task);
+        } else {
+            // the limit was reached, defer the job to a new macro task so other tasks in the event queue may run first
+            trace!("max_microtasks_per_turn reached, deferring job");
+            let cached_id = register_cached_object(cx, job.get());
+            esrt_inner.do_in_es_event_queue(move |_sm_rt| {
+                run_cached_promise_job(cached_id);
+            });
+        }
         result = true
     });
     result
 }
 
+/// called by the engine when a garbage collection begins or ends, this passes a GcPhase to the on_gc closure of the EsRuntime
+unsafe extern "C" fn gc_callback(
+    _cx: *mut JSContext,
+    status: JSGCStatus,
+    _reason: mozjs::jsapi::GCReason,
+    _data: *mut c_void,
+) {
+    let phase = if status == JSGCStatus::JSGC_BEGIN {
+        GC_STARTED.with(|started| started.set(Some(Instant::now())));
+        GcPhase::Begin
+    } else {
+        let duration = GC_STARTED
+            .with(|started| started.take())
+            .map(|start| start.elapsed())
+            .unwrap_or_default();
+        GcPhase::End { duration }
+    };
+    trace!(
+        "gc {:?} in runtime {}",
+        phase,
+        jsapi_utils::current_runtime_name().unwrap_or_default()
+    );
+
+    // the SmRuntime may be borrowed mutably during init, in that case we just skip the callback
+    let esrt_inner_opt = SmRuntime::try_clone_current_esrt_inner_arc();
+    if let Some(esrt_inner) = esrt_inner_opt {
+        if let Some(on_gc) = &esrt_inner.config.on_gc {
+            if let Err(payload) =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| on_gc(phase)))
+            {
+                log::error!(
+                    "on_gc callback of runtime {} panicked: {}",
+                    jsapi_utils::current_runtime_name().unwrap_or_default(),
+                    jsapi_utils::panic_message(&payload)
+                );
+            }
+        }
+    }
+}
+
+/// pass an uncaught error of an async task to the error callback of the runtime of the current thread, see EsRuntimeBuilder::error_callback()
+/// if the runtime has no error callback the error is logged
+pub(crate) fn report_uncaught_error(err: EsErrorInfo) {
+    let esrt_inner_opt = SmRuntime::try_clone_current_esrt_inner_arc();
+    match esrt_inner_opt
+        .as_ref()
+        .and_then(|esrt_inner| esrt_inner.config.error_callback.as_ref())
+    {
+        Some(error_callback) => {
+            if let Err(payload) =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| error_callback(err)))
+            {
+                log::error!(
+                    "error callback panicked: {}",
+                    jsapi_utils::panic_message(&payload)
+                );
+            }
+        }
+        None => {
+            log::error!("uncaught error in async task: {}", err.err_msg());
+        }
+    }
+}
+
+/// store the error of an operation as the last error of the thread which called it, a successful operation clears the last error
+/// this should be called in the job which ran the operation so an operation of another thread can not replace it
+pub(crate) fn record_last_error(caller: ThreadId, err: Option<EsErrorInfo>) {
+    LAST_ERRORS.with(|last_errors_rc| {
+        let last_errors = &mut *last_errors_rc.borrow_mut();
+        match err {
+            Some(err) => {
+                last_errors.insert(caller, err);
+            }
+            None => {
+                last_errors.remove(&caller);
+            }
+        }
+    });
+}
+
+/// take the error of the last eval, call or module load of the given thread
+pub(crate) fn take_last_error(caller: ThreadId) -> Option<EsErrorInfo> {
+    LAST_ERRORS.with(|last_errors_rc| last_errors_rc.borrow_mut().remove(&caller))
+}
+
+/// get the number of promise jobs which were added as microtasks in this thread but have not run yet
+pub(crate) fn pending_microtask_count() -> usize {
+    MICROTASK_QUEUE.with(|queue_rc| queue_rc.borrow().len())
+}
+
+// run the oldest pending microtask of this thread, returns false if there was none
+fn run_next_microtask() -> bool {
+    let cb_opt = MICROTASK_QUEUE.with(|queue_rc| queue_rc.borrow_mut().pop_front());
+    match cb_opt {
+        Some(cb) => {
+            run_promise_job(cb);
+            true
+        }
+        None => false,
+    }
+}
+
+/// run the pending microtasks of this thread in the current job, including the microtasks they add
+/// at most max microtasks are run so a promise chain which keeps adding jobs can not block the current job forever
+/// returns the number of microtasks which were run
+pub(crate) fn drain_microtasks(max: usize) -> usize {
+    let mut ran = 0;
+    while ran < max && run_next_microtask() {
+        ran += 1;
+    }
+    if ran == max && pending_microtask_count() > 0 {
+        log::warn!(
+            "stopped draining microtasks after {} jobs, the remaining jobs run later",
+            max
+        );
+    }
+    ran
+}
+
+/// reset the number of microtasks which were enqueued in the current turn of the event queue
+/// this is called when a job of the event queue starts
+pub(crate) fn reset_microtask_count() {
+    MICROTASKS_THIS_TURN.with(|count| count.set(0));
+}
+
+/// run a promise job which was stored in the object cache, the job is removed from the cache
+pub(crate) fn run_cached_promise_job(cached_id: usize) {
+    let job_epr = remove_cached_object(cached_id);
+    let cb = SM_RT.with(|rc| {
+        let sm_rt = &*rc.borrow();
+        sm_rt.do_with_jsapi(|_rt, cx, _global| PromiseJobCallback::new(cx, job_epr.get()))
+    });
+    run_promise_job(cb);
+}
+
+fn run_promise_job(cb: Rc<PromiseJobCallback>) {
+    SM_RT.with(move |rc| {
+        trace!("running a job");
+
+        let sm_rt = &*rc.borrow();
+
+        sm_rt.do_with_jsapi(|_rt, cx, _global| {
+            trace!("calling cb.call");
+            let call_res = unsafe { cb.call(cx, HandleObject::null()) };
+            trace!("checking cb.call res");
+            if call_res.is_err() {
+                debug!("job failed");
+                if let Some(err) = jsapi_utils::get_pending_exception(cx) {
+                    report_uncaught_error(err);
+                }
+            }
+        });
+        trace!("job ran ok");
+    });
+}
+
 /// the code below was copied and altered from the servo project
 /// https://github.com/servo/servo
 /// so it falls under this LICENSE https://raw.githubusercontent.com/servo/servo/master/LICENSE
@@ -542,17 +1117,7 @@ unsafe extern "C" fn get_incumbent_global(_: *const c_void, _: *mut JSContext) -
 #[allow(unsafe_code)]
 unsafe extern "C" fn empty(_extra: *const c_void) -> bool {
     trace!("empty called");
-    //let mut result = false;
-    //wrap_panic(&mut || {
-    //result = SM_RT.with(|sm_rt_rc| {
-    // todo, create is_empty for EventLoop (AtomicUsize?)
-    //let sm_rt = &*sm_rt_rc.borrow();
-
-    //sm_rt.clone_esrt_inner().event_loop.is_empty()
-    false
-    //})
-    //});
-    //result
+    pending_microtask_count() == 0
 }
 
 static JOB_QUEUE_TRAPS: JobQueueTraps = JobQueueTraps {
@@ -632,10 +1197,27 @@ mod tests {
     use crate::esvaluefacade::EsValueFacade;
     use crate::jsapi_utils;
     use crate::jsapi_utils::EsErrorInfo;
-    use crate::spidermonkeyruntimewrapper::{do_with_rooted_esvf_vec, SmRuntime};
+    use crate::spidermonkeyruntimewrapper::{
+        do_with_args_buffer, do_with_rooted_esvf_vec, object_cache_pool_size, SmRuntime,
+        OBJECT_CACHE_POOL_MAX_SIZE,
+    };
     use log::trace;
     use mozjs::jsval::UndefinedValue;
 
+    #[test]
+    fn test_global_this() {
+        log::info!("test: test_global_this");
+        let rt = init_test_runtime();
+        let esvf = rt
+            .eval_sync(
+                "globalThis.test_global_this_foo = 1; this.test_global_this_foo === 1 && globalThis === this;",
+                "test_global_this.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert!(esvf.get_boolean());
+    }
+
     #[test]
     fn test_call_method_name() {
         log::info!("test: test_call_method_name");
@@ -824,4 +1406,217 @@ mod tests {
         });
         assert_eq!(ret.as_str(), "1-abc-3-def");
     }
+
+    #[test]
+    fn test_args_buffer() {
+        log::info!("test: test_args_buffer");
+
+        let rt = init_test_runtime();
+        let ret = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in (cx) let mut func_root = UndefinedValue());
+                rt.evaluate_script(
+                    global,
+                    "(function(a, b, c, d){return [a, b, c, d].join('-');});",
+                    "test_args_buffer.es",
+                    0,
+                    func_root.handle_mut(),
+                )
+                .ok()
+                .unwrap();
+
+                do_with_args_buffer(cx, 4, |buffer| {
+                    let buffer_ptr = buffer.values.as_ptr();
+                    let mut ret = "".to_string();
+                    for x in 0..1000 {
+                        buffer.fill(&[
+                            EsValueFacade::new_i32(x),
+                            EsValueFacade::new_str("abc".to_string()),
+                            EsValueFacade::new_i32(3),
+                            EsValueFacade::new_str("def".to_string()),
+                        ]);
+                        if x % 100 == 0 {
+                            // make sure the values stay rooted while we reuse the buffer
+                            jsapi_utils::gc(cx);
+                        }
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        jsapi_utils::functions::call_function_value2(
+                            cx,
+                            global,
+                            func_root.handle(),
+                            *buffer.handle_value_array(),
+                            rval.handle_mut(),
+                        )
+                        .ok()
+                        .unwrap();
+                        ret = jsapi_utils::es_value_to_str(cx, *rval).ok().unwrap();
+                    }
+                    // the buffer never had to grow so its memory was reused for every call
+                    assert_eq!(buffer_ptr, buffer.values.as_ptr());
+                    assert_eq!(buffer.len(), 4);
+                    ret
+                })
+            })
+        });
+        assert_eq!(ret.as_str(), "999-abc-3-def");
+    }
+
+    #[test]
+    fn test_args_buffer_set() {
+        log::info!("test: test_args_buffer_set");
+        let rt = init_test_runtime();
+        let ret = rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.do_with_jsapi(|rt, cx, global| {
+                rooted!(in (cx) let mut func_root = UndefinedValue());
+                rt.evaluate_script(
+                    global,
+                    "(function(a, b, c){return [a, b, c].join('-');});",
+                    "test_args_buffer_set.es",
+                    0,
+                    func_root.handle_mut(),
+                )
+                .ok()
+                .unwrap();
+
+                do_with_args_buffer(cx, 3, |buffer| {
+                    // only the first arg changes between calls, the others are set once
+                    buffer.set(2, &EsValueFacade::new_str("c".to_string()));
+                    buffer.set(1, &EsValueFacade::new_str("b".to_string()));
+                    assert_eq!(buffer.len(), 3);
+                    let mut ret = "".to_string();
+                    for x in 0..1000 {
+                        buffer.set(0, &EsValueFacade::new_i32(x));
+                        if x % 100 == 0 {
+                            jsapi_utils::gc(cx);
+                        }
+                        rooted!(in (cx) let mut rval = UndefinedValue());
+                        jsapi_utils::functions::call_function_value2(
+                            cx,
+                            global,
+                            func_root.handle(),
+                            *buffer.handle_value_array(),
+                            rval.handle_mut(),
+                        )
+                        .ok()
+                        .unwrap();
+                        ret = jsapi_utils::es_value_to_str(cx, *rval).ok().unwrap();
+                    }
+                    ret
+                })
+            })
+        });
+        assert_eq!(ret.as_str(), "999-b-c");
+    }
+
+    #[test]
+    fn test_cached_object_pool() {
+        log::info!("test: test_cached_object_pool");
+        let rt = init_test_runtime();
+
+        // pass a lot of short lived closures from script to rust
+        rt.add_global_sync_function("test_cached_object_pool_op", |args: Vec<EsValueFacade>| {
+            assert!(args.get(0).expect("no arg").is_function());
+            Ok(EsValueFacade::new_bool(true))
+        });
+        rt.eval_sync(
+            "for (let x = 0; x < 5000; x++) {test_cached_object_pool_op(() => {return x;});}",
+            "test_cached_object_pool.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        // the released roots should now be in the pool
+        let pool_size = rt.do_in_es_event_queue_sync(|_sm_rt: &SmRuntime| object_cache_pool_size());
+        assert!(pool_size > 0);
+
+        // and be reused for new cached objects which should still work as expected
+        for round in 0..20 {
+            let funcs_esvf = rt
+                .eval_sync(
+                    format!(
+                        "[...Array(200).keys()].map((i) => {{return () => {{return i + {};}};}});",
+                        round
+                    )
+                    .as_str(),
+                    "test_cached_object_pool2.es",
+                )
+                .ok()
+                .expect("script failed");
+            rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+                sm_rt.do_with_jsapi(|_rt, cx, _global| {
+                    jsapi_utils::gc(cx);
+                })
+            });
+            let funcs = funcs_esvf.get_array();
+            assert_eq!(funcs.len(), 200);
+            for (i, func) in funcs.iter().enumerate().step_by(50) {
+                let res = func.invoke_function(vec![]).ok().expect("function failed");
+                assert_eq!(res.get_i32(), (i + round) as i32);
+            }
+        }
+
+        let pool_size = rt.do_in_es_event_queue_sync(|_sm_rt: &SmRuntime| object_cache_pool_size());
+        assert!(pool_size <= OBJECT_CACHE_POOL_MAX_SIZE);
+    }
+
+    #[test]
+    fn test_current_script_name() {
+        log::info!("test: test_current_script_name");
+        let rt = init_test_runtime();
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let calls2 = calls.clone();
+        rt.do_in_es_event_queue_sync(move |sm_rt: &SmRuntime| {
+            assert!(sm_rt.current_script_name().is_none());
+            sm_rt.add_global_function("test_current_script_name_op", move |cx, args| {
+                let caller = jsapi_utils::scripts::get_scripted_caller(cx);
+                calls2.lock().unwrap().push(caller);
+                args.rval().set(UndefinedValue());
+                true
+            });
+        });
+
+        rt.eval_sync(
+            "\ntest_current_script_name_op();",
+            "plugins/test_current_script_name.es",
+        )
+        .ok()
+        .expect("script failed");
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        let (file_name, lineno) = calls[0].clone().expect("no scripted caller");
+        assert_eq!(file_name.as_str(), "plugins/test_current_script_name.es");
+        assert_eq!(lineno, 2);
+    }
+
+    #[test]
+    fn test_global_function_attrs() {
+        log::info!("test: test_global_function_attrs");
+        let rt = init_test_runtime();
+        rt.do_in_es_event_queue_sync(|sm_rt: &SmRuntime| {
+            sm_rt.add_global_function("test_hidden_global_op", |_cx, args| {
+                args.rval().set(mozjs::jsval::Int32Value(1));
+                true
+            });
+            sm_rt.add_global_function_with_attrs(
+                "test_enumerable_global_op",
+                (mozjs::jsapi::JSPROP_ENUMERATE | mozjs::jsapi::JSPROP_PERMANENT) as u32,
+                |_cx, args| {
+                    args.rval().set(mozjs::jsval::Int32Value(2));
+                    true
+                },
+            );
+        });
+
+        let esvf = rt
+            .eval_sync(
+                "let keys = []; for (let k in globalThis) {keys.push(k);}\n\
+                 [keys.includes('test_hidden_global_op'), keys.includes('test_enumerable_global_op'), \
+                 test_hidden_global_op() + test_enumerable_global_op(), delete globalThis.test_enumerable_global_op].join(',');",
+                "test_global_function_attrs.es",
+            )
+            .ok()
+            .expect("script failed");
+        assert_eq!(esvf.get_string(), "false,true,3,false");
+    }
 }